@@ -0,0 +1,256 @@
+//! Typed wrappers for the open-ils.acq acquisitions APIs:
+//! provider/fund lookups, purchase order and lineitem creation,
+//! receiving, and invoicing.
+
+use crate::editor::Editor;
+use crate::event::EgEvent;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::sync::Arc;
+
+const ACQ_TIMEOUT: u64 = 120;
+
+/// Drives acquisitions workflows for one authenticated session.
+pub struct Acq {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+}
+
+impl Acq {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Acq {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// Call an open-ils.acq method with the authtoken prepended and
+    /// return its first response.
+    fn request(&self, method: &str, mut params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        params.insert(0, json::from(self.authtoken.as_str()));
+
+        let session = self.client.session("open-ils.acq");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(ACQ_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Fail on a non-success event response.
+    fn check_event(method: &str, resp: JsonValue) -> Result<JsonValue, String> {
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("{method} failed: {evt}"));
+            }
+        }
+        Ok(resp)
+    }
+
+    // -- Lookups -----------------------------------------------------
+
+    /// Find a provider by code, scoped to an owning org.
+    pub fn provider_by_code(
+        &mut self,
+        code: &str,
+        owner: i64,
+    ) -> Result<Option<JsonValue>, String> {
+        let mut hits = self
+            .editor
+            .search("acqpro", json::object! {code: code, owner: owner})?;
+        Ok(hits.pop())
+    }
+
+    /// Find a fund by code for an org and (optionally) a fiscal
+    /// year; defaults to the newest year.
+    pub fn fund_by_code(
+        &mut self,
+        code: &str,
+        org: i64,
+        year: Option<i64>,
+    ) -> Result<Option<JsonValue>, String> {
+        let mut filter = json::object! {code: code, org: org};
+        if let Some(year) = year {
+            filter["year"] = year.into();
+        }
+
+        let mut funds = self.editor.search("acqf", filter)?;
+        funds.sort_by_key(|f| util::json_int(&f["year"]).unwrap_or(0));
+
+        Ok(funds.pop())
+    }
+
+    /// Current spent/encumbered totals for a fund.
+    pub fn fund_summary(&self, fund_id: i64) -> Result<JsonValue, String> {
+        let method = "open-ils.acq.fund.summary.retrieve";
+        let resp = self.request(method, vec![json::from(fund_id)])?;
+        Acq::check_event(method, resp)
+    }
+
+    // -- Ordering ----------------------------------------------------
+
+    /// Create a purchase order for a provider, returning the new PO.
+    pub fn create_purchase_order(
+        &self,
+        provider: i64,
+        ordering_agency: i64,
+        name: Option<&str>,
+    ) -> Result<JsonValue, String> {
+        let method = "open-ils.acq.purchase_order.create";
+
+        let mut po = json::object! {
+            "_classname": "acqpo",
+            provider: provider,
+            ordering_agency: ordering_agency,
+        };
+
+        if let Some(name) = name {
+            po["name"] = name.into();
+        }
+
+        let resp = self.request(method, vec![po])?;
+        Acq::check_event(method, resp)
+    }
+
+    /// Add a lineitem to a PO from a MARCXML record, with copies
+    /// (lineitem details) charged to a fund.  Returns the lineitem
+    /// ID.
+    pub fn create_lineitem(
+        &self,
+        po_id: i64,
+        marc_xml: &str,
+        fund_id: i64,
+        owning_lib: i64,
+        copies: u32,
+    ) -> Result<i64, String> {
+        let method = "open-ils.acq.lineitem.create";
+
+        let lineitem = json::object! {
+            "_classname": "jub",
+            purchase_order: po_id,
+            marc: marc_xml,
+            state: "new",
+        };
+
+        let resp = self.request(method, vec![lineitem])?;
+        let resp = Acq::check_event(method, resp)?;
+
+        let li_id = util::json_int(&resp)
+            .or_else(|_| util::json_int(&resp["id"]))
+            .map_err(|_| format!("Unexpected lineitem response: {}", resp.dump()))?;
+
+        for _ in 0..copies {
+            let detail = json::object! {
+                "_classname": "acqlid",
+                lineitem: li_id,
+                fund: fund_id,
+                owning_lib: owning_lib,
+            };
+
+            let resp = self.request(
+                "open-ils.acq.lineitem_detail.create",
+                vec![json::from(li_id), detail],
+            )?;
+            Acq::check_event("open-ils.acq.lineitem_detail.create", resp)?;
+        }
+
+        Ok(li_id)
+    }
+
+    /// Activate a PO: encumber funds and mark lineitems on-order.
+    pub fn activate_purchase_order(&self, po_id: i64) -> Result<(), String> {
+        let method = "open-ils.acq.purchase_order.activate";
+        let resp = self.request(method, vec![json::from(po_id)])?;
+        Acq::check_event(method, resp).map(|_| ())
+    }
+
+    // -- Receiving ---------------------------------------------------
+
+    /// Receive a whole lineitem.
+    pub fn receive_lineitem(&self, li_id: i64) -> Result<(), String> {
+        let method = "open-ils.acq.lineitem.receive";
+        let resp = self.request(method, vec![json::from(li_id)])?;
+        Acq::check_event(method, resp).map(|_| ())
+    }
+
+    /// Receive one copy (lineitem detail).
+    pub fn receive_lineitem_detail(&self, lid_id: i64) -> Result<(), String> {
+        let method = "open-ils.acq.lineitem_detail.receive";
+        let resp = self.request(method, vec![json::from(lid_id)])?;
+        Acq::check_event(method, resp).map(|_| ())
+    }
+
+    /// Un-receive a lineitem (receiving rollback).
+    pub fn rollback_receive_lineitem(&self, li_id: i64) -> Result<(), String> {
+        let method = "open-ils.acq.lineitem.receive.rollback";
+        let resp = self.request(method, vec![json::from(li_id)])?;
+        Acq::check_event(method, resp).map(|_| ())
+    }
+
+    // -- Invoicing ---------------------------------------------------
+
+    /// Create an invoice with entries billing lineitems, in one API
+    /// call.  Entries are (lineitem ID, item count, cost billed).
+    pub fn create_invoice(
+        &self,
+        receiver: i64,
+        provider: i64,
+        inv_ident: &str,
+        entries: &[(i64, i64, f64)],
+    ) -> Result<JsonValue, String> {
+        let method = "open-ils.acq.invoice.update";
+
+        let invoice = json::object! {
+            "_classname": "acqinv",
+            isnew: "t",
+            receiver: receiver,
+            provider: provider,
+            shipper: provider,
+            inv_ident: inv_ident,
+            recv_date: chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%z").to_string(),
+        };
+
+        let mut entry_list = json::array![];
+
+        for (li_id, count, cost) in entries {
+            let entry = json::object! {
+                "_classname": "acqie",
+                isnew: "t",
+                lineitem: *li_id,
+                inv_item_count: *count,
+                phys_item_count: *count,
+                cost_billed: format!("{cost:.2}"),
+            };
+            entry_list.push(entry).expect("push to array succeeds");
+        }
+
+        let resp = self.request(method, vec![invoice, entry_list, json::array![]])?;
+        Acq::check_event(method, resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_event() {
+        let ok = json::object! {id: 1, provider: 2};
+        assert!(Acq::check_event("test", ok).is_ok());
+
+        let blocked = json::object! {
+            ilsevent: 7008, textcode: "ACQ_FUND_EXCEEDS_STOP_PERCENT", desc: "",
+        };
+        let err = Acq::check_event("test", blocked).unwrap_err();
+        assert!(err.contains("ACQ_FUND_EXCEEDS_STOP_PERCENT"));
+    }
+}
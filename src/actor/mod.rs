@@ -0,0 +1,3 @@
+//! Actor-domain (patron/staff/org) helpers.
+
+pub mod patron;
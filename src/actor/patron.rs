@@ -0,0 +1,256 @@
+//! Patron registration and maintenance helpers.
+//!
+//! Wraps open-ils.actor's fleshed patron update API for
+//! registration and profile edits, with direct handling of cards,
+//! addresses, and stat-cat assignments.
+
+use crate::editor::Editor;
+use crate::event::EgEvent;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::sync::Arc;
+
+const ACTOR_TIMEOUT: u64 = 60;
+
+/// Set the fieldmapper change-tracking flag on an object and any
+/// fleshed card/address lists, so the actor service knows what to
+/// write.  `flag` is "isnew", "ischanged", or "isdeleted".
+fn flag_object(obj: &mut JsonValue, flag: &str) {
+    obj[flag] = "t".into();
+
+    for key in ["card", "billing_address", "mailing_address"] {
+        if obj[key].is_object() {
+            obj[key][flag] = "t".into();
+        }
+    }
+
+    for key in ["cards", "addresses"] {
+        for entry in obj[key].members_mut() {
+            if entry.is_object() {
+                entry[flag] = "t".into();
+            }
+        }
+    }
+}
+
+/// Manages patron accounts for one authenticated session.
+pub struct PatronManager {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+}
+
+impl PatronManager {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        PatronManager {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// Call an open-ils.actor method and return its first response.
+    fn actor_request(&self, method: &str, params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        let session = self.client.session("open-ils.actor");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(ACTOR_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Send a fleshed patron object through the actor update API,
+    /// returning the stored patron.
+    fn patron_update(&self, patron: JsonValue) -> Result<JsonValue, String> {
+        let resp = self.actor_request(
+            "open-ils.actor.patron.update",
+            vec![json::from(self.authtoken.as_str()), patron],
+        )?;
+
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("Patron update failed: {evt}"));
+            }
+        }
+
+        Ok(resp)
+    }
+
+    /// Register a new patron.  The object may include a fleshed
+    /// "card" and "addresses"; everything is flagged new.
+    pub fn register(&self, mut patron: JsonValue) -> Result<JsonValue, String> {
+        patron[idl::CLASSNAME_KEY] = "au".into();
+        flag_object(&mut patron, "isnew");
+
+        self.patron_update(patron)
+    }
+
+    /// Apply changes to an existing patron object.
+    pub fn update(&self, mut patron: JsonValue) -> Result<JsonValue, String> {
+        if patron["id"].is_null() {
+            return Err("Patron update requires an id".to_string());
+        }
+
+        flag_object(&mut patron, "ischanged");
+
+        self.patron_update(patron)
+    }
+
+    /// Fetch a patron with cards and addresses fleshed, ready for
+    /// editing.
+    pub fn retrieve_fleshed(&mut self, patron_id: i64) -> Result<JsonValue, String> {
+        let mut patron = self
+            .editor
+            .retrieve("au", json::from(patron_id))?
+            .ok_or_else(|| format!("No such patron: {patron_id}"))?;
+
+        patron["cards"] =
+            JsonValue::Array(self.editor.search("ac", json::object! {usr: patron_id})?);
+        patron["addresses"] =
+            JsonValue::Array(self.editor.search("aua", json::object! {usr: patron_id})?);
+
+        Ok(patron)
+    }
+
+    /// Issue a replacement barcode: deactivate the patron's current
+    /// cards, create the new one, and make it primary.  Returns the
+    /// new card ID.
+    pub fn reissue_barcode(&mut self, patron_id: i64, barcode: &str) -> Result<i64, String> {
+        let existing = self
+            .editor
+            .search("ac", json::object! {usr: patron_id, active: "t"})?;
+
+        self.editor.xact_begin()?;
+
+        for mut card in existing {
+            card["active"] = "f".into();
+
+            let resp = self
+                .editor
+                .request("open-ils.cstore.direct.actor.card.update", vec![card]);
+
+            if let Err(e) = resp {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+        }
+
+        let card = json::object! {
+            "_classname": "ac",
+            usr: patron_id,
+            barcode: barcode,
+            active: "t",
+        };
+
+        let card = match self
+            .editor
+            .request("open-ils.cstore.direct.actor.card.create", vec![card])
+        {
+            Ok(c) => c,
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+        };
+
+        let card_id = util::json_int(&card["id"])?;
+
+        let mut patron = match self.editor.retrieve("au", json::from(patron_id))? {
+            Some(p) => p,
+            None => {
+                self.editor.xact_rollback()?;
+                return Err(format!("No such patron: {patron_id}"));
+            }
+        };
+
+        patron["card"] = card_id.into();
+
+        let resp = self
+            .editor
+            .request("open-ils.cstore.direct.actor.user.update", vec![patron]);
+
+        match resp {
+            Ok(_) => {
+                self.editor.commit()?;
+                Ok(card_id)
+            }
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Assign a stat-cat entry to a patron, replacing any existing
+    /// value for that stat cat.
+    pub fn assign_stat_cat(
+        &mut self,
+        patron_id: i64,
+        stat_cat: i64,
+        value: &str,
+    ) -> Result<(), String> {
+        let existing = self.editor.search(
+            "actscecm",
+            json::object! {target_usr: patron_id, stat_cat: stat_cat},
+        )?;
+
+        self.editor.xact_begin()?;
+
+        let resp = match existing.into_iter().next() {
+            Some(mut map) => {
+                map["stat_cat_entry"] = value.into();
+                self.editor.request(
+                    "open-ils.cstore.direct.actor.stat_cat_entry_user_map.update",
+                    vec![map],
+                )
+            }
+            None => {
+                let map = json::object! {
+                    "_classname": "actscecm",
+                    target_usr: patron_id,
+                    stat_cat: stat_cat,
+                    stat_cat_entry: value,
+                };
+                self.editor.request(
+                    "open-ils.cstore.direct.actor.stat_cat_entry_user_map.create",
+                    vec![map],
+                )
+            }
+        };
+
+        match resp {
+            Ok(_) => Ok(self.editor.commit()?),
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_object() {
+        let mut patron = json::object! {
+            family_name: "Example",
+            card: {barcode: "123"},
+            addresses: [{street1: "1 Main St"}],
+        };
+
+        flag_object(&mut patron, "isnew");
+
+        assert_eq!(patron["isnew"], "t");
+        assert_eq!(patron["card"]["isnew"], "t");
+        assert_eq!(patron["addresses"][0]["isnew"], "t");
+    }
+}
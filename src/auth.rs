@@ -0,0 +1,132 @@
+//! Authentication with open-ils.auth.
+
+use crate::event::EgEvent;
+use crate::osrf::Client;
+use crate::util;
+use json::JsonValue;
+
+const LOGIN_TIMEOUT: u64 = 30;
+
+/// Credentials and context for a login request.
+#[derive(Clone)]
+pub struct AuthLoginArgs {
+    pub username: String,
+    pub password: String,
+    pub login_type: String, // "staff", "opac", "temp", etc.
+    pub workstation: Option<String>,
+}
+
+impl AuthLoginArgs {
+    pub fn new(username: &str, password: &str, login_type: &str, workstation: Option<&str>) -> Self {
+        AuthLoginArgs {
+            username: username.to_string(),
+            password: password.to_string(),
+            login_type: login_type.to_string(),
+            workstation: workstation.map(|w| w.to_string()),
+        }
+    }
+}
+
+/// An authenticated login session.
+pub struct AuthSession {
+    token: String,
+    authtime: i64,
+    workstation: Option<String>,
+}
+
+impl AuthSession {
+    /// Login via open-ils.auth, returning None on login failure.
+    pub fn login(client: &Client, args: &AuthLoginArgs) -> Result<Option<AuthSession>, String> {
+        let session = client.session("open-ils.auth");
+
+        // Phase 1: fetch the auth seed.
+        let mut req = session.request(
+            "open-ils.auth.authenticate.init",
+            vec![json::from(args.username.as_str())],
+        )?;
+
+        let seed = match req.recv(LOGIN_TIMEOUT)? {
+            Some(s) => util::json_string(&s)?,
+            None => return Err("No auth seed returned".to_string()),
+        };
+
+        // Phase 2: complete the login with the seed-hashed password.
+        let password_md5 = format!("{:x}", md5::compute(args.password.as_bytes()));
+        let hashed = format!("{:x}", md5::compute(format!("{seed}{password_md5}").as_bytes()));
+
+        let mut params = json::object! {
+            username: args.username.as_str(),
+            password: hashed.as_str(),
+            type: args.login_type.as_str(),
+        };
+
+        if let Some(ws) = &args.workstation {
+            params["workstation"] = ws.as_str().into();
+        }
+
+        let mut req = session.request("open-ils.auth.authenticate.complete", vec![params])?;
+
+        let resp = match req.recv(LOGIN_TIMEOUT)? {
+            Some(r) => r,
+            None => return Err("No login response returned".to_string()),
+        };
+
+        let evt = match EgEvent::parse(&resp) {
+            Some(e) => e,
+            None => return Err(format!("Unexpected login response: {}", resp.dump())),
+        };
+
+        if !evt.is_success() {
+            log::warn!("Login failed for {}: {}", args.username, evt);
+            return Ok(None);
+        }
+
+        let token = match evt.payload()["authtoken"].as_str() {
+            Some(t) => t.to_string(),
+            None => return Err("Login response contains no authtoken".to_string()),
+        };
+
+        let authtime = util::json_int(&evt.payload()["authtime"]).unwrap_or(0);
+
+        Ok(Some(AuthSession {
+            token,
+            authtime,
+            workstation: args.workstation.clone(),
+        }))
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn authtime(&self) -> i64 {
+        self.authtime
+    }
+
+    pub fn workstation(&self) -> Option<&str> {
+        self.workstation.as_deref()
+    }
+
+    /// Retrieve the session's user object, or None if the session has
+    /// expired.
+    pub fn session_user(client: &Client, token: &str) -> Result<Option<JsonValue>, String> {
+        let session = client.session("open-ils.auth");
+
+        let mut req = session.request(
+            "open-ils.auth.session.retrieve",
+            vec![json::from(token)],
+        )?;
+
+        let user = match req.recv(LOGIN_TIMEOUT)? {
+            Some(u) => u,
+            None => return Ok(None),
+        };
+
+        if let Some(evt) = EgEvent::parse(&user) {
+            log::debug!("Session retrieve returned: {evt}");
+            return Ok(None);
+        }
+
+        Ok(Some(user))
+    }
+}
@@ -0,0 +1,451 @@
+//! Link bib record headings to authority records.
+
+use crate::editor::Editor;
+use crate::marc;
+use crate::norm::Normalizer;
+use crate::util;
+use json::JsonValue;
+
+/// Maps controlled bib fields to the authority fields that control
+/// them.
+const CONTROLLED_FIELDS: &[(&str, &str)] = &[
+    ("100", "100"),
+    ("110", "110"),
+    ("111", "111"),
+    ("130", "130"),
+    ("600", "100"),
+    ("610", "110"),
+    ("611", "111"),
+    ("630", "130"),
+    ("650", "150"),
+    ("651", "151"),
+    ("655", "155"),
+    ("700", "100"),
+    ("710", "110"),
+    ("711", "111"),
+    ("730", "130"),
+];
+
+/// Subfields included when building a heading string for matching.
+const HEADING_SUBFIELDS: &str = "abcdgqtvxyz";
+
+/// Authority 1XX tags that carry the established heading.
+const AUTH_HEADING_TAGS: &[&str] = &["100", "110", "111", "130", "150", "151", "155"];
+
+/// The established heading field of an authority record.
+pub fn heading_field(record: &marc::Record) -> Option<&marc::Field> {
+    AUTH_HEADING_TAGS
+        .iter()
+        .find_map(|tag| record.first_field(tag))
+}
+
+/// Returns the controlling authority tag for a bib field tag.
+pub fn auth_tag_for(bib_tag: &str) -> Option<&'static str> {
+    CONTROLLED_FIELDS
+        .iter()
+        .find(|(bib, _)| *bib == bib_tag)
+        .map(|(_, auth)| *auth)
+}
+
+/// Summary of one linker run.
+#[derive(Debug, Default)]
+pub struct LinkerCounts {
+    pub bibs_scanned: usize,
+    pub bibs_updated: usize,
+    pub headings_linked: usize,
+}
+
+/// Scans bib records for controlled headings and adds $0 links to
+/// matching authority records.
+pub struct Linker {
+    editor: Editor,
+    normalizer: Normalizer,
+    counts: LinkerCounts,
+}
+
+impl Linker {
+    pub fn new(editor: Editor) -> Self {
+        Linker {
+            editor,
+            normalizer: Normalizer::new(),
+            counts: LinkerCounts::default(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    pub fn counts(&self) -> &LinkerCounts {
+        &self.counts
+    }
+
+    /// Build the heading string for a controlled bib field.
+    pub fn field_heading(field: &marc::Field) -> String {
+        let mut parts = Vec::new();
+        for sf in field.subfields() {
+            if HEADING_SUBFIELDS.contains(sf.code()) {
+                parts.push(sf.content().trim());
+            }
+        }
+        parts.join(" ")
+    }
+
+    /// Link the controlled headings of one bib record, updating the
+    /// record if any new links are found.
+    pub fn link_bib(&mut self, bib_id: i64) -> Result<bool, String> {
+        self.counts.bibs_scanned += 1;
+
+        let bre = match self.editor.retrieve("bre", json::from(bib_id))? {
+            Some(b) => b,
+            None => return Err(format!("No such bib record: {bib_id}")),
+        };
+
+        if util::json_bool(&bre["deleted"]) {
+            log::debug!("Skipping deleted bib {bib_id}");
+            return Ok(false);
+        }
+
+        let marc_xml = util::json_string(&bre["marc"])?;
+        let mut record = marc::Record::from_xml(&marc_xml)?;
+
+        let mut changed = false;
+        let mut linked = 0;
+
+        for field in record.fields_mut() {
+            let auth_tag = match auth_tag_for(field.tag()) {
+                Some(t) => t,
+                None => continue,
+            };
+
+            if field.first_subfield("0").is_some() {
+                continue; // already linked
+            }
+
+            let heading = Linker::field_heading(field);
+            if heading.is_empty() {
+                continue;
+            }
+
+            if let Some(auth_id) = self.find_authority(auth_tag, &heading)? {
+                field.set_subfield("0", &auth_id.to_string());
+                changed = true;
+                linked += 1;
+            }
+        }
+
+        if !changed {
+            return Ok(false);
+        }
+
+        let mut bre = bre;
+        bre["marc"] = record.to_xml().into();
+        self.update_bib(bre)?;
+
+        self.counts.bibs_updated += 1;
+        self.counts.headings_linked += linked;
+
+        Ok(true)
+    }
+
+    /// Find a non-deleted authority record whose simple heading
+    /// matches the normalized heading string.
+    fn find_authority(&mut self, _auth_tag: &str, heading: &str) -> Result<Option<i64>, String> {
+        let normalized = self.normalizer.naco_normalize(heading);
+
+        let headings = self.editor.search(
+            "ash",
+            json::object! {sort_value: normalized.as_str()},
+        )?;
+
+        for hrow in headings {
+            let record_id = util::json_int(&hrow["record"])?;
+
+            // We only need to know the record exists and is not
+            // deleted; skip fetching the whole authority record.
+            let hits = self
+                .editor
+                .search_ids("are", json::object! {id: record_id, deleted: "f"})?;
+
+            if hits.is_empty() {
+                continue;
+            }
+
+            return Ok(Some(record_id));
+        }
+
+        Ok(None)
+    }
+
+    /// Apply a bib record update within its own transaction.
+    fn update_bib(&mut self, bre: JsonValue) -> Result<(), String> {
+        self.editor.xact_begin()?;
+
+        let resp = self
+            .editor
+            .request("open-ils.cstore.direct.biblio.record_entry.update", vec![bre]);
+
+        match resp {
+            Ok(_) => Ok(self.editor.commit()?),
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Summary of one propagation run.
+#[derive(Debug, Default)]
+pub struct PropagatorCounts {
+    pub auths_processed: usize,
+    pub headings_rebuilt: usize,
+    pub bibs_updated: usize,
+    pub errors: usize,
+}
+
+/// Pushes new/updated authority records out to the rest of the
+/// system: regenerates simple headings and rewrites the controlled
+/// subfields of linked bib fields to match the established heading.
+pub struct Propagator {
+    editor: Editor,
+    normalizer: Normalizer,
+    counts: PropagatorCounts,
+    /// Bib updates are committed in batches this large.
+    batch_size: usize,
+    pending_in_batch: usize,
+}
+
+impl Propagator {
+    pub fn new(editor: Editor) -> Self {
+        Propagator {
+            editor,
+            normalizer: Normalizer::new(),
+            counts: PropagatorCounts::default(),
+            batch_size: 100,
+            pending_in_batch: 0,
+        }
+    }
+
+    pub fn counts(&self) -> &PropagatorCounts {
+        &self.counts
+    }
+
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Ingest one authority record: rebuild its simple headings,
+    /// then propagate its heading to linked bib fields.
+    pub fn process_authority(&mut self, auth_id: i64) -> Result<(), String> {
+        self.counts.auths_processed += 1;
+
+        let are = match self.editor.retrieve("are", json::from(auth_id))? {
+            Some(a) => a,
+            None => return Err(format!("No such authority record: {auth_id}")),
+        };
+
+        let marc_xml = util::json_string(&are["marc"])?;
+        let record = marc::Record::from_xml(&marc_xml)?;
+
+        self.rebuild_headings(auth_id, &record, util::json_bool(&are["deleted"]))?;
+
+        if !util::json_bool(&are["deleted"]) {
+            self.propagate(auth_id, &record)?;
+        }
+
+        self.flush_batch()
+    }
+
+    /// Replace the simple heading rows for an authority record.
+    /// Deleted authorities just lose their headings.
+    fn rebuild_headings(
+        &mut self,
+        auth_id: i64,
+        record: &marc::Record,
+        deleted: bool,
+    ) -> Result<(), String> {
+        let existing = self
+            .editor
+            .search("ash", json::object! {record: auth_id})?;
+
+        self.editor.xact_begin()?;
+
+        for heading in existing {
+            let resp = self.editor.request(
+                "open-ils.cstore.direct.authority.simple_heading.delete",
+                vec![heading],
+            );
+
+            if let Err(e) = resp {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+        }
+
+        if !deleted {
+            if let Some(field) = heading_field(record) {
+                let value = Linker::field_heading(field);
+                let sort_value = self.normalizer.naco_normalize(&value);
+
+                let heading = json::object! {
+                    "_classname": "ash",
+                    record: auth_id,
+                    value: value.as_str(),
+                    sort_value: sort_value.as_str(),
+                };
+
+                let resp = self.editor.request(
+                    "open-ils.cstore.direct.authority.simple_heading.create",
+                    vec![heading],
+                );
+
+                if let Err(e) = resp {
+                    self.editor.xact_rollback()?;
+                    return Err(e.into());
+                }
+
+                self.counts.headings_rebuilt += 1;
+            }
+        }
+
+        Ok(self.editor.commit()?)
+    }
+
+    /// Rewrite the controlled subfields of every bib field linked
+    /// ($0) to this authority so they match the established heading.
+    fn propagate(&mut self, auth_id: i64, auth_record: &marc::Record) -> Result<(), String> {
+        let auth_field = match heading_field(auth_record) {
+            Some(f) => f,
+            None => return Ok(()),
+        };
+
+        // Linked bibs are found via the flattened record index.
+        let hits = self.editor.search(
+            "mfr",
+            json::object! {
+                subfield: "0",
+                value: auth_id.to_string(),
+            },
+        )?;
+
+        for hit in hits {
+            let bib_id = util::json_int(&hit["record"])?;
+
+            if let Err(e) = self.propagate_to_bib(auth_id, auth_field, bib_id) {
+                self.counts.errors += 1;
+                log::error!("Error propagating authority {auth_id} to bib {bib_id}: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn propagate_to_bib(
+        &mut self,
+        auth_id: i64,
+        auth_field: &marc::Field,
+        bib_id: i64,
+    ) -> Result<(), String> {
+        let bre = match self.editor.retrieve("bre", json::from(bib_id))? {
+            Some(b) => b,
+            None => return Err(format!("No such bib record: {bib_id}")),
+        };
+
+        if util::json_bool(&bre["deleted"]) {
+            return Ok(());
+        }
+
+        let marc_xml = util::json_string(&bre["marc"])?;
+        let mut record = marc::Record::from_xml(&marc_xml)?;
+
+        let auth_link = auth_id.to_string();
+        let mut changed = false;
+
+        for field in record.fields_mut() {
+            if auth_tag_for(field.tag()).is_none() {
+                continue;
+            }
+
+            if field.first_subfield("0") != Some(auth_link.as_str()) {
+                continue;
+            }
+
+            if Propagator::rewrite_heading(field, auth_field) {
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return Ok(());
+        }
+
+        let mut bre = bre;
+        bre["marc"] = record.to_xml().into();
+
+        self.update_bib_batched(bre)?;
+        self.counts.bibs_updated += 1;
+
+        Ok(())
+    }
+
+    /// Swap a bib field's heading subfields for the authority's,
+    /// keeping non-heading subfields ($0, $e, ...) in place.
+    /// Returns true if the field changed.
+    fn rewrite_heading(bib_field: &mut marc::Field, auth_field: &marc::Field) -> bool {
+        if Linker::field_heading(bib_field) == Linker::field_heading(auth_field) {
+            return false;
+        }
+
+        let mut subfields: Vec<marc::Subfield> = auth_field
+            .subfields()
+            .iter()
+            .filter(|sf| HEADING_SUBFIELDS.contains(sf.code()))
+            .cloned()
+            .collect();
+
+        for sf in bib_field.subfields() {
+            if !HEADING_SUBFIELDS.contains(sf.code()) {
+                subfields.push(sf.clone());
+            }
+        }
+
+        *bib_field.subfields_mut() = subfields;
+        true
+    }
+
+    /// Apply a bib update within the running batch transaction.
+    fn update_bib_batched(&mut self, bre: JsonValue) -> Result<(), String> {
+        if !self.editor.in_transaction() {
+            self.editor.xact_begin()?;
+        }
+
+        let resp = self
+            .editor
+            .request("open-ils.cstore.direct.biblio.record_entry.update", vec![bre]);
+
+        match resp {
+            Ok(_) => {
+                self.pending_in_batch += 1;
+                if self.pending_in_batch >= self.batch_size {
+                    self.flush_batch()?;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                self.pending_in_batch = 0;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Commit any open batch transaction.
+    pub fn flush_batch(&mut self) -> Result<(), String> {
+        if self.editor.in_transaction() {
+            self.editor.commit()?;
+        }
+        self.pending_in_batch = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+//! Barcode check digits, completion, and batch generation.
+//!
+//! Supports the check-digit schemes in common library use -- mod10
+//! (Luhn, used by 14-digit Codabar barcodes) and mod43 (Code 39) --
+//! plus org-configurable prefix padding ("barcode completion") and
+//! bulk generation of unused card/item barcodes.
+
+use crate::editor::Editor;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::settings::Settings;
+use std::sync::Arc;
+
+/// The Code 39 character set, in mod43 value order.
+const MOD43_CHARSET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+
+/// Codabar patron/item barcodes are 14 digits, mod10-checked.
+const CODABAR_LENGTH: usize = 14;
+
+/// How many candidates to try per requested barcode before giving up
+/// during batch generation.
+const GENERATION_SLACK: usize = 10;
+
+/// Compute the mod10 (Luhn) check digit for a digit string.
+pub fn mod10_check_digit(digits: &str) -> Result<char, String> {
+    if digits.is_empty() {
+        return Err("mod10 requires at least one digit".to_string());
+    }
+
+    let mut sum = 0;
+    for (idx, ch) in digits.chars().rev().enumerate() {
+        let mut value = ch
+            .to_digit(10)
+            .ok_or_else(|| format!("mod10 requires digits, found {ch}"))?;
+
+        if idx % 2 == 0 {
+            value *= 2;
+            if value > 9 {
+                value -= 9;
+            }
+        }
+
+        sum += value;
+    }
+
+    let check = (10 - sum % 10) % 10;
+    Ok(char::from_digit(check, 10).unwrap())
+}
+
+/// True if the final character is a correct mod10 check digit for
+/// the preceding digits.
+pub fn validate_mod10(barcode: &str) -> bool {
+    if barcode.len() < 2 {
+        return false;
+    }
+
+    let (body, check) = barcode.split_at(barcode.len() - 1);
+
+    match mod10_check_digit(body) {
+        Ok(digit) => check == digit.to_string(),
+        Err(_) => false,
+    }
+}
+
+/// True if this looks like a valid Codabar barcode: 14 digits with a
+/// correct mod10 check digit.
+pub fn validate_codabar(barcode: &str) -> bool {
+    barcode.len() == CODABAR_LENGTH
+        && barcode.chars().all(|c| c.is_ascii_digit())
+        && validate_mod10(barcode)
+}
+
+/// Compute the mod43 (Code 39) check character for a value.
+pub fn mod43_check_digit(value: &str) -> Result<char, String> {
+    if value.is_empty() {
+        return Err("mod43 requires at least one character".to_string());
+    }
+
+    let mut sum = 0;
+    for ch in value.chars() {
+        let ch = ch.to_ascii_uppercase();
+        sum += MOD43_CHARSET
+            .find(ch)
+            .ok_or_else(|| format!("Character not in the Code 39 set: {ch}"))?;
+    }
+
+    Ok(MOD43_CHARSET.as_bytes()[sum % 43] as char)
+}
+
+/// True if the final character is a correct mod43 check character
+/// for the preceding value.
+pub fn validate_mod43(barcode: &str) -> bool {
+    if barcode.len() < 2 {
+        return false;
+    }
+
+    let (body, check) = barcode.split_at(barcode.len() - 1);
+
+    match mod43_check_digit(body) {
+        Ok(digit) => check.to_ascii_uppercase() == digit.to_string(),
+        Err(_) => false,
+    }
+}
+
+/// Complete a partial barcode: prepend the prefix and zero-pad the
+/// input up to the target length.  Inputs already at or past the
+/// target length pass through unchanged.
+pub fn complete(input: &str, prefix: &str, length: usize) -> String {
+    if input.len() >= length {
+        return input.to_string();
+    }
+
+    let padding = length.saturating_sub(prefix.len() + input.len());
+    format!("{prefix}{}{input}", "0".repeat(padding))
+}
+
+/// The barcode namespaces we complete and generate within.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarcodeKind {
+    Item,
+    Patron,
+}
+
+impl BarcodeKind {
+    /// Org setting naming this kind's completion prefix.
+    pub fn prefix_setting(&self) -> &'static str {
+        match self {
+            Self::Item => "cat.barcode.item.prefix",
+            Self::Patron => "cat.barcode.patron.prefix",
+        }
+    }
+
+    /// Org setting naming this kind's completed barcode length.
+    pub fn length_setting(&self) -> &'static str {
+        match self {
+            Self::Item => "cat.barcode.item.length",
+            Self::Patron => "cat.barcode.patron.length",
+        }
+    }
+
+    /// The IDL class holding barcodes of this kind.
+    pub fn idl_class(&self) -> &'static str {
+        match self {
+            Self::Item => "acp",
+            Self::Patron => "ac",
+        }
+    }
+}
+
+impl std::str::FromStr for BarcodeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "item" | "copy" => Ok(Self::Item),
+            "patron" | "card" => Ok(Self::Patron),
+            _ => Err(format!("Unknown barcode kind: {s}")),
+        }
+    }
+}
+
+/// Completes and generates barcodes for one authenticated session.
+pub struct Barcodes {
+    editor: Editor,
+    settings: Settings,
+}
+
+impl Barcodes {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Barcodes {
+            editor: Editor::with_auth(client, idl, authtoken),
+            settings: Settings::new(client, idl, authtoken),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// The org's completion shape for a barcode kind:
+    /// (prefix, length).  Unset orgs get no prefix and the Codabar
+    /// length.
+    fn completion_shape(
+        &mut self,
+        org_id: i64,
+        kind: BarcodeKind,
+    ) -> Result<(String, usize), String> {
+        let prefix = self
+            .settings
+            .ou_setting(org_id, kind.prefix_setting())?
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+
+        let length = self
+            .settings
+            .ou_setting(org_id, kind.length_setting())?
+            .as_usize()
+            .unwrap_or(CODABAR_LENGTH);
+
+        Ok((prefix, length))
+    }
+
+    /// Complete a partial barcode per the org's settings.
+    pub fn complete_barcode(
+        &mut self,
+        org_id: i64,
+        kind: BarcodeKind,
+        input: &str,
+    ) -> Result<String, String> {
+        let (prefix, length) = self.completion_shape(org_id, kind)?;
+        Ok(complete(input, &prefix, length))
+    }
+
+    /// True if no row of the kind's class carries this barcode.
+    pub fn is_unused(&mut self, kind: BarcodeKind, barcode: &str) -> Result<bool, String> {
+        let hits = self
+            .editor
+            .search(kind.idl_class(), json::object! {barcode: barcode})?;
+        Ok(hits.is_empty())
+    }
+
+    /// Generate `count` unused barcodes starting the sequence at
+    /// `start`, completed per the org's settings with a trailing
+    /// mod10 check digit.
+    pub fn generate_unused(
+        &mut self,
+        org_id: i64,
+        kind: BarcodeKind,
+        start: i64,
+        count: usize,
+    ) -> Result<Vec<String>, String> {
+        let (prefix, length) = self.completion_shape(org_id, kind)?;
+
+        let mut barcodes = Vec::new();
+        let mut seq = start;
+        let mut attempts = 0;
+
+        while barcodes.len() < count {
+            if attempts >= count * GENERATION_SLACK {
+                return Err(format!(
+                    "Gave up after {attempts} attempts; {} barcodes generated",
+                    barcodes.len()
+                ));
+            }
+            attempts += 1;
+
+            // Leave room for the check digit.
+            let body = complete(&seq.to_string(), &prefix, length.saturating_sub(1));
+            seq += 1;
+
+            let check = match mod10_check_digit(&body) {
+                Ok(c) => c,
+                Err(_) => continue, // prefix contains non-digits
+            };
+
+            let barcode = format!("{body}{check}");
+
+            if self.is_unused(kind, &barcode)? {
+                barcodes.push(barcode);
+            }
+        }
+
+        Ok(barcodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mod10() {
+        assert_eq!(mod10_check_digit("2000000012345").unwrap(), '1');
+        assert!(validate_mod10("20000000123451"));
+        assert!(!validate_mod10("20000000123452"));
+        assert!(validate_codabar("20000000123451"));
+        assert!(!validate_codabar("2000000012345")); // 13 digits
+        assert!(mod10_check_digit("12A4").is_err());
+    }
+
+    #[test]
+    fn test_mod43() {
+        assert_eq!(mod43_check_digit("CODE39").unwrap(), 'W');
+        assert!(validate_mod43("CODE39W"));
+        assert!(!validate_mod43("CODE39X"));
+        assert!(mod43_check_digit("code_39").is_err());
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(complete("12345", "31234", 14), "31234000012345");
+        assert_eq!(complete("31234000012345", "31234", 14), "31234000012345");
+        assert_eq!(complete("12345", "", 8), "00012345");
+    }
+}
@@ -0,0 +1,139 @@
+//! Authority record deduplication tool.
+//!
+//! Groups authority records sharing a normalized heading and
+//! thesaurus, then exports the groups for review or merges them,
+//! re-pointing bib $0 links to the surviving record.
+
+use evergreen as eg;
+
+use eg::dedup::{AuthorityDeduper, MatchGroup};
+use eg::editor::Editor;
+use eg::util;
+use std::env;
+use std::fs;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-auth-dedup [options]
+
+Options:
+
+    --auth-id <id>
+        Consider only this authority record.  Repeatable.  Defaults
+        to every non-deleted authority record.
+
+    --export <file>
+        Write the match groups to <file> as tab-separated
+        group/lead/members rows instead of printing them.
+
+    --auto-merge
+        Merge each group's subordinate records into its lead record.
+        Without this flag groups are only reported.
+"#;
+
+fn export_text(groups: &[MatchGroup]) -> String {
+    let mut text = String::from("group\tlead\tmembers\n");
+
+    for (idx, group) in groups.iter().enumerate() {
+        let members: Vec<String> = group.records().iter().map(|id| id.to_string()).collect();
+        text += &format!("{idx}\t{}\t{}\n", group.lead(), members.join(","));
+    }
+
+    text
+}
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "auto-merge", "");
+    opts.optmulti("", "auth-id", "", "");
+    opts.optopt("", "export", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let mut deduper = AuthorityDeduper::new(Editor::new(ctx.client(), ctx.idl()));
+
+    let auth_ids: Vec<i64> = if params.opt_present("auth-id") {
+        params
+            .opt_strs("auth-id")
+            .iter()
+            .map(|id| {
+                id.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid --auth-id value: {id}");
+                    process::exit(1);
+                })
+            })
+            .collect()
+    } else {
+        let auths = deduper
+            .editor_mut()
+            .search("are", json::object! {deleted: "f", id: {">": 0}})
+            .unwrap_or_else(|e| {
+                eprintln!("Cannot list authority records: {e}");
+                process::exit(1);
+            });
+
+        auths
+            .iter()
+            .filter_map(|a| util::json_int(&a["id"]).ok())
+            .collect()
+    };
+
+    let groups = deduper.find_groups(&auth_ids).unwrap_or_else(|e| {
+        eprintln!("Grouping failed: {e}");
+        process::exit(1);
+    });
+
+    let export = export_text(&groups);
+
+    match params.opt_str("export") {
+        Some(file) => {
+            if let Err(e) = fs::write(&file, &export) {
+                eprintln!("Cannot write {file}: {e}");
+                process::exit(1);
+            }
+            println!("Wrote {} groups to {file}", groups.len());
+        }
+        None => print!("{export}"),
+    }
+
+    let mut merge_errors = 0;
+
+    if params.opt_present("auto-merge") {
+        for group in &groups {
+            if let Err(e) = deduper.merge_group(group) {
+                merge_errors += 1;
+                log::error!("Cannot merge group led by {}: {e}", group.lead());
+            }
+        }
+    }
+
+    let counts = deduper.counts();
+    println!(
+        "Scanned {} records; found {} groups; merged {} records; errors {}",
+        counts.records_scanned,
+        counts.groups_found,
+        counts.records_merged,
+        counts.errors + merge_errors
+    );
+
+    if counts.errors + merge_errors > 0 {
+        process::exit(1);
+    }
+}
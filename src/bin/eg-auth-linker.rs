@@ -0,0 +1,104 @@
+//! Link controlled bib headings to authority records by adding $0
+//! subfields.
+
+use evergreen as eg;
+
+use eg::authority::Linker;
+use eg::editor::Editor;
+use eg::util;
+use std::env;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-auth-linker [options]
+
+Options:
+
+    --bib-id <id>
+        Link a specific bib record.  Repeatable.
+
+    --start-id <id>
+    --end-id <id>
+        Link all non-deleted bib records in the ID range.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optmulti("", "bib-id", "", "");
+    opts.optopt("", "start-id", "", "");
+    opts.optopt("", "end-id", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let mut bib_ids: Vec<i64> = params
+        .opt_strs("bib-id")
+        .iter()
+        .filter_map(|id| id.parse::<i64>().ok())
+        .collect();
+
+    let mut editor = Editor::new(ctx.client(), ctx.idl());
+
+    if let (Some(start), Some(end)) = (params.opt_str("start-id"), params.opt_str("end-id")) {
+        let start: i64 = start.parse().expect("--start-id should be numeric");
+        let end: i64 = end.parse().expect("--end-id should be numeric");
+
+        let bibs = editor
+            .search(
+                "bre",
+                json::object! {
+                    id: {">=": start},
+                    deleted: "f",
+                },
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("Error fetching bib range: {e}");
+                process::exit(1);
+            });
+
+        for bib in bibs {
+            if let Ok(id) = util::json_int(&bib["id"]) {
+                if id <= end {
+                    bib_ids.push(id);
+                }
+            }
+        }
+    }
+
+    if bib_ids.is_empty() {
+        eprintln!("Nothing to do.  See --help");
+        return;
+    }
+
+    let mut linker = Linker::new(editor);
+
+    for bib_id in bib_ids {
+        match linker.link_bib(bib_id) {
+            Ok(true) => println!("bib {bib_id}: updated"),
+            Ok(false) => println!("bib {bib_id}: no changes"),
+            Err(e) => eprintln!("bib {bib_id}: error: {e}"),
+        }
+    }
+
+    let counts = linker.counts();
+    println!(
+        "Scanned {} bibs; updated {}; linked {} headings",
+        counts.bibs_scanned, counts.bibs_updated, counts.headings_linked
+    );
+}
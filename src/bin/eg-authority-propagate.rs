@@ -0,0 +1,98 @@
+//! Regenerate authority simple headings and push heading changes
+//! out to linked bib records.
+
+use evergreen as eg;
+
+use eg::authority::Propagator;
+use eg::editor::Editor;
+use eg::util;
+use std::env;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-authority-propagate [options]
+
+Options:
+
+    --auth-id <id>
+        Process a specific authority record.  Repeatable.
+
+    --modified-since <ISO timestamp>
+        Process all authority records edited since this time.
+
+    --batch-size <n>
+        Commit bib updates in batches this large.  Default 100.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optmulti("", "auth-id", "", "");
+    opts.optopt("", "modified-since", "", "");
+    opts.optopt("", "batch-size", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let mut auth_ids: Vec<i64> = params
+        .opt_strs("auth-id")
+        .iter()
+        .filter_map(|id| id.parse::<i64>().ok())
+        .collect();
+
+    let mut editor = Editor::new(ctx.client(), ctx.idl());
+
+    if let Some(since) = params.opt_str("modified-since") {
+        let auths = editor
+            .search("are", json::object! {edit_date: {">=": since.as_str()}})
+            .unwrap_or_else(|e| {
+                eprintln!("Error fetching modified authorities: {e}");
+                process::exit(1);
+            });
+
+        for auth in auths {
+            if let Ok(id) = util::json_int(&auth["id"]) {
+                auth_ids.push(id);
+            }
+        }
+    }
+
+    if auth_ids.is_empty() {
+        eprintln!("Nothing to do.  See --help");
+        return;
+    }
+
+    let mut propagator = Propagator::new(editor);
+
+    if let Some(size) = params.opt_str("batch-size").and_then(|v| v.parse().ok()) {
+        propagator.set_batch_size(size);
+    }
+
+    for auth_id in auth_ids {
+        match propagator.process_authority(auth_id) {
+            Ok(()) => println!("authority {auth_id}: done"),
+            Err(e) => eprintln!("authority {auth_id}: error: {e}"),
+        }
+    }
+
+    let counts = propagator.counts();
+    println!(
+        "Processed {} authorities; rebuilt {} headings; updated {} bibs; {} errors",
+        counts.auths_processed, counts.headings_rebuilt, counts.bibs_updated, counts.errors
+    );
+}
@@ -0,0 +1,145 @@
+//! Emergency closing processor.
+//!
+//! Applies a closed-date range to an org unit: optionally records
+//! the closure, slides affected due dates and hold shelf
+//! expirations past it, and voids overdue fines accrued during it.
+
+use evergreen as eg;
+
+use eg::calendar::ClosedRange;
+use eg::closing::Closing;
+use eg::date::parse_timestamp;
+use eg::editor::Editor;
+use eg::money::Money;
+use std::env;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-closing --org <org-id> --start <ts> --end <ts> [options]
+
+Options:
+
+    --org <org-id>
+        The closing org unit.  Required.
+
+    --start <ISO timestamp>
+    --end <ISO timestamp>
+        The closed range.  Required.
+
+    --reason <text>
+        Why the org is closed; recorded on the closure and on voided
+        billings.
+
+    --create-closure
+        Also create the actor.org_unit.closed_date row.
+
+    --dry-run
+        Report the changes without applying them.
+
+    --batch-size <n>
+        Commit updates in batches this large.  Default 100.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "create-closure", "");
+    opts.optflag("", "dry-run", "");
+    opts.optopt("", "org", "", "");
+    opts.optopt("", "start", "", "");
+    opts.optopt("", "end", "", "");
+    opts.optopt("", "reason", "", "");
+    opts.optopt("", "batch-size", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let org_id: i64 = params
+        .opt_str("org")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| {
+            eprintln!("--org required");
+            process::exit(1);
+        });
+
+    let mut range_bounds = Vec::new();
+    for opt in ["start", "end"] {
+        let value = params.opt_str(opt).unwrap_or_else(|| {
+            eprintln!("--{opt} required");
+            process::exit(1);
+        });
+
+        range_bounds.push(parse_timestamp(&value).unwrap_or_else(|e| {
+            eprintln!("Invalid --{opt} value: {e}");
+            process::exit(1);
+        }));
+    }
+
+    let range = ClosedRange {
+        start: range_bounds[0],
+        end: range_bounds[1],
+        reason: params
+            .opt_str("reason")
+            .unwrap_or_else(|| "Emergency closing".to_string()),
+    };
+
+    if range.end < range.start {
+        eprintln!("--end precedes --start");
+        process::exit(1);
+    }
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let editor = Editor::new(ctx.client(), ctx.idl());
+    let mut closing = Closing::new(editor, org_id, range, params.opt_present("dry-run"));
+
+    if let Some(size) = params.opt_str("batch-size").and_then(|v| v.parse().ok()) {
+        closing.set_batch_size(size);
+    }
+
+    if params.opt_present("create-closure") {
+        if let Err(e) = closing.create_closure() {
+            eprintln!("Cannot create closure: {e}");
+            process::exit(1);
+        }
+    }
+
+    if let Err(e) = closing.process() {
+        eprintln!("Closing processing failed: {e}");
+        process::exit(1);
+    }
+
+    for line in closing.report() {
+        println!("{line}");
+    }
+
+    let counts = closing.counts();
+    println!(
+        "Examined {} circs ({} due dates moved, {} billings voided totaling {}); \
+         examined {} holds ({} shelf expirations moved); {} errors",
+        counts.circs_examined,
+        counts.due_dates_moved,
+        counts.billings_voided,
+        Money::from_cents(counts.amount_voided_cents),
+        counts.holds_examined,
+        counts.shelf_expirations_moved,
+        counts.errors
+    );
+
+    if counts.errors > 0 {
+        process::exit(1);
+    }
+}
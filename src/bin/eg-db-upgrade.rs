@@ -0,0 +1,96 @@
+//! Database schema upgrade runner.
+//!
+//! Applies the pending SQL upgrade scripts from a directory in
+//! version order, recording each in config.upgrade_log.
+
+use evergreen as eg;
+
+use eg::db::DatabaseConnection;
+use eg::upgrade::{scan_dir, Upgrader};
+use std::env;
+use std::path::Path;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-db-upgrade --script-dir <dir> [options]
+
+Options:
+
+    --script-dir <dir>
+        Directory of <version>.<description>.sql upgrade scripts.
+        Required.
+
+    --dry-run
+        Report the pending scripts without applying them.
+
+    --stop-on-error
+        Abort at the first failing script instead of continuing.
+
+    --db-host / --db-port / --db-user / --db-name
+        Database connection overrides; PG* environment variables are
+        the fallback.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "dry-run", "");
+    opts.optflag("", "stop-on-error", "");
+    opts.optopt("", "script-dir", "", "");
+    DatabaseConnection::append_options(&mut opts);
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let dir = params.opt_str("script-dir").unwrap_or_else(|| {
+        eprintln!("--script-dir required");
+        process::exit(1);
+    });
+
+    let scripts = scan_dir(Path::new(&dir)).unwrap_or_else(|e| {
+        eprintln!("Cannot scan {dir}: {e}");
+        process::exit(1);
+    });
+
+    if scripts.is_empty() {
+        println!("No upgrade scripts found in {dir}");
+        return;
+    }
+
+    let mut upgrader = Upgrader::new(DatabaseConnection::new_from_options(&params));
+
+    if let Err(e) = upgrader.connect() {
+        eprintln!("Cannot connect to database: {e}");
+        process::exit(1);
+    }
+
+    let counts = upgrader
+        .run(
+            &scripts,
+            params.opt_present("dry-run"),
+            params.opt_present("stop-on-error"),
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("Upgrade failed: {e}");
+            process::exit(1);
+        });
+
+    println!(
+        "Applied {} scripts; {} already applied; {} errors",
+        counts.applied, counts.skipped, counts.errors
+    );
+
+    if counts.errors > 0 {
+        process::exit(1);
+    }
+}
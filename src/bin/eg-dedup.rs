@@ -0,0 +1,199 @@
+//! Bib record deduplication tool.
+//!
+//! Fingerprints bib records, groups likely duplicates, and either
+//! exports the groups for review or merges them into their lead
+//! records.
+
+use evergreen as eg;
+
+use eg::auth::{AuthLoginArgs, AuthSession};
+use eg::dedup::{Deduper, MatchGroup, DEFAULT_THRESHOLD};
+use eg::util;
+use std::env;
+use std::fs;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-dedup [options]
+
+Options:
+
+    --bib-id <id>
+        Consider only this bib record.  Repeatable.  Defaults to
+        every non-deleted bib record.
+
+    --threshold <score>
+        Minimum pairwise match score (0-100) for two records to be
+        grouped.  Defaults to 90: shared ISBN plus matching title.
+
+    --export <file>
+        Write the match groups to <file> as tab-separated
+        group/score/lead/members rows instead of printing them.
+
+    --auto-merge
+        Merge each group's subordinate records into its lead record.
+        Without this flag groups are only reported.
+
+    --username <username>
+    --password <password>
+    --workstation <workstation>
+        Staff credentials.  The password may also be supplied via
+        EG_DEDUP_PASSWORD.
+"#;
+
+fn export_text(groups: &[MatchGroup]) -> String {
+    let mut text = String::from("group\tscore\tlead\tmembers\n");
+
+    for (idx, group) in groups.iter().enumerate() {
+        let members: Vec<String> = group.records().iter().map(|id| id.to_string()).collect();
+        text += &format!(
+            "{idx}\t{}\t{}\t{}\n",
+            group.score(),
+            group.lead(),
+            members.join(",")
+        );
+    }
+
+    text
+}
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "auto-merge", "");
+    opts.optmulti("", "bib-id", "", "");
+    opts.optopt("", "threshold", "", "");
+    opts.optopt("", "export", "", "");
+    opts.optopt("", "username", "", "");
+    opts.optopt("", "password", "", "");
+    opts.optopt("", "workstation", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let threshold: u32 = match params.opt_str("threshold") {
+        Some(t) => t.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid --threshold value: {t}");
+            process::exit(1);
+        }),
+        None => DEFAULT_THRESHOLD,
+    };
+
+    let username = params.opt_str("username").unwrap_or_else(|| {
+        eprintln!("--username required");
+        process::exit(1);
+    });
+
+    let password = params
+        .opt_str("password")
+        .or_else(|| env::var("EG_DEDUP_PASSWORD").ok())
+        .unwrap_or_else(|| {
+            eprintln!("--password or EG_DEDUP_PASSWORD required");
+            process::exit(1);
+        });
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let auth_args = AuthLoginArgs::new(
+        &username,
+        &password,
+        "staff",
+        params.opt_str("workstation").as_deref(),
+    );
+
+    let auth = match AuthSession::login(ctx.client(), &auth_args) {
+        Ok(Some(a)) => a,
+        Ok(None) => {
+            eprintln!("Login failed for {username}");
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Login error: {e}");
+            process::exit(1);
+        }
+    };
+
+    let mut deduper = Deduper::new(ctx.client(), ctx.idl(), auth.token());
+
+    let bib_ids: Vec<i64> = if params.opt_present("bib-id") {
+        params
+            .opt_strs("bib-id")
+            .iter()
+            .map(|id| {
+                id.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid --bib-id value: {id}");
+                    process::exit(1);
+                })
+            })
+            .collect()
+    } else {
+        let bibs = deduper
+            .editor_mut()
+            .search("bre", json::object! {deleted: "f", id: {">": 0}})
+            .unwrap_or_else(|e| {
+                eprintln!("Cannot list bib records: {e}");
+                process::exit(1);
+            });
+
+        bibs.iter()
+            .filter_map(|b| util::json_int(&b["id"]).ok())
+            .collect()
+    };
+
+    let groups = deduper
+        .find_groups(&bib_ids, threshold)
+        .unwrap_or_else(|e| {
+            eprintln!("Grouping failed: {e}");
+            process::exit(1);
+        });
+
+    let export = export_text(&groups);
+
+    match params.opt_str("export") {
+        Some(file) => {
+            if let Err(e) = fs::write(&file, &export) {
+                eprintln!("Cannot write {file}: {e}");
+                process::exit(1);
+            }
+            println!("Wrote {} groups to {file}", groups.len());
+        }
+        None => print!("{export}"),
+    }
+
+    let mut merge_errors = 0;
+
+    if params.opt_present("auto-merge") {
+        for group in &groups {
+            if let Err(e) = deduper.merge_group(group) {
+                merge_errors += 1;
+                log::error!("Cannot merge group led by {}: {e}", group.lead());
+            }
+        }
+    }
+
+    let counts = deduper.counts();
+    println!(
+        "Scanned {} records; found {} groups; merged {} records; errors {}",
+        counts.records_scanned,
+        counts.groups_found,
+        counts.records_merged,
+        counts.errors + merge_errors
+    );
+
+    if counts.errors + merge_errors > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,159 @@
+//! Fetches inbound EDI files from vendor accounts and applies them
+//! to acq data.
+
+use evergreen as eg;
+
+use eg::edi;
+use eg::edi::remote::RemoteAccount;
+use eg::editor::Editor;
+use json::JsonValue;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+const HELP_TEXT: &str = r#"Usage: eg-edi-fetcher [options]
+
+Options:
+
+    --account <id>
+        Only exchange files with this EDI account.  Repeatable;
+        default is every account.
+
+    --save-dir <dir>
+        Archive fetched files here.  Files already present in the
+        archive are not fetched again.  Default /tmp/eg-edi.
+
+    --dry-run
+        Fetch and parse, but do not apply messages.
+
+    --loop-interval <seconds>
+        Run as a daemon, sleeping this long between passes.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "dry-run", "");
+    opts.optmulti("", "account", "", "");
+    opts.optopt("", "save-dir", "", "");
+    opts.optopt("", "loop-interval", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let save_dir = params
+        .opt_str("save-dir")
+        .unwrap_or_else(|| "/tmp/eg-edi".to_string());
+
+    if let Err(e) = fs::create_dir_all(&save_dir) {
+        eprintln!("Cannot create {save_dir}: {e}");
+        process::exit(1);
+    }
+
+    let account_ids: Vec<i64> = params
+        .opt_strs("account")
+        .iter()
+        .filter_map(|id| id.parse().ok())
+        .collect();
+
+    let loop_interval = params
+        .opt_str("loop-interval")
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let dry_run = params.opt_present("dry-run");
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    loop {
+        let mut editor = Editor::new(ctx.client(), ctx.idl());
+
+        let mut filter = json::object! {"-not": {host: JsonValue::Null}};
+        if !account_ids.is_empty() {
+            filter = json::object! {id: account_ids.clone()};
+        }
+
+        let accounts = editor.search("acqedi", filter).unwrap_or_else(|e| {
+            eprintln!("Cannot fetch EDI accounts: {e}");
+            process::exit(1);
+        });
+
+        for account in &accounts {
+            let editor = Editor::new(ctx.client(), ctx.idl());
+            if let Err(e) = process_account(editor, account, &save_dir, dry_run) {
+                eprintln!("EDI account {}: {e}", account["id"].dump());
+            }
+        }
+
+        match loop_interval {
+            Some(secs) => thread::sleep(Duration::from_secs(secs)),
+            None => break,
+        }
+    }
+}
+
+fn process_account(
+    editor: Editor,
+    account: &JsonValue,
+    save_dir: &str,
+    dry_run: bool,
+) -> Result<(), String> {
+    let remote = RemoteAccount::from_edi_account(account)?;
+    let mut applier = edi::Applier::new(editor);
+
+    for filename in remote.list()? {
+        let archive = Path::new(save_dir).join(&filename);
+
+        if archive.exists() {
+            continue; // already fetched
+        }
+
+        println!("Fetching {filename} from {}", remote.host);
+
+        let content = remote.retrieve(&filename)?;
+
+        fs::write(&archive, &content)
+            .map_err(|e| format!("Cannot archive {filename}: {e}"))?;
+
+        let messages = match edi::parse_messages(&content) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("{filename}: unparseable: {e}");
+                continue;
+            }
+        };
+
+        for message in &messages {
+            if dry_run {
+                println!(
+                    "{filename}: would apply {:?} for PO {:?}",
+                    message.msg_type, message.purchase_order
+                );
+                continue;
+            }
+
+            match applier.apply(message) {
+                Ok(desc) => println!("{filename}: {desc}"),
+                Err(e) => eprintln!("{filename}: {e}"),
+            }
+        }
+    }
+
+    Ok(())
+}
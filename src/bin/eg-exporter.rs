@@ -0,0 +1,191 @@
+//! Prometheus exporter for Evergreen operational stats.
+//!
+//! Serves gathered metrics on GET /metrics.  Collection runs at
+//! scrape time, rate-limited by --min-interval so aggressive
+//! scrapers cannot hammer the services.
+
+use evergreen as eg;
+
+use eg::db::DatabaseConnection;
+use eg::editor::Editor;
+use eg::exporter::{render, Collector};
+use std::env;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::time::{Duration, Instant};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:9899";
+const DEFAULT_MIN_INTERVAL: u64 = 10;
+
+const HELP_TEXT: &str = r#"Usage: eg-exporter [options]
+
+Options:
+
+    --listen <host:port>
+        Address to listen on.  Defaults to 127.0.0.1:9899.
+
+    --min-interval <seconds>
+        Minimum seconds between metric collections; scrapes inside
+        the window are served from cache.  Default 10.
+
+    --with-db
+        Also connect directly to the database for replication lag.
+        Connection details come from the --db-* options and PG*
+        environment variables.
+
+    --db-host / --db-port / --db-user / --db-name
+        Database connection overrides.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "with-db", "");
+    opts.optopt("", "listen", "", "");
+    opts.optopt("", "min-interval", "", "");
+    DatabaseConnection::append_options(&mut opts);
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let listen = params
+        .opt_str("listen")
+        .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+
+    let min_interval = params
+        .opt_str("min-interval")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MIN_INTERVAL);
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let db = if params.opt_present("with-db") {
+        let mut db = DatabaseConnection::new_from_options(&params);
+        if let Err(e) = db.connect() {
+            eprintln!("Cannot connect to database: {e}");
+            process::exit(1);
+        }
+        Some(db)
+    } else {
+        None
+    };
+
+    let mut collector = Collector::new(Editor::new(ctx.client(), ctx.idl()), db);
+
+    let listener = TcpListener::bind(&listen).unwrap_or_else(|e| {
+        eprintln!("Cannot listen on {listen}: {e}");
+        process::exit(1);
+    });
+
+    log::info!("eg-exporter listening on {listen}");
+
+    let mut cached = String::new();
+    let mut last_collected: Option<Instant> = None;
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                log::error!("Accept failed: {e}");
+                continue;
+            }
+        };
+
+        let stale = match last_collected {
+            Some(at) => at.elapsed() >= Duration::from_secs(min_interval),
+            None => true,
+        };
+
+        if stale {
+            cached = render(&collector.collect());
+            last_collected = Some(Instant::now());
+        }
+
+        if let Err(e) = handle_connection(&mut stream, &cached) {
+            log::error!("Request handling failed: {e}");
+        }
+    }
+}
+
+/// Read one HTTP request and reply with the metrics payload.
+fn handle_connection(stream: &mut TcpStream, metrics: &str) -> Result<(), String> {
+    let request_line = read_request_line(stream)?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let target = parts.next().unwrap_or("");
+
+    let (status, status_text, body) = if method != "GET" {
+        (405, "Method Not Allowed", String::new())
+    } else if target == "/metrics" {
+        (200, "OK", metrics.to_string())
+    } else {
+        (404, "Not Found", String::new())
+    };
+
+    log::info!("{method} {target} => {status}");
+
+    let http = format!(
+        concat!(
+            "HTTP/1.1 {} {}\r\n",
+            "Content-Type: text/plain; version=0.0.4\r\n",
+            "Content-Length: {}\r\n",
+            "Connection: close\r\n",
+            "\r\n",
+            "{}"
+        ),
+        status,
+        status_text,
+        body.len(),
+        body
+    );
+
+    stream
+        .write_all(http.as_bytes())
+        .map_err(|e| format!("Error writing response: {e}"))
+}
+
+/// Read up to the end of the request headers and return the request
+/// line.  Scrapes carry no body worth reading.
+fn read_request_line(stream: &mut TcpStream) -> Result<String, String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        let count = stream
+            .read(&mut chunk)
+            .map_err(|e| format!("Error reading request: {e}"))?;
+
+        if count == 0 {
+            return Err("Client disconnected mid-request".to_string());
+        }
+
+        buffer.extend_from_slice(&chunk[..count]);
+
+        if buffer.windows(4).any(|w| w == b"\r\n\r\n") {
+            break;
+        }
+
+        if buffer.len() > 65536 {
+            return Err("Request headers too large".to_string());
+        }
+    }
+
+    let head = String::from_utf8_lossy(&buffer).to_string();
+    Ok(head.lines().next().unwrap_or("").to_string())
+}
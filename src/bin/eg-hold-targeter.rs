@@ -0,0 +1,162 @@
+//! Batch hold targeter: discovers holds needing (re)targeting and
+//! retargets them in parallel batches.
+
+use evergreen as eg;
+
+use eg::editor::Editor;
+use eg::targeter::{HoldTargeter, TargeterCounts};
+use std::env;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+const DEFAULT_RETARGET_INTERVAL: i64 = 86400; // 24 hours
+const HELP_TEXT: &str = r#"Usage: eg-hold-targeter [options]
+
+Options:
+
+    --hold-id <id>
+        Target a specific hold.  Repeatable.  When absent, all holds
+        due for retargeting are processed.
+
+    --retarget-interval <seconds>
+        Holds checked within this window are left alone.
+        Defaults to 86400 (24 hours).
+
+    --parallel <count>
+        Number of worker threads.  Defaults to 1.
+
+    --loop-interval <seconds>
+        Run as a daemon, sleeping this long between batches.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optmulti("", "hold-id", "", "");
+    opts.optopt("", "retarget-interval", "", "");
+    opts.optopt("", "parallel", "", "");
+    opts.optopt("", "loop-interval", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let retarget_interval = params
+        .opt_str("retarget-interval")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_RETARGET_INTERVAL);
+
+    let parallel = params
+        .opt_str("parallel")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+
+    let loop_interval = params
+        .opt_str("loop-interval")
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let requested_holds: Vec<i64> = params
+        .opt_strs("hold-id")
+        .iter()
+        .filter_map(|id| id.parse::<i64>().ok())
+        .collect();
+
+    loop {
+        let start = Instant::now();
+        let counts = run_batch(&requested_holds, retarget_interval, parallel);
+
+        println!(
+            "Processed {} holds in {:.1}s: {} retargeted, {} untargetable, {} skipped, {} errors",
+            counts.processed,
+            start.elapsed().as_secs_f64(),
+            counts.retargeted,
+            counts.untargetable,
+            counts.skipped,
+            counts.errors,
+        );
+
+        match loop_interval {
+            Some(secs) => thread::sleep(std::time::Duration::from_secs(secs)),
+            None => break,
+        }
+    }
+}
+
+/// Run one pass over the requested (or discovered) holds.
+fn run_batch(requested_holds: &[i64], retarget_interval: i64, parallel: usize) -> TargeterCounts {
+    let hold_ids = if requested_holds.is_empty() {
+        let ctx = eg::init::init().unwrap_or_else(|e| {
+            eprintln!("Cannot initialize: {e}");
+            process::exit(1);
+        });
+
+        let mut targeter = HoldTargeter::new(Editor::new(ctx.client(), ctx.idl()));
+
+        targeter
+            .holds_to_target(retarget_interval)
+            .unwrap_or_else(|e| {
+                eprintln!("Cannot load hold list: {e}");
+                process::exit(1);
+            })
+    } else {
+        requested_holds.to_vec()
+    };
+
+    log::info!("Targeting {} holds", hold_ids.len());
+
+    // Slice the hold list into one chunk per worker.  Each worker
+    // maintains its own bus connection.
+    let chunk_size = hold_ids.len().div_ceil(parallel).max(1);
+    let (tx, rx) = mpsc::channel::<TargeterCounts>();
+    let mut workers = 0;
+
+    for chunk in hold_ids.chunks(chunk_size) {
+        let chunk = chunk.to_vec();
+        let tx = tx.clone();
+        workers += 1;
+
+        thread::spawn(move || {
+            let ctx = match eg::init::init() {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("Worker cannot initialize: {e}");
+                    return;
+                }
+            };
+
+            let mut targeter = HoldTargeter::new(Editor::new(ctx.client(), ctx.idl()));
+
+            for hold_id in chunk {
+                if let Err(e) = targeter.target_hold(hold_id) {
+                    eprintln!("hold {hold_id}: {e}");
+                }
+            }
+
+            tx.send(targeter.counts().clone()).ok();
+        });
+    }
+
+    drop(tx);
+
+    let mut totals = TargeterCounts::default();
+    for _ in 0..workers {
+        if let Ok(counts) = rx.recv() {
+            totals.absorb(&counts);
+        }
+    }
+
+    totals
+}
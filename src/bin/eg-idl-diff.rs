@@ -0,0 +1,48 @@
+//! Report structural differences between two fm_IDL.xml files.
+//!
+//! Prints one change per line and exits non-zero when the files
+//! differ, diff-style.
+
+use evergreen as eg;
+
+use eg::idl;
+use eg::idldiff;
+use std::env;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-idl-diff <old-fm_IDL.xml> <new-fm_IDL.xml>
+
+Reports added/removed classes, fields, datatype changes, and link
+changes between two IDL files.  Exits 1 when differences are found.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+
+    if args.iter().any(|a| a == "-h" || a == "--help") || args.len() != 3 {
+        println!("{HELP_TEXT}");
+        process::exit(if args.len() == 3 { 0 } else { 2 });
+    }
+
+    let old = idl::Parser::parse_file(&args[1]).unwrap_or_else(|e| {
+        eprintln!("Error parsing {}: {e}", args[1]);
+        process::exit(2);
+    });
+
+    let new = idl::Parser::parse_file(&args[2]).unwrap_or_else(|e| {
+        eprintln!("Error parsing {}: {e}", args[2]);
+        process::exit(2);
+    });
+
+    let changes = idldiff::diff(&old, &new);
+
+    for change in &changes {
+        println!("{change}");
+    }
+
+    if !changes.is_empty() {
+        process::exit(1);
+    }
+}
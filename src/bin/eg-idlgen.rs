@@ -0,0 +1,82 @@
+//! Emit typed Rust structs for selected IDL classes.
+//!
+//! Suitable for one-off runs or wiring into a build script.  Output
+//! goes to stdout unless --out is given.
+
+use evergreen as eg;
+
+use eg::idl;
+use eg::idlgen;
+use std::env;
+use std::fs;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-idlgen --class aou --class au [options]
+
+Options:
+
+    --class <classname>
+        Generate a struct for this IDL class.  Repeatable.
+
+    --idl-file <file>
+        IDL file to read.  Defaults to OILS_IDL_FILE or the stock
+        install path.
+
+    --out <file>
+        Write generated source to <file> instead of stdout.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optmulti("", "class", "", "");
+    opts.optopt("", "idl-file", "", "");
+    opts.optopt("", "out", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let classes = params.opt_strs("class");
+
+    if classes.is_empty() {
+        eprintln!("At least one --class is required");
+        process::exit(1);
+    }
+
+    let idl_file = params
+        .opt_str("idl-file")
+        .unwrap_or_else(eg::init::idl_file);
+
+    let parser = idl::Parser::parse_file(&idl_file).unwrap_or_else(|e| {
+        eprintln!("Error parsing IDL: {e}");
+        process::exit(1);
+    });
+
+    let classnames: Vec<&str> = classes.iter().map(|c| c.as_str()).collect();
+
+    let src = idlgen::generate(&parser, &classnames).unwrap_or_else(|e| {
+        eprintln!("Error generating structs: {e}");
+        process::exit(1);
+    });
+
+    match params.opt_str("out") {
+        Some(filename) => {
+            if let Err(e) = fs::write(&filename, src) {
+                eprintln!("Cannot write {filename}: {e}");
+                process::exit(1);
+            }
+        }
+        None => print!("{src}"),
+    }
+}
@@ -0,0 +1,210 @@
+//! Juvenile-to-adult batch updater.
+//!
+//! Clears the juvenile flag for patrons who have aged past their
+//! home org's configured threshold, walking each org unit that can
+//! have users.  Replaces the long-standing Perl cron script.
+
+use evergreen as eg;
+
+use eg::actor::patron::PatronManager;
+use eg::auth::{AuthLoginArgs, AuthSession};
+use eg::date;
+use eg::org::OrgTree;
+use eg::settings::Settings;
+use eg::util;
+use std::env;
+use std::process;
+
+/// The org setting naming the age threshold as a PG interval.
+const AGE_THRESHOLD_SETTING: &str = "global.juvenile_age_threshold";
+const DEFAULT_THRESHOLD: &str = "18 years";
+
+const HELP_TEXT: &str = r#"Usage: eg-juv-to-adult [options]
+
+Options:
+
+    --org <org-id>
+        Process only this org unit.  Repeatable.  Defaults to every
+        org unit that can have users.
+
+    --dry-run
+        Report the patrons that would change without updating them.
+
+    --username <username>
+    --password <password>
+    --workstation <workstation>
+        Staff credentials.  The password may also be supplied via
+        EG_JUV_PASSWORD.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "dry-run", "");
+    opts.optmulti("", "org", "", "");
+    opts.optopt("", "username", "", "");
+    opts.optopt("", "password", "", "");
+    opts.optopt("", "workstation", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let dry_run = params.opt_present("dry-run");
+
+    let username = params.opt_str("username").unwrap_or_else(|| {
+        eprintln!("--username required");
+        process::exit(1);
+    });
+
+    let password = params
+        .opt_str("password")
+        .or_else(|| env::var("EG_JUV_PASSWORD").ok())
+        .unwrap_or_else(|| {
+            eprintln!("--password or EG_JUV_PASSWORD required");
+            process::exit(1);
+        });
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let auth_args = AuthLoginArgs::new(
+        &username,
+        &password,
+        "staff",
+        params.opt_str("workstation").as_deref(),
+    );
+
+    let auth = match AuthSession::login(ctx.client(), &auth_args) {
+        Ok(Some(a)) => a,
+        Ok(None) => {
+            eprintln!("Login failed for {username}");
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Login error: {e}");
+            process::exit(1);
+        }
+    };
+
+    let mut manager = PatronManager::new(ctx.client(), ctx.idl(), auth.token());
+    let mut settings = Settings::new(ctx.client(), ctx.idl(), auth.token());
+
+    let tree = OrgTree::load(manager.editor_mut()).unwrap_or_else(|e| {
+        eprintln!("Cannot load org tree: {e}");
+        process::exit(1);
+    });
+
+    let org_ids: Vec<i64> = if params.opt_present("org") {
+        params
+            .opt_strs("org")
+            .iter()
+            .map(|o| {
+                o.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid --org value: {o}");
+                    process::exit(1);
+                })
+            })
+            .collect()
+    } else {
+        let mut ids: Vec<i64> = tree
+            .all_ids()
+            .into_iter()
+            .filter(|id| tree.can_have_users(*id))
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    let mut examined = 0;
+    let mut updated = 0;
+    let mut errors = 0;
+
+    for org_id in org_ids {
+        let threshold = match settings.ou_setting(org_id, AGE_THRESHOLD_SETTING) {
+            Ok(value) => value
+                .as_str()
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| DEFAULT_THRESHOLD.to_string()),
+            Err(e) => {
+                log::error!("Cannot read threshold for org {org_id}: {e}");
+                errors += 1;
+                continue;
+            }
+        };
+
+        let threshold_secs = match date::interval_to_seconds(&threshold) {
+            Ok(secs) => secs,
+            Err(e) => {
+                log::error!("Bad threshold for org {org_id}: {e}");
+                errors += 1;
+                continue;
+            }
+        };
+
+        // Patrons born on or before this date have come of age.
+        let cutoff = date::now() - chrono::Duration::seconds(threshold_secs);
+        let cutoff = cutoff.format("%Y-%m-%d").to_string();
+
+        let filter = json::object! {
+            home_ou: org_id,
+            juvenile: "t",
+            deleted: "f",
+            dob: {"<=": cutoff.as_str()},
+        };
+
+        let patrons = match manager.editor_mut().search("au", filter) {
+            Ok(patrons) => patrons,
+            Err(e) => {
+                log::error!("Patron search failed for org {org_id}: {e}");
+                errors += 1;
+                continue;
+            }
+        };
+
+        for mut patron in patrons {
+            examined += 1;
+
+            let patron_id = util::json_int(&patron["id"]).unwrap_or(0);
+            let barcode = patron["card"]["barcode"].as_str().unwrap_or("");
+
+            if dry_run {
+                println!(
+                    "DRY RUN: would flip patron {patron_id} {barcode} at org {org_id} \
+                     (dob {}, threshold {threshold})",
+                    patron["dob"].as_str().unwrap_or("")
+                );
+                continue;
+            }
+
+            patron["juvenile"] = "f".into();
+            patron["ischanged"] = 1.into();
+
+            match manager.update(patron) {
+                Ok(_) => updated += 1,
+                Err(e) => {
+                    log::error!("Cannot update patron {patron_id}: {e}");
+                    errors += 1;
+                }
+            }
+        }
+    }
+
+    println!("Examined {examined} patrons; updated {updated}; errors {errors}");
+
+    if errors > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,221 @@
+//! Spine and pocket label generator.
+//!
+//! Renders printer-ready labels for a list of copies or the contents
+//! of a copy bucket, one label per form-feed-separated page.
+
+use evergreen as eg;
+
+use eg::auth::{AuthLoginArgs, AuthSession};
+use eg::bucket::BucketType;
+use eg::labels::Labeler;
+use eg::util;
+use std::env;
+use std::fs;
+use std::process;
+
+/// Separates labels in printer-ready output.
+const FORM_FEED: char = '\x0c';
+
+const HELP_TEXT: &str = r#"Usage: eg-labels [options]
+
+Options:
+
+    --copy-id <id>
+        Label this copy.  Repeatable.
+
+    --bucket <bucket-id>
+        Label every copy in this copy bucket.
+
+    --label-type <spine|pocket|both>
+        Which labels to render.  Default spine.
+
+    --spine-template <file>
+    --pocket-template <file>
+        Tera template overrides.  The context contains barcode,
+        call_number, lines (the wrapped call number), title, author,
+        and library.
+
+    --out <file>
+        Write labels to <file> instead of stdout.  Labels are
+        separated by form feeds.
+
+    --username <username>
+    --password <password>
+    --workstation <workstation>
+        Staff credentials.  The password may also be supplied via
+        EG_LABELS_PASSWORD.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optmulti("", "copy-id", "", "");
+    opts.optopt("", "bucket", "", "");
+    opts.optopt("", "label-type", "", "");
+    opts.optopt("", "spine-template", "", "");
+    opts.optopt("", "pocket-template", "", "");
+    opts.optopt("", "out", "", "");
+    opts.optopt("", "username", "", "");
+    opts.optopt("", "password", "", "");
+    opts.optopt("", "workstation", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let label_type = params
+        .opt_str("label-type")
+        .unwrap_or_else(|| "spine".to_string());
+
+    if !matches!(label_type.as_str(), "spine" | "pocket" | "both") {
+        eprintln!("Invalid --label-type value: {label_type}");
+        process::exit(1);
+    }
+
+    let username = params.opt_str("username").unwrap_or_else(|| {
+        eprintln!("--username required");
+        process::exit(1);
+    });
+
+    let password = params
+        .opt_str("password")
+        .or_else(|| env::var("EG_LABELS_PASSWORD").ok())
+        .unwrap_or_else(|| {
+            eprintln!("--password or EG_LABELS_PASSWORD required");
+            process::exit(1);
+        });
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let auth_args = AuthLoginArgs::new(
+        &username,
+        &password,
+        "staff",
+        params.opt_str("workstation").as_deref(),
+    );
+
+    let auth = match AuthSession::login(ctx.client(), &auth_args) {
+        Ok(Some(a)) => a,
+        Ok(None) => {
+            eprintln!("Login failed for {username}");
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Login error: {e}");
+            process::exit(1);
+        }
+    };
+
+    let mut labeler = Labeler::new(ctx.client(), ctx.idl(), auth.token());
+
+    for (opt, setter) in [
+        ("spine-template", Labeler::set_spine_template as fn(&mut Labeler, &str)),
+        ("pocket-template", Labeler::set_pocket_template),
+    ] {
+        if let Some(file) = params.opt_str(opt) {
+            let template = fs::read_to_string(&file).unwrap_or_else(|e| {
+                eprintln!("Cannot read {file}: {e}");
+                process::exit(1);
+            });
+            setter(&mut labeler, &template);
+        }
+    }
+
+    let mut copy_ids: Vec<i64> = params
+        .opt_strs("copy-id")
+        .iter()
+        .map(|id| {
+            id.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid --copy-id value: {id}");
+                process::exit(1);
+            })
+        })
+        .collect();
+
+    if let Some(bucket) = params.opt_str("bucket") {
+        let bucket_id: i64 = bucket.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid --bucket value: {bucket}");
+            process::exit(1);
+        });
+
+        let btype = BucketType::Copy;
+        let items = labeler
+            .editor_mut()
+            .search(btype.item_class(), json::object! {bucket: bucket_id})
+            .unwrap_or_else(|e| {
+                eprintln!("Cannot read bucket {bucket_id}: {e}");
+                process::exit(1);
+            });
+
+        for item in &items {
+            if let Ok(id) = util::json_int(&item[btype.target_field()]) {
+                copy_ids.push(id);
+            }
+        }
+    }
+
+    if copy_ids.is_empty() {
+        eprintln!("Nothing to do.  See --help");
+        return;
+    }
+
+    let mut labels = Vec::new();
+    let mut errors = 0;
+
+    for copy_id in copy_ids {
+        let data = match labeler.label_data(copy_id) {
+            Ok(d) => d,
+            Err(e) => {
+                log::error!("Cannot load copy {copy_id}: {e}");
+                errors += 1;
+                continue;
+            }
+        };
+
+        let rendered = match label_type.as_str() {
+            "spine" => labeler.spine_label(&data),
+            "pocket" => labeler.pocket_label(&data),
+            _ => labeler
+                .spine_label(&data)
+                .and_then(|s| Ok(format!("{s}{FORM_FEED}{}", labeler.pocket_label(&data)?))),
+        };
+
+        match rendered {
+            Ok(text) => labels.push(text),
+            Err(e) => {
+                log::error!("Cannot render label for copy {copy_id}: {e}");
+                errors += 1;
+            }
+        }
+    }
+
+    let output = labels.join(&FORM_FEED.to_string());
+
+    match params.opt_str("out") {
+        Some(file) => {
+            if let Err(e) = fs::write(&file, &output) {
+                eprintln!("Cannot write {file}: {e}");
+                process::exit(1);
+            }
+            println!("Wrote {} labels to {file}", labels.len());
+        }
+        None => print!("{output}"),
+    }
+
+    if errors > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,207 @@
+//! NCIP 2.0 responder HTTP service.
+//!
+//! Accepts NCIPMessage documents via HTTP POST and responds with
+//! NCIP response documents.  Requests are processed serially on a
+//! single bus connection.
+
+use evergreen as eg;
+
+use eg::auth::{AuthLoginArgs, AuthSession};
+use eg::ncip::Responder;
+use std::env;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8687";
+const HELP_TEXT: &str = r#"Usage: eg-ncip [options]
+
+Options:
+
+    --listen <host:port>
+        Address to listen on.  Defaults to 127.0.0.1:8687.
+
+    --username <username>
+    --password <password>
+    --workstation <workstation>
+        Staff credentials used when calling circulation APIs.
+        The password may also be supplied via EG_NCIP_PASSWORD.
+
+    --work-ou <org-id>
+        Org unit to use as the circ / pickup lib when the NCIP
+        message does not specify one.  Defaults to 1.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optopt("", "listen", "", "");
+    opts.optopt("", "username", "", "");
+    opts.optopt("", "password", "", "");
+    opts.optopt("", "workstation", "", "");
+    opts.optopt("", "work-ou", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let listen = params
+        .opt_str("listen")
+        .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+
+    let username = params.opt_str("username").unwrap_or_else(|| {
+        eprintln!("--username required");
+        process::exit(1);
+    });
+
+    let password = params
+        .opt_str("password")
+        .or_else(|| env::var("EG_NCIP_PASSWORD").ok())
+        .unwrap_or_else(|| {
+            eprintln!("--password or EG_NCIP_PASSWORD required");
+            process::exit(1);
+        });
+
+    let work_ou = params
+        .opt_str("work-ou")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(1);
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let auth_args = AuthLoginArgs::new(
+        &username,
+        &password,
+        "staff",
+        params.opt_str("workstation").as_deref(),
+    );
+
+    let auth = match AuthSession::login(ctx.client(), &auth_args) {
+        Ok(Some(a)) => a,
+        Ok(None) => {
+            eprintln!("Login failed for {username}");
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Login error: {e}");
+            process::exit(1);
+        }
+    };
+
+    let mut responder = Responder::new(ctx.client(), ctx.idl(), auth.token(), work_ou);
+
+    let listener = TcpListener::bind(&listen).unwrap_or_else(|e| {
+        eprintln!("Cannot listen on {listen}: {e}");
+        process::exit(1);
+    });
+
+    log::info!("eg-ncip listening on {listen}");
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                log::error!("Accept failed: {e}");
+                continue;
+            }
+        };
+
+        if let Err(e) = handle_connection(&mut stream, &mut responder) {
+            log::error!("Request handling failed: {e}");
+        }
+    }
+}
+
+/// Read one HTTP request, dispatch to the responder, and reply.
+fn handle_connection(stream: &mut TcpStream, responder: &mut Responder) -> Result<(), String> {
+    let body = read_http_body(stream)?;
+    let response = responder.handle(&body);
+
+    let http = format!(
+        concat!(
+            "HTTP/1.1 200 OK\r\n",
+            "Content-Type: application/xml\r\n",
+            "Content-Length: {}\r\n",
+            "Connection: close\r\n",
+            "\r\n",
+            "{}"
+        ),
+        response.len(),
+        response
+    );
+
+    stream
+        .write_all(http.as_bytes())
+        .map_err(|e| format!("Error writing response: {e}"))
+}
+
+/// Read HTTP headers + body from the stream, returning the body.
+fn read_http_body(stream: &mut TcpStream) -> Result<String, String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    // Read until the end of the headers.
+    let header_end;
+    loop {
+        let count = stream
+            .read(&mut chunk)
+            .map_err(|e| format!("Error reading request: {e}"))?;
+
+        if count == 0 {
+            return Err("Client disconnected mid-request".to_string());
+        }
+
+        buffer.extend_from_slice(&chunk[..count]);
+
+        if let Some(pos) = find_header_end(&buffer) {
+            header_end = pos;
+            break;
+        }
+
+        if buffer.len() > 65536 {
+            return Err("Request headers too large".to_string());
+        }
+    }
+
+    let headers = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+
+    let content_length: usize = headers
+        .lines()
+        .find(|l| l.to_lowercase().starts_with("content-length:"))
+        .and_then(|l| l.split(':').nth(1))
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or("Request has no Content-Length")?;
+
+    let mut body = buffer[header_end + 4..].to_vec();
+
+    while body.len() < content_length {
+        let count = stream
+            .read(&mut chunk)
+            .map_err(|e| format!("Error reading request body: {e}"))?;
+
+        if count == 0 {
+            break;
+        }
+
+        body.extend_from_slice(&chunk[..count]);
+    }
+
+    String::from_utf8(body).map_err(|e| format!("Request body is not valid UTF-8: {e}"))
+}
+
+fn find_header_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|w| w == b"\r\n\r\n")
+}
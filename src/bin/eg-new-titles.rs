@@ -0,0 +1,149 @@
+//! New-titles feed generator.
+//!
+//! Produces an RSS, Atom, or JSON feed of recently cataloged titles
+//! for website integration.
+
+use evergreen as eg;
+
+use eg::editor::Editor;
+use eg::feeds::{render_atom, render_json, render_rss, FeedBuilder};
+use std::env;
+use std::fs;
+use std::process;
+
+const DEFAULT_DAYS: i64 = 30;
+const DEFAULT_LIMIT: usize = 50;
+
+const HELP_TEXT: &str = r#"Usage: eg-new-titles [options]
+
+Options:
+
+    --days <n>
+        Include titles cataloged in the last n days.  Default 30.
+
+    --org <org-id>
+        Require a copy at this org unit.  Repeatable.
+
+    --location <location-id>
+        Require a copy in this shelving location.  Repeatable.
+
+    --audience <code>
+        Require this 008/22 audience code (e.g. j for juvenile).
+        Repeatable.
+
+    --format <rss|atom|json>
+        Output format.  Default rss.
+
+    --limit <n>
+        Maximum feed entries.  Default 50.
+
+    --title <text>
+    --link <url>
+        Feed metadata.
+
+    --out <file>
+        Write the feed to <file> instead of stdout.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optmulti("", "org", "", "");
+    opts.optmulti("", "location", "", "");
+    opts.optmulti("", "audience", "", "");
+    opts.optopt("", "days", "", "");
+    opts.optopt("", "format", "", "");
+    opts.optopt("", "limit", "", "");
+    opts.optopt("", "title", "", "");
+    opts.optopt("", "link", "", "");
+    opts.optopt("", "out", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let format = params.opt_str("format").unwrap_or_else(|| "rss".to_string());
+
+    if !matches!(format.as_str(), "rss" | "atom" | "json") {
+        eprintln!("Invalid --format value: {format}");
+        process::exit(1);
+    }
+
+    let days = params
+        .opt_str("days")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_DAYS);
+
+    let limit = params
+        .opt_str("limit")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LIMIT);
+
+    let orgs: Vec<i64> = params
+        .opt_strs("org")
+        .iter()
+        .filter_map(|v| v.parse().ok())
+        .collect();
+
+    let locations: Vec<i64> = params
+        .opt_strs("location")
+        .iter()
+        .filter_map(|v| v.parse().ok())
+        .collect();
+
+    let audiences: Vec<char> = params
+        .opt_strs("audience")
+        .iter()
+        .filter_map(|v| v.chars().next())
+        .collect();
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let since = (eg::date::now() - chrono::Duration::days(days))
+        .format("%Y-%m-%dT%H:%M:%S%z")
+        .to_string();
+
+    let mut builder = FeedBuilder::new(Editor::new(ctx.client(), ctx.idl()));
+
+    let entries = builder
+        .recent_titles(&since, &orgs, &locations, &audiences, limit)
+        .unwrap_or_else(|e| {
+            eprintln!("Cannot collect titles: {e}");
+            process::exit(1);
+        });
+
+    let feed_title = params
+        .opt_str("title")
+        .unwrap_or_else(|| "New Titles".to_string());
+    let link = params.opt_str("link").unwrap_or_default();
+
+    let output = match format.as_str() {
+        "rss" => render_rss(&feed_title, &link, &entries),
+        "atom" => render_atom(&feed_title, &link, &entries),
+        _ => render_json(&feed_title, &link, &entries),
+    };
+
+    match params.opt_str("out") {
+        Some(file) => {
+            if let Err(e) = fs::write(&file, &output) {
+                eprintln!("Cannot write {file}: {e}");
+                process::exit(1);
+            }
+            println!("Wrote {} entries to {file}", entries.len());
+        }
+        None => println!("{output}"),
+    }
+}
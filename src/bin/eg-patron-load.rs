@@ -0,0 +1,94 @@
+//! Imports/updates patrons from a delimited file.
+
+use evergreen as eg;
+
+use eg::editor::Editor;
+use eg::patronload::{FieldMap, PatronLoader};
+use std::env;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-patron-load --field-map <file> [options] <data-file>
+
+Options:
+
+    --field-map <file>
+        JSON field-mapping config.  Maps source columns to patron
+        fields, sets defaults for new patrons, and picks the
+        matchpoint (barcode, usrname, or email).
+
+    --tsv
+        The data file is tab-delimited instead of comma-delimited.
+
+    --dry-run
+        Report what would change without writing anything.
+
+    --batch-size <n>
+        Commit after this many creates/updates.  Default 100.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "tsv", "");
+    opts.optflag("", "dry-run", "");
+    opts.optopt("", "field-map", "", "");
+    opts.optopt("", "batch-size", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let map_file = params.opt_str("field-map").unwrap_or_else(|| {
+        eprintln!("--field-map is required");
+        process::exit(1);
+    });
+
+    let data_file = params.free.first().cloned().unwrap_or_else(|| {
+        eprintln!("A data file is required");
+        process::exit(1);
+    });
+
+    let map = FieldMap::from_file(&map_file).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    });
+
+    let delimiter = if params.opt_present("tsv") { '\t' } else { ',' };
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let editor = Editor::new(ctx.client(), ctx.idl());
+    let mut loader = PatronLoader::new(editor, map, params.opt_present("dry-run"));
+
+    if let Some(size) = params.opt_str("batch-size").and_then(|v| v.parse().ok()) {
+        loader.set_batch_size(size);
+    }
+
+    if let Err(e) = loader.load_file(&data_file, delimiter) {
+        eprintln!("Load failed: {e}");
+        process::exit(1);
+    }
+
+    let counts = loader.counts();
+    println!(
+        "rows={} created={} updated={} unchanged={} errors={}",
+        counts.rows_read,
+        counts.patrons_created,
+        counts.patrons_updated,
+        counts.unchanged,
+        counts.errors,
+    );
+}
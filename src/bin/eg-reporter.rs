@@ -0,0 +1,200 @@
+//! Scheduled reports daemon: claims runs from reporter.schedule,
+//! executes them, writes output files, and sends completion notices.
+
+use evergreen as eg;
+
+use eg::db::DatabaseConnection;
+use eg::idl;
+use eg::notice::{EmailSender, Notice, SendOutcome, SmtpConfig};
+use eg::reporter::{ClaimedRun, ReportRunner};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+const HELP_TEXT: &str = r#"Usage: eg-reporter [options]
+
+Options:
+
+    --output-dir <dir>
+        Report output is written to
+        <dir>/<report-id>/<schedule-id>/report.<ext>.
+        Default /openils/var/web/reporter.
+
+    --parallel <n>
+        Number of concurrent report runners.  Default 1.
+
+    --report-timeout <seconds>
+        Per-report statement timeout.  Default 3600.
+
+    --loop-interval <seconds>
+        Run as a daemon, sleeping this long when the queue is empty.
+
+    --smtp-host <host>
+    --smtp-port <port>
+    --smtp-sender <address>
+        Enable completion notices for schedule entries that request
+        email.
+
+    --db-host / --db-port / --db-user / --db-name
+        Database connection overrides; PG* environment variables are
+        the fallback.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optopt("", "output-dir", "", "");
+    opts.optopt("", "parallel", "", "");
+    opts.optopt("", "report-timeout", "", "");
+    opts.optopt("", "loop-interval", "", "");
+    opts.optopt("", "smtp-host", "", "");
+    opts.optopt("", "smtp-port", "", "");
+    opts.optopt("", "smtp-sender", "", "");
+    DatabaseConnection::append_options(&mut opts);
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let output_dir = params
+        .opt_str("output-dir")
+        .unwrap_or_else(|| "/openils/var/web/reporter".to_string());
+
+    let parallel: usize = params
+        .opt_str("parallel")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1)
+        .max(1);
+
+    let timeout: u64 = params
+        .opt_str("report-timeout")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3600);
+
+    let loop_interval = params
+        .opt_str("loop-interval")
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let smtp = params.opt_str("smtp-host").map(|host| SmtpConfig {
+        host,
+        port: params
+            .opt_str("smtp-port")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(25),
+        default_sender: params
+            .opt_str("smtp-sender")
+            .unwrap_or_else(|| "evergreen@localhost".to_string()),
+    });
+
+    let idl = idl::Parser::parse_file(&eg::init::idl_file()).unwrap_or_else(|e| {
+        eprintln!("Error parsing IDL: {e}");
+        process::exit(1);
+    });
+
+    let pool = threadpool::ThreadPool::new(parallel);
+
+    loop {
+        // The claim query uses SKIP LOCKED, so each runner claims its
+        // own work without coordination.
+        for _ in 0..parallel {
+            let params = params.clone();
+            let idl = idl.clone();
+            let output_dir = output_dir.clone();
+            let smtp = smtp.clone();
+
+            pool.execute(move || {
+                let db = DatabaseConnection::new_from_options(&params);
+                let mut runner = ReportRunner::new(db, idl);
+                runner.set_timeout_secs(timeout);
+
+                if let Err(e) = runner.connect() {
+                    eprintln!("{e}");
+                    return;
+                }
+
+                loop {
+                    match runner.claim_next() {
+                        Ok(Some(run)) => run_one(&mut runner, &run, &output_dir, &smtp),
+                        Ok(None) => break,
+                        Err(e) => {
+                            eprintln!("{e}");
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+
+        pool.join();
+
+        match loop_interval {
+            Some(secs) => thread::sleep(Duration::from_secs(secs)),
+            None => break,
+        }
+    }
+}
+
+fn run_one(runner: &mut ReportRunner, run: &ClaimedRun, output_dir: &str, smtp: &Option<SmtpConfig>) {
+    println!("Starting {run}");
+
+    let output = match runner.execute(run) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{run} failed: {e}");
+            runner.mark_error(run, &e).unwrap_or_else(|e| eprintln!("{e}"));
+            return;
+        }
+    };
+
+    let dir = Path::new(output_dir)
+        .join(run.report_id.to_string())
+        .join(run.schedule_id.to_string());
+
+    let file = dir.join(format!("report.{}", run.format.file_extension()));
+
+    let write_result = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&file, output.format(run.format)))
+        .map_err(|e| format!("Cannot write {}: {e}", file.display()));
+
+    if let Err(e) = write_result {
+        eprintln!("{run}: {e}");
+        runner.mark_error(run, &e).unwrap_or_else(|e| eprintln!("{e}"));
+        return;
+    }
+
+    if let Err(e) = runner.mark_complete(run) {
+        eprintln!("{e}");
+        return;
+    }
+
+    println!("{run} complete: {} rows to {}", output.rows.len(), file.display());
+
+    if let (Some(config), Some(email)) = (smtp, &run.email) {
+        let notice = Notice {
+            to: email.clone(),
+            from: None,
+            subject: format!("Report {} complete", run.report_id),
+            body: format!("Your report output is ready: {}", file.display()),
+        };
+
+        match EmailSender::new(config.clone()).send(&notice) {
+            SendOutcome::Sent => {}
+            SendOutcome::Bounced(why) | SendOutcome::Failed(why) => {
+                eprintln!("{run}: completion notice to {email} failed: {why}");
+            }
+        }
+    }
+}
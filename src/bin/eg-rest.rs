@@ -0,0 +1,198 @@
+//! REST+JSON HTTP service.
+//!
+//! Exposes pcrud-style CRUD and search over HTTP.  Clients log in
+//! via POST /auth/login and pass the returned authtoken as a Bearer
+//! token on subsequent requests.  Requests are processed serially
+//! on a single bus connection.
+
+use evergreen as eg;
+
+use eg::rest::{parse_query, RestServer};
+use std::collections::HashMap;
+use std::env;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8688";
+const HELP_TEXT: &str = r#"Usage: eg-rest [options]
+
+Options:
+
+    --listen <host:port>
+        Address to listen on.  Defaults to 127.0.0.1:8688.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optopt("", "listen", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let listen = params
+        .opt_str("listen")
+        .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let server = RestServer::new(ctx.client(), ctx.idl());
+
+    let listener = TcpListener::bind(&listen).unwrap_or_else(|e| {
+        eprintln!("Cannot listen on {listen}: {e}");
+        process::exit(1);
+    });
+
+    log::info!("eg-rest listening on {listen}");
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                log::error!("Accept failed: {e}");
+                continue;
+            }
+        };
+
+        if let Err(e) = handle_connection(&mut stream, &server) {
+            log::error!("Request handling failed: {e}");
+        }
+    }
+}
+
+/// Read one HTTP request, dispatch to the server, and reply.
+fn handle_connection(stream: &mut TcpStream, server: &RestServer) -> Result<(), String> {
+    let (method, target, headers, body) = read_http_request(stream)?;
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, parse_query(query)),
+        None => (target.as_str(), HashMap::new()),
+    };
+
+    let authtoken = headers
+        .get("authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim());
+
+    let response = server.handle(&method, path, &query, authtoken, &body);
+
+    log::info!("{method} {target} => {}", response.status);
+
+    let body = response.body.dump();
+    let http = format!(
+        concat!(
+            "HTTP/1.1 {} {}\r\n",
+            "Content-Type: application/json\r\n",
+            "Content-Length: {}\r\n",
+            "Connection: close\r\n",
+            "\r\n",
+            "{}"
+        ),
+        response.status,
+        status_text(response.status),
+        body.len(),
+        body
+    );
+
+    stream
+        .write_all(http.as_bytes())
+        .map_err(|e| format!("Error writing response: {e}"))
+}
+
+fn status_text(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Read one HTTP request, returning the method, target, lowercased
+/// headers, and body.
+fn read_http_request(
+    stream: &mut TcpStream,
+) -> Result<(String, String, HashMap<String, String>, String), String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    // Read until the end of the headers.
+    let header_end;
+    loop {
+        let count = stream
+            .read(&mut chunk)
+            .map_err(|e| format!("Error reading request: {e}"))?;
+
+        if count == 0 {
+            return Err("Client disconnected mid-request".to_string());
+        }
+
+        buffer.extend_from_slice(&chunk[..count]);
+
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            header_end = pos;
+            break;
+        }
+
+        if buffer.len() > 65536 {
+            return Err("Request headers too large".to_string());
+        }
+    }
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+
+    let request_line = lines.next().ok_or("Empty request")?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().ok_or("Request has no method")?.to_string();
+    let target = parts.next().ok_or("Request has no target")?.to_string();
+
+    let mut headers = HashMap::new();
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+
+    let mut body = buffer[header_end + 4..].to_vec();
+
+    while body.len() < content_length {
+        let count = stream
+            .read(&mut chunk)
+            .map_err(|e| format!("Error reading request body: {e}"))?;
+
+        if count == 0 {
+            break;
+        }
+
+        body.extend_from_slice(&chunk[..count]);
+    }
+
+    let body =
+        String::from_utf8(body).map_err(|e| format!("Request body is not valid UTF-8: {e}"))?;
+
+    Ok((method, target, headers, body))
+}
@@ -0,0 +1,97 @@
+//! Action/Trigger event processor: runs pending events, optionally
+//! creating events for a hook first.
+
+use evergreen as eg;
+
+use eg::editor::Editor;
+use eg::trigger::Processor;
+use std::env;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+const HELP_TEXT: &str = r#"Usage: eg-trigger-runner [options]
+
+Options:
+
+    --event-def <id>
+        Only process events for this event definition.
+
+    --create-for-hook <hook>
+        Before processing, create pending events on every active
+        definition of this hook for the target IDs provided as
+        free arguments.
+
+    --loop-interval <seconds>
+        Run as a daemon, sleeping this long between passes.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optopt("", "event-def", "", "");
+    opts.optopt("", "create-for-hook", "", "");
+    opts.optopt("", "loop-interval", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let def_filter = params
+        .opt_str("event-def")
+        .and_then(|v| v.parse::<i64>().ok());
+
+    let loop_interval = params
+        .opt_str("loop-interval")
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let mut processor = Processor::new(Editor::new(ctx.client(), ctx.idl()));
+
+    if let Some(hook) = params.opt_str("create-for-hook") {
+        let targets: Vec<i64> = params
+            .free
+            .iter()
+            .filter_map(|id| id.parse::<i64>().ok())
+            .collect();
+
+        if targets.is_empty() {
+            eprintln!("--create-for-hook requires target IDs as arguments");
+            process::exit(1);
+        }
+
+        match processor.create_events_for_hook(&hook, &targets) {
+            Ok(count) => println!("Created {count} events for hook {hook}"),
+            Err(e) => {
+                eprintln!("Event creation failed: {e}");
+                process::exit(1);
+            }
+        }
+    }
+
+    loop {
+        match processor.process_pending(def_filter) {
+            Ok(count) => println!("Processed {count} events"),
+            Err(e) => eprintln!("Event processing failed: {e}"),
+        }
+
+        match loop_interval {
+            Some(secs) => thread::sleep(Duration::from_secs(secs)),
+            None => break,
+        }
+    }
+}
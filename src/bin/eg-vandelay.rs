@@ -0,0 +1,170 @@
+//! End-to-end vandelay import from a MARCXML file: create a queue,
+//! upload the records, run matching, and optionally import.
+
+use evergreen as eg;
+
+use eg::auth::{AuthLoginArgs, AuthSession};
+use eg::vandelay::{ImportArgs, QueueType, Vandelay};
+use std::env;
+use std::fs;
+use std::process;
+
+const HELP_TEXT: &str = r#"Usage: eg-vandelay --username <user> --password <pass> \
+    --queue-name <name> [options] <marcxml-file>
+
+Options:
+
+    --username <user>
+    --password <pass>
+        Evergreen login; the importing user.
+
+    --queue-name <name>
+        Name for the new record queue.
+
+    --authority
+        Import authority records instead of bibs.
+
+    --match-set <id>
+        Match set to attach to the queue and run after upload.
+
+    --import-def <id>
+        Import item attribute definition for the queue.
+
+    --merge-profile <id>
+        Overlay/merge profile applied at import time.
+
+    --import-no-match
+    --auto-overlay-exact
+    --auto-overlay-1match
+        Import behavior flags; see the vandelay docs.
+
+    --import
+        Actually import the queue.  Without this, records are only
+        queued and matched.
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "authority", "");
+    opts.optflag("", "import", "");
+    opts.optflag("", "import-no-match", "");
+    opts.optflag("", "auto-overlay-exact", "");
+    opts.optflag("", "auto-overlay-1match", "");
+    opts.optopt("", "username", "", "");
+    opts.optopt("", "password", "", "");
+    opts.optopt("", "queue-name", "", "");
+    opts.optopt("", "match-set", "", "");
+    opts.optopt("", "import-def", "", "");
+    opts.optopt("", "merge-profile", "", "");
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let required = |name: &str| {
+        params.opt_str(name).unwrap_or_else(|| {
+            eprintln!("--{name} is required");
+            process::exit(1);
+        })
+    };
+
+    let username = required("username");
+    let password = required("password");
+    let queue_name = required("queue-name");
+
+    let marc_file = params.free.first().cloned().unwrap_or_else(|| {
+        eprintln!("A MARCXML file is required");
+        process::exit(1);
+    });
+
+    let xml = fs::read_to_string(&marc_file).unwrap_or_else(|e| {
+        eprintln!("Cannot read {marc_file}: {e}");
+        process::exit(1);
+    });
+
+    let queue_type = if params.opt_present("authority") {
+        QueueType::Authority
+    } else {
+        QueueType::Bib
+    };
+
+    let opt_id = |name: &str| params.opt_str(name).and_then(|v| v.parse::<i64>().ok());
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let login = AuthLoginArgs::new(&username, &password, "staff", None);
+    let session = AuthSession::login(ctx.client(), &login).unwrap_or_else(|e| {
+        eprintln!("Login error: {e}");
+        process::exit(1);
+    });
+
+    let session = session.unwrap_or_else(|| {
+        eprintln!("Login failed for {username}");
+        process::exit(1);
+    });
+
+    let mut vandelay = Vandelay::new(ctx.client(), ctx.idl(), session.token());
+
+    let match_set = opt_id("match-set");
+
+    let queue_id = vandelay
+        .create_queue(queue_type, &queue_name, match_set, opt_id("import-def"))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(1);
+        });
+
+    println!("Created queue {queue_id} ({queue_name})");
+
+    let record_ids = vandelay
+        .queue_records(queue_type, queue_id, &xml)
+        .unwrap_or_else(|e| {
+            eprintln!("Record upload failed: {e}");
+            process::exit(1);
+        });
+
+    println!("Queued {} records", record_ids.len());
+
+    if match_set.is_some() {
+        match vandelay.run_matches(queue_type, queue_id) {
+            Ok(count) => println!("Found {count} matches"),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+
+    if params.opt_present("import") {
+        let import_args = ImportArgs {
+            merge_profile: opt_id("merge-profile"),
+            import_no_match: params.opt_present("import-no-match"),
+            auto_overlay_exact: params.opt_present("auto-overlay-exact"),
+            auto_overlay_1match: params.opt_present("auto-overlay-1match"),
+        };
+
+        match vandelay.import_queue(queue_type, queue_id, &import_args) {
+            Ok(summary) => println!("Import complete: {}", summary.dump()),
+            Err(e) => {
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+    }
+
+    match vandelay.queue_summary(queue_type, queue_id) {
+        Ok(summary) => println!("Queue summary: {}", summary.dump()),
+        Err(e) => eprintln!("Cannot fetch queue summary: {e}"),
+    }
+}
@@ -0,0 +1,367 @@
+//! egsh -- the Evergreen interactive shell.
+//!
+//! Supports direct-database IDL queries (--db) and API calls over
+//! the bus.
+
+use evergreen as eg;
+
+use eg::auth::{AuthLoginArgs, AuthSession};
+use eg::db::DatabaseConnection;
+use eg::editor::Editor;
+use eg::idldb::{IdlClassSearch, Translator};
+use std::cell::RefCell;
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+use std::rc::Rc;
+
+const PROMPT: &str = "egsh# ";
+
+const HELP_TEXT: &str = r#"Commands:
+
+    login <username> <password> [<type> [<workstation>]]
+
+    req <service> <method> [<param> ...]
+        Send an API request.  Params are JSON values.
+
+    retrieve <classname> <pkey>
+        Retrieve an object by primary key via the Editor.
+
+    idl classes [<filter>]
+        List IDL classes, optionally filtered by substring.
+
+    idl class <classname>
+        Display the fields of an IDL class.
+
+    idl search <classname> <field> <operand> <value>
+        Search the database directly (requires --db).
+        Example: idl search aou name ~* "branch"
+
+    idl get <classname> <pkey> [--flesh <field> ...]
+        Retrieve one object by primary key directly from the
+        database (requires --db), optionally fleshing linked fields.
+        Example: idl get au 1 --flesh home_ou
+
+    help
+    quit
+"#;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    let mut opts = getopts::Options::new();
+
+    opts.optflag("h", "help", "");
+    opts.optflag("", "db", "Connect to the database for direct queries");
+    DatabaseConnection::append_options(&mut opts);
+
+    let params = opts.parse(&args[1..]).unwrap_or_else(|e| {
+        eprintln!("Error parsing options: {e}");
+        process::exit(1);
+    });
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
+    let ctx = eg::init::init().unwrap_or_else(|e| {
+        eprintln!("Cannot initialize: {e}");
+        process::exit(1);
+    });
+
+    let translator = if params.opt_present("db") {
+        let mut db = DatabaseConnection::new_from_options(&params);
+        if let Err(e) = db.connect() {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+        Some(Translator::new(
+            ctx.idl().clone(),
+            Rc::new(RefCell::new(db)),
+        ))
+    } else {
+        None
+    };
+
+    let mut shell = Shell {
+        ctx,
+        translator,
+        authtoken: None,
+    };
+
+    shell.main_loop();
+}
+
+struct Shell {
+    ctx: eg::init::Context,
+    translator: Option<Translator>,
+    authtoken: Option<String>,
+}
+
+impl Shell {
+    fn main_loop(&mut self) {
+        let stdin = io::stdin();
+
+        loop {
+            print!("{PROMPT}");
+            io::stdout().flush().ok();
+
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) => break, // EOF
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Error reading input: {e}");
+                    break;
+                }
+            }
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "quit" || line == "exit" {
+                break;
+            }
+
+            if let Err(e) = self.dispatch(line) {
+                eprintln!("Error: {e}");
+            }
+        }
+    }
+
+    fn dispatch(&mut self, line: &str) -> Result<(), String> {
+        let tokens = tokenize(line);
+        let command = tokens[0].as_str();
+
+        match command {
+            "help" => {
+                println!("{HELP_TEXT}");
+                Ok(())
+            }
+            "login" => self.login(&tokens[1..]),
+            "req" => self.send_request(&tokens[1..]),
+            "retrieve" => self.retrieve(&tokens[1..]),
+            "idl" => self.idl_command(&tokens[1..]),
+            _ => Err(format!("Unknown command: {command}")),
+        }
+    }
+
+    fn login(&mut self, args: &[String]) -> Result<(), String> {
+        if args.len() < 2 {
+            return Err("login requires a username and password".to_string());
+        }
+
+        let login_type = args.get(2).map(|t| t.as_str()).unwrap_or("temp");
+        let workstation = args.get(3).map(|w| w.as_str());
+
+        let auth_args = AuthLoginArgs::new(&args[0], &args[1], login_type, workstation);
+
+        match AuthSession::login(self.ctx.client(), &auth_args)? {
+            Some(session) => {
+                println!("Logged in: {}", session.token());
+                self.authtoken = Some(session.token().to_string());
+                Ok(())
+            }
+            None => Err("Login failed".to_string()),
+        }
+    }
+
+    fn send_request(&mut self, args: &[String]) -> Result<(), String> {
+        if args.len() < 2 {
+            return Err("req requires a service and method".to_string());
+        }
+
+        let mut params = Vec::new();
+        for arg in &args[2..] {
+            params.push(json::parse(arg).map_err(|e| format!("Invalid JSON param {arg}: {e}"))?);
+        }
+
+        let session = self.ctx.client().session(&args[0]);
+        let mut req = session.request(&args[1], params)?;
+
+        while let Some(resp) = req.recv(60)? {
+            println!("{}", resp.pretty(2));
+        }
+
+        Ok(())
+    }
+
+    fn retrieve(&mut self, args: &[String]) -> Result<(), String> {
+        if args.len() < 2 {
+            return Err("retrieve requires a classname and pkey".to_string());
+        }
+
+        let pkey = json::parse(&args[1]).map_err(|e| format!("Invalid pkey: {e}"))?;
+
+        let mut editor = match &self.authtoken {
+            Some(t) => Editor::with_auth(self.ctx.client(), self.ctx.idl(), t),
+            None => Editor::new(self.ctx.client(), self.ctx.idl()),
+        };
+
+        match editor.retrieve(&args[0], pkey)? {
+            Some(obj) => println!("{}", obj.pretty(2)),
+            None => println!("No such {} object", args[0]),
+        }
+
+        Ok(())
+    }
+
+    fn idl_command(&mut self, args: &[String]) -> Result<(), String> {
+        if args.is_empty() {
+            return Err("idl requires a subcommand".to_string());
+        }
+
+        match args[0].as_str() {
+            "classes" => {
+                let filter = args.get(1);
+                let mut names: Vec<&str> = self
+                    .ctx
+                    .idl()
+                    .classes()
+                    .keys()
+                    .map(|k| k.as_str())
+                    .filter(|k| match filter {
+                        Some(f) => k.contains(f.as_str()),
+                        None => true,
+                    })
+                    .collect();
+                names.sort_unstable();
+                for name in names {
+                    println!("{name}");
+                }
+                Ok(())
+            }
+            "class" => {
+                let classname = args.get(1).ok_or("idl class requires a classname")?;
+                let class = self
+                    .ctx
+                    .idl()
+                    .get_class(classname)
+                    .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+                println!(
+                    "{} [{}] table={}",
+                    class.classname(),
+                    class.label().unwrap_or(""),
+                    class.tablename().unwrap_or("")
+                );
+
+                for field in class.real_fields_sorted() {
+                    println!("  {} [{}]", field.name(), field.datatype());
+                }
+                Ok(())
+            }
+            "search" => self.idl_search(&args[1..]),
+            "get" => self.idl_get(&args[1..]),
+            sub => Err(format!("Unknown idl subcommand: {sub}")),
+        }
+    }
+
+    fn idl_get(&mut self, args: &[String]) -> Result<(), String> {
+        if args.len() < 2 {
+            return Err("idl get requires a classname and pkey".to_string());
+        }
+
+        let translator = self
+            .translator
+            .as_ref()
+            .ok_or("idl get requires a database connection (--db)")?;
+
+        let classname = args[0].as_str();
+        let pkey_field = translator
+            .idl()
+            .get_class(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?
+            .pkey()
+            .ok_or_else(|| format!("Class {classname} has no primary key"))?
+            .to_string();
+
+        let pkey = match json::parse(&args[1]) {
+            Ok(v) => v,
+            Err(_) => json::from(args[1].as_str()),
+        };
+
+        let mut search = IdlClassSearch::new(classname);
+        search.set_filter(json::object! { [pkey_field.as_str()]: pkey });
+
+        let mut rest = args[2..].iter();
+        while let Some(arg) = rest.next() {
+            if arg != "--flesh" {
+                return Err(format!("Unexpected argument: {arg}"));
+            }
+            let field = rest.next().ok_or("--flesh requires a field name")?;
+            search.set_flesh_depth(1);
+            search.add_flesh_field(classname, field);
+        }
+
+        match translator.idl_class_search(&search)?.first() {
+            Some(obj) => println!("{}", obj.pretty(2)),
+            None => println!("No such {classname} object"),
+        }
+
+        Ok(())
+    }
+
+    fn idl_search(&mut self, args: &[String]) -> Result<(), String> {
+        if args.len() < 4 {
+            return Err("idl search requires: classname field operand value".to_string());
+        }
+
+        let translator = self
+            .translator
+            .as_ref()
+            .ok_or("idl search requires a database connection (--db)")?;
+
+        let value = match json::parse(&args[3]) {
+            Ok(v) => v,
+            Err(_) => json::from(args[3].as_str()),
+        };
+
+        let mut filter = json::object! {};
+        match args[2].as_str() {
+            "=" => filter[args[1].as_str()] = value,
+            op => {
+                let mut test = json::object! {};
+                test[op] = value;
+                filter[args[1].as_str()] = test;
+            }
+        }
+
+        let mut search = IdlClassSearch::new(&args[0]);
+        search.set_filter(filter);
+
+        for obj in translator.idl_class_search(&search)? {
+            println!("{}", obj.pretty(2));
+        }
+
+        Ok(())
+    }
+}
+
+/// Split a command line into tokens, honoring double quotes.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for ch in line.chars() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
@@ -0,0 +1,204 @@
+//! Typed wrappers for the open-ils.booking reservation APIs:
+//! resource availability, reservation creation and cancellation,
+//! and pickup/return.
+
+use crate::event::EgEvent;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+
+const BOOKING_TIMEOUT: u64 = 60;
+
+/// Parameters for an availability query or reservation request.
+#[derive(Debug, Clone)]
+pub struct ReservationParams {
+    /// The booking resource type ID.
+    pub resource_type: i64,
+    /// Requested window, ISO timestamps.
+    pub start_time: String,
+    pub end_time: String,
+    /// Restrict to specific resources of the type.
+    pub resources: Vec<i64>,
+    /// Attribute value IDs the resource must carry.
+    pub attributes: Vec<i64>,
+    pub pickup_lib: Option<i64>,
+}
+
+impl ReservationParams {
+    pub fn new(resource_type: i64, start_time: &str, end_time: &str) -> Self {
+        ReservationParams {
+            resource_type,
+            start_time: start_time.to_string(),
+            end_time: end_time.to_string(),
+            resources: Vec::new(),
+            attributes: Vec::new(),
+            pickup_lib: None,
+        }
+    }
+
+    fn to_json_value(&self) -> JsonValue {
+        let mut args = json::object! {
+            type: self.resource_type,
+            start_time: self.start_time.as_str(),
+            end_time: self.end_time.as_str(),
+        };
+
+        if !self.resources.is_empty() {
+            args["resource_list"] = self.resources.clone().into();
+        }
+        if !self.attributes.is_empty() {
+            args["attribute_values"] = self.attributes.clone().into();
+        }
+        if let Some(lib) = self.pickup_lib {
+            args["pickup_lib"] = lib.into();
+        }
+
+        args
+    }
+}
+
+/// Drives booking operations for one authenticated session.
+pub struct Booking {
+    client: Client,
+    authtoken: String,
+    timeout: u64,
+}
+
+impl Booking {
+    pub fn new(client: &Client, authtoken: &str) -> Self {
+        Booking {
+            client: client.clone(),
+            authtoken: authtoken.to_string(),
+            timeout: BOOKING_TIMEOUT,
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: u64) {
+        self.timeout = timeout;
+    }
+
+    /// Call an open-ils.booking method with the authtoken prepended
+    /// and return its first response.
+    fn request(&self, method: &str, mut params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        params.insert(0, json::from(self.authtoken.as_str()));
+
+        let session = self.client.session("open-ils.booking");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(self.timeout)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Fail on a non-success event response.
+    fn check_event(method: &str, resp: JsonValue) -> Result<JsonValue, String> {
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("{method} failed: {evt}"));
+            }
+        }
+        Ok(resp)
+    }
+
+    /// Resource IDs of the requested type available over the window.
+    pub fn resource_availability(&self, params: &ReservationParams) -> Result<Vec<i64>, String> {
+        let method = "open-ils.booking.resources.filtered_id_list";
+
+        let resp = self.request(method, vec![params.to_json_value()])?;
+        let resp = Booking::check_event(method, resp)?;
+
+        let mut ids = Vec::new();
+        for id in resp.members() {
+            ids.push(util::json_int(id)?);
+        }
+
+        Ok(ids)
+    }
+
+    /// Reserve a resource for a patron, returning the new
+    /// reservation.
+    pub fn create_reservation(
+        &self,
+        patron_barcode: &str,
+        params: &ReservationParams,
+    ) -> Result<JsonValue, String> {
+        let method = "open-ils.booking.reservations.create";
+
+        let mut args = params.to_json_value();
+        args["patron_barcode"] = patron_barcode.into();
+
+        let resp = self.request(method, vec![args])?;
+        Booking::check_event(method, resp)
+    }
+
+    /// Cancel reservations by ID, returning the IDs actually
+    /// cancelled.
+    pub fn cancel_reservations(&self, reservation_ids: &[i64]) -> Result<Vec<i64>, String> {
+        let method = "open-ils.booking.reservations.cancel";
+
+        let ids: Vec<JsonValue> = reservation_ids.iter().map(|id| json::from(*id)).collect();
+
+        let resp = self.request(method, vec![JsonValue::Array(ids)])?;
+        let resp = Booking::check_event(method, resp)?;
+
+        let mut cancelled = Vec::new();
+        for id in resp.members() {
+            cancelled.push(util::json_int(id)?);
+        }
+
+        Ok(cancelled)
+    }
+
+    /// Hand a captured reservation's resource to the patron.
+    pub fn pickup_reservation(&self, reservation: &JsonValue) -> Result<JsonValue, String> {
+        let method = "open-ils.booking.resources.deliver";
+
+        let args = json::object! {
+            reservation: reservation["id"].clone(),
+            patron_barcode: reservation["usr"]["card"]["barcode"].clone(),
+        };
+
+        let resp = self.request(method, vec![args])?;
+        Booking::check_event(method, resp)
+    }
+
+    /// Accept a resource back from the patron.
+    pub fn return_reservation(&self, reservation_id: i64) -> Result<JsonValue, String> {
+        let method = "open-ils.booking.resources.return";
+
+        let args = json::object! {reservation: reservation_id};
+
+        let resp = self.request(method, vec![args])?;
+        Booking::check_event(method, resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params() {
+        let mut params =
+            ReservationParams::new(7, "2026-09-01T09:00:00-0400", "2026-09-01T11:00:00-0400");
+        params.resources = vec![1, 2];
+        params.pickup_lib = Some(4);
+
+        let value = params.to_json_value();
+        assert_eq!(value["type"], 7);
+        assert_eq!(value["resource_list"][1], 2);
+        assert_eq!(value["pickup_lib"], 4);
+        assert!(value["attribute_values"].is_null());
+    }
+
+    #[test]
+    fn test_check_event() {
+        let blocked = json::object! {
+            ilsevent: 1588, textcode: "RESERVATION_NOT_FOUND", desc: "",
+        };
+        assert!(Booking::check_event("test", blocked).is_err());
+
+        assert!(Booking::check_event("test", json::array![1, 2]).is_ok());
+    }
+}
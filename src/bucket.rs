@@ -0,0 +1,273 @@
+//! Container ("bucket") wrappers: create buckets, add and remove
+//! items, and read contents back as ID lists.
+//!
+//! Batch tools accept "run against bucket N" by resolving the
+//! bucket's target IDs up front and then proceeding as if the IDs
+//! had been passed directly.
+
+use crate::editor::Editor;
+use crate::event::EgEvent;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::sync::Arc;
+
+const BUCKET_TIMEOUT: u64 = 60;
+
+/// The container classes the actor APIs know about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketType {
+    Biblio,
+    Copy,
+    User,
+    CallNumber,
+}
+
+impl BucketType {
+    /// The class tag the open-ils.actor.container APIs expect.
+    pub fn api_class(&self) -> &'static str {
+        match self {
+            Self::Biblio => "biblio",
+            Self::Copy => "copy",
+            Self::User => "user",
+            Self::CallNumber => "callnumber",
+        }
+    }
+
+    /// The bucket row's IDL class.
+    pub fn bucket_class(&self) -> &'static str {
+        match self {
+            Self::Biblio => "cbreb",
+            Self::Copy => "ccb",
+            Self::User => "cub",
+            Self::CallNumber => "ccnb",
+        }
+    }
+
+    /// The bucket item row's IDL class.
+    pub fn item_class(&self) -> &'static str {
+        match self {
+            Self::Biblio => "cbrebi",
+            Self::Copy => "ccbi",
+            Self::User => "cubi",
+            Self::CallNumber => "ccnbi",
+        }
+    }
+
+    /// The item field holding the target ID.
+    pub fn target_field(&self) -> &'static str {
+        match self {
+            Self::Biblio => "target_biblio_record_entry",
+            Self::Copy => "target_copy",
+            Self::User => "target_user",
+            Self::CallNumber => "target_call_number",
+        }
+    }
+}
+
+impl std::str::FromStr for BucketType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "biblio" | "bib" => Ok(Self::Biblio),
+            "copy" => Ok(Self::Copy),
+            "user" => Ok(Self::User),
+            "callnumber" | "volume" => Ok(Self::CallNumber),
+            _ => Err(format!("Unknown bucket type: {s}")),
+        }
+    }
+}
+
+/// Drives bucket operations for one authenticated session.
+pub struct Buckets {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+}
+
+impl Buckets {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Buckets {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+        }
+    }
+
+    /// Call an open-ils.actor method with the authtoken prepended
+    /// and return its first response.
+    fn request(&self, method: &str, mut params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        params.insert(0, json::from(self.authtoken.as_str()));
+
+        let session = self.client.session("open-ils.actor");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(BUCKET_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Fail on a non-success event response.
+    fn check_event(method: &str, resp: JsonValue) -> Result<JsonValue, String> {
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("{method} failed: {evt}"));
+            }
+        }
+        Ok(resp)
+    }
+
+    /// Create a bucket, returning its ID.
+    pub fn create_bucket(
+        &self,
+        bucket_type: BucketType,
+        owner: i64,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<i64, String> {
+        let method = "open-ils.actor.container.create";
+
+        let mut bucket = json::object! {
+            "_classname": bucket_type.bucket_class(),
+            isnew: 1,
+            owner: owner,
+            name: name,
+            btype: "staff_client",
+            pub: "f",
+        };
+
+        if let Some(description) = description {
+            bucket["description"] = description.into();
+        }
+
+        let resp = self.request(
+            method,
+            vec![json::from(bucket_type.api_class()), bucket],
+        )?;
+        let resp = Buckets::check_event(method, resp)?;
+
+        util::json_int(&resp)
+    }
+
+    /// Find a user's bucket by name.
+    pub fn find_bucket(
+        &mut self,
+        bucket_type: BucketType,
+        owner: i64,
+        name: &str,
+    ) -> Result<Option<JsonValue>, String> {
+        let mut hits = self.editor.search(
+            bucket_type.bucket_class(),
+            json::object! {owner: owner, name: name, btype: "staff_client"},
+        )?;
+        Ok(hits.pop())
+    }
+
+    /// Add target IDs to a bucket.
+    pub fn add_items(
+        &self,
+        bucket_type: BucketType,
+        bucket_id: i64,
+        target_ids: &[i64],
+    ) -> Result<(), String> {
+        let method = "open-ils.actor.container.item.create";
+
+        for target_id in target_ids {
+            let mut item = json::object! {
+                "_classname": bucket_type.item_class(),
+                isnew: 1,
+                bucket: bucket_id,
+            };
+            item[bucket_type.target_field()] = (*target_id).into();
+
+            let resp = self.request(
+                method,
+                vec![json::from(bucket_type.api_class()), item],
+            )?;
+            Buckets::check_event(method, resp)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove target IDs from a bucket.  Returns how many items
+    /// were removed.
+    pub fn remove_items(
+        &mut self,
+        bucket_type: BucketType,
+        bucket_id: i64,
+        target_ids: &[i64],
+    ) -> Result<usize, String> {
+        let ids: Vec<JsonValue> = target_ids.iter().map(|id| json::from(*id)).collect();
+
+        let mut filter = json::object! {bucket: bucket_id};
+        filter[bucket_type.target_field()] = JsonValue::Array(ids);
+
+        let items = self.editor.search(bucket_type.item_class(), filter)?;
+
+        let method = "open-ils.actor.container.item.delete";
+        let mut removed = 0;
+
+        for item in items {
+            let resp = self.request(
+                method,
+                vec![
+                    json::from(bucket_type.api_class()),
+                    item["id"].clone(),
+                ],
+            )?;
+            Buckets::check_event(method, resp)?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// The target IDs in a bucket.
+    pub fn bucket_ids(
+        &mut self,
+        bucket_type: BucketType,
+        bucket_id: i64,
+    ) -> Result<Vec<i64>, String> {
+        let items = self
+            .editor
+            .search(bucket_type.item_class(), json::object! {bucket: bucket_id})?;
+
+        let mut ids = Vec::new();
+        for item in &items {
+            ids.push(util::json_int(&item[bucket_type.target_field()])?);
+        }
+
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_type() {
+        let btype: BucketType = "biblio".parse().unwrap();
+        assert_eq!(btype, BucketType::Biblio);
+        assert_eq!(btype.bucket_class(), "cbreb");
+        assert_eq!(btype.item_class(), "cbrebi");
+        assert_eq!(btype.target_field(), "target_biblio_record_entry");
+
+        let btype: BucketType = "volume".parse().unwrap();
+        assert_eq!(btype.api_class(), "callnumber");
+
+        assert!("shelf".parse::<BucketType>().is_err());
+    }
+
+    #[test]
+    fn test_copy_and_user_mappings() {
+        assert_eq!(BucketType::Copy.item_class(), "ccbi");
+        assert_eq!(BucketType::Copy.target_field(), "target_copy");
+        assert_eq!(BucketType::User.bucket_class(), "cub");
+        assert_eq!(BucketType::User.target_field(), "target_user");
+    }
+}
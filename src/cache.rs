@@ -0,0 +1,222 @@
+//! Cache access with memcached and Redis backends.
+//!
+//! Provides the get/put/delete-with-TTL semantics OpenSRF services
+//! get from OpenSRF::Utils::Cache: auth session lookups, org
+//! setting values, IDL-derived metadata, and the like.  Values are
+//! stored as JSON.
+
+use json::JsonValue;
+use redis::Commands;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+const DEFAULT_MEMCACHED: &str = "127.0.0.1:11211";
+
+/// Memcached keys max out at 250 bytes and cannot contain
+/// whitespace; longer or unruly keys are hashed, as the Perl cache
+/// layer does.
+const MAX_KEY_LENGTH: usize = 250;
+
+/// Normalize a cache key for safe use on any backend.
+fn sanitize_key(key: &str) -> String {
+    if key.len() <= MAX_KEY_LENGTH && !key.contains(char::is_whitespace) {
+        return key.to_string();
+    }
+    format!("{:x}", md5::compute(key.as_bytes()))
+}
+
+/// A hand-rolled memcached text-protocol connection.
+struct Memcached {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Memcached {
+    fn connect(address: &str) -> Result<Memcached, String> {
+        let stream = TcpStream::connect(address)
+            .map_err(|e| format!("Cannot connect to memcached at {address}: {e}"))?;
+
+        let reader = BufReader::new(
+            stream
+                .try_clone()
+                .map_err(|e| format!("Cannot clone memcached stream: {e}"))?,
+        );
+
+        Ok(Memcached {
+            reader,
+            writer: stream,
+        })
+    }
+
+    fn send_line(&mut self, line: &str) -> Result<(), String> {
+        self.writer
+            .write_all(line.as_bytes())
+            .and_then(|_| self.writer.write_all(b"\r\n"))
+            .map_err(|e| format!("Error writing to memcached: {e}"))
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        self.reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Error reading from memcached: {e}"))?;
+        Ok(line.trim_end().to_string())
+    }
+
+    fn get(&mut self, key: &str) -> Result<Option<String>, String> {
+        self.send_line(&format!("get {key}"))?;
+
+        let header = self.read_line()?;
+        if header == "END" {
+            return Ok(None);
+        }
+
+        // VALUE <key> <flags> <bytes>
+        let bytes: usize = header
+            .split_whitespace()
+            .nth(3)
+            .and_then(|b| b.parse().ok())
+            .ok_or_else(|| format!("Unexpected memcached response: {header}"))?;
+
+        let mut value = String::new();
+        while value.len() < bytes {
+            value.push_str(&self.read_line()?);
+        }
+
+        self.read_line()?; // END
+
+        Ok(Some(value))
+    }
+
+    fn set(&mut self, key: &str, value: &str, ttl: u64) -> Result<(), String> {
+        self.send_line(&format!("set {key} 0 {ttl} {}", value.len()))?;
+        self.send_line(value)?;
+
+        match self.read_line()?.as_str() {
+            "STORED" => Ok(()),
+            resp => Err(format!("memcached set failed: {resp}")),
+        }
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), String> {
+        self.send_line(&format!("delete {key}"))?;
+
+        match self.read_line()?.as_str() {
+            "DELETED" | "NOT_FOUND" => Ok(()),
+            resp => Err(format!("memcached delete failed: {resp}")),
+        }
+    }
+}
+
+enum Backend {
+    Memcached(Memcached),
+    Redis(Box<redis::Connection>),
+}
+
+/// A connection to a cache server.
+pub struct Cache {
+    backend: Backend,
+}
+
+impl Cache {
+    /// Connect to a memcached server, e.g. "127.0.0.1:11211".
+    pub fn new_memcached(address: &str) -> Result<Cache, String> {
+        Ok(Cache {
+            backend: Backend::Memcached(Memcached::connect(address)?),
+        })
+    }
+
+    /// Connect to a Redis server via a redis:// URI.
+    pub fn new_redis(uri: &str) -> Result<Cache, String> {
+        let client =
+            redis::Client::open(uri).map_err(|e| format!("Invalid cache URI {uri}: {e}"))?;
+
+        let connection = client
+            .get_connection()
+            .map_err(|e| format!("Cannot connect to cache at {uri}: {e}"))?;
+
+        Ok(Cache {
+            backend: Backend::Redis(Box::new(connection)),
+        })
+    }
+
+    /// Connect using EG_CACHE_URI: either a redis:// URI or a
+    /// memcached host:port.  Defaults to memcached on localhost.
+    pub fn from_env() -> Result<Cache, String> {
+        let uri = std::env::var("EG_CACHE_URI").unwrap_or_else(|_| DEFAULT_MEMCACHED.to_string());
+
+        if uri.starts_with("redis://") {
+            Cache::new_redis(&uri)
+        } else {
+            Cache::new_memcached(&uri)
+        }
+    }
+
+    /// Fetch a cached value.
+    pub fn get(&mut self, key: &str) -> Result<Option<JsonValue>, String> {
+        let key = sanitize_key(key);
+
+        let text = match &mut self.backend {
+            Backend::Memcached(conn) => conn.get(&key)?,
+            Backend::Redis(conn) => conn
+                .get(&key)
+                .map_err(|e| format!("Error reading from cache: {e}"))?,
+        };
+
+        match text {
+            Some(text) => json::parse(&text)
+                .map(Some)
+                .map_err(|e| format!("Cannot parse cached value for {key}: {e}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Store a value.  A TTL of 0 means no expiration.
+    pub fn put(&mut self, key: &str, value: &JsonValue, ttl: u64) -> Result<(), String> {
+        let key = sanitize_key(key);
+        let text = value.dump();
+
+        match &mut self.backend {
+            Backend::Memcached(conn) => conn.set(&key, &text, ttl),
+            Backend::Redis(conn) => {
+                let result: Result<(), redis::RedisError> = if ttl > 0 {
+                    conn.set_ex(&key, text, ttl)
+                } else {
+                    conn.set(&key, text)
+                };
+                result.map_err(|e| format!("Error writing to cache: {e}"))
+            }
+        }
+    }
+
+    /// Remove a value.  Removing an absent key is not an error.
+    pub fn delete(&mut self, key: &str) -> Result<(), String> {
+        let key = sanitize_key(key);
+
+        match &mut self.backend {
+            Backend::Memcached(conn) => conn.delete(&key),
+            Backend::Redis(conn) => conn
+                .del::<_, i64>(&key)
+                .map(|_| ())
+                .map_err(|e| format!("Error deleting from cache: {e}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_key() {
+        assert_eq!(sanitize_key("eg.auth.token.abc123"), "eg.auth.token.abc123");
+
+        // Whitespace and over-length keys are hashed to 32 hex chars.
+        let hashed = sanitize_key("a key with spaces");
+        assert_eq!(hashed.len(), 32);
+        assert!(hashed.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let long_key = "x".repeat(MAX_KEY_LENGTH + 1);
+        assert_eq!(sanitize_key(&long_key).len(), 32);
+    }
+}
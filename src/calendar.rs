@@ -0,0 +1,260 @@
+//! Org unit calendars: closed dates and hours of operation.
+//!
+//! Answers "is this org open at time T" and slides due dates past
+//! closures, combining actor.org_unit.closed_date ranges with the
+//! org's weekly hours of operation.
+
+use crate::date::parse_timestamp;
+use crate::editor::Editor;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveTime, Timelike};
+use json::JsonValue;
+
+/// How far forward a due-date slide will search before giving up,
+/// so an org closed forever cannot loop us.
+const MAX_SLIDE_DAYS: u32 = 366;
+
+/// One day's open/close times; None means closed all day.
+type DayHours = Option<(NaiveTime, NaiveTime)>;
+
+/// Weekly hours of operation, Monday-first to match chrono's
+/// `weekday().num_days_from_monday()`.
+#[derive(Debug, Clone)]
+pub struct HoursOfOperation {
+    days: [DayHours; 7],
+}
+
+impl HoursOfOperation {
+    /// Parse an aouhoo row: dow_0_open .. dow_6_close, where dow 0
+    /// is Monday and "00:00:00" open and close means closed.
+    pub fn from_row(row: &JsonValue) -> Result<HoursOfOperation, String> {
+        let mut days: [DayHours; 7] = Default::default();
+
+        for (dow, day) in days.iter_mut().enumerate() {
+            let open = row[format!("dow_{dow}_open")]
+                .as_str()
+                .unwrap_or("00:00:00");
+            let close = row[format!("dow_{dow}_close")]
+                .as_str()
+                .unwrap_or("00:00:00");
+
+            if open == close {
+                continue; // closed all day
+            }
+
+            let open = NaiveTime::parse_from_str(open, "%H:%M:%S")
+                .map_err(|e| format!("Invalid open time {open}: {e}"))?;
+            let close = NaiveTime::parse_from_str(close, "%H:%M:%S")
+                .map_err(|e| format!("Invalid close time {close}: {e}"))?;
+
+            *day = Some((open, close));
+        }
+
+        Ok(HoursOfOperation { days })
+    }
+
+    /// The open/close window for a moment's day of week.
+    fn hours_for(&self, time: &DateTime<FixedOffset>) -> DayHours {
+        self.days[time.weekday().num_days_from_monday() as usize]
+    }
+
+    fn is_open_at(&self, time: &DateTime<FixedOffset>) -> bool {
+        match self.hours_for(time) {
+            Some((open, close)) => time.time() >= open && time.time() < close,
+            None => false,
+        }
+    }
+}
+
+/// A closed date range.
+#[derive(Debug, Clone)]
+pub struct ClosedRange {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub reason: String,
+}
+
+impl ClosedRange {
+    fn contains(&self, time: &DateTime<FixedOffset>) -> bool {
+        *time >= self.start && *time <= self.end
+    }
+}
+
+/// One org unit's closure calendar.
+pub struct Calendar {
+    /// None means hours were never configured: open around the
+    /// clock except during closed ranges.
+    hours: Option<HoursOfOperation>,
+    closures: Vec<ClosedRange>,
+}
+
+impl Calendar {
+    /// Load the closed dates and hours of operation for an org.
+    pub fn load(editor: &mut Editor, org_id: i64) -> Result<Calendar, String> {
+        let mut closures = Vec::new();
+        for row in editor.search("aoucd", json::object! {org_unit: org_id})? {
+            closures.push(ClosedRange {
+                start: parse_timestamp(row["close_start"].as_str().unwrap_or(""))?,
+                end: parse_timestamp(row["close_end"].as_str().unwrap_or(""))?,
+                reason: row["reason"].as_str().unwrap_or("").to_string(),
+            });
+        }
+
+        let hours = match editor.retrieve("aouhoo", json::from(org_id))? {
+            Some(row) => Some(HoursOfOperation::from_row(&row)?),
+            None => None,
+        };
+
+        Ok(Calendar { hours, closures })
+    }
+
+    /// Build a calendar directly, mainly for tests and callers with
+    /// already-fetched data.
+    pub fn new(hours: Option<HoursOfOperation>, closures: Vec<ClosedRange>) -> Calendar {
+        Calendar { hours, closures }
+    }
+
+    /// Add a closure not (yet) present in the database, e.g. one a
+    /// dry run should account for without creating.
+    pub fn add_closure(&mut self, closure: ClosedRange) {
+        self.closures.push(closure);
+    }
+
+    /// The closure covering a moment, if any.
+    pub fn closure_at(&self, time: &DateTime<FixedOffset>) -> Option<&ClosedRange> {
+        self.closures.iter().find(|c| c.contains(time))
+    }
+
+    /// Whether the org is open at a moment: not in a closed range
+    /// and within hours of operation.
+    pub fn is_open_at(&self, time: &DateTime<FixedOffset>) -> bool {
+        if self.closure_at(time).is_some() {
+            return false;
+        }
+
+        match &self.hours {
+            Some(hours) => hours.is_open_at(time),
+            None => true,
+        }
+    }
+
+    /// Whether the org opens at all on a moment's calendar day.
+    pub fn is_open_day(&self, time: &DateTime<FixedOffset>) -> bool {
+        // A closure spanning the whole day wins over hours.
+        let day_start = time
+            .with_hour(0)
+            .and_then(|t| t.with_minute(0))
+            .and_then(|t| t.with_second(0))
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(*time);
+        let day_end = day_start + chrono::Duration::days(1) - chrono::Duration::seconds(1);
+
+        if self
+            .closures
+            .iter()
+            .any(|c| c.start <= day_start && c.end >= day_end)
+        {
+            return false;
+        }
+
+        match &self.hours {
+            Some(hours) => hours.hours_for(time).is_some(),
+            None => true,
+        }
+    }
+
+    /// Slide a due date forward, one day at a time, until it lands
+    /// on a day the org is open.
+    pub fn slide_due_date(
+        &self,
+        due: DateTime<FixedOffset>,
+    ) -> Result<DateTime<FixedOffset>, String> {
+        let mut due = due;
+
+        for _ in 0..MAX_SLIDE_DAYS {
+            if self.is_open_day(&due) {
+                return Ok(due);
+            }
+            due += chrono::Duration::days(1);
+        }
+
+        Err(format!(
+            "No open day within {MAX_SLIDE_DAYS} days of {due}"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Open 09:00-17:00 Monday through Friday.
+    fn weekday_hours() -> HoursOfOperation {
+        let row = json::object! {
+            dow_0_open: "09:00:00", dow_0_close: "17:00:00",
+            dow_1_open: "09:00:00", dow_1_close: "17:00:00",
+            dow_2_open: "09:00:00", dow_2_close: "17:00:00",
+            dow_3_open: "09:00:00", dow_3_close: "17:00:00",
+            dow_4_open: "09:00:00", dow_4_close: "17:00:00",
+            dow_5_open: "00:00:00", dow_5_close: "00:00:00",
+            dow_6_open: "00:00:00", dow_6_close: "00:00:00",
+        };
+        HoursOfOperation::from_row(&row).expect("hours should parse")
+    }
+
+    fn ts(value: &str) -> DateTime<FixedOffset> {
+        parse_timestamp(value).expect("timestamp should parse")
+    }
+
+    #[test]
+    fn test_is_open_at() {
+        let calendar = Calendar::new(Some(weekday_hours()), Vec::new());
+
+        // 2026-09-01 is a Tuesday.
+        assert!(calendar.is_open_at(&ts("2026-09-01T10:00:00+0000")));
+        assert!(!calendar.is_open_at(&ts("2026-09-01T18:00:00+0000")));
+        // 2026-09-05 is a Saturday.
+        assert!(!calendar.is_open_at(&ts("2026-09-05T10:00:00+0000")));
+    }
+
+    #[test]
+    fn test_closures_override_hours() {
+        let closure = ClosedRange {
+            start: ts("2026-09-01T00:00:00+0000"),
+            end: ts("2026-09-02T23:59:59+0000"),
+            reason: "Inventory".to_string(),
+        };
+        let calendar = Calendar::new(Some(weekday_hours()), vec![closure]);
+
+        assert!(!calendar.is_open_at(&ts("2026-09-01T10:00:00+0000")));
+        assert!(!calendar.is_open_day(&ts("2026-09-01T10:00:00+0000")));
+        assert!(calendar.is_open_day(&ts("2026-09-03T10:00:00+0000")));
+    }
+
+    #[test]
+    fn test_slide_due_date() {
+        let closure = ClosedRange {
+            start: ts("2026-09-01T00:00:00+0000"),
+            end: ts("2026-09-02T23:59:59+0000"),
+            reason: "Inventory".to_string(),
+        };
+        let calendar = Calendar::new(Some(weekday_hours()), vec![closure]);
+
+        // Due during the closure: slides to Thursday the 3rd.
+        let due = calendar
+            .slide_due_date(ts("2026-09-01T23:59:59+0000"))
+            .unwrap();
+        assert_eq!(due, ts("2026-09-03T23:59:59+0000"));
+
+        // Due on a Saturday: slides past Sunday to Monday.
+        let due = calendar
+            .slide_due_date(ts("2026-09-05T23:59:59+0000"))
+            .unwrap();
+        assert_eq!(due, ts("2026-09-07T23:59:59+0000"));
+
+        // Already open: unchanged.
+        let due = calendar
+            .slide_due_date(ts("2026-09-03T23:59:59+0000"))
+            .unwrap();
+        assert_eq!(due, ts("2026-09-03T23:59:59+0000"));
+    }
+}
@@ -0,0 +1,336 @@
+//! Typed wrappers for the open-ils.circ circulation APIs.
+//!
+//! Groundwork for SIP and self-check integrations: checkout,
+//! checkin, renewal, in-house use, and lost/claims-returned
+//! handling, with permit tests and override flows.
+
+use crate::event::EgEvent;
+use crate::osrf::client::Client;
+use json::JsonValue;
+
+/// Circ calls can block on patron/copy locks; be patient.
+const CIRC_TIMEOUT: u64 = 120;
+
+/// Parameters shared by checkout/checkin/renew calls.  Only the
+/// fields relevant to the call need to be set.
+#[derive(Debug, Clone, Default)]
+pub struct CircParams {
+    pub patron_id: Option<i64>,
+    pub patron_barcode: Option<String>,
+    pub copy_id: Option<i64>,
+    pub copy_barcode: Option<String>,
+    /// Checkin/backdate time, ISO format.
+    pub backdate: Option<String>,
+    /// Checkout due date override, ISO format.
+    pub due_date: Option<String>,
+    /// Non-cataloged item type for noncat checkouts.
+    pub noncat_type: Option<i64>,
+}
+
+impl CircParams {
+    fn to_json_value(&self) -> JsonValue {
+        let mut args = json::object! {};
+
+        if let Some(v) = self.patron_id {
+            args["patron_id"] = v.into();
+        }
+        if let Some(v) = &self.patron_barcode {
+            args["patron_barcode"] = v.as_str().into();
+        }
+        if let Some(v) = self.copy_id {
+            args["copy_id"] = v.into();
+        }
+        if let Some(v) = &self.copy_barcode {
+            args["copy_barcode"] = v.as_str().into();
+        }
+        if let Some(v) = &self.backdate {
+            args["backdate"] = v.as_str().into();
+        }
+        if let Some(v) = &self.due_date {
+            args["due_date"] = v.as_str().into();
+        }
+        if let Some(v) = self.noncat_type {
+            args["noncat"] = json::from(1);
+            args["noncat_type"] = v.into();
+        }
+
+        args
+    }
+}
+
+/// The outcome of a circulation call: the returned events plus the
+/// payload of the first event, which carries the circ/copy/record
+/// objects on success.
+#[derive(Debug)]
+pub struct CircResult {
+    events: Vec<EgEvent>,
+    payload: JsonValue,
+}
+
+impl CircResult {
+    /// Normalize a circ API response: a single event or a list of
+    /// events.
+    pub fn from_response(resp: JsonValue) -> Result<CircResult, String> {
+        let mut events = Vec::new();
+        let mut payload = JsonValue::Null;
+
+        let list = match resp {
+            JsonValue::Array(entries) => entries,
+            other => vec![other],
+        };
+
+        for entry in list {
+            let event = EgEvent::parse(&entry)
+                .ok_or_else(|| format!("Unexpected circ response: {}", entry.dump()))?;
+
+            if payload.is_null() && !entry["payload"].is_null() {
+                payload = entry["payload"].clone();
+            }
+
+            events.push(event);
+        }
+
+        if events.is_empty() {
+            return Err("Empty circ response".to_string());
+        }
+
+        Ok(CircResult { events, payload })
+    }
+
+    /// True if every returned event is a SUCCESS.
+    pub fn success(&self) -> bool {
+        self.events.iter().all(|e| e.is_success())
+    }
+
+    pub fn events(&self) -> &[EgEvent] {
+        &self.events
+    }
+
+    pub fn first_event(&self) -> &EgEvent {
+        &self.events[0] // from_response guarantees one event
+    }
+
+    /// The circulation object, when the call produced one.
+    pub fn circ(&self) -> Option<&JsonValue> {
+        match self.payload["circ"].is_null() {
+            true => None,
+            false => Some(&self.payload["circ"]),
+        }
+    }
+
+    pub fn copy(&self) -> Option<&JsonValue> {
+        match self.payload["copy"].is_null() {
+            true => None,
+            false => Some(&self.payload["copy"]),
+        }
+    }
+
+    pub fn record(&self) -> Option<&JsonValue> {
+        match self.payload["record"].is_null() {
+            true => None,
+            false => Some(&self.payload["record"]),
+        }
+    }
+
+    /// Event textcodes that blocked the call.
+    pub fn blocking_textcodes(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .filter(|e| !e.is_success())
+            .map(|e| e.textcode())
+            .collect()
+    }
+}
+
+/// Drives circulation operations for one authenticated session.
+pub struct Circulator {
+    client: Client,
+    authtoken: String,
+    timeout: u64,
+}
+
+impl Circulator {
+    pub fn new(client: &Client, authtoken: &str) -> Self {
+        Circulator {
+            client: client.clone(),
+            authtoken: authtoken.to_string(),
+            timeout: CIRC_TIMEOUT,
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: u64) {
+        self.timeout = timeout;
+    }
+
+    /// Call an open-ils.circ method and return its first response.
+    fn request(&self, method: &str, params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        let session = self.client.session("open-ils.circ");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(self.timeout)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Call a circ method; if it's blocked only by events named in
+    /// `overrides`, retry the `.override` variant.
+    fn request_with_override(
+        &self,
+        method: &str,
+        args: &CircParams,
+        overrides: &[&str],
+    ) -> Result<CircResult, String> {
+        let params = vec![json::from(self.authtoken.as_str()), args.to_json_value()];
+
+        let result = CircResult::from_response(self.request(method, params.clone())?)?;
+
+        if result.success() || overrides.is_empty() {
+            return Ok(result);
+        }
+
+        let blockers = result.blocking_textcodes();
+        if blockers.iter().any(|code| !overrides.contains(code)) {
+            return Ok(result); // something non-overridable happened
+        }
+
+        log::info!("Overriding {method} events: {}", blockers.join(", "));
+
+        CircResult::from_response(self.request(&format!("{method}.override"), params)?)
+    }
+
+    /// Run the checkout permit test without circulating.
+    pub fn checkout_permit(&self, args: &CircParams) -> Result<CircResult, String> {
+        CircResult::from_response(self.request(
+            "open-ils.circ.checkout.permit",
+            vec![json::from(self.authtoken.as_str()), args.to_json_value()],
+        )?)
+    }
+
+    /// Check an item out, overriding any events named in
+    /// `overrides`.
+    pub fn checkout(&self, args: &CircParams, overrides: &[&str]) -> Result<CircResult, String> {
+        self.request_with_override("open-ils.circ.checkout.full", args, overrides)
+    }
+
+    /// Check an item in.
+    pub fn checkin(&self, args: &CircParams, overrides: &[&str]) -> Result<CircResult, String> {
+        self.request_with_override("open-ils.circ.checkin", args, overrides)
+    }
+
+    /// Renew a circulation by patron + copy.
+    pub fn renew(&self, args: &CircParams, overrides: &[&str]) -> Result<CircResult, String> {
+        self.request_with_override("open-ils.circ.renew", args, overrides)
+    }
+
+    /// Record an in-house use of a copy.
+    pub fn in_house_use(&self, copy_id: i64, location: i64, count: u32) -> Result<CircResult, String> {
+        let args = json::object! {
+            copyid: copy_id,
+            location: location,
+            count: count,
+        };
+
+        CircResult::from_response(self.request(
+            "open-ils.circ.in_house_use.create",
+            vec![json::from(self.authtoken.as_str()), args],
+        )?)
+    }
+
+    /// Mark the open circulation of a copy lost.
+    pub fn mark_lost(&self, copy_barcode: &str) -> Result<CircResult, String> {
+        self.set_circ_status("open-ils.circ.circulation.set_lost", copy_barcode, None)
+    }
+
+    /// Mark the open circulation of a copy claims-returned,
+    /// optionally backdated.
+    pub fn mark_claims_returned(
+        &self,
+        copy_barcode: &str,
+        backdate: Option<&str>,
+    ) -> Result<CircResult, String> {
+        self.set_circ_status(
+            "open-ils.circ.circulation.set_claims_returned",
+            copy_barcode,
+            backdate,
+        )
+    }
+
+    fn set_circ_status(
+        &self,
+        method: &str,
+        copy_barcode: &str,
+        backdate: Option<&str>,
+    ) -> Result<CircResult, String> {
+        let mut args = json::object! {barcode: copy_barcode};
+        if let Some(backdate) = backdate {
+            args["backdate"] = backdate.into();
+        }
+
+        let resp = self.request(method, vec![json::from(self.authtoken.as_str()), args])?;
+
+        // These methods return 1 on success instead of an event.
+        if resp == 1 {
+            return CircResult::from_response(json::object! {
+                ilsevent: 0,
+                textcode: "SUCCESS",
+                desc: "Success",
+            });
+        }
+
+        CircResult::from_response(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_success() {
+        let resp = json::object! {
+            ilsevent: 0,
+            textcode: "SUCCESS",
+            desc: "Success",
+            payload: {circ: {id: 1}, copy: {id: 2}},
+        };
+
+        let result = CircResult::from_response(resp).expect("response should parse");
+        assert!(result.success());
+        assert_eq!(result.circ().unwrap()["id"], 1);
+        assert_eq!(result.copy().unwrap()["id"], 2);
+        assert!(result.record().is_none());
+    }
+
+    #[test]
+    fn test_result_blockers() {
+        let resp = json::array![
+            {ilsevent: 7013, textcode: "PATRON_EXCEEDS_FINES", desc: ""},
+            {ilsevent: 1212, textcode: "PATRON_EXCEEDS_OVERDUE_COUNT", desc: ""},
+        ];
+
+        let result = CircResult::from_response(resp).expect("response should parse");
+        assert!(!result.success());
+        assert_eq!(
+            result.blocking_textcodes(),
+            vec!["PATRON_EXCEEDS_FINES", "PATRON_EXCEEDS_OVERDUE_COUNT"]
+        );
+    }
+
+    #[test]
+    fn test_params() {
+        let args = CircParams {
+            patron_barcode: Some("123".to_string()),
+            copy_barcode: Some("456".to_string()),
+            noncat_type: Some(3),
+            ..Default::default()
+        };
+
+        let value = args.to_json_value();
+        assert_eq!(value["patron_barcode"], "123");
+        assert_eq!(value["copy_barcode"], "456");
+        assert_eq!(value["noncat"], 1);
+        assert_eq!(value["noncat_type"], 3);
+        assert!(value["due_date"].is_null());
+    }
+}
@@ -0,0 +1,318 @@
+//! Emergency closing processing.
+//!
+//! Given a closed-date range for an org unit, slides affected due
+//! dates past the closure, voids overdue fines accrued during it,
+//! and reschedules hold shelf expirations, recording every change
+//! for the run report.
+
+use crate::calendar::{Calendar, ClosedRange};
+use crate::date::{now_str, parse_timestamp, to_timestamp};
+use crate::editor::Editor;
+use crate::money::Money;
+use crate::util;
+use json::JsonValue;
+
+const OVERDUE_BTYPE: i64 = 1;
+
+/// Counters for one closing run.
+#[derive(Debug, Clone, Default)]
+pub struct ClosingCounts {
+    pub circs_examined: usize,
+    pub due_dates_moved: usize,
+    pub billings_voided: usize,
+    pub amount_voided_cents: i64,
+    pub holds_examined: usize,
+    pub shelf_expirations_moved: usize,
+    pub errors: usize,
+}
+
+/// Processes one closed-date range for one org unit.
+pub struct Closing {
+    editor: Editor,
+    org_id: i64,
+    range: ClosedRange,
+    dry_run: bool,
+    counts: ClosingCounts,
+    /// One line per change, for the run report.
+    report: Vec<String>,
+    /// Updates are committed in batches this large.
+    batch_size: usize,
+    pending_in_batch: usize,
+}
+
+impl Closing {
+    pub fn new(
+        editor: Editor,
+        org_id: i64,
+        range: ClosedRange,
+        dry_run: bool,
+    ) -> Self {
+        Closing {
+            editor,
+            org_id,
+            range,
+            dry_run,
+            counts: ClosingCounts::default(),
+            report: Vec::new(),
+            batch_size: 100,
+            pending_in_batch: 0,
+        }
+    }
+
+    pub fn counts(&self) -> &ClosingCounts {
+        &self.counts
+    }
+
+    pub fn report(&self) -> &Vec<String> {
+        &self.report
+    }
+
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    fn note(&mut self, line: String) {
+        log::info!("{line}");
+        self.report.push(line);
+    }
+
+    /// Create the actor.org_unit.closed_date row for this range.
+    /// Dry runs skip the write.
+    pub fn create_closure(&mut self) -> Result<(), String> {
+        if self.dry_run {
+            self.note(format!(
+                "Would create closure {} - {} for org {}",
+                to_timestamp(&self.range.start),
+                to_timestamp(&self.range.end),
+                self.org_id
+            ));
+            return Ok(());
+        }
+
+        let closure = json::object! {
+            "_classname": "aoucd",
+            org_unit: self.org_id,
+            close_start: to_timestamp(&self.range.start),
+            close_end: to_timestamp(&self.range.end),
+            reason: self.range.reason.as_str(),
+        };
+
+        self.editor.xact_begin()?;
+
+        let resp = self.editor.request(
+            "open-ils.cstore.direct.actor.org_unit.closed_date.create",
+            vec![closure],
+        );
+
+        match resp {
+            Ok(_) => Ok(self.editor.commit()?),
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Run the full adjustment: due dates, fines, then hold shelf
+    /// expirations.
+    pub fn process(&mut self) -> Result<(), String> {
+        let mut calendar = Calendar::load(&mut self.editor, self.org_id)?;
+        // Make sure the new range is accounted for even when the
+        // closure row was not created (dry runs).
+        calendar.add_closure(self.range.clone());
+
+        self.adjust_circulations(&calendar)?;
+        self.adjust_holds(&calendar)?;
+        self.flush_batch()
+    }
+
+    /// Slide due dates landing in the closure and void overdue
+    /// fines accrued during it.
+    fn adjust_circulations(&mut self, calendar: &Calendar) -> Result<(), String> {
+        let circs = self.editor.search(
+            "circ",
+            json::object! {
+                circ_lib: self.org_id,
+                checkin_time: JsonValue::Null,
+                xact_finish: JsonValue::Null,
+                due_date: {
+                    between: [
+                        to_timestamp(&self.range.start),
+                        to_timestamp(&self.range.end),
+                    ]
+                },
+            },
+        )?;
+
+        for circ in circs {
+            self.counts.circs_examined += 1;
+
+            let circ_id = util::json_int(&circ["id"])?;
+
+            if let Err(e) = self.adjust_circulation(calendar, circ) {
+                self.counts.errors += 1;
+                log::error!("Error adjusting circ {circ_id}: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn adjust_circulation(
+        &mut self,
+        calendar: &Calendar,
+        mut circ: JsonValue,
+    ) -> Result<(), String> {
+        let circ_id = util::json_int(&circ["id"])?;
+
+        let due = parse_timestamp(
+            circ["due_date"]
+                .as_str()
+                .ok_or_else(|| format!("Circ {circ_id} has no due date"))?,
+        )?;
+
+        let slid = calendar.slide_due_date(due)?;
+
+        if slid != due {
+            self.note(format!(
+                "circ {circ_id}: due {} -> {}",
+                to_timestamp(&due),
+                to_timestamp(&slid)
+            ));
+            self.counts.due_dates_moved += 1;
+
+            if !self.dry_run {
+                circ["due_date"] = to_timestamp(&slid).into();
+                self.update_batched("open-ils.cstore.direct.action.circulation.update", circ)?;
+            }
+        }
+
+        self.void_closure_billings(circ_id)
+    }
+
+    /// Void unvoided overdue billings stamped during the closure.
+    fn void_closure_billings(&mut self, circ_id: i64) -> Result<(), String> {
+        let billings = self.editor.search(
+            "mb",
+            json::object! {
+                xact: circ_id,
+                btype: OVERDUE_BTYPE,
+                voided: "f",
+                billing_ts: {
+                    between: [
+                        to_timestamp(&self.range.start),
+                        to_timestamp(&self.range.end),
+                    ]
+                },
+            },
+        )?;
+
+        for mut billing in billings {
+            let billing_id = util::json_int(&billing["id"])?;
+            let amount = Money::from_json(&billing["amount"])?;
+
+            self.note(format!(
+                "circ {circ_id}: voiding billing {billing_id} ({amount})"
+            ));
+            self.counts.billings_voided += 1;
+            self.counts.amount_voided_cents += amount.cents();
+
+            if self.dry_run {
+                continue;
+            }
+
+            billing["voided"] = "t".into();
+            billing["void_time"] = now_str().into();
+            billing["note"] = format!("Voided for closure: {}", self.range.reason).into();
+
+            self.update_batched("open-ils.cstore.direct.money.billing.update", billing)?;
+        }
+
+        Ok(())
+    }
+
+    /// Slide hold shelf expirations landing in the closure.
+    fn adjust_holds(&mut self, calendar: &Calendar) -> Result<(), String> {
+        let holds = self.editor.search(
+            "ahr",
+            json::object! {
+                pickup_lib: self.org_id,
+                cancel_time: JsonValue::Null,
+                fulfillment_time: JsonValue::Null,
+                shelf_expire_time: {
+                    between: [
+                        to_timestamp(&self.range.start),
+                        to_timestamp(&self.range.end),
+                    ]
+                },
+            },
+        )?;
+
+        for mut hold in holds {
+            self.counts.holds_examined += 1;
+
+            let hold_id = util::json_int(&hold["id"])?;
+
+            let expire = parse_timestamp(hold["shelf_expire_time"].as_str().unwrap_or(""))?;
+            let slid = match calendar.slide_due_date(expire) {
+                Ok(s) => s,
+                Err(e) => {
+                    self.counts.errors += 1;
+                    log::error!("Error adjusting hold {hold_id}: {e}");
+                    continue;
+                }
+            };
+
+            if slid == expire {
+                continue;
+            }
+
+            self.note(format!(
+                "hold {hold_id}: shelf expire {} -> {}",
+                to_timestamp(&expire),
+                to_timestamp(&slid)
+            ));
+            self.counts.shelf_expirations_moved += 1;
+
+            if !self.dry_run {
+                hold["shelf_expire_time"] = to_timestamp(&slid).into();
+                self.update_batched("open-ils.cstore.direct.action.hold_request.update", hold)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply an update within the running batch transaction.
+    fn update_batched(&mut self, method: &str, obj: JsonValue) -> Result<(), String> {
+        if !self.editor.in_transaction() {
+            self.editor.xact_begin()?;
+        }
+
+        let resp = self.editor.request(method, vec![obj]);
+
+        match resp {
+            Ok(_) => {
+                self.pending_in_batch += 1;
+                if self.pending_in_batch >= self.batch_size {
+                    self.flush_batch()?;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                self.pending_in_batch = 0;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Commit any open batch transaction.
+    pub fn flush_batch(&mut self) -> Result<(), String> {
+        if self.editor.in_transaction() {
+            self.editor.commit()?;
+        }
+        self.pending_in_batch = 0;
+        Ok(())
+    }
+}
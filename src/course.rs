@@ -0,0 +1,260 @@
+//! Course reserves: courses, attached materials, and instructors.
+//!
+//! Wraps the course materials module for bulk loading from campus
+//! systems: list and create courses, attach/detach materials, and
+//! manage instructor links.
+
+use crate::editor::Editor;
+use crate::event::EgEvent;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::sync::Arc;
+
+const COURSE_TIMEOUT: u64 = 60;
+
+/// Build a course material row linking a course to a bib record or
+/// a copy.
+pub fn material_object(course_id: i64, record: Option<i64>, item: Option<i64>) -> JsonValue {
+    let mut material = json::object! {
+        "_classname": "acmcm",
+        isnew: 1,
+        course: course_id,
+    };
+
+    if let Some(record) = record {
+        material["record"] = record.into();
+    }
+
+    if let Some(item) = item {
+        material["item"] = item.into();
+    }
+
+    material
+}
+
+/// Build a course user row linking an instructor to a course.
+pub fn instructor_object(course_id: i64, usr_id: i64, role: Option<i64>) -> JsonValue {
+    let mut link = json::object! {
+        "_classname": "acmcu",
+        isnew: 1,
+        course: course_id,
+        usr: usr_id,
+    };
+
+    if let Some(role) = role {
+        link["usr_role"] = role.into();
+    }
+
+    link
+}
+
+/// Drives course-reserve operations for one authenticated session.
+pub struct Courses {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+}
+
+impl Courses {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Courses {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// Call an open-ils.courses method with the authtoken prepended
+    /// and return its first response.
+    fn request(&self, method: &str, mut params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        params.insert(0, json::from(self.authtoken.as_str()));
+
+        let session = self.client.session("open-ils.courses");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(COURSE_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Fail on a non-success event response.
+    fn check_event(method: &str, resp: JsonValue) -> Result<JsonValue, String> {
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("{method} failed: {evt}"));
+            }
+        }
+        Ok(resp)
+    }
+
+    /// The unarchived courses owned by an org unit.
+    pub fn list_courses(&mut self, org_id: i64) -> Result<Vec<JsonValue>, String> {
+        Ok(self.editor.search(
+            "acmc",
+            json::object! {owning_lib: org_id, is_archived: "f"},
+        )?)
+    }
+
+    /// Find a course by org and course number.
+    pub fn course_by_number(
+        &mut self,
+        org_id: i64,
+        course_number: &str,
+    ) -> Result<Option<JsonValue>, String> {
+        let mut hits = self.editor.search(
+            "acmc",
+            json::object! {
+                owning_lib: org_id,
+                course_number: course_number,
+                is_archived: "f",
+            },
+        )?;
+        Ok(hits.pop())
+    }
+
+    /// Create a course, returning its ID.
+    pub fn create_course(
+        &mut self,
+        org_id: i64,
+        course_number: &str,
+        name: &str,
+        section_number: Option<&str>,
+    ) -> Result<i64, String> {
+        let mut course = json::object! {
+            "_classname": "acmc",
+            isnew: 1,
+            owning_lib: org_id,
+            course_number: course_number,
+            name: name,
+            is_archived: "f",
+        };
+
+        if let Some(section) = section_number {
+            course["section_number"] = section.into();
+        }
+
+        self.editor.xact_begin()?;
+
+        let resp = self.editor.request(
+            "open-ils.cstore.direct.asset.course_module_course.create",
+            vec![course],
+        );
+
+        match resp {
+            Ok(created) => {
+                self.editor.commit()?;
+                util::json_int(&created["id"])
+            }
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// The materials attached to a course.
+    pub fn materials(&mut self, course_id: i64) -> Result<Vec<JsonValue>, String> {
+        Ok(self.editor.search("acmcm", json::object! {course: course_id})?)
+    }
+
+    /// The instructor links for a course.
+    pub fn instructors(&mut self, course_id: i64) -> Result<Vec<JsonValue>, String> {
+        Ok(self.editor.search("acmcu", json::object! {course: course_id})?)
+    }
+
+    /// Attach a bib record to a course, returning the material ID.
+    pub fn attach_record(&self, course_id: i64, record_id: i64) -> Result<i64, String> {
+        let method = "open-ils.courses.attach.biblio_record";
+
+        let resp = self.request(
+            method,
+            vec![json::from(record_id), json::from(course_id)],
+        )?;
+        let resp = Courses::check_event(method, resp)?;
+
+        util::json_int(&resp["id"])
+    }
+
+    /// Attach a copy to a course, returning the material ID.
+    pub fn attach_item(&self, course_id: i64, copy_id: i64) -> Result<i64, String> {
+        let method = "open-ils.courses.attach.item";
+
+        let resp = self.request(
+            method,
+            vec![json::from(copy_id), json::from(course_id)],
+        )?;
+        let resp = Courses::check_event(method, resp)?;
+
+        util::json_int(&resp["id"])
+    }
+
+    /// Detach a material from its course.
+    pub fn detach_material(&self, material_id: i64) -> Result<(), String> {
+        let method = "open-ils.courses.detach_material";
+
+        let resp = self.request(method, vec![json::from(material_id)])?;
+        Courses::check_event(method, resp)?;
+
+        Ok(())
+    }
+
+    /// Link an instructor to a course.
+    pub fn add_instructor(
+        &mut self,
+        course_id: i64,
+        usr_id: i64,
+        role: Option<i64>,
+    ) -> Result<(), String> {
+        let link = instructor_object(course_id, usr_id, role);
+
+        self.editor.xact_begin()?;
+
+        let resp = self.editor.request(
+            "open-ils.cstore.direct.asset.course_module_course_users.create",
+            vec![link],
+        );
+
+        match resp {
+            Ok(_) => Ok(self.editor.commit()?),
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_material_object() {
+        let material = material_object(7, Some(42), None);
+        assert_eq!(material["_classname"], "acmcm");
+        assert_eq!(material["course"], 7);
+        assert_eq!(material["record"], 42);
+        assert!(material["item"].is_null());
+
+        let material = material_object(7, None, Some(99));
+        assert_eq!(material["item"], 99);
+    }
+
+    #[test]
+    fn test_instructor_object() {
+        let link = instructor_object(7, 12, Some(3));
+        assert_eq!(link["_classname"], "acmcu");
+        assert_eq!(link["usr"], 12);
+        assert_eq!(link["usr_role"], 3);
+
+        let link = instructor_object(7, 12, None);
+        assert!(link["usr_role"].is_null());
+    }
+}
@@ -0,0 +1,210 @@
+//! Sample dataset loading and teardown for test databases.
+//!
+//! A dataset is a directory containing a `manifest` file -- table
+//! file names, one per line, in dependency order -- plus one data
+//! file per table.  Data files use COPY text format: a first line
+//! of tab-separated column names, then one row per line with `\N`
+//! for null.  Loads go through COPY for speed; teardown truncates
+//! in reverse manifest order.
+
+use crate::db::DatabaseConnection;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// One table's worth of data.
+#[derive(Debug, Clone)]
+pub struct TableData {
+    /// Schema-qualified table name, e.g. "actor.org_unit".
+    pub table: String,
+    pub columns: Vec<String>,
+    /// Raw COPY-format rows, without trailing newlines.
+    pub rows: Vec<String>,
+}
+
+impl TableData {
+    /// Parse a data file: header line of column names, then rows.
+    pub fn parse(table: &str, text: &str) -> Result<TableData, String> {
+        let mut lines = text.lines();
+
+        let header = lines
+            .next()
+            .ok_or_else(|| format!("Data file for {table} is empty"))?;
+
+        let columns: Vec<String> = header.split('\t').map(|c| c.trim().to_string()).collect();
+
+        if columns.iter().any(|c| c.is_empty()) {
+            return Err(format!("Data file for {table} has a blank column name"));
+        }
+
+        let rows: Vec<String> = lines
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect();
+
+        Ok(TableData {
+            table: table.to_string(),
+            columns,
+            rows,
+        })
+    }
+}
+
+/// Parse a manifest: table names in load order, one per line,
+/// ignoring blanks and # comments.
+pub fn parse_manifest(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.to_string())
+        .collect()
+}
+
+/// A dataset read from disk, tables in load order.
+pub struct Dataset {
+    pub tables: Vec<TableData>,
+}
+
+impl Dataset {
+    /// Read a dataset directory.  Each manifest entry names both
+    /// the table and its data file (dots intact): "actor.org_unit"
+    /// loads from <dir>/actor.org_unit.
+    pub fn load_dir(dir: &Path) -> Result<Dataset, String> {
+        let manifest_path = dir.join("manifest");
+        let manifest = fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("Cannot read {}: {e}", manifest_path.display()))?;
+
+        let mut tables = Vec::new();
+
+        for table in parse_manifest(&manifest) {
+            let data_path = dir.join(&table);
+            let text = fs::read_to_string(&data_path)
+                .map_err(|e| format!("Cannot read {}: {e}", data_path.display()))?;
+
+            tables.push(TableData::parse(&table, &text)?);
+        }
+
+        if tables.is_empty() {
+            return Err(format!("Dataset at {} lists no tables", dir.display()));
+        }
+
+        Ok(Dataset { tables })
+    }
+}
+
+/// Loads and tears down datasets on a database connection.
+pub struct Loader {
+    db: DatabaseConnection,
+}
+
+impl Loader {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Loader { db }
+    }
+
+    pub fn connect(&mut self) -> Result<(), String> {
+        self.db.connect()
+    }
+
+    /// COPY every table in, in manifest order, within one
+    /// transaction.  Returns rows loaded per table.
+    pub fn load(&mut self, dataset: &Dataset) -> Result<Vec<(String, usize)>, String> {
+        let client = self.db.client();
+
+        let mut xact = client
+            .transaction()
+            .map_err(|e| format!("Cannot begin transaction: {e}"))?;
+
+        xact.batch_execute("SET CONSTRAINTS ALL DEFERRED")
+            .map_err(|e| format!("Cannot defer constraints: {e}"))?;
+
+        let mut counts = Vec::new();
+
+        for table in &dataset.tables {
+            let copy = format!(
+                "COPY {} ({}) FROM STDIN",
+                table.table,
+                table.columns.join(", ")
+            );
+
+            let mut writer = xact
+                .copy_in(&copy)
+                .map_err(|e| format!("COPY failed for {}: {e}", table.table))?;
+
+            for row in &table.rows {
+                writer
+                    .write_all(row.as_bytes())
+                    .and_then(|_| writer.write_all(b"\n"))
+                    .map_err(|e| format!("Error writing {} row: {e}", table.table))?;
+            }
+
+            writer
+                .finish()
+                .map_err(|e| format!("COPY failed for {}: {e}", table.table))?;
+
+            counts.push((table.table.clone(), table.rows.len()));
+        }
+
+        // Bump serial sequences past the loaded IDs.
+        for table in &dataset.tables {
+            if !table.columns.iter().any(|c| c == "id") {
+                continue;
+            }
+
+            let setval = format!(
+                "SELECT SETVAL(PG_GET_SERIAL_SEQUENCE('{}', 'id'), \
+                 (SELECT COALESCE(MAX(id), 1) FROM {}))",
+                table.table, table.table
+            );
+
+            // Not every id column is serial-backed; skip those.
+            if let Err(e) = xact.batch_execute(&setval) {
+                log::debug!("No sequence bumped for {}: {e}", table.table);
+            }
+        }
+
+        xact.commit()
+            .map_err(|e| format!("Cannot commit dataset load: {e}"))?;
+
+        Ok(counts)
+    }
+
+    /// Truncate every table, children first.
+    pub fn purge(&mut self, dataset: &Dataset) -> Result<(), String> {
+        let client = self.db.client();
+
+        for table in dataset.tables.iter().rev() {
+            client
+                .batch_execute(&format!("TRUNCATE {} CASCADE", table.table))
+                .map_err(|e| format!("Cannot truncate {}: {e}", table.table))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = "# load order matters\nactor.org_unit\n\nactor.usr\n";
+        assert_eq!(
+            parse_manifest(manifest),
+            vec!["actor.org_unit", "actor.usr"]
+        );
+    }
+
+    #[test]
+    fn test_parse_table_data() {
+        let text = "id\tname\tparent_ou\n1\tCONS\t\\N\n2\tBR1\t1\n";
+        let data = TableData::parse("actor.org_unit", text).expect("data should parse");
+
+        assert_eq!(data.columns, vec!["id", "name", "parent_ou"]);
+        assert_eq!(data.rows.len(), 2);
+        assert_eq!(data.rows[0], "1\tCONS\t\\N");
+
+        assert!(TableData::parse("actor.usr", "").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Timestamp and interval handling shared by circ, fines, and
+//! action/trigger tooling.
+//!
+//! Covers the timestamp formats postgres and the services hand us,
+//! PG-style interval strings ("1 day 2 hours", "3 mons",
+//! "1 day 02:30:00"), and due-date computation.
+
+use chrono::{DateTime, FixedOffset, Local, Timelike};
+
+/// The timestamp format Evergreen APIs expect.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%z";
+
+/// Parse an Evergreen timestamp in the formats postgres hands us.
+pub fn parse_timestamp(value: &str) -> Result<DateTime<FixedOffset>, String> {
+    for format in [TIMESTAMP_FORMAT, "%Y-%m-%d %H:%M:%S%z", "%Y-%m-%d %H:%M:%S%.f%#z"] {
+        if let Ok(dt) = DateTime::parse_from_str(value, format) {
+            return Ok(dt);
+        }
+    }
+    DateTime::parse_from_rfc3339(value).map_err(|e| format!("Invalid timestamp {value}: {e}"))
+}
+
+/// Format a timestamp the way Evergreen APIs expect.
+pub fn to_timestamp(dt: &DateTime<FixedOffset>) -> String {
+    dt.format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// The current time, fixed to the local offset.
+pub fn now() -> DateTime<FixedOffset> {
+    Local::now().fixed_offset()
+}
+
+/// The current time as an Evergreen timestamp string.
+pub fn now_str() -> String {
+    to_timestamp(&now())
+}
+
+/// Parse a postgres-style interval string ("1 day", "2 hours",
+/// "90 minutes", "3 mons", "1 day 02:30:00") into seconds.
+pub fn interval_to_seconds(interval: &str) -> Result<i64, String> {
+    let mut seconds = 0;
+    let mut amount: Option<i64> = None;
+
+    for part in interval.split_whitespace() {
+        if let Ok(num) = part.parse::<i64>() {
+            amount = Some(num);
+            continue;
+        }
+
+        // A trailing HH:MM:SS chunk, as PG emits for sub-day amounts.
+        if part.contains(':') && amount.is_none() {
+            seconds += clock_to_seconds(part)?;
+            continue;
+        }
+
+        let num = amount
+            .take()
+            .ok_or_else(|| format!("Invalid interval: {interval}"))?;
+
+        let unit = part.trim_end_matches('s');
+
+        seconds += match unit {
+            "sec" | "second" => num,
+            "min" | "minute" => num * 60,
+            "hour" => num * 3600,
+            "day" => num * 86400,
+            "week" => num * 604800,
+            "mon" | "month" => num * 2592000,
+            "year" => num * 31536000,
+            _ => return Err(format!("Unknown interval unit: {part}")),
+        };
+    }
+
+    if amount.is_some() {
+        return Err(format!("Invalid interval: {interval}"));
+    }
+
+    Ok(seconds)
+}
+
+/// Seconds in an HH:MM:SS chunk.
+fn clock_to_seconds(clock: &str) -> Result<i64, String> {
+    let parts: Vec<&str> = clock.split(':').collect();
+    if parts.len() != 3 {
+        return Err(format!("Invalid interval time: {clock}"));
+    }
+
+    let mut seconds = 0;
+    for (index, part) in parts.iter().enumerate() {
+        let num: i64 = part
+            .parse()
+            .map_err(|_| format!("Invalid interval time: {clock}"))?;
+        seconds += num * 60_i64.pow(2 - index as u32);
+    }
+
+    Ok(seconds)
+}
+
+/// Add a PG interval to a timestamp.
+pub fn add_interval(
+    dt: &DateTime<FixedOffset>,
+    interval: &str,
+) -> Result<DateTime<FixedOffset>, String> {
+    Ok(*dt + chrono::Duration::seconds(interval_to_seconds(interval)?))
+}
+
+/// Compute a due date: start plus the loan duration.  Durations of
+/// a day or more are day-granular, so the due time extends to the
+/// final second of the due day in the start time's zone, matching
+/// how circulation sets due dates.
+pub fn due_date(
+    start: &DateTime<FixedOffset>,
+    duration: &str,
+) -> Result<DateTime<FixedOffset>, String> {
+    let duration_secs = interval_to_seconds(duration)?;
+    let due = *start + chrono::Duration::seconds(duration_secs);
+
+    if duration_secs % 86400 != 0 {
+        return Ok(due); // hourly loan; due to the second
+    }
+
+    due.with_hour(23)
+        .and_then(|d| d.with_minute(59))
+        .and_then(|d| d.with_second(59))
+        .and_then(|d| d.with_nanosecond(0))
+        .ok_or_else(|| format!("Cannot extend due date {due}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let dt = parse_timestamp("2026-09-01T10:00:00-0400").unwrap();
+        assert_eq!(to_timestamp(&dt), "2026-09-01T10:00:00-0400");
+
+        assert!(parse_timestamp("2026-09-01 10:00:00-04").is_ok());
+        assert!(parse_timestamp("not a date").is_err());
+    }
+
+    #[test]
+    fn test_interval_to_seconds() {
+        assert_eq!(interval_to_seconds("1 day").unwrap(), 86400);
+        assert_eq!(interval_to_seconds("2 hours").unwrap(), 7200);
+        assert_eq!(interval_to_seconds("1 day 2 hours").unwrap(), 93600);
+        assert_eq!(interval_to_seconds("90 minutes").unwrap(), 5400);
+        assert_eq!(interval_to_seconds("3 mons").unwrap(), 7776000);
+        assert_eq!(interval_to_seconds("1 day 02:30:00").unwrap(), 95400);
+        assert!(interval_to_seconds("soon").is_err());
+        assert!(interval_to_seconds("3").is_err());
+    }
+
+    #[test]
+    fn test_due_date() {
+        let start = parse_timestamp("2026-09-01T10:15:00-0400").unwrap();
+
+        // Day-granular loans are due at day's end.
+        let due = due_date(&start, "7 days").unwrap();
+        assert_eq!(to_timestamp(&due), "2026-09-08T23:59:59-0400");
+
+        // Hourly loans are due to the second.
+        let due = due_date(&start, "2 hours").unwrap();
+        assert_eq!(to_timestamp(&due), "2026-09-01T12:15:00-0400");
+    }
+}
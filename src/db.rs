@@ -0,0 +1,187 @@
+//! Database connection support for direct-to-database operations.
+
+use std::env;
+
+const DEFAULT_DB_HOST: &str = "localhost";
+const DEFAULT_DB_PORT: u16 = 5432;
+const DEFAULT_DB_USER: &str = "evergreen";
+const DEFAULT_DB_NAME: &str = "evergreen";
+
+/// A postgres connection and the settings used to create it.
+pub struct DatabaseConnection {
+    client: Option<postgres::Client>,
+    host: String,
+    port: u16,
+    user: String,
+    password: Option<String>,
+    database: String,
+    in_transaction: bool,
+}
+
+impl DatabaseConnection {
+    /// Add the standard database command line options to a getopts
+    /// spec.
+    pub fn append_options(options: &mut getopts::Options) {
+        options.optopt("", "db-host", "Database Host", "HOST");
+        options.optopt("", "db-port", "Database Port", "PORT");
+        options.optopt("", "db-user", "Database User", "USER");
+        options.optopt("", "db-name", "Database Name", "DATABASE");
+    }
+
+    /// Create a new connection from command line options, falling
+    /// back to PG environment variables, then defaults.
+    ///
+    /// The password is only read from PGPASSWORD.
+    pub fn new_from_options(params: &getopts::Matches) -> Self {
+        let host = params
+            .opt_str("db-host")
+            .or_else(|| env::var("PGHOST").ok())
+            .unwrap_or_else(|| DEFAULT_DB_HOST.to_string());
+
+        let port = params
+            .opt_str("db-port")
+            .or_else(|| env::var("PGPORT").ok())
+            .and_then(|p| p.parse::<u16>().ok())
+            .unwrap_or(DEFAULT_DB_PORT);
+
+        let user = params
+            .opt_str("db-user")
+            .or_else(|| env::var("PGUSER").ok())
+            .unwrap_or_else(|| DEFAULT_DB_USER.to_string());
+
+        let database = params
+            .opt_str("db-name")
+            .or_else(|| env::var("PGDATABASE").ok())
+            .unwrap_or_else(|| DEFAULT_DB_NAME.to_string());
+
+        DatabaseConnection {
+            client: None,
+            host,
+            port,
+            user,
+            password: env::var("PGPASSWORD").ok(),
+            database,
+            in_transaction: false,
+        }
+    }
+
+    /// Create a new connection purely from the environment.
+    pub fn from_env() -> Self {
+        let options = getopts::Options::new();
+        let params = options.parse::<&[&str]>(&[]).unwrap();
+        DatabaseConnection::new_from_options(&params)
+    }
+
+    pub fn dsn(&self) -> String {
+        let mut dsn = format!(
+            "host={} port={} user={} dbname={}",
+            self.host, self.port, self.user, self.database
+        );
+        if let Some(pw) = &self.password {
+            dsn += &format!(" password={pw}");
+        }
+        dsn
+    }
+
+    /// Connect to the database.
+    pub fn connect(&mut self) -> Result<(), String> {
+        let client = postgres::Client::connect(&self.dsn(), postgres::NoTls)
+            .map_err(|e| format!("Cannot connect to database: {e}"))?;
+
+        self.client = Some(client);
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self) {
+        self.client = None;
+        self.in_transaction = false;
+    }
+
+    pub fn connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Mutable reference to our postgres client.
+    ///
+    /// Panics if the connection has not been established.
+    pub fn client(&mut self) -> &mut postgres::Client {
+        self.client
+            .as_mut()
+            .expect("DatabaseConnection is not connected")
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.in_transaction
+    }
+
+    /// Start a transaction.
+    pub fn xact_begin(&mut self) -> Result<(), String> {
+        if self.in_transaction {
+            return Err("Transaction already in progress".to_string());
+        }
+        self.execute_stmt("BEGIN")?;
+        self.in_transaction = true;
+        Ok(())
+    }
+
+    /// Commit the open transaction.
+    pub fn xact_commit(&mut self) -> Result<(), String> {
+        if !self.in_transaction {
+            return Err("No transaction to commit".to_string());
+        }
+        self.in_transaction = false;
+        self.execute_stmt("COMMIT")
+    }
+
+    /// Roll back the open transaction.
+    pub fn xact_rollback(&mut self) -> Result<(), String> {
+        if !self.in_transaction {
+            return Err("No transaction to roll back".to_string());
+        }
+        self.in_transaction = false;
+        self.execute_stmt("ROLLBACK")
+    }
+
+    /// Create a savepoint within the open transaction.
+    pub fn savepoint(&mut self, name: &str) -> Result<(), String> {
+        self.check_savepoint(name)?;
+        self.execute_stmt(&format!("SAVEPOINT {name}"))
+    }
+
+    /// Release a savepoint, keeping its changes.
+    pub fn release_savepoint(&mut self, name: &str) -> Result<(), String> {
+        self.check_savepoint(name)?;
+        self.execute_stmt(&format!("RELEASE SAVEPOINT {name}"))
+    }
+
+    /// Roll back to a savepoint, discarding changes made since.
+    pub fn rollback_to_savepoint(&mut self, name: &str) -> Result<(), String> {
+        self.check_savepoint(name)?;
+        self.execute_stmt(&format!("ROLLBACK TO SAVEPOINT {name}"))
+    }
+
+    /// Savepoints only make sense inside a transaction, and the name
+    /// lands in SQL verbatim, so keep it a plain identifier.
+    fn check_savepoint(&self, name: &str) -> Result<(), String> {
+        if !self.in_transaction {
+            return Err(format!("Savepoint {name} requires a transaction"));
+        }
+
+        let valid = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("Invalid savepoint name: {name}"))
+        }
+    }
+
+    fn execute_stmt(&mut self, sql: &str) -> Result<(), String> {
+        log::debug!("DB executing: {sql}");
+        self.client()
+            .batch_execute(sql)
+            .map_err(|e| format!("{sql} failed: {e}"))
+    }
+}
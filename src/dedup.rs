@@ -0,0 +1,680 @@
+//! Bib record match scoring, candidate grouping, and merging.
+//!
+//! Records are reduced to fingerprints (normalized ISBNs, title, and
+//! author) and scored pairwise; groups of likely duplicates can then
+//! be exported for review or merged outright into a lead record.
+
+use crate::authority;
+use crate::editor::Editor;
+use crate::event::EgEvent;
+use crate::idl;
+use crate::marc;
+use crate::norm::Normalizer;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const DEDUP_TIMEOUT: u64 = 300;
+
+/// Score contribution for a shared ISBN.
+const SCORE_ISBN: u32 = 60;
+/// Score contribution for matching title fingerprints.
+const SCORE_TITLE: u32 = 30;
+/// Score contribution for matching author fingerprints.
+const SCORE_AUTHOR: u32 = 10;
+
+/// The default auto-merge threshold: shared ISBN plus matching title.
+pub const DEFAULT_THRESHOLD: u32 = 90;
+
+/// Normalize an ISBN value from an 020$a: strip qualifiers and
+/// hyphens, upcase the check digit.  Returns None unless the result
+/// is a plausible 10- or 13-character ISBN.
+pub fn normalize_isbn(value: &str) -> Option<String> {
+    let digits = value.split_whitespace().next().unwrap_or("");
+
+    let isbn: String = digits
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if (isbn.len() == 10 || isbn.len() == 13)
+        && isbn
+            .chars()
+            .all(|c| c.is_ascii_digit() || (c == 'X' && isbn.len() == 10))
+    {
+        Some(isbn)
+    } else {
+        None
+    }
+}
+
+/// The match-relevant shape of one bib record.
+#[derive(Debug, Clone, Default)]
+pub struct Fingerprint {
+    isbns: Vec<String>,
+    title: String,
+    author: String,
+}
+
+impl Fingerprint {
+    /// Extract a fingerprint from a MARC record: ISBNs from 020$a,
+    /// title from 245$a$b, author from the first 1XX name field.
+    pub fn from_record(normalizer: &Normalizer, record: &marc::Record) -> Self {
+        let mut isbns: Vec<String> = record
+            .get_values("020", "a")
+            .iter()
+            .filter_map(|v| normalize_isbn(v))
+            .collect();
+        isbns.sort();
+        isbns.dedup();
+
+        let title = match record.first_field("245") {
+            Some(field) => {
+                let mut parts = Vec::new();
+                parts.extend(field.get_subfields("a"));
+                parts.extend(field.get_subfields("b"));
+                normalizer.naco_normalize(&parts.join(" "))
+            }
+            None => String::new(),
+        };
+
+        let author = ["100", "110", "111"]
+            .iter()
+            .find_map(|tag| record.first_field(tag))
+            .and_then(|field| field.first_subfield("a"))
+            .map(|a| normalizer.naco_normalize(a))
+            .unwrap_or_default();
+
+        Fingerprint {
+            isbns,
+            title,
+            author,
+        }
+    }
+
+    pub fn isbns(&self) -> &Vec<String> {
+        &self.isbns
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+}
+
+/// Score the likelihood that two fingerprints describe the same
+/// title.  Empty components never match.
+pub fn match_score(a: &Fingerprint, b: &Fingerprint) -> u32 {
+    let mut score = 0;
+
+    if a.isbns.iter().any(|isbn| b.isbns.contains(isbn)) {
+        score += SCORE_ISBN;
+    }
+
+    if !a.title.is_empty() && a.title == b.title {
+        score += SCORE_TITLE;
+    }
+
+    if !a.author.is_empty() && a.author == b.author {
+        score += SCORE_AUTHOR;
+    }
+
+    score
+}
+
+/// A set of bib records believed to be duplicates of one another.
+#[derive(Debug)]
+pub struct MatchGroup {
+    records: Vec<i64>,
+    /// The weakest pairwise score that joined the group.
+    score: u32,
+}
+
+impl MatchGroup {
+    pub fn records(&self) -> &Vec<i64> {
+        &self.records
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// The lead (merge target) record: the lowest, i.e. oldest, ID.
+    pub fn lead(&self) -> i64 {
+        *self.records.iter().min().unwrap_or(&0)
+    }
+
+    /// The records to merge into the lead.
+    pub fn subordinates(&self) -> Vec<i64> {
+        let lead = self.lead();
+        self.records
+            .iter()
+            .filter(|id| **id != lead)
+            .copied()
+            .collect()
+    }
+}
+
+/// Group fingerprints whose pairwise score meets the threshold.
+///
+/// Candidate pairs are drawn from shared-ISBN and shared-title
+/// buckets rather than comparing every pair, so unrelated records
+/// cost nothing.
+pub fn group_records(
+    fingerprints: &HashMap<i64, Fingerprint>,
+    threshold: u32,
+) -> Vec<MatchGroup> {
+    let mut buckets: HashMap<String, Vec<i64>> = HashMap::new();
+
+    for (id, fp) in fingerprints {
+        for isbn in &fp.isbns {
+            buckets.entry(format!("i:{isbn}")).or_default().push(*id);
+        }
+        if !fp.title.is_empty() {
+            buckets
+                .entry(format!("t:{}", fp.title))
+                .or_default()
+                .push(*id);
+        }
+    }
+
+    let mut seen_pairs: HashSet<(i64, i64)> = HashSet::new();
+    let mut group_of: HashMap<i64, usize> = HashMap::new();
+    let mut groups: Vec<(Vec<i64>, u32)> = Vec::new();
+
+    for ids in buckets.values() {
+        for (idx, a) in ids.iter().enumerate() {
+            for b in &ids[idx + 1..] {
+                let pair = (*a.min(b), *a.max(b));
+                if !seen_pairs.insert(pair) {
+                    continue;
+                }
+
+                let score = match_score(&fingerprints[a], &fingerprints[b]);
+                if score < threshold {
+                    continue;
+                }
+
+                match (group_of.get(a).copied(), group_of.get(b).copied()) {
+                    (None, None) => {
+                        groups.push((vec![*a, *b], score));
+                        group_of.insert(*a, groups.len() - 1);
+                        group_of.insert(*b, groups.len() - 1);
+                    }
+                    (Some(ga), None) => {
+                        groups[ga].0.push(*b);
+                        groups[ga].1 = groups[ga].1.min(score);
+                        group_of.insert(*b, ga);
+                    }
+                    (None, Some(gb)) => {
+                        groups[gb].0.push(*a);
+                        groups[gb].1 = groups[gb].1.min(score);
+                        group_of.insert(*a, gb);
+                    }
+                    (Some(ga), Some(gb)) => {
+                        if ga != gb {
+                            // Merge the later group into the earlier.
+                            let (keep, drop) = (ga.min(gb), ga.max(gb));
+                            let moved = std::mem::take(&mut groups[drop].0);
+                            let drop_score = groups[drop].1;
+                            for id in &moved {
+                                group_of.insert(*id, keep);
+                            }
+                            groups[keep].0.extend(moved);
+                            groups[keep].1 = groups[keep].1.min(drop_score).min(score);
+                        } else {
+                            groups[ga].1 = groups[ga].1.min(score);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut result: Vec<MatchGroup> = groups
+        .into_iter()
+        .filter(|(records, _)| records.len() > 1)
+        .map(|(mut records, score)| {
+            records.sort();
+            MatchGroup { records, score }
+        })
+        .collect();
+
+    result.sort_by_key(|g| g.lead());
+    result
+}
+
+/// Summary of one dedup run.
+#[derive(Debug, Default)]
+pub struct DedupCounts {
+    pub records_scanned: usize,
+    pub groups_found: usize,
+    pub records_merged: usize,
+    pub errors: usize,
+}
+
+/// Drives fingerprinting and merging for one authenticated session.
+pub struct Deduper {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+    normalizer: Normalizer,
+    counts: DedupCounts,
+}
+
+impl Deduper {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Deduper {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+            normalizer: Normalizer::new(),
+            counts: DedupCounts::default(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    pub fn counts(&self) -> &DedupCounts {
+        &self.counts
+    }
+
+    /// Fingerprint one bib record.  Deleted records return None.
+    pub fn fingerprint_bib(&mut self, bib_id: i64) -> Result<Option<Fingerprint>, String> {
+        let bre = match self.editor.retrieve("bre", json::from(bib_id))? {
+            Some(b) => b,
+            None => return Err(format!("No such bib record: {bib_id}")),
+        };
+
+        if util::json_bool(&bre["deleted"]) {
+            return Ok(None);
+        }
+
+        let marc_xml = util::json_string(&bre["marc"])?;
+        let record = marc::Record::from_xml(&marc_xml)?;
+
+        self.counts.records_scanned += 1;
+
+        Ok(Some(Fingerprint::from_record(&self.normalizer, &record)))
+    }
+
+    /// Fingerprint a batch of bib records, skipping deleted ones and
+    /// logging (but not failing on) per-record errors.
+    pub fn fingerprint_bibs(
+        &mut self,
+        bib_ids: &[i64],
+    ) -> Result<HashMap<i64, Fingerprint>, String> {
+        let mut fingerprints = HashMap::new();
+
+        for bib_id in bib_ids {
+            match self.fingerprint_bib(*bib_id) {
+                Ok(Some(fp)) => {
+                    fingerprints.insert(*bib_id, fp);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.counts.errors += 1;
+                    log::error!("Cannot fingerprint bib {bib_id}: {e}");
+                }
+            }
+        }
+
+        Ok(fingerprints)
+    }
+
+    /// Group a batch of records at the requested score threshold.
+    pub fn find_groups(
+        &mut self,
+        bib_ids: &[i64],
+        threshold: u32,
+    ) -> Result<Vec<MatchGroup>, String> {
+        let fingerprints = self.fingerprint_bibs(bib_ids)?;
+        let groups = group_records(&fingerprints, threshold);
+        self.counts.groups_found += groups.len();
+        Ok(groups)
+    }
+
+    /// Merge a group's subordinate records into its lead, moving
+    /// holdings, holds, etc. along with them.
+    pub fn merge_group(&mut self, group: &MatchGroup) -> Result<(), String> {
+        let method = "open-ils.cat.biblio.records.merge";
+
+        let subs: Vec<JsonValue> = group
+            .subordinates()
+            .iter()
+            .map(|id| json::from(*id))
+            .collect();
+
+        let session = self.client.session("open-ils.cat");
+        let mut req = session.request(
+            method,
+            vec![
+                json::from(self.authtoken.as_str()),
+                json::from(group.lead()),
+                JsonValue::Array(subs),
+            ],
+        )?;
+
+        let resp = match req.recv(DEDUP_TIMEOUT)? {
+            Some(resp) => resp,
+            None => return Err(format!("No response to {method}")),
+        };
+
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("{method} failed: {evt}"));
+            }
+        }
+
+        self.counts.records_merged += group.subordinates().len();
+
+        Ok(())
+    }
+}
+
+/// The dedup key for an authority record: its thesaurus code
+/// (008/11) plus the normalized established heading.  Records with
+/// no heading return None.
+pub fn authority_key(normalizer: &Normalizer, record: &marc::Record) -> Option<String> {
+    let field = authority::heading_field(record)?;
+
+    let heading = normalizer.naco_normalize(&authority::Linker::field_heading(field));
+    if heading.is_empty() {
+        return None;
+    }
+
+    let thesaurus = record
+        .control_field("008")
+        .and_then(|content| content.chars().nth(11))
+        .unwrap_or(' ');
+
+    Some(format!("{thesaurus}:{heading}"))
+}
+
+/// Detects authority records sharing a normalized heading and
+/// thesaurus, merges them, and re-points bib $0 links to the
+/// surviving record.
+pub struct AuthorityDeduper {
+    editor: Editor,
+    normalizer: Normalizer,
+    counts: DedupCounts,
+}
+
+impl AuthorityDeduper {
+    pub fn new(editor: Editor) -> Self {
+        AuthorityDeduper {
+            editor,
+            normalizer: Normalizer::new(),
+            counts: DedupCounts::default(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    pub fn counts(&self) -> &DedupCounts {
+        &self.counts
+    }
+
+    /// Group authority records whose dedup keys are identical.
+    /// Authority matching is exact, so every group scores 100.
+    pub fn find_groups(&mut self, auth_ids: &[i64]) -> Result<Vec<MatchGroup>, String> {
+        let mut by_key: HashMap<String, Vec<i64>> = HashMap::new();
+
+        for auth_id in auth_ids {
+            let are = match self.editor.retrieve("are", json::from(*auth_id))? {
+                Some(a) => a,
+                None => {
+                    self.counts.errors += 1;
+                    log::error!("No such authority record: {auth_id}");
+                    continue;
+                }
+            };
+
+            if util::json_bool(&are["deleted"]) {
+                continue;
+            }
+
+            let marc_xml = util::json_string(&are["marc"])?;
+            let record = marc::Record::from_xml(&marc_xml)?;
+
+            self.counts.records_scanned += 1;
+
+            if let Some(key) = authority_key(&self.normalizer, &record) {
+                by_key.entry(key).or_default().push(*auth_id);
+            }
+        }
+
+        let mut groups: Vec<MatchGroup> = by_key
+            .into_values()
+            .filter(|ids| ids.len() > 1)
+            .map(|mut records| {
+                records.sort();
+                MatchGroup {
+                    records,
+                    score: 100,
+                }
+            })
+            .collect();
+
+        groups.sort_by_key(|g| g.lead());
+        self.counts.groups_found += groups.len();
+
+        Ok(groups)
+    }
+
+    /// Merge a group: re-point bib $0 links from each subordinate to
+    /// the lead, then retire the subordinate record.
+    pub fn merge_group(&mut self, group: &MatchGroup) -> Result<(), String> {
+        for sub_id in group.subordinates() {
+            self.repoint_bibs(sub_id, group.lead())?;
+            self.retire_authority(sub_id)?;
+            self.counts.records_merged += 1;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the $0 links of every bib field linked to the
+    /// subordinate so they point at the lead.
+    fn repoint_bibs(&mut self, sub_id: i64, lead_id: i64) -> Result<(), String> {
+        // Linked bibs are found via the flattened record index.
+        let hits = self.editor.search(
+            "mfr",
+            json::object! {
+                subfield: "0",
+                value: sub_id.to_string(),
+            },
+        )?;
+
+        for hit in hits {
+            let bib_id = util::json_int(&hit["record"])?;
+            self.repoint_bib(sub_id, lead_id, bib_id)?;
+        }
+
+        Ok(())
+    }
+
+    fn repoint_bib(&mut self, sub_id: i64, lead_id: i64, bib_id: i64) -> Result<(), String> {
+        let bre = match self.editor.retrieve("bre", json::from(bib_id))? {
+            Some(b) => b,
+            None => return Err(format!("No such bib record: {bib_id}")),
+        };
+
+        if util::json_bool(&bre["deleted"]) {
+            return Ok(());
+        }
+
+        let marc_xml = util::json_string(&bre["marc"])?;
+        let mut record = marc::Record::from_xml(&marc_xml)?;
+
+        let sub_link = sub_id.to_string();
+        let mut changed = false;
+
+        for field in record.fields_mut() {
+            if authority::auth_tag_for(field.tag()).is_none() {
+                continue;
+            }
+
+            if field.first_subfield("0") == Some(sub_link.as_str()) {
+                field.set_subfield("0", &lead_id.to_string());
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return Ok(());
+        }
+
+        let mut bre = bre;
+        bre["marc"] = record.to_xml().into();
+
+        self.editor.xact_begin()?;
+
+        let resp = self
+            .editor
+            .request("open-ils.cstore.direct.biblio.record_entry.update", vec![bre]);
+
+        match resp {
+            Ok(_) => Ok(self.editor.commit()?),
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Delete a subordinate's simple headings and mark the record
+    /// deleted.
+    fn retire_authority(&mut self, auth_id: i64) -> Result<(), String> {
+        let mut are = match self.editor.retrieve("are", json::from(auth_id))? {
+            Some(a) => a,
+            None => return Err(format!("No such authority record: {auth_id}")),
+        };
+
+        let headings = self.editor.search("ash", json::object! {record: auth_id})?;
+
+        self.editor.xact_begin()?;
+
+        for heading in headings {
+            let resp = self.editor.request(
+                "open-ils.cstore.direct.authority.simple_heading.delete",
+                vec![heading],
+            );
+
+            if let Err(e) = resp {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+        }
+
+        are["deleted"] = "t".into();
+
+        let resp = self.editor.request(
+            "open-ils.cstore.direct.authority.record_entry.update",
+            vec![are],
+        );
+
+        match resp {
+            Ok(_) => Ok(self.editor.commit()?),
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fp(isbns: &[&str], title: &str, author: &str) -> Fingerprint {
+        Fingerprint {
+            isbns: isbns.iter().map(|i| i.to_string()).collect(),
+            title: title.to_string(),
+            author: author.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_isbn() {
+        assert_eq!(
+            normalize_isbn("0-19-852663-6 (pbk.)"),
+            Some("0198526636".to_string())
+        );
+        assert_eq!(
+            normalize_isbn("978-0-19-852663-9"),
+            Some("9780198526639".to_string())
+        );
+        assert_eq!(normalize_isbn("043942089x"), Some("043942089X".to_string()));
+        assert_eq!(normalize_isbn("not an isbn"), None);
+        assert_eq!(normalize_isbn("12345"), None);
+    }
+
+    #[test]
+    fn test_match_score() {
+        let a = fp(&["9780198526639"], "MOBY DICK", "MELVILLE HERMAN");
+        let b = fp(&["9780198526639"], "MOBY DICK", "MELVILLE HERMAN");
+        let c = fp(&["0000000000"], "MOBY DICK", "MELVILLE HERMAN");
+        let d = fp(&[], "", "");
+
+        assert_eq!(match_score(&a, &b), 100);
+        assert_eq!(match_score(&a, &c), 40);
+        assert_eq!(match_score(&a, &d), 0);
+        assert_eq!(match_score(&d, &d), 0); // empty components never match
+    }
+
+    #[test]
+    fn test_group_records() {
+        let mut fingerprints = HashMap::new();
+        fingerprints.insert(1, fp(&["9780198526639"], "MOBY DICK", "MELVILLE HERMAN"));
+        fingerprints.insert(2, fp(&["9780198526639"], "MOBY DICK", "MELVILLE HERMAN"));
+        fingerprints.insert(3, fp(&["9780198526639"], "MOBY DICK", ""));
+        fingerprints.insert(4, fp(&["1111111111"], "OTHER TITLE", "SOMEONE ELSE"));
+
+        let groups = group_records(&fingerprints, DEFAULT_THRESHOLD);
+        assert_eq!(groups.len(), 1);
+
+        let group = &groups[0];
+        assert_eq!(group.records(), &vec![1, 2, 3]);
+        assert_eq!(group.lead(), 1);
+        assert_eq!(group.subordinates(), vec![2, 3]);
+        assert_eq!(group.score(), 90); // weakest link: record 3 has no author
+
+        // At a lower threshold the loner still matches nothing.
+        let groups = group_records(&fingerprints, 30);
+        assert_eq!(groups.len(), 1);
+    }
+
+    #[test]
+    fn test_authority_key() {
+        let xml = r#"<record xmlns="http://www.loc.gov/MARC21/slim">
+            <leader>00000nz  a2200000o  4500</leader>
+            <controlfield tag="008">850101i| anannbabn          |a aaa     </controlfield>
+            <datafield tag="150" ind1=" " ind2=" ">
+                <subfield code="a">Dogs</subfield>
+                <subfield code="x">Fiction.</subfield>
+            </datafield>
+        </record>"#;
+
+        let record = marc::Record::from_xml(xml).unwrap();
+        let normalizer = Normalizer::new();
+
+        assert_eq!(
+            authority_key(&normalizer, &record),
+            Some("a:DOGS FICTION".to_string())
+        );
+
+        let empty = marc::Record::new();
+        assert_eq!(authority_key(&normalizer, &empty), None);
+    }
+}
@@ -0,0 +1,676 @@
+//! EDIFACT message handling for acquisitions.
+//!
+//! Parses and generates the EDI message types Evergreen exchanges
+//! with vendors -- ORDERS out; ORDRSP, INVOIC, and DESADV in --
+//! mapped to acq purchase orders and invoices.
+
+pub mod remote;
+
+use crate::editor::Editor;
+use crate::util;
+use std::fmt;
+
+// EDIFACT syntax characters (UNOA defaults).
+const SEGMENT_TERMINATOR: char = '\'';
+const ELEMENT_SEPARATOR: char = '+';
+const COMPONENT_SEPARATOR: char = ':';
+const RELEASE_CHAR: char = '?';
+
+/// One EDIFACT segment: a tag plus elements, each element a list of
+/// components.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    tag: String,
+    elements: Vec<Vec<String>>,
+}
+
+impl Segment {
+    pub fn new(tag: &str) -> Self {
+        Segment {
+            tag: tag.to_string(),
+            elements: Vec::new(),
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Add a simple (single-component) element.
+    pub fn add_element(mut self, value: &str) -> Self {
+        self.elements.push(vec![value.to_string()]);
+        self
+    }
+
+    /// Add a composite element.
+    pub fn add_composite(mut self, components: &[&str]) -> Self {
+        self.elements
+            .push(components.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// The first component of element `index`, if present and
+    /// non-empty.
+    pub fn element(&self, index: usize) -> Option<&str> {
+        self.component(index, 0)
+    }
+
+    /// Component `cindex` of element `eindex`.
+    pub fn component(&self, eindex: usize, cindex: usize) -> Option<&str> {
+        self.elements
+            .get(eindex)
+            .and_then(|e| e.get(cindex))
+            .map(|c| c.as_str())
+            .filter(|c| !c.is_empty())
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.tag)?;
+        for element in &self.elements {
+            let element: Vec<String> = element.iter().map(|c| escape(c)).collect();
+            write!(f, "{ELEMENT_SEPARATOR}{}", element.join(":"))?;
+        }
+        write!(f, "{SEGMENT_TERMINATOR}")
+    }
+}
+
+/// Escape EDIFACT syntax characters with the release character.
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(
+            ch,
+            SEGMENT_TERMINATOR | ELEMENT_SEPARATOR | COMPONENT_SEPARATOR | RELEASE_CHAR
+        ) {
+            out.push(RELEASE_CHAR);
+        }
+        out.push(ch);
+    }
+    out
+}
+
+/// Parse a raw EDIFACT interchange into segments, honoring the
+/// release character.  A leading UNA service string advice is
+/// consumed but nonstandard separators are not supported.
+pub fn parse_interchange(data: &str) -> Result<Vec<Segment>, String> {
+    let data = data.trim_start();
+    let data = match data.strip_prefix("UNA") {
+        // UNA is followed by exactly six syntax characters.
+        Some(rest) => rest.get(6..).ok_or("Truncated UNA segment")?,
+        None => data,
+    };
+
+    let mut segments = Vec::new();
+    let mut element: Vec<String> = Vec::new();
+    let mut component = String::new();
+    let mut tag_done = false;
+    let mut tag = String::new();
+    let mut released = false;
+
+    for ch in data.chars() {
+        if released {
+            component.push(ch);
+            released = false;
+            continue;
+        }
+
+        match ch {
+            RELEASE_CHAR => released = true,
+            ELEMENT_SEPARATOR if !tag_done => tag_done = true,
+            ELEMENT_SEPARATOR => {
+                element.push(std::mem::take(&mut component));
+                segments
+                    .last_mut()
+                    .map(|s: &mut Segment| &mut s.elements)
+                    .ok_or("Element data before a segment tag")?
+                    .push(std::mem::take(&mut element));
+            }
+            COMPONENT_SEPARATOR if tag_done => {
+                element.push(std::mem::take(&mut component));
+            }
+            SEGMENT_TERMINATOR => {
+                if tag_done {
+                    element.push(std::mem::take(&mut component));
+                    segments
+                        .last_mut()
+                        .map(|s: &mut Segment| &mut s.elements)
+                        .ok_or("Element data before a segment tag")?
+                        .push(std::mem::take(&mut element));
+                }
+                tag_done = false;
+                tag.clear();
+            }
+            '\r' | '\n' if tag.is_empty() && !tag_done => {} // inter-segment whitespace
+            _ if !tag_done => {
+                tag.push(ch);
+                if tag.len() == 3 {
+                    segments.push(Segment::new(&tag));
+                }
+            }
+            _ => component.push(ch),
+        }
+    }
+
+    if segments.is_empty() {
+        return Err("No EDI segments found".to_string());
+    }
+
+    Ok(segments)
+}
+
+/// The EDI message types we exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    /// Outbound purchase order.
+    Orders,
+    /// Vendor response to an order.
+    OrderResponse,
+    /// Vendor invoice.
+    Invoice,
+    /// Dispatch (shipment) advice.
+    ShipNotice,
+}
+
+impl MessageType {
+    fn from_edi_name(name: &str) -> Result<MessageType, String> {
+        match name {
+            "ORDERS" => Ok(MessageType::Orders),
+            "ORDRSP" => Ok(MessageType::OrderResponse),
+            "INVOIC" => Ok(MessageType::Invoice),
+            "DESADV" => Ok(MessageType::ShipNotice),
+            _ => Err(format!("Unsupported EDI message type: {name}")),
+        }
+    }
+}
+
+/// One line (LIN group) of a parsed inbound message.
+#[derive(Debug, Clone, Default)]
+pub struct EdiLine {
+    /// Our lineitem ID when the vendor echoes it back (RFF+LI).
+    pub lineitem: Option<i64>,
+    /// Vendor-supplied item number (usually ISBN/EAN).
+    pub item_number: Option<String>,
+    pub quantity: Option<i64>,
+    pub price: Option<f64>,
+    /// ORDRSP line status (IMD/industry code), e.g. backordered.
+    pub status: Option<String>,
+}
+
+/// A parsed inbound EDI message.
+#[derive(Debug, Clone)]
+pub struct EdiMessage {
+    pub msg_type: MessageType,
+    /// The BGM document number: vendor invoice number, or our PO
+    /// number echoed back.
+    pub document_number: Option<String>,
+    /// Our purchase order ID from RFF+ON, when present.
+    pub purchase_order: Option<i64>,
+    pub lines: Vec<EdiLine>,
+}
+
+/// Parse every message in a raw interchange.
+pub fn parse_messages(data: &str) -> Result<Vec<EdiMessage>, String> {
+    let segments = parse_interchange(data)?;
+
+    let mut messages = Vec::new();
+    let mut current: Option<EdiMessage> = None;
+
+    for segment in &segments {
+        match segment.tag() {
+            "UNH" => {
+                let name = segment
+                    .component(1, 0)
+                    .ok_or("UNH segment has no message type")?;
+                current = Some(EdiMessage {
+                    msg_type: MessageType::from_edi_name(name)?,
+                    document_number: None,
+                    purchase_order: None,
+                    lines: Vec::new(),
+                });
+            }
+            "UNT" => {
+                if let Some(message) = current.take() {
+                    messages.push(message);
+                }
+            }
+            _ => {
+                if let Some(message) = current.as_mut() {
+                    apply_segment(message, segment);
+                }
+            }
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Fold one segment into the message being built.
+fn apply_segment(message: &mut EdiMessage, segment: &Segment) {
+    match segment.tag() {
+        "BGM" => message.document_number = segment.element(1).map(|v| v.to_string()),
+        "RFF" => {
+            // RFF+ON:<po id> at message level; RFF+LI:<lineitem id>
+            // at line level.
+            let qualifier = segment.component(0, 0);
+            let value = segment.component(0, 1).and_then(|v| v.parse::<i64>().ok());
+
+            match qualifier {
+                Some("ON") => message.purchase_order = message.purchase_order.or(value),
+                Some("LI") => {
+                    if let Some(line) = message.lines.last_mut() {
+                        line.lineitem = value;
+                    }
+                }
+                _ => {}
+            }
+        }
+        "LIN" => {
+            // LIN+<num>++<item number>:<code>
+            message.lines.push(EdiLine {
+                item_number: segment.component(2, 0).map(|v| v.to_string()),
+                ..Default::default()
+            });
+        }
+        "PIA" => {
+            if let Some(line) = message.lines.last_mut() {
+                if line.item_number.is_none() {
+                    line.item_number = segment.component(1, 0).map(|v| v.to_string());
+                }
+            }
+        }
+        "QTY" => {
+            if let Some(line) = message.lines.last_mut() {
+                line.quantity = segment.component(0, 1).and_then(|v| v.parse().ok());
+            }
+        }
+        "PRI" => {
+            if let Some(line) = message.lines.last_mut() {
+                line.price = segment.component(0, 1).and_then(|v| v.parse().ok());
+            }
+        }
+        "IMD" => {
+            if let Some(line) = message.lines.last_mut() {
+                if line.status.is_none() {
+                    line.status = segment.component(2, 0).map(|v| v.to_string());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Data needed to generate one ORDERS line.
+#[derive(Debug, Clone)]
+pub struct OrderLine {
+    pub lineitem: i64,
+    pub isbn: Option<String>,
+    pub quantity: i64,
+    pub price: Option<f64>,
+}
+
+/// Data needed to generate an ORDERS message.
+#[derive(Debug, Clone)]
+pub struct PurchaseOrder {
+    pub id: i64,
+    /// Sender (library) identifier, typically a SAN.
+    pub sender: String,
+    /// Receiver (vendor) identifier.
+    pub receiver: String,
+    pub lines: Vec<OrderLine>,
+}
+
+impl PurchaseOrder {
+    /// Build order data for a PO from the database: the lineitems,
+    /// their copy counts, and their ISBN attributes.
+    pub fn from_editor(editor: &mut Editor, po_id: i64) -> Result<PurchaseOrder, String> {
+        let po = editor
+            .retrieve("acqpo", json::from(po_id))?
+            .ok_or_else(|| format!("No such purchase order: {po_id}"))?;
+
+        // Sender/receiver come from the provider's EDI account.
+        let provider = util::json_int(&po["provider"])?;
+        let accounts = editor.search("acqedi", json::object! {provider: provider})?;
+        let account = accounts
+            .first()
+            .ok_or_else(|| format!("Provider {provider} has no EDI account"))?;
+
+        let lineitems = editor.search("jub", json::object! {purchase_order: po_id})?;
+
+        let mut lines = Vec::new();
+
+        for li in &lineitems {
+            let li_id = util::json_int(&li["id"])?;
+
+            let details = editor.search("acqlid", json::object! {lineitem: li_id})?;
+
+            let isbns = editor.search(
+                "acqlia",
+                json::object! {lineitem: li_id, attr_name: "isbn"},
+            )?;
+
+            lines.push(OrderLine {
+                lineitem: li_id,
+                isbn: isbns
+                    .first()
+                    .and_then(|a| a["attr_value"].as_str())
+                    .map(|v| v.to_string()),
+                quantity: details.len() as i64,
+                price: util::json_float(&li["estimated_unit_price"]).ok(),
+            });
+        }
+
+        Ok(PurchaseOrder {
+            id: po_id,
+            sender: account["vendcode"].as_str().unwrap_or("").to_string(),
+            receiver: account["vendacct"].as_str().unwrap_or("").to_string(),
+            lines,
+        })
+    }
+
+    /// Generate the EDIFACT ORDERS interchange for this PO.
+    pub fn to_orders(&self) -> String {
+        let now = chrono::Local::now();
+        let date = now.format("%y%m%d").to_string();
+        let time = now.format("%H%M").to_string();
+        let control_ref = format!("{}", self.id);
+
+        let mut segments = vec![
+            Segment::new("UNB")
+                .add_composite(&["UNOA", "3"])
+                .add_element(&self.sender)
+                .add_element(&self.receiver)
+                .add_composite(&[&date, &time])
+                .add_element(&control_ref),
+            Segment::new("UNH")
+                .add_element("1")
+                .add_composite(&["ORDERS", "D", "96A", "UN"]),
+            Segment::new("BGM")
+                .add_element("220")
+                .add_element(&self.id.to_string())
+                .add_element("9"),
+            Segment::new("DTM").add_composite(&["137", &now.format("%Y%m%d").to_string(), "102"]),
+        ];
+
+        for (index, line) in self.lines.iter().enumerate() {
+            let number = (index + 1).to_string();
+
+            let mut lin = Segment::new("LIN").add_element(&number).add_element("");
+            if let Some(isbn) = &line.isbn {
+                lin = lin.add_composite(&[isbn, "EN"]);
+            }
+            segments.push(lin);
+
+            segments.push(
+                Segment::new("RFF").add_composite(&["LI", &line.lineitem.to_string()]),
+            );
+
+            segments.push(
+                Segment::new("QTY").add_composite(&["21", &line.quantity.to_string()]),
+            );
+
+            if let Some(price) = line.price {
+                segments
+                    .push(Segment::new("PRI").add_composite(&["AAA", &format!("{price:.2}")]));
+            }
+        }
+
+        segments.push(Segment::new("UNS").add_element("S"));
+        segments.push(
+            Segment::new("CNT").add_composite(&["2", &self.lines.len().to_string()]),
+        );
+
+        // UNT counts every segment from UNH through UNT itself.
+        let unt_count = segments.len(); // minus UNB, plus UNT
+        segments.push(
+            Segment::new("UNT")
+                .add_element(&unt_count.to_string())
+                .add_element("1"),
+        );
+        segments.push(
+            Segment::new("UNZ")
+                .add_element("1")
+                .add_element(&control_ref),
+        );
+
+        let body: Vec<String> = segments.iter().map(|s| s.to_string()).collect();
+        format!("UNA:+.? '{}", body.join(""))
+    }
+}
+
+/// Applies parsed inbound messages to acq data.
+pub struct Applier {
+    editor: Editor,
+}
+
+impl Applier {
+    pub fn new(editor: Editor) -> Self {
+        Applier { editor }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// Apply one inbound message.  Returns a short description of
+    /// what was done, for logging.
+    pub fn apply(&mut self, message: &EdiMessage) -> Result<String, String> {
+        match message.msg_type {
+            MessageType::OrderResponse => self.apply_order_response(message),
+            MessageType::Invoice => self.apply_invoice(message),
+            MessageType::ShipNotice => {
+                // Nothing to update yet; receiving happens at checkin.
+                Ok(format!(
+                    "DESADV for PO {:?} acknowledged",
+                    message.purchase_order
+                ))
+            }
+            MessageType::Orders => Err("ORDERS is an outbound message type".to_string()),
+        }
+    }
+
+    /// ORDRSP: mark echoed lineitems on-order, recording any vendor
+    /// status note.
+    fn apply_order_response(&mut self, message: &EdiMessage) -> Result<String, String> {
+        let mut updated = 0;
+
+        self.editor.xact_begin()?;
+
+        for line in &message.lines {
+            let li_id = match line.lineitem {
+                Some(id) => id,
+                None => continue,
+            };
+
+            let mut li = match self.editor.retrieve("jub", json::from(li_id))? {
+                Some(li) => li,
+                None => {
+                    log::warn!("ORDRSP references unknown lineitem {li_id}");
+                    continue;
+                }
+            };
+
+            li["state"] = "on-order".into();
+
+            if let Some(price) = line.price {
+                li["estimated_unit_price"] = format!("{price:.2}").into();
+            }
+
+            let resp = self
+                .editor
+                .request("open-ils.cstore.direct.acq.lineitem.update", vec![li]);
+
+            if let Err(e) = resp {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+
+            updated += 1;
+        }
+
+        self.editor.commit()?;
+
+        Ok(format!("ORDRSP updated {updated} lineitems"))
+    }
+
+    /// INVOIC: create an acq invoice with one entry per echoed
+    /// lineitem.
+    fn apply_invoice(&mut self, message: &EdiMessage) -> Result<String, String> {
+        let inv_ident = message
+            .document_number
+            .as_deref()
+            .ok_or("INVOIC has no document number")?;
+
+        let po_id = message
+            .purchase_order
+            .ok_or("INVOIC references no purchase order")?;
+
+        let po = self
+            .editor
+            .retrieve("acqpo", json::from(po_id))?
+            .ok_or_else(|| format!("No such purchase order: {po_id}"))?;
+
+        self.editor.xact_begin()?;
+
+        let invoice = json::object! {
+            "_classname": "acqinv",
+            receiver: po["ordering_agency"].clone(),
+            provider: po["provider"].clone(),
+            shipper: po["provider"].clone(),
+            inv_ident: inv_ident,
+            recv_date: chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%z").to_string(),
+        };
+
+        let invoice = match self
+            .editor
+            .request("open-ils.cstore.direct.acq.invoice.create", vec![invoice])
+        {
+            Ok(inv) => inv,
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+        };
+
+        let mut entries = 0;
+
+        for line in &message.lines {
+            let li_id = match line.lineitem {
+                Some(id) => id,
+                None => continue,
+            };
+
+            let quantity = line.quantity.unwrap_or(1);
+            let price = line.price.unwrap_or(0.0);
+
+            let entry = json::object! {
+                "_classname": "acqie",
+                invoice: invoice["id"].clone(),
+                purchase_order: po_id,
+                lineitem: li_id,
+                inv_item_count: quantity,
+                phys_item_count: quantity,
+                cost_billed: format!("{:.2}", price * quantity as f64),
+            };
+
+            let resp = self
+                .editor
+                .request("open-ils.cstore.direct.acq.invoice_entry.create", vec![entry]);
+
+            if let Err(e) = resp {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+
+            entries += 1;
+        }
+
+        self.editor.commit()?;
+
+        Ok(format!("INVOIC {inv_ident} created with {entries} entries"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interchange() {
+        let data = "UNA:+.? 'UNB+UNOA:3+SENDER+RECEIVER+200101:1200+1'\
+            UNH+1+ORDRSP:D:96A:UN'BGM+231+PO99'RFF+ON:42'\
+            LIN+1++9780123456789:EN'RFF+LI:7'QTY+21:2'PRI+AAA:10.50'\
+            UNT+8+1'UNZ+1+1'";
+
+        let segments = parse_interchange(data).expect("interchange should parse");
+        assert_eq!(segments[0].tag(), "UNB");
+        assert_eq!(segments[0].component(0, 0), Some("UNOA"));
+        assert_eq!(segments[1].element(1), Some("ORDRSP"));
+
+        // Garbage from a vendor server must error, not panic.
+        assert!(parse_interchange("AB+1'").is_err());
+        assert!(parse_interchange("+1'").is_err());
+    }
+
+    #[test]
+    fn test_release_character() {
+        let segments = parse_interchange("BGM+220+AB?+CD'").expect("should parse");
+        assert_eq!(segments[0].element(1), Some("AB+CD"));
+
+        let segment = Segment::new("BGM").add_element("AB+CD");
+        assert_eq!(segment.to_string(), "BGM+AB?+CD'");
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let data = "UNB+UNOA:3+V+L+200101:1200+1'\
+            UNH+1+ORDRSP:D:96A:UN'BGM+231+PO99'RFF+ON:42'\
+            LIN+1++9780123456789:EN'RFF+LI:7'QTY+21:2'PRI+AAA:10.50'\
+            UNT+8+1'UNZ+1+1'";
+
+        let messages = parse_messages(data).expect("messages should parse");
+        assert_eq!(messages.len(), 1);
+
+        let message = &messages[0];
+        assert_eq!(message.msg_type, MessageType::OrderResponse);
+        assert_eq!(message.document_number.as_deref(), Some("PO99"));
+        assert_eq!(message.purchase_order, Some(42));
+
+        assert_eq!(message.lines.len(), 1);
+        let line = &message.lines[0];
+        assert_eq!(line.lineitem, Some(7));
+        assert_eq!(line.item_number.as_deref(), Some("9780123456789"));
+        assert_eq!(line.quantity, Some(2));
+        assert_eq!(line.price, Some(10.50));
+    }
+
+    #[test]
+    fn test_generate_orders() {
+        let po = PurchaseOrder {
+            id: 42,
+            sender: "1234567".to_string(),
+            receiver: "7654321".to_string(),
+            lines: vec![OrderLine {
+                lineitem: 7,
+                isbn: Some("9780123456789".to_string()),
+                quantity: 2,
+                price: Some(10.5),
+            }],
+        };
+
+        let orders = po.to_orders();
+        assert!(orders.starts_with("UNA:+.? 'UNB+UNOA:3+1234567+7654321+"));
+        assert!(orders.contains("BGM+220+42+9'"));
+        assert!(orders.contains("LIN+1++9780123456789:EN'"));
+        assert!(orders.contains("RFF+LI:7'"));
+        assert!(orders.contains("QTY+21:2'"));
+        assert!(orders.contains("PRI+AAA:10.50'"));
+        assert!(orders.ends_with("UNZ+1+42'"));
+
+        // The generated interchange should round-trip.
+        let messages = parse_messages(&orders).expect("orders should parse");
+        assert_eq!(messages[0].msg_type, MessageType::Orders);
+        assert_eq!(messages[0].lines[0].lineitem, Some(7));
+    }
+}
@@ -0,0 +1,333 @@
+//! File exchange with vendor EDI accounts over FTP or SFTP.
+
+use crate::util;
+use json::JsonValue;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Ftp,
+    /// SFTP is driven through the system `sftp` binary and requires
+    /// key-based auth; EDI account passwords are ignored.
+    Sftp,
+}
+
+/// A vendor's file exchange account, from acq.edi_account.
+#[derive(Debug, Clone)]
+pub struct RemoteAccount {
+    pub protocol: Protocol,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    /// Remote directory we retrieve vendor files from.
+    pub in_dir: String,
+}
+
+impl RemoteAccount {
+    /// Build an account from an acq.edi_account row.  The host field
+    /// may carry a protocol prefix (`sftp://host`) and port suffix.
+    pub fn from_edi_account(account: &JsonValue) -> Result<RemoteAccount, String> {
+        let mut host = util::json_string(&account["host"])?;
+
+        let protocol = if let Some(rest) = host.strip_prefix("sftp://") {
+            host = rest.to_string();
+            Protocol::Sftp
+        } else if let Some(rest) = host.strip_prefix("ftp://") {
+            host = rest.to_string();
+            Protocol::Ftp
+        } else {
+            Protocol::Ftp
+        };
+
+        let mut port = match protocol {
+            Protocol::Ftp => 21,
+            Protocol::Sftp => 22,
+        };
+
+        if let Some((h, p)) = host.split_once(':') {
+            port = p
+                .parse()
+                .map_err(|_| format!("Invalid port in host {host}"))?;
+            host = h.to_string();
+        }
+
+        Ok(RemoteAccount {
+            protocol,
+            host,
+            port,
+            username: account["username"].as_str().unwrap_or("anonymous").to_string(),
+            password: account["password"].as_str().unwrap_or("").to_string(),
+            in_dir: account["in_dir"].as_str().unwrap_or(".").to_string(),
+        })
+    }
+
+    /// List the files available in the account's pickup directory.
+    pub fn list(&self) -> Result<Vec<String>, String> {
+        match self.protocol {
+            Protocol::Ftp => FtpSession::connect(self)?.list(&self.in_dir),
+            Protocol::Sftp => self.sftp_list(),
+        }
+    }
+
+    /// Retrieve one remote file's contents.
+    pub fn retrieve(&self, filename: &str) -> Result<String, String> {
+        match self.protocol {
+            Protocol::Ftp => {
+                FtpSession::connect(self)?.retrieve(&self.remote_path(filename))
+            }
+            Protocol::Sftp => self.sftp_retrieve(filename),
+        }
+    }
+
+    /// Upload a file to the remote account.
+    pub fn send(&self, filename: &str, content: &str) -> Result<(), String> {
+        match self.protocol {
+            Protocol::Ftp => {
+                FtpSession::connect(self)?.store(&self.remote_path(filename), content)
+            }
+            Protocol::Sftp => self.sftp_send(filename, content),
+        }
+    }
+
+    fn remote_path(&self, filename: &str) -> String {
+        if self.in_dir == "." || self.in_dir.is_empty() {
+            filename.to_string()
+        } else {
+            format!("{}/{}", self.in_dir.trim_end_matches('/'), filename)
+        }
+    }
+
+    // -- SFTP via the system binary ----------------------------------
+
+    fn sftp_batch(&self, batch: &str) -> Result<String, String> {
+        let output = Command::new("sftp")
+            .arg("-b")
+            .arg("-")
+            .arg("-P")
+            .arg(self.port.to_string())
+            .arg("-o")
+            .arg("BatchMode=yes")
+            .arg(format!("{}@{}", self.username, self.host))
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                child
+                    .stdin
+                    .take()
+                    .expect("stdin is piped")
+                    .write_all(batch.as_bytes())?;
+                child.wait_with_output()
+            })
+            .map_err(|e| format!("Cannot run sftp: {e}"))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "sftp failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn sftp_list(&self) -> Result<Vec<String>, String> {
+        let out = self.sftp_batch(&format!("ls -1 {}\n", self.in_dir))?;
+
+        Ok(out
+            .lines()
+            .map(|l| l.trim())
+            // sftp echoes batch commands prefixed with "sftp>".
+            .filter(|l| !l.is_empty() && !l.starts_with("sftp>"))
+            .map(|l| {
+                l.rsplit('/')
+                    .next()
+                    .unwrap_or(l)
+                    .to_string()
+            })
+            .collect())
+    }
+
+    fn sftp_retrieve(&self, filename: &str) -> Result<String, String> {
+        let local = std::env::temp_dir().join(format!("eg-edi-{filename}"));
+        let local_str = local.to_string_lossy().to_string();
+
+        self.sftp_batch(&format!(
+            "get {} {}\n",
+            self.remote_path(filename),
+            local_str
+        ))?;
+
+        let content = std::fs::read_to_string(&local)
+            .map_err(|e| format!("Cannot read fetched file {local_str}: {e}"));
+        std::fs::remove_file(&local).ok();
+        content
+    }
+
+    fn sftp_send(&self, filename: &str, content: &str) -> Result<(), String> {
+        let local = std::env::temp_dir().join(format!("eg-edi-{filename}"));
+        let local_str = local.to_string_lossy().to_string();
+
+        std::fs::write(&local, content).map_err(|e| format!("Cannot write {local_str}: {e}"))?;
+
+        let result = self.sftp_batch(&format!(
+            "put {} {}\n",
+            local_str,
+            self.remote_path(filename)
+        ));
+
+        std::fs::remove_file(&local).ok();
+        result.map(|_| ())
+    }
+}
+
+/// A minimal FTP client: USER/PASS login and passive-mode LIST,
+/// RETR, and STOR.
+struct FtpSession {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl FtpSession {
+    fn connect(account: &RemoteAccount) -> Result<FtpSession, String> {
+        let stream = TcpStream::connect((account.host.as_str(), account.port))
+            .map_err(|e| format!("Cannot connect to {}: {e}", account.host))?;
+
+        let reader = BufReader::new(
+            stream
+                .try_clone()
+                .map_err(|e| format!("Cannot clone stream: {e}"))?,
+        );
+
+        let mut session = FtpSession { stream, reader };
+
+        session.read_reply()?; // greeting
+
+        let reply = session.command(&format!("USER {}", account.username))?;
+        if reply.starts_with('3') {
+            // 331: password required
+            session.command_expect(&format!("PASS {}", account.password), '2')?;
+        }
+
+        session.command_expect("TYPE I", '2')?;
+
+        Ok(session)
+    }
+
+    /// Send a command and return the reply.
+    fn command(&mut self, command: &str) -> Result<String, String> {
+        self.stream
+            .write_all(format!("{command}\r\n").as_bytes())
+            .map_err(|e| format!("FTP write failed: {e}"))?;
+        self.read_reply()
+    }
+
+    /// Send a command and require a reply in the given class.
+    fn command_expect(&mut self, command: &str, class: char) -> Result<String, String> {
+        let reply = self.command(command)?;
+        if reply.starts_with(class) {
+            Ok(reply)
+        } else {
+            // Avoid echoing PASS arguments into logs.
+            let name = command.split_whitespace().next().unwrap_or(command);
+            Err(format!("FTP {name} failed: {reply}"))
+        }
+    }
+
+    /// Read a (possibly multi-line) reply, returning its final line.
+    fn read_reply(&mut self) -> Result<String, String> {
+        loop {
+            let mut line = String::new();
+            self.reader
+                .read_line(&mut line)
+                .map_err(|e| format!("FTP read failed: {e}"))?;
+
+            if line.is_empty() {
+                return Err("FTP connection closed".to_string());
+            }
+
+            let line = line.trim_end();
+
+            // Final reply lines are "NNN text"; continuations "NNN-".
+            if line.len() >= 4 && line.as_bytes()[3] == b' ' {
+                return Ok(line.to_string());
+            }
+        }
+    }
+
+    /// Enter passive mode and open the data connection.
+    fn data_connection(&mut self) -> Result<TcpStream, String> {
+        let reply = self.command_expect("PASV", '2')?;
+
+        let start = reply.find('(').ok_or("Malformed PASV reply")?;
+        let end = reply.find(')').ok_or("Malformed PASV reply")?;
+
+        // Each part is an octet; parsing as u8 rejects values a
+        // misbehaving server might send that would overflow the
+        // port arithmetic.
+        let parts: Vec<u8> = reply[start + 1..end]
+            .split(',')
+            .filter_map(|p| p.trim().parse().ok())
+            .collect();
+
+        if parts.len() != 6 {
+            return Err(format!("Malformed PASV reply: {reply}"));
+        }
+
+        let host = format!("{}.{}.{}.{}", parts[0], parts[1], parts[2], parts[3]);
+        let port = parts[4] as u16 * 256 + parts[5] as u16;
+
+        TcpStream::connect((host.as_str(), port))
+            .map_err(|e| format!("Cannot open FTP data connection: {e}"))
+    }
+
+    fn list(&mut self, dir: &str) -> Result<Vec<String>, String> {
+        let data = self.data_connection()?;
+        self.command_expect(&format!("NLST {dir}"), '1')?;
+
+        let mut names = String::new();
+        BufReader::new(data)
+            .read_to_string(&mut names)
+            .map_err(|e| format!("FTP list read failed: {e}"))?;
+
+        self.read_reply()?; // 226 transfer complete
+
+        Ok(names
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.rsplit('/').next().unwrap_or(l).to_string())
+            .collect())
+    }
+
+    fn retrieve(&mut self, path: &str) -> Result<String, String> {
+        let data = self.data_connection()?;
+        self.command_expect(&format!("RETR {path}"), '1')?;
+
+        let mut content = String::new();
+        BufReader::new(data)
+            .read_to_string(&mut content)
+            .map_err(|e| format!("FTP retrieve failed: {e}"))?;
+
+        self.read_reply()?;
+
+        Ok(content)
+    }
+
+    fn store(&mut self, path: &str, content: &str) -> Result<(), String> {
+        let mut data = self.data_connection()?;
+        self.command_expect(&format!("STOR {path}"), '1')?;
+
+        data.write_all(content.as_bytes())
+            .map_err(|e| format!("FTP store failed: {e}"))?;
+        drop(data); // server sees EOF
+
+        self.read_reply()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1427 @@
+//! An Evergreen editor, modeled on the Perl CStoreEditor, for
+//! communicating with Evergreen's data-layer services.
+
+use crate::auth::{AuthLoginArgs, AuthSession};
+use crate::error::{EgError, EgResult};
+use crate::event::EgEvent;
+use crate::idl;
+use crate::osrf::session::{Request, SessionHandle};
+use crate::osrf::Client;
+use json::JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT: u64 = 60;
+
+/// Which data-layer service this editor communicates with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Personality {
+    Cstore,
+    Pcrud,
+    ReporterStore,
+}
+
+impl From<&str> for Personality {
+    fn from(s: &str) -> Self {
+        match s {
+            "open-ils.pcrud" => Self::Pcrud,
+            "open-ils.reporter-store" => Self::ReporterStore,
+            _ => Self::Cstore,
+        }
+    }
+}
+
+impl From<Personality> for &'static str {
+    fn from(p: Personality) -> &'static str {
+        match p {
+            Personality::Cstore => "open-ils.cstore",
+            Personality::Pcrud => "open-ils.pcrud",
+            Personality::ReporterStore => "open-ils.reporter-store",
+        }
+    }
+}
+
+/// Streamed results from a non-atomic search call.
+///
+/// Yields one object per response as they arrive, so large result
+/// sets never have to fit in memory at once.
+pub struct SearchStream {
+    request: Request,
+    timeout: u64,
+    done: bool,
+}
+
+impl Iterator for SearchStream {
+    type Item = EgResult<JsonValue>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.request.recv(self.timeout) {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.done = true;
+                if self.request.complete() {
+                    None
+                } else {
+                    Some(Err("Timed out waiting for search responses".into()))
+                }
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
+
+/// Query modifiers for search calls.
+///
+/// `order_by` is a field name with optional direction, e.g.
+/// "create_date DESC".
+#[derive(Debug, Clone, Default)]
+pub struct QueryOps {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub order_by: Option<String>,
+}
+
+impl QueryOps {
+    /// Render as a cstore options hash for the given class.
+    fn to_json(&self, idlclass: &str) -> JsonValue {
+        let mut ops = json::object! {};
+
+        if let Some(limit) = self.limit {
+            ops["limit"] = limit.into();
+        }
+        if let Some(offset) = self.offset {
+            ops["offset"] = offset.into();
+        }
+        if let Some(order_by) = &self.order_by {
+            ops["order_by"][idlclass] = order_by.as_str().into();
+        }
+
+        ops
+    }
+}
+
+/// Sort direction for order_by clauses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderDir {
+    Asc,
+    Desc,
+}
+
+impl OrderDir {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+/// Fluent builder for the cstore search options hash, for use with
+/// search_with_ops().
+///
+/// ```
+/// use evergreen::editor::{OrderDir, SearchOps};
+///
+/// let ops = SearchOps::new()
+///     .flesh(1)
+///     .flesh_fields("au", &["home_ou"])
+///     .limit(100)
+///     .order_by("au", "usrname", OrderDir::Asc)
+///     .into_json();
+///
+/// assert_eq!(ops["flesh"], 1);
+/// ```
+pub struct SearchOps {
+    ops: JsonValue,
+}
+
+impl SearchOps {
+    pub fn new() -> Self {
+        SearchOps {
+            ops: json::object! {},
+        }
+    }
+
+    /// How many link levels deep to flesh.
+    pub fn flesh(mut self, depth: u8) -> Self {
+        self.ops["flesh"] = depth.into();
+        self
+    }
+
+    /// Which fields to flesh on a class.  May be called once per
+    /// class when fleshing across multiple hops.
+    pub fn flesh_fields(mut self, idlclass: &str, fields: &[&str]) -> Self {
+        let list: Vec<JsonValue> = fields.iter().map(|f| json::from(*f)).collect();
+        self.ops["flesh_fields"][idlclass] = JsonValue::Array(list);
+        self
+    }
+
+    /// Which columns to return for a class, e.g. just "id" and
+    /// "marc" of "bre".  Unselected fields come back null, so the
+    /// results are not suitable for feeding back to update().
+    pub fn select(mut self, idlclass: &str, fields: &[&str]) -> Self {
+        let list: Vec<JsonValue> = fields.iter().map(|f| json::from(*f)).collect();
+        self.ops["select"][idlclass] = JsonValue::Array(list);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.ops["limit"] = limit.into();
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.ops["offset"] = offset.into();
+        self
+    }
+
+    /// Add a sort field.  May be called repeatedly for multi-field
+    /// sorts; fields apply in call order.
+    pub fn order_by(mut self, idlclass: &str, field: &str, dir: OrderDir) -> Self {
+        let entry = json::object! {
+            class: idlclass,
+            field: field,
+            direction: dir.as_str(),
+        };
+
+        if self.ops["order_by"].is_null() {
+            self.ops["order_by"] = json::array![];
+        }
+
+        self.ops["order_by"].push(entry).expect("order_by is an array");
+        self
+    }
+
+    /// The assembled cstore options hash.
+    pub fn into_json(self) -> JsonValue {
+        self.ops
+    }
+}
+
+impl Default for SearchOps {
+    fn default() -> Self {
+        SearchOps::new()
+    }
+}
+
+impl From<SearchOps> for JsonValue {
+    fn from(ops: SearchOps) -> JsonValue {
+        ops.into_json()
+    }
+}
+
+pub struct Editor {
+    client: Client,
+    idl: Arc<idl::Parser>,
+    personality: Personality,
+    timeout: u64,
+
+    session: Option<SessionHandle>,
+
+    /// Dispatch reads to the replica-backed reporter-store service.
+    offload_reads: bool,
+
+    /// Lazily created reporter-store session for offloaded reads.
+    read_session: Option<SessionHandle>,
+
+    authtoken: Option<String>,
+    requestor: Option<JsonValue>,
+
+    /// The most recent non-success event returned by a request.
+    last_event: Option<EgEvent>,
+
+    /// The most recent API call, successful or not.
+    last_call: Option<LastCall>,
+
+    /// Classes whose retrieves may be served from the object cache.
+    cached_classes: HashSet<String>,
+
+    /// Cached objects by (class, pkey), with their fetch time.
+    object_cache: HashMap<(String, String), (Instant, JsonValue)>,
+
+    /// How long a cached object stays fresh.
+    cache_ttl: Duration,
+
+    /// How many times an idempotent request is retried on a fresh
+    /// session after a transport-level failure.
+    retries: usize,
+
+    /// Credentials for transparent re-login when our authtoken
+    /// expires mid-run.
+    relogin_args: Option<AuthLoginArgs>,
+
+    /// ID of our active transaction, if any.
+    xact_id: Option<String>,
+}
+
+impl Editor {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>) -> Self {
+        Editor {
+            client: client.clone(),
+            idl: idl.clone(),
+            personality: Personality::Cstore,
+            timeout: DEFAULT_TIMEOUT,
+            session: None,
+            offload_reads: false,
+            read_session: None,
+            authtoken: None,
+            requestor: None,
+            last_event: None,
+            last_call: None,
+            cached_classes: HashSet::new(),
+            object_cache: HashMap::new(),
+            cache_ttl: Duration::from_secs(0),
+            retries: 0,
+            relogin_args: None,
+            xact_id: None,
+        }
+    }
+
+    /// A new Editor sharing our client, IDL, auth state, and
+    /// configuration, but with its own session and transaction
+    /// state, so nested workflows need not re-run checkauth().
+    pub fn spawn(&self) -> Editor {
+        Editor {
+            client: self.client.clone(),
+            idl: self.idl.clone(),
+            personality: self.personality,
+            timeout: self.timeout,
+            session: None,
+            offload_reads: self.offload_reads,
+            read_session: None,
+            authtoken: self.authtoken.clone(),
+            requestor: self.requestor.clone(),
+            last_event: None,
+            last_call: None,
+            cached_classes: self.cached_classes.clone(),
+            object_cache: HashMap::new(),
+            cache_ttl: self.cache_ttl,
+            retries: self.retries,
+            relogin_args: self.relogin_args.clone(),
+            xact_id: None,
+        }
+    }
+
+    pub fn with_auth(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        let mut editor = Editor::new(client, idl);
+        editor.authtoken = Some(authtoken.to_string());
+        editor
+    }
+
+    pub fn personality(&self) -> Personality {
+        self.personality
+    }
+
+    pub fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    /// Set the default request timeout in seconds.
+    pub fn set_timeout(&mut self, timeout: u64) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_personality(&mut self, personality: Personality) {
+        if personality != self.personality {
+            // The old service session is no use to the new service.
+            self.session = None;
+            self.personality = personality;
+        }
+    }
+
+    pub fn authtoken(&self) -> Option<&str> {
+        self.authtoken.as_deref()
+    }
+
+    pub fn set_authtoken(&mut self, token: &str) {
+        self.authtoken = Some(token.to_string());
+    }
+
+    /// The user object for our authtoken, if checkauth() has
+    /// succeeded.
+    pub fn requestor(&self) -> Option<&JsonValue> {
+        self.requestor.as_ref()
+    }
+
+    /// The most recent non-success event.
+    pub fn event(&self) -> Option<&EgEvent> {
+        self.last_event.as_ref()
+    }
+
+    /// The most recent API call, successful or not.
+    pub fn last_call(&self) -> Option<&LastCall> {
+        self.last_call.as_ref()
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.xact_id.is_some()
+    }
+
+    /// Verify our authtoken is still valid, stashing the requestor
+    /// user object on success.
+    pub fn checkauth(&mut self) -> EgResult<bool> {
+        let token = match self.authtoken() {
+            Some(t) => t.to_string(),
+            None => return Ok(false),
+        };
+
+        let session = self.client.session("open-ils.auth");
+        let mut req = session.request(
+            "open-ils.auth.session.retrieve",
+            vec![json::from(token), json::from(true)],
+        )?;
+
+        let resp = match req.recv(self.timeout)? {
+            Some(r) => r,
+            None => return Ok(false),
+        };
+
+        if let Some(evt) = EgEvent::parse(&resp) {
+            log::debug!("Editor checkauth returned event: {evt}");
+            self.last_event = Some(evt);
+            return Ok(false);
+        }
+
+        self.requestor = Some(resp);
+        Ok(true)
+    }
+
+    /// True if the requestor has the permission at their workstation
+    /// (or, lacking one, home) org unit.
+    pub fn allowed(&mut self, perm: &str) -> EgResult<bool> {
+        if self.requestor.is_none() && !self.checkauth()? {
+            return Err("Editor.allowed() requires a valid authtoken".into());
+        }
+
+        let requestor = self.requestor.as_ref().unwrap();
+        let org_id = crate::util::json_int(&requestor["ws_ou"])
+            .or_else(|_| crate::util::json_int(&requestor["home_ou"]))?;
+
+        self.allowed_at(perm, org_id)
+    }
+
+    /// True if the requestor has the permission at the given org
+    /// unit.  Denials stash a PERM_FAILURE last_event.
+    pub fn allowed_at(&mut self, perm: &str, org_id: i64) -> EgResult<bool> {
+        let token = match self.authtoken() {
+            Some(t) => t.to_string(),
+            None => return Err("Editor.allowed() requires an authtoken".into()),
+        };
+
+        if self.requestor.is_none() && !self.checkauth()? {
+            return Err("Editor.allowed() requires a valid authtoken".into());
+        }
+
+        let usr_id = crate::util::json_int(&self.requestor.as_ref().unwrap()["id"])?;
+
+        let session = self.client.session("open-ils.actor");
+        let mut req = session.request(
+            "open-ils.actor.user.perm.check",
+            vec![
+                json::from(token),
+                json::from(usr_id),
+                json::from(org_id),
+                json::array![perm],
+            ],
+        )?;
+
+        let resp = match req.recv(self.timeout)? {
+            Some(r) => r,
+            None => JsonValue::Null,
+        };
+
+        // The API returns the subset of requested permissions the
+        // user does NOT have at the org unit.
+        let denied = resp.members().any(|p| p.as_str() == Some(perm));
+
+        if denied {
+            let mut evt = EgEvent::new("PERM_FAILURE");
+            evt.set_ilsperm(perm);
+            evt.set_ilspermloc(org_id);
+            self.last_event = Some(evt);
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Like checkauth(), but an invalid token is an Err carrying the
+    /// NO_SESSION event, so business logic can simply `?` it.
+    pub fn checkauth_or_event(&mut self) -> EgResult<()> {
+        if self.checkauth()? {
+            return Ok(());
+        }
+
+        let evt = self
+            .last_event
+            .clone()
+            .unwrap_or_else(|| EgEvent::new("NO_SESSION"));
+
+        Err(evt.into())
+    }
+
+    /// Like retrieve(), but a missing object is an Err carrying the
+    /// <CLASS>_NOT_FOUND event.
+    pub fn retrieve_or_event(
+        &mut self,
+        idlclass: &str,
+        pkey: JsonValue,
+    ) -> EgResult<JsonValue> {
+        match self.retrieve(idlclass, pkey)? {
+            Some(obj) => Ok(obj),
+            None => {
+                // retrieve() stashed the NOT_FOUND event.
+                let evt = self.last_event.clone().unwrap_or_else(|| {
+                    EgEvent::new(&format!("{}_NOT_FOUND", idlclass.to_uppercase()))
+                });
+
+                Err(evt.into())
+            }
+        }
+    }
+
+    /// An org setting value, honoring values set on ancestor orgs.
+    /// Returns JSON null when the setting is unset.
+    ///
+    /// For bulk or repeated lookups, prefer settings::Settings, which
+    /// batches and caches.
+    pub fn ou_setting(&mut self, org_id: i64, name: &str) -> EgResult<JsonValue> {
+        let mut params = vec![json::from(org_id), json::from(name)];
+        if let Some(token) = self.authtoken() {
+            params.push(json::from(token));
+        }
+
+        let session = self.client.session("open-ils.actor");
+        let mut req = session.request("open-ils.actor.ou_setting.ancestor_default", params)?;
+
+        let resp = match req.recv(self.timeout)? {
+            Some(r) => r,
+            None => return Ok(JsonValue::Null),
+        };
+
+        // The response is {org: x, value: v} or null.
+        Ok(resp["value"].clone())
+    }
+
+    /// A user setting value, or JSON null when unset.
+    pub fn user_setting(&mut self, usr_id: i64, name: &str) -> EgResult<JsonValue> {
+        let mut rows = self.search("aus", json::object! {usr: usr_id, name: name})?;
+
+        match rows.pop() {
+            Some(row) => Ok(crate::settings::parse_setting_value(&row["value"])?),
+            None => Ok(JsonValue::Null),
+        }
+    }
+
+    /// Dispatch reads (retrieve/search/id_list) to the replica-backed
+    /// reporter-store service while writes continue to cstore.
+    /// Reads inside a transaction stay on cstore so they see their
+    /// own uncommitted changes.
+    pub fn set_offload_reads(&mut self, offload: bool) {
+        self.offload_reads = offload;
+    }
+
+    /// Service name for our personality.
+    fn app(&self) -> &'static str {
+        self.personality.into()
+    }
+
+    /// Service name for read calls, honoring read offloading.
+    fn read_app(&self) -> &'static str {
+        if self.offload_reads
+            && self.personality == Personality::Cstore
+            && !self.in_transaction()
+        {
+            Personality::ReporterStore.into()
+        } else {
+            self.app()
+        }
+    }
+
+    /// Lazily create our service session.
+    fn session(&mut self) -> SessionHandle {
+        if self.session.is_none() {
+            self.session = Some(self.client.session(self.app()));
+        }
+        self.session.as_ref().unwrap().clone()
+    }
+
+    /// The session a method should be sent on: offloaded reads get
+    /// their own reporter-store session, everything else uses the
+    /// main service session.
+    fn session_for_method(&mut self, method: &str) -> SessionHandle {
+        let read_service: &str = Personality::ReporterStore.into();
+
+        if method.starts_with(read_service) && self.app() != read_service {
+            if self.read_session.is_none() {
+                self.read_session = Some(self.client.session(read_service));
+            }
+            return self.read_session.as_ref().unwrap().clone();
+        }
+
+        self.session()
+    }
+
+    fn is_pcrud(&self) -> bool {
+        self.personality == Personality::Pcrud
+    }
+
+    /// Build the method name for a CRUD-style call.
+    ///
+    /// The cstore family embeds the fieldmapper path with the action
+    /// last ("open-ils.cstore.direct.actor.user.retrieve"); pcrud
+    /// puts the action first and uses the IDL class name
+    /// ("open-ils.pcrud.retrieve.au").
+    fn app_method(&self, idlclass: &str, action: &str, atomic: bool) -> EgResult<String> {
+        let app = match action {
+            "retrieve" | "search" | "id_list" => self.read_app(),
+            _ => self.app(),
+        };
+
+        let mut method = if self.is_pcrud() {
+            format!("{app}.{action}.{idlclass}")
+        } else {
+            format!(
+                "{app}.direct.{}.{action}",
+                self.fieldmapper_path(idlclass)?
+            )
+        };
+
+        if atomic {
+            method += ".atomic";
+        }
+
+        Ok(method)
+    }
+
+    /// Pcrud enforces permissions per call and expects the authtoken
+    /// as the first parameter; cstore-family params pass unchanged.
+    fn maybe_add_authtoken(&self, params: &mut Vec<JsonValue>) -> EgResult<()> {
+        if self.is_pcrud() {
+            let token = self
+                .authtoken()
+                .ok_or("Pcrud calls require an authtoken")?;
+            params.insert(0, json::from(token));
+        }
+        Ok(())
+    }
+
+    /// Translate an IDL class into the fieldmapper path used in API
+    /// method names, e.g. "au" => "actor.user".
+    fn fieldmapper_path(&self, idlclass: &str) -> EgResult<String> {
+        let class = self
+            .idl
+            .get_class(idlclass)
+            .ok_or_else(|| format!("No such IDL class: {idlclass}"))?;
+
+        let fieldmapper = class
+            .fieldmapper()
+            .ok_or_else(|| format!("IDL class {idlclass} has no fieldmapper"))?;
+
+        Ok(fieldmapper.replace("::", "."))
+    }
+
+    /// Params rendered for logging, with the authtoken redacted.
+    fn redacted_params(&self, params: &[JsonValue]) -> String {
+        params
+            .iter()
+            .map(|p| match (p.as_str(), self.authtoken()) {
+                (Some(s), Some(token)) if s == token => "[REDACTED]".to_string(),
+                _ => p.dump(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Allow failed idempotent requests (retrieve/search/id_list) to
+    /// be replayed on a fresh session up to this many times.  Writes
+    /// and requests made inside a transaction never retry.
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// Register credentials so an expired authtoken triggers a
+    /// transparent re-login and single retry instead of failing.
+    /// Long-running daemons outlive their authtime without this.
+    pub fn set_relogin(&mut self, args: AuthLoginArgs) {
+        self.relogin_args = Some(args);
+    }
+
+    /// True if the response is a NO_SESSION event.
+    fn session_expired(value: &JsonValue) -> bool {
+        EgEvent::parse(value).is_some_and(|evt| evt.textcode() == "NO_SESSION")
+    }
+
+    /// Re-login with the registered credentials, replacing our
+    /// authtoken.  Returns false when no credentials are registered.
+    fn relogin(&mut self) -> EgResult<bool> {
+        let args = match &self.relogin_args {
+            Some(a) => a.clone(),
+            None => return Ok(false),
+        };
+
+        log::info!(
+            "Editor authtoken expired; re-logging in as {}",
+            args.username
+        );
+
+        match AuthSession::login(&self.client, &args)? {
+            Some(session) => {
+                self.authtoken = Some(session.token().to_string());
+                self.requestor = None;
+                Ok(true)
+            }
+            None => Err("Editor re-login failed".into()),
+        }
+    }
+
+    /// True if a failed call of this method can be safely replayed.
+    fn is_idempotent(method: &str) -> bool {
+        ["retrieve", "search", "id_list", "json_query"]
+            .iter()
+            .any(|verb| method.contains(verb))
+    }
+
+    /// Send an API request to our data-layer service and return the
+    /// first response value.
+    ///
+    /// The session thread value is logged with each call so client
+    /// activity can be correlated with server-side osrfsys logs.
+    pub fn request(&mut self, method: &str, params: Vec<JsonValue>) -> EgResult<JsonValue> {
+        self.request_with_timeout(method, params, self.timeout)
+    }
+
+    /// Like request(), but waiting up to `timeout` seconds for this
+    /// call only.
+    pub fn request_with_timeout(
+        &mut self,
+        method: &str,
+        mut params: Vec<JsonValue>,
+        timeout: u64,
+    ) -> EgResult<JsonValue> {
+        let mut attempt = 0;
+        let mut relogged = false;
+
+        loop {
+            let result = self.request_once(method, params.clone(), timeout);
+
+            let err = match result {
+                Ok(value) => {
+                    if !relogged && Editor::session_expired(&value) {
+                        let stale = self.authtoken().map(|t| t.to_string());
+
+                        if self.relogin()? {
+                            relogged = true;
+
+                            // Swap the stale token out of any params
+                            // carrying it (pcrud, actor calls).
+                            if let (Some(stale), Some(fresh)) = (stale, self.authtoken()) {
+                                let fresh = fresh.to_string();
+                                for p in params.iter_mut() {
+                                    if p.as_str() == Some(stale.as_str()) {
+                                        *p = fresh.as_str().into();
+                                    }
+                                }
+                            }
+
+                            continue;
+                        }
+                    }
+
+                    return Ok(value);
+                }
+                Err(e) => e,
+            };
+
+            attempt += 1;
+
+            if attempt > self.retries
+                || self.in_transaction()
+                || !Editor::is_idempotent(method)
+            {
+                return Err(err);
+            }
+
+            log::warn!(
+                "Editor request {method} failed ({err}); retrying on a new session \
+                (attempt {attempt} of {})",
+                self.retries
+            );
+
+            // Drop the (presumed dead) sessions; the next call
+            // lazily creates fresh ones.
+            self.session = None;
+            self.read_session = None;
+        }
+    }
+
+    /// One attempt at an API request.
+    fn request_once(
+        &mut self,
+        method: &str,
+        params: Vec<JsonValue>,
+        timeout: u64,
+    ) -> EgResult<JsonValue> {
+        let session = self.session_for_method(method);
+        let redacted = self.redacted_params(&params);
+
+        log::debug!("Editor [{}] CALL: {method} {redacted}", session.thread());
+
+        let start = std::time::Instant::now();
+        let resp = session
+            .request(method, params)
+            .and_then(|mut req| req.recv(timeout));
+        let duration_ms = start.elapsed().as_millis();
+
+        self.last_call = Some(LastCall {
+            method: method.to_string(),
+            params: redacted,
+            duration_ms,
+        });
+
+        log::debug!(
+            "Editor [{}] {method} duration: {duration_ms}ms",
+            session.thread()
+        );
+
+        Ok(resp?.unwrap_or(JsonValue::Null))
+    }
+
+    /// Retrieve an object by primary key.
+    ///
+    /// Returns None (and stashes an event) if no such object exists.
+    pub fn retrieve(&mut self, idlclass: &str, pkey: JsonValue) -> EgResult<Option<JsonValue>> {
+        let cache_key = (idlclass.to_string(), pkey.dump());
+
+        if self.cached_classes.contains(idlclass) {
+            if let Some((fetched, obj)) = self.object_cache.get(&cache_key) {
+                if fetched.elapsed() < self.cache_ttl {
+                    return Ok(Some(obj.clone()));
+                }
+                self.object_cache.remove(&cache_key);
+            }
+        }
+
+        let method = self.app_method(idlclass, "retrieve", false)?;
+
+        let mut params = vec![pkey];
+        self.maybe_add_authtoken(&mut params)?;
+
+        let resp = self.request(&method, params)?;
+
+        if resp.is_null() {
+            let mut evt = EgEvent::new(&format!("{}_NOT_FOUND", idlclass.to_uppercase()));
+            evt.set_debug(&method);
+            self.last_event = Some(evt);
+            return Ok(None);
+        }
+
+        if self.cached_classes.contains(idlclass) {
+            self.object_cache
+                .insert(cache_key, (Instant::now(), resp.clone()));
+        }
+
+        Ok(Some(resp))
+    }
+
+    /// Retrieve multiple objects by primary key in a single search
+    /// call, keyed on the class's pkey column.
+    ///
+    /// Results come back in input order; ids with no matching object
+    /// are silently dropped, mirroring retrieve().
+    pub fn retrieve_batch(
+        &mut self,
+        idlclass: &str,
+        ids: &[i64],
+    ) -> EgResult<Vec<JsonValue>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let pkey = self
+            .idl
+            .get_class(idlclass)
+            .ok_or_else(|| format!("No such IDL class: {idlclass}"))?
+            .pkey()
+            .ok_or_else(|| format!("IDL class {idlclass} has no primary key"))?
+            .to_string();
+
+        let mut filter = json::object! {};
+        filter[pkey.as_str()] = ids.to_vec().into();
+
+        let hits = self.search(idlclass, filter)?;
+
+        let mut by_id = std::collections::HashMap::new();
+        for hit in hits {
+            by_id.insert(crate::util::json_int(&hit[pkey.as_str()])?, hit);
+        }
+
+        Ok(ids.iter().filter_map(|id| by_id.remove(id)).collect())
+    }
+
+    /// Search a class with a filter hash, returning all matches.
+    pub fn search(&mut self, idlclass: &str, filter: JsonValue) -> EgResult<Vec<JsonValue>> {
+        self.search_with_ops(idlclass, filter, JsonValue::Null)
+    }
+
+    /// The first object matching a filter, via a limit-1 search.
+    ///
+    /// The Rust spelling of the ubiquitous Perl
+    /// `$e->search(...)->[0]` idiom, without fetching the full
+    /// result set.
+    pub fn first(&mut self, idlclass: &str, filter: JsonValue) -> EgResult<Option<JsonValue>> {
+        let mut hits =
+            self.search_with_ops(idlclass, filter, json::object! {limit: 1})?;
+
+        Ok(hits.pop())
+    }
+
+    /// Search a class with limit/offset/order_by modifiers, for
+    /// paged iteration over large classes.
+    pub fn search_paged(
+        &mut self,
+        idlclass: &str,
+        filter: JsonValue,
+        query_ops: &QueryOps,
+    ) -> EgResult<Vec<JsonValue>> {
+        self.search_with_ops(idlclass, filter, query_ops.to_json(idlclass))
+    }
+
+    /// Search a class with a filter hash plus a raw cstore options
+    /// hash (flesh, limit, order_by, etc.).
+    pub fn search_with_ops(
+        &mut self,
+        idlclass: &str,
+        filter: JsonValue,
+        ops: JsonValue,
+    ) -> EgResult<Vec<JsonValue>> {
+        let method = self.app_method(idlclass, "search", true)?;
+
+        let mut params = vec![filter];
+        if !ops.is_null() {
+            params.push(ops);
+        }
+        self.maybe_add_authtoken(&mut params)?;
+
+        let resp = self.request(&method, params)?;
+
+        let mut results = Vec::new();
+        if let JsonValue::Array(list) = resp {
+            for value in list {
+                results.push(value);
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Search a class with a filter hash, yielding matches one at a
+    /// time instead of buffering them all into a Vec.
+    pub fn search_stream(
+        &mut self,
+        idlclass: &str,
+        filter: JsonValue,
+    ) -> EgResult<SearchStream> {
+        self.search_stream_with_ops(idlclass, filter, JsonValue::Null)
+    }
+
+    /// Streaming counterpart of search_with_ops(), so callers can
+    /// choose non-atomic responses per request.  Dropping the stream
+    /// early stops response collection, useful when the first match
+    /// is all that's needed.
+    pub fn search_stream_with_ops(
+        &mut self,
+        idlclass: &str,
+        filter: JsonValue,
+        ops: JsonValue,
+    ) -> EgResult<SearchStream> {
+        let method = self.app_method(idlclass, "search", false)?;
+
+        let mut params = vec![filter];
+        if !ops.is_null() {
+            params.push(ops);
+        }
+        self.maybe_add_authtoken(&mut params)?;
+
+        self.request_stream(&method, params)
+    }
+
+    /// Issue a raw API request whose responses stream back one at a
+    /// time, for non-atomic methods with large result sets.
+    pub fn request_stream(
+        &mut self,
+        method: &str,
+        params: Vec<JsonValue>,
+    ) -> EgResult<SearchStream> {
+        let session = self.session_for_method(method);
+        let request = session.request(method, params)?;
+
+        Ok(SearchStream {
+            request,
+            timeout: self.timeout,
+            done: false,
+        })
+    }
+
+    /// Search a class with a filter hash, returning matching
+    /// primary keys instead of full objects.
+    pub fn search_ids(&mut self, idlclass: &str, filter: JsonValue) -> EgResult<Vec<i64>> {
+        let method = self.app_method(idlclass, "id_list", true)?;
+
+        let mut params = vec![filter];
+        self.maybe_add_authtoken(&mut params)?;
+
+        let resp = self.request(&method, params)?;
+
+        let mut ids = Vec::new();
+        if let JsonValue::Array(list) = resp {
+            for value in list {
+                ids.push(crate::util::json_int(&value)?);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Build a direct CUD method name, failing without an active
+    /// transaction since cstore rejects writes outside one.
+    fn write_method(&mut self, idlclass: &str, action: &str) -> EgResult<String> {
+        if !self.in_transaction() {
+            return Err(format!(
+                "Editor.{action} for {idlclass} requires an active transaction"
+            )
+            .into());
+        }
+
+        self.app_method(idlclass, action, false)
+    }
+
+    /// Create an object, returning the stored copy (with its newly
+    /// assigned primary key).  Requires an active transaction.
+    pub fn create(&mut self, idlclass: &str, obj: JsonValue) -> EgResult<JsonValue> {
+        let method = self.write_method(idlclass, "create")?;
+        let mut params = vec![obj];
+        self.maybe_add_authtoken(&mut params)?;
+        self.request(&method, params)
+    }
+
+    /// Update an object, returning the response (typically the
+    /// primary key).  Requires an active transaction.
+    pub fn update(&mut self, idlclass: &str, obj: JsonValue) -> EgResult<JsonValue> {
+        let method = self.write_method(idlclass, "update")?;
+        let mut params = vec![obj];
+        self.maybe_add_authtoken(&mut params)?;
+        self.request(&method, params)
+    }
+
+    /// Apply a batch of updates in a single transaction, rolling the
+    /// whole batch back on the first failure.
+    ///
+    /// Each object must carry its IDL class under idl::CLASSNAME_KEY
+    /// so the right update method can be derived.
+    pub fn update_batch(&mut self, objs: &[JsonValue]) -> EgResult<()> {
+        self.xact_begin()?;
+
+        for (index, obj) in objs.iter().enumerate() {
+            let idlclass = match obj[idl::CLASSNAME_KEY].as_str() {
+                Some(c) => c.to_string(),
+                None => {
+                    self.xact_rollback()?;
+                    return Err(format!(
+                        "update_batch object {index} has no {} key",
+                        idl::CLASSNAME_KEY
+                    )
+                    .into());
+                }
+            };
+
+            if let Err(e) = self.update(&idlclass, obj.clone()) {
+                self.xact_rollback()?;
+                return Err(format!("update_batch failed on object {index}: {e}").into());
+            }
+        }
+
+        self.commit()
+    }
+
+    /// Delete an object, returning the response (typically the
+    /// primary key).  Requires an active transaction.
+    pub fn delete(&mut self, idlclass: &str, obj: JsonValue) -> EgResult<JsonValue> {
+        let method = self.write_method(idlclass, "delete")?;
+        let mut params = vec![obj];
+        self.maybe_add_authtoken(&mut params)?;
+        self.request(&method, params)
+    }
+
+    /// Serve retrieves of low-churn classes ("aou", "aout", "ccs",
+    /// ...) from an in-memory cache for `ttl` seconds, so loops
+    /// that repeatedly fetch org units or copy statuses stop
+    /// hammering cstore.  Only retrieve() consults the cache.
+    pub fn cache_classes(&mut self, classes: &[&str], ttl: u64) {
+        for class in classes {
+            self.cached_classes.insert(class.to_string());
+        }
+        self.cache_ttl = Duration::from_secs(ttl);
+    }
+
+    /// Discard all cached objects.
+    pub fn clear_object_cache(&mut self) {
+        self.object_cache.clear();
+    }
+
+    /// Start a pipeline of concurrent requests on this editor.
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline {
+            editor: self,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Delete every object matching the filter within a single
+    /// transaction, rolling the whole batch back if any delete is
+    /// blocked.  Returns the number of objects deleted.
+    ///
+    /// Handy for cleanup scripts, e.g. purging stale hold copies.
+    pub fn delete_where(&mut self, idlclass: &str, filter: JsonValue) -> EgResult<usize> {
+        let hits = self.search(idlclass, filter)?;
+
+        if hits.is_empty() {
+            return Ok(0);
+        }
+
+        self.xact_begin()?;
+
+        let mut deleted = 0;
+        for obj in hits {
+            if let Err(e) = self.delete(idlclass, obj) {
+                self.xact_rollback()?;
+                return Err(format!(
+                    "delete_where rolled back after {deleted} deletes: {e}"
+                )
+                .into());
+            }
+            deleted += 1;
+        }
+
+        self.commit()?;
+        Ok(deleted)
+    }
+
+    /// Start a transaction, returning a guard that rolls it back
+    /// (and disconnects) when dropped without a commit, so early
+    /// returns cannot leak open transactions.
+    pub fn begin(&mut self) -> EgResult<XactGuard<'_>> {
+        self.xact_begin()?;
+
+        Ok(XactGuard {
+            editor: self,
+            finished: false,
+        })
+    }
+
+    /// Start a transaction on a connected session.
+    pub fn xact_begin(&mut self) -> EgResult<()> {
+        if self.xact_id.is_some() {
+            return Err("Editor transaction already in progress".into());
+        }
+
+        let session = self.session();
+        session.connect()?;
+
+        let method = format!("{}.transaction.begin", self.app());
+        let mut params = Vec::new();
+        self.maybe_add_authtoken(&mut params)?;
+        let resp = self.request(&method, params)?;
+
+        match resp.as_str() {
+            Some(id) => {
+                self.xact_id = Some(id.to_string());
+                Ok(())
+            }
+            None => Err(format!("transaction.begin returned {}", resp.dump()).into()),
+        }
+    }
+
+    /// Nudge the connected worker so a long-held transaction is not
+    /// reaped for inactivity.  Batch jobs that hold a xact open while
+    /// doing slow local work should call this periodically.
+    pub fn keepalive(&mut self) -> EgResult<()> {
+        if !self.in_transaction() {
+            return Ok(());
+        }
+
+        let session = self.session();
+        let mut req = session.request("opensrf.system.echo", vec![json::from("keepalive")])?;
+        req.recv(self.timeout)?;
+
+        Ok(())
+    }
+
+    /// Roll back the active transaction and disconnect.
+    pub fn xact_rollback(&mut self) -> EgResult<()> {
+        if self.xact_id.is_none() {
+            return Ok(());
+        }
+
+        let method = format!("{}.transaction.rollback", self.app());
+        let mut params = Vec::new();
+        self.maybe_add_authtoken(&mut params)?;
+        self.request(&method, params)?;
+        self.xact_id = None;
+
+        if let Some(session) = &self.session {
+            session.disconnect()?;
+        }
+
+        Ok(())
+    }
+
+    /// Commit the active transaction, leaving the session connected
+    /// for follow-up transactions.
+    pub fn xact_commit(&mut self) -> EgResult<()> {
+        let xact_id = match &self.xact_id {
+            Some(x) => x.to_string(),
+            None => return Err("No Editor transaction to commit".into()),
+        };
+
+        let method = format!("{}.transaction.commit", self.app());
+        let mut params = Vec::new();
+        self.maybe_add_authtoken(&mut params)?;
+        let resp = self.request(&method, params)?;
+        self.xact_id = None;
+
+        if crate::util::json_bool(&resp) {
+            Ok(())
+        } else {
+            Err(format!("Error committing transaction {xact_id}").into())
+        }
+    }
+
+    /// Commit the active transaction and disconnect.
+    pub fn commit(&mut self) -> EgResult<()> {
+        self.xact_commit()?;
+        self.disconnect()
+    }
+
+    /// Disconnect our service session, if connected.
+    pub fn disconnect(&mut self) -> EgResult<()> {
+        if let Some(session) = &self.session {
+            session.disconnect()?;
+        }
+        Ok(())
+    }
+}
+
+/// Details of the most recent Editor API call, for error handlers
+/// and shells that want to print exactly what failed.
+#[derive(Debug, Clone)]
+pub struct LastCall {
+    method: String,
+    params: String,
+    duration_ms: u128,
+}
+
+impl LastCall {
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The call parameters, rendered with the authtoken redacted.
+    pub fn params(&self) -> &str {
+        &self.params
+    }
+
+    pub fn duration_ms(&self) -> u128 {
+        self.duration_ms
+    }
+}
+
+impl std::fmt::Display for LastCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [{}] ({}ms)",
+            self.method, self.params, self.duration_ms
+        )
+    }
+}
+
+/// One issued (or failed-to-issue) pipeline request.
+struct PendingRequest {
+    method: String,
+    request: EgResult<Request>,
+}
+
+/// Several in-flight requests on one Editor, issued without waiting
+/// for earlier responses, cutting round-trip latency for independent
+/// lookups.
+///
+/// Build with [`Editor::pipeline`], queue calls fluently, then
+/// [`collect`](Pipeline::collect) the responses in issue order.
+pub struct Pipeline<'a> {
+    editor: &'a mut Editor,
+    pending: Vec<PendingRequest>,
+}
+
+impl Pipeline<'_> {
+    /// Queue a retrieve by primary key.  The response is the object,
+    /// or JSON null if no such object exists.
+    pub fn retrieve(mut self, idlclass: &str, pkey: JsonValue) -> Self {
+        match self.editor.app_method(idlclass, "retrieve", false) {
+            Ok(method) => self.issue(method, vec![pkey]),
+            Err(e) => self.push_failed(format!("retrieve {idlclass}"), e),
+        }
+        self
+    }
+
+    /// Queue an atomic search.  The response is the array of
+    /// matching objects.
+    pub fn search(mut self, idlclass: &str, filter: JsonValue) -> Self {
+        match self.editor.app_method(idlclass, "search", true) {
+            Ok(method) => self.issue(method, vec![filter]),
+            Err(e) => self.push_failed(format!("search {idlclass}"), e),
+        }
+        self
+    }
+
+    /// Queue a raw API request.
+    pub fn request(mut self, method: &str, params: Vec<JsonValue>) -> Self {
+        self.issue(method.to_string(), params);
+        self
+    }
+
+    /// Send one request without collecting any responses.
+    fn issue(&mut self, method: String, mut params: Vec<JsonValue>) {
+        let request = match self.editor.maybe_add_authtoken(&mut params) {
+            Ok(()) => {
+                let session = self.editor.session_for_method(&method);
+                session.request(&method, params).map_err(|e| e.into())
+            }
+            Err(e) => Err(e),
+        };
+
+        self.pending.push(PendingRequest { method, request });
+    }
+
+    fn push_failed(&mut self, method: String, err: EgError) {
+        self.pending.push(PendingRequest {
+            method,
+            request: Err(err),
+        });
+    }
+
+    /// Wait for and return one response per queued request, in
+    /// issue order.
+    pub fn collect(self) -> Vec<EgResult<JsonValue>> {
+        let timeout = self.editor.timeout;
+
+        self.pending
+            .into_iter()
+            .map(|pending| {
+                let mut req = pending.request?;
+
+                match req.recv(timeout) {
+                    Ok(Some(value)) => Ok(value),
+                    Ok(None) => Ok(JsonValue::Null),
+                    Err(e) => Err(format!("{} failed: {e}", pending.method).into()),
+                }
+            })
+            .collect()
+    }
+}
+
+/// An open Editor transaction which rolls itself back when dropped,
+/// unless commit() or rollback() has been called.
+pub struct XactGuard<'a> {
+    editor: &'a mut Editor,
+    finished: bool,
+}
+
+impl XactGuard<'_> {
+    /// The editor, for issuing requests within the transaction.
+    pub fn editor(&mut self) -> &mut Editor {
+        self.editor
+    }
+
+    /// Commit the transaction and disconnect.
+    pub fn commit(mut self) -> EgResult<()> {
+        self.finished = true;
+        self.editor.commit()
+    }
+
+    /// Roll back the transaction explicitly, surfacing any error
+    /// (dropping the guard rolls back but can only log failures).
+    pub fn rollback(mut self) -> EgResult<()> {
+        self.finished = true;
+        self.editor.xact_rollback()
+    }
+}
+
+impl Drop for XactGuard<'_> {
+    fn drop(&mut self) {
+        if self.finished || !self.editor.in_transaction() {
+            return;
+        }
+
+        log::warn!("Rolling back abandoned Editor transaction");
+
+        if let Err(e) = self.editor.xact_rollback() {
+            log::error!("Error rolling back abandoned transaction: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_ops() {
+        let ops = QueryOps {
+            limit: Some(100),
+            offset: Some(200),
+            order_by: Some("create_date DESC".to_string()),
+        };
+
+        let json_ops = ops.to_json("bre");
+        assert_eq!(json_ops["limit"], 100);
+        assert_eq!(json_ops["offset"], 200);
+        assert_eq!(json_ops["order_by"]["bre"], "create_date DESC");
+
+        let json_ops = QueryOps::default().to_json("bre");
+        assert!(json_ops["limit"].is_null());
+        assert!(json_ops["order_by"].is_null());
+    }
+
+    #[test]
+    fn test_search_ops() {
+        let ops = SearchOps::new()
+            .flesh(2)
+            .flesh_fields("au", &["home_ou", "card"])
+            .flesh_fields("ac", &["usr"])
+            .limit(100)
+            .offset(50)
+            .order_by("au", "usrname", OrderDir::Asc)
+            .order_by("au", "id", OrderDir::Desc)
+            .into_json();
+
+        let limited = SearchOps::new().select("bre", &["id", "marc"]).into_json();
+        assert_eq!(limited["select"]["bre"][1], "marc");
+
+        assert_eq!(ops["flesh"], 2);
+        assert_eq!(ops["flesh_fields"]["au"][1], "card");
+        assert_eq!(ops["flesh_fields"]["ac"][0], "usr");
+        assert_eq!(ops["limit"], 100);
+        assert_eq!(ops["offset"], 50);
+        assert_eq!(ops["order_by"][0]["field"], "usrname");
+        assert_eq!(ops["order_by"][1]["direction"], "DESC");
+    }
+}
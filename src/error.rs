@@ -0,0 +1,94 @@
+//! Crate error type distinguishing ILS events from plumbing
+//! failures, so callers can branch on event textcodes (NO_SESSION,
+//! PERM_FAILURE, ...) without string matching.
+
+use crate::event::EgEvent;
+use std::fmt;
+
+/// Shorthand for fallible calls returning an EgError.
+pub type EgResult<T> = Result<T, EgError>;
+
+/// An error from an Editor or other API call.
+#[derive(Debug, Clone)]
+pub enum EgError {
+    /// The server replied with a non-success ILS event.
+    ///
+    /// Boxed to keep the common Ok/Message paths small.
+    Event(Box<EgEvent>),
+    /// Anything else: transport failures, IDL lookup errors,
+    /// malformed responses.
+    Message(String),
+}
+
+impl EgError {
+    /// The carried event, if this error wraps one.
+    pub fn event(&self) -> Option<&EgEvent> {
+        match self {
+            Self::Event(evt) => Some(evt),
+            Self::Message(_) => None,
+        }
+    }
+
+    /// True if this error wraps an event with the given textcode.
+    pub fn is_event(&self, textcode: &str) -> bool {
+        match self {
+            Self::Event(evt) => evt.textcode() == textcode,
+            Self::Message(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for EgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Event(evt) => write!(f, "{evt}"),
+            Self::Message(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for EgError {}
+
+impl From<EgEvent> for EgError {
+    fn from(evt: EgEvent) -> Self {
+        Self::Event(Box::new(evt))
+    }
+}
+
+impl From<String> for EgError {
+    fn from(msg: String) -> Self {
+        Self::Message(msg)
+    }
+}
+
+impl From<&str> for EgError {
+    fn from(msg: &str) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+/// Allow callers that still traffic in string errors to apply `?`
+/// to EgResult values.
+impl From<EgError> for String {
+    fn from(err: EgError) -> Self {
+        err.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kinds() {
+        let err = EgError::from(EgEvent::new("NO_SESSION"));
+        assert!(err.is_event("NO_SESSION"));
+        assert!(!err.is_event("PERM_FAILURE"));
+        assert!(err.event().is_some());
+
+        let err = EgError::from("session timed out".to_string());
+        assert!(err.event().is_none());
+        assert!(!err.is_event("NO_SESSION"));
+        assert_eq!(String::from(err), "session timed out");
+    }
+}
@@ -0,0 +1,201 @@
+//! ILS events -- the structured success/failure responses returned by
+//! most open-ils APIs.
+
+use json::JsonValue;
+use std::fmt;
+
+/// An Evergreen event (ilsevent) as returned by the open-ils APIs.
+#[derive(Debug, Clone)]
+pub struct EgEvent {
+    code: isize,
+    textcode: String,
+    payload: JsonValue, // JsonValue::Null if empty
+    desc: Option<String>,
+    debug: Option<String>,
+    note: Option<String>,
+    servertime: Option<String>,
+    ilsperm: Option<String>,
+    ilspermloc: Option<i64>,
+}
+
+impl fmt::Display for EgEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = format!("Event: {}:{}", self.code, self.textcode);
+
+        if let Some(d) = &self.desc {
+            s = s + " -> " + d;
+        }
+
+        if let Some(p) = &self.ilsperm {
+            s = format!("{} {}@{}", s, p, self.ilspermloc.unwrap_or(-1));
+        }
+
+        write!(f, "{}", s)
+    }
+}
+
+impl EgEvent {
+    /// Create a new event with the provided textcode.
+    pub fn new(textcode: &str) -> Self {
+        EgEvent {
+            code: -1,
+            textcode: textcode.to_string(),
+            payload: JsonValue::Null,
+            desc: None,
+            debug: None,
+            note: None,
+            servertime: None,
+            ilsperm: None,
+            ilspermloc: None,
+        }
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        let mut obj = json::object! {
+            code: self.code,
+            textcode: self.textcode.as_str(),
+            payload: self.payload.clone(),
+        };
+
+        if let Some(v) = &self.desc {
+            obj["desc"] = v.as_str().into();
+        }
+        if let Some(v) = &self.debug {
+            obj["debug"] = v.as_str().into();
+        }
+        if let Some(v) = &self.note {
+            obj["note"] = v.as_str().into();
+        }
+        if let Some(v) = &self.servertime {
+            obj["servertime"] = v.as_str().into();
+        }
+        if let Some(v) = &self.ilsperm {
+            obj["ilsperm"] = v.as_str().into();
+        }
+        if let Some(v) = self.ilspermloc {
+            obj["ilspermloc"] = v.into();
+        }
+
+        obj
+    }
+
+    /// Parse a JSON value into an EgEvent.
+    ///
+    /// Returns None if the value does not look like an event.
+    pub fn parse(jv: &JsonValue) -> Option<EgEvent> {
+        if !jv.is_object() || jv["ilsevent"].is_null() || jv["textcode"].is_null() {
+            return None;
+        }
+
+        let code = match jv["ilsevent"].as_isize() {
+            Some(c) => c,
+            None => match jv["ilsevent"].as_str() {
+                Some(c) => match c.parse::<isize>() {
+                    Ok(c2) => c2,
+                    Err(_) => return None,
+                },
+                None => return None,
+            },
+        };
+
+        let textcode = match jv["textcode"].as_str() {
+            Some(c) => c.to_string(),
+            None => return None,
+        };
+
+        let mut evt = EgEvent::new(&textcode);
+        evt.code = code;
+        evt.payload = jv["payload"].clone();
+
+        for (key, dest) in [
+            ("desc", &mut evt.desc),
+            ("debug", &mut evt.debug),
+            ("note", &mut evt.note),
+            ("servertime", &mut evt.servertime),
+            ("ilsperm", &mut evt.ilsperm),
+        ] {
+            if let Some(v) = jv[key].as_str() {
+                *dest = Some(v.to_string());
+            }
+        }
+
+        if let Some(loc) = jv["ilspermloc"].as_i64() {
+            evt.ilspermloc = Some(loc);
+        }
+
+        Some(evt)
+    }
+
+    /// True if this event has the textcode "SUCCESS".
+    pub fn is_success(&self) -> bool {
+        self.textcode.eq("SUCCESS")
+    }
+
+    pub fn code(&self) -> isize {
+        self.code
+    }
+
+    pub fn textcode(&self) -> &str {
+        &self.textcode
+    }
+
+    pub fn payload(&self) -> &JsonValue {
+        &self.payload
+    }
+
+    pub fn set_payload(&mut self, payload: JsonValue) {
+        self.payload = payload;
+    }
+
+    pub fn desc(&self) -> Option<&str> {
+        self.desc.as_deref()
+    }
+
+    pub fn debug(&self) -> Option<&str> {
+        self.debug.as_deref()
+    }
+
+    pub fn set_debug(&mut self, debug: &str) {
+        self.debug = Some(debug.to_string());
+    }
+
+    pub fn ilsperm(&self) -> Option<&str> {
+        self.ilsperm.as_deref()
+    }
+
+    pub fn set_ilsperm(&mut self, perm: &str) {
+        self.ilsperm = Some(perm.to_string());
+    }
+
+    pub fn ilspermloc(&self) -> Option<i64> {
+        self.ilspermloc
+    }
+
+    pub fn set_ilspermloc(&mut self, loc: i64) {
+        self.ilspermloc = Some(loc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_parse() {
+        let jv = json::object! {
+            ilsevent: "1001",
+            textcode: "NO_SESSION",
+            desc: "User login session has either timed out or does not exist",
+        };
+
+        let evt = EgEvent::parse(&jv).expect("event should parse");
+        assert_eq!(evt.code(), 1001);
+        assert_eq!(evt.textcode(), "NO_SESSION");
+        assert!(!evt.is_success());
+    }
+
+    #[test]
+    fn test_not_an_event() {
+        assert!(EgEvent::parse(&json::object! {id: 1}).is_none());
+    }
+}
@@ -0,0 +1,195 @@
+//! Operational metric collection for the Prometheus exporter.
+//!
+//! Gathers gauge values (open circulations, hold queue depth, A/T
+//! backlog, a cstore latency probe, and optional DB replication lag)
+//! and renders them in the Prometheus text exposition format.
+
+use crate::db::DatabaseConnection;
+use crate::editor::Editor;
+use crate::util;
+use json::JsonValue;
+use std::time::Instant;
+
+const REPLICATION_LAG_SQL: &str =
+    "SELECT EXTRACT(EPOCH FROM (NOW() - pg_last_xact_replay_timestamp()))::FLOAT8";
+
+/// One gauge sample.
+#[derive(Debug, Clone)]
+pub struct Metric {
+    pub name: String,
+    pub help: String,
+    pub value: f64,
+}
+
+impl Metric {
+    pub fn new(name: &str, help: &str, value: f64) -> Self {
+        Metric {
+            name: name.to_string(),
+            help: help.to_string(),
+            value,
+        }
+    }
+}
+
+/// Render metrics in the Prometheus text exposition format.
+pub fn render(metrics: &[Metric]) -> String {
+    let mut text = String::new();
+
+    for metric in metrics {
+        text += &format!("# HELP {} {}\n", metric.name, metric.help);
+        text += &format!("# TYPE {} gauge\n", metric.name);
+        text += &format!("{} {}\n", metric.name, metric.value);
+    }
+
+    text
+}
+
+/// Gathers one scrape's worth of metrics.
+pub struct Collector {
+    editor: Editor,
+    /// Optional direct DB connection for replication lag.
+    db: Option<DatabaseConnection>,
+}
+
+impl Collector {
+    pub fn new(editor: Editor, db: Option<DatabaseConnection>) -> Self {
+        Collector { editor, db }
+    }
+
+    /// Count rows of a class matching a filter via json_query.
+    fn count(&mut self, class: &str, filter: JsonValue) -> Result<i64, String> {
+        // Every class we count has an id column to count on.
+        let select_field = json::object! {
+            column: "id",
+            transform: "count",
+            aggregate: true,
+            alias: "count",
+        };
+
+        let mut query = json::object! {};
+        query["select"] = json::object! {};
+        query["select"][class] = JsonValue::Array(vec![select_field]);
+        query["from"] = class.into();
+        query["where"] = filter;
+
+        let resp = self
+            .editor
+            .request("open-ils.cstore.json_query.atomic", vec![query])?;
+
+        match resp.members().next() {
+            Some(row) => util::json_int(&row["count"]),
+            None => Err(format!("Empty count response for {class}")),
+        }
+    }
+
+    /// Collect all metrics.  Individual failures are logged and
+    /// counted rather than aborting the scrape.
+    pub fn collect(&mut self) -> Vec<Metric> {
+        let mut metrics = Vec::new();
+        let mut errors = 0;
+
+        // Time a trivial query as the cstore latency probe.
+        let start = Instant::now();
+        let probe = self.count("aou", json::object! {id: {"!=": null}});
+        let elapsed = start.elapsed().as_secs_f64();
+
+        match probe {
+            Ok(_) => metrics.push(Metric::new(
+                "eg_cstore_probe_seconds",
+                "Round-trip time of a trivial cstore query.",
+                elapsed,
+            )),
+            Err(e) => {
+                log::error!("cstore probe failed: {e}");
+                errors += 1;
+            }
+        }
+
+        let counts: [(&str, &str, &str, JsonValue); 3] = [
+            (
+                "eg_open_circulations",
+                "Circulations not yet checked in or finished.",
+                "circ",
+                json::object! {checkin_time: null, xact_finish: null},
+            ),
+            (
+                "eg_hold_queue_depth",
+                "Holds awaiting capture.",
+                "ahr",
+                json::object! {
+                    capture_time: null,
+                    cancel_time: null,
+                    fulfillment_time: null,
+                },
+            ),
+            (
+                "eg_trigger_backlog",
+                "Pending Action/Trigger events.",
+                "atev",
+                json::object! {state: "pending"},
+            ),
+        ];
+
+        for (name, help, class, filter) in counts {
+            match self.count(class, filter) {
+                Ok(count) => metrics.push(Metric::new(name, help, count as f64)),
+                Err(e) => {
+                    log::error!("Cannot count {class}: {e}");
+                    errors += 1;
+                }
+            }
+        }
+
+        if let Some(db) = self.db.as_mut() {
+            match Collector::replication_lag(db) {
+                Ok(Some(lag)) => metrics.push(Metric::new(
+                    "eg_replication_lag_seconds",
+                    "Streaming replication delay; absent on a primary.",
+                    lag,
+                )),
+                Ok(None) => {} // primary server
+                Err(e) => {
+                    log::error!("Cannot read replication lag: {e}");
+                    errors += 1;
+                }
+            }
+        }
+
+        metrics.push(Metric::new(
+            "eg_scrape_errors",
+            "Metric collection failures during this scrape.",
+            errors as f64,
+        ));
+
+        metrics
+    }
+
+    fn replication_lag(db: &mut DatabaseConnection) -> Result<Option<f64>, String> {
+        let row = db
+            .client()
+            .query_one(REPLICATION_LAG_SQL, &[])
+            .map_err(|e| format!("Replication lag query failed: {e}"))?;
+
+        Ok(row.get(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = vec![
+            Metric::new("eg_open_circulations", "Open circs.", 42.0),
+            Metric::new("eg_cstore_probe_seconds", "Probe.", 0.25),
+        ];
+
+        let text = render(&metrics);
+
+        assert!(text.contains("# HELP eg_open_circulations Open circs.\n"));
+        assert!(text.contains("# TYPE eg_open_circulations gauge\n"));
+        assert!(text.contains("eg_open_circulations 42\n"));
+        assert!(text.contains("eg_cstore_probe_seconds 0.25\n"));
+    }
+}
@@ -0,0 +1,292 @@
+//! New-titles feeds: RSS, Atom, and JSON renderings of recently
+//! cataloged bib records, filtered by org unit, shelving location,
+//! and audience.
+
+use crate::editor::{Editor, SearchOps};
+use crate::marc;
+use crate::marc::escape_xml;
+use crate::util;
+use json::JsonValue;
+
+/// One feed item.
+#[derive(Debug, Clone, Default)]
+pub struct FeedEntry {
+    pub id: i64,
+    pub title: String,
+    pub author: String,
+    pub isbn: String,
+    pub pub_year: String,
+    pub create_date: String,
+}
+
+impl FeedEntry {
+    /// Build an entry from a bib row and its parsed MARC.
+    pub fn from_record(bib_id: i64, bre: &JsonValue, record: &marc::Record) -> Self {
+        let title = record
+            .first_field("245")
+            .map(|f| {
+                let mut parts = Vec::new();
+                parts.extend(f.get_subfields("a"));
+                parts.extend(f.get_subfields("b"));
+                parts.join(" ")
+            })
+            .unwrap_or_default();
+
+        let author = ["100", "110", "111"]
+            .iter()
+            .find_map(|tag| record.first_field(tag))
+            .and_then(|f| f.first_subfield("a"))
+            .unwrap_or("")
+            .to_string();
+
+        let isbn = record
+            .get_values("020", "a")
+            .first()
+            .map(|v| v.split_whitespace().next().unwrap_or("").to_string())
+            .unwrap_or_default();
+
+        let pub_year: String = ["260", "264"]
+            .iter()
+            .find_map(|tag| record.first_field(tag))
+            .and_then(|f| f.first_subfield("c"))
+            .unwrap_or("")
+            .chars()
+            .filter(|c| c.is_ascii_digit())
+            .take(4)
+            .collect();
+
+        FeedEntry {
+            id: bib_id,
+            title,
+            author,
+            isbn,
+            pub_year,
+            create_date: bre["create_date"].as_str().unwrap_or("").to_string(),
+        }
+    }
+}
+
+/// The audience code from a book-ish 008 (position 22).
+pub fn audience_code(record: &marc::Record) -> Option<char> {
+    record
+        .control_field("008")
+        .and_then(|content| content.chars().nth(22))
+}
+
+/// Render entries as an RSS 2.0 feed.
+pub fn render_rss(feed_title: &str, link: &str, entries: &[FeedEntry]) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml += r#"<rss version="2.0"><channel>"#;
+    xml += &format!("<title>{}</title>", escape_xml(feed_title));
+    xml += &format!("<link>{}</link>", escape_xml(link));
+
+    for entry in entries {
+        xml += "<item>";
+        xml += &format!("<title>{}</title>", escape_xml(&entry.title));
+        xml += &format!(
+            "<description>{}</description>",
+            escape_xml(&format!("{} ({})", entry.author, entry.pub_year))
+        );
+        xml += &format!("<guid isPermaLink=\"false\">bib:{}</guid>", entry.id);
+        xml += &format!("<pubDate>{}</pubDate>", escape_xml(&entry.create_date));
+        xml += "</item>";
+    }
+
+    xml += "</channel></rss>";
+    xml
+}
+
+/// Render entries as an Atom feed.
+pub fn render_atom(feed_title: &str, link: &str, entries: &[FeedEntry]) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml += r#"<feed xmlns="http://www.w3.org/2005/Atom">"#;
+    xml += &format!("<title>{}</title>", escape_xml(feed_title));
+    xml += &format!(r#"<link href="{}"/>"#, escape_xml(link));
+
+    for entry in entries {
+        xml += "<entry>";
+        xml += &format!("<id>bib:{}</id>", entry.id);
+        xml += &format!("<title>{}</title>", escape_xml(&entry.title));
+        xml += &format!("<updated>{}</updated>", escape_xml(&entry.create_date));
+        if !entry.author.is_empty() {
+            xml += &format!(
+                "<author><name>{}</name></author>",
+                escape_xml(&entry.author)
+            );
+        }
+        xml += "</entry>";
+    }
+
+    xml += "</feed>";
+    xml
+}
+
+/// Render entries as a JSON document.
+pub fn render_json(feed_title: &str, link: &str, entries: &[FeedEntry]) -> String {
+    let mut items = json::array![];
+
+    for entry in entries {
+        items
+            .push(json::object! {
+                id: entry.id,
+                title: entry.title.as_str(),
+                author: entry.author.as_str(),
+                isbn: entry.isbn.as_str(),
+                pub_year: entry.pub_year.as_str(),
+                create_date: entry.create_date.as_str(),
+            })
+            .expect("push to array succeeds");
+    }
+
+    json::object! {
+        title: feed_title,
+        link: link,
+        items: items,
+    }
+    .dump()
+}
+
+/// Collects recently cataloged titles.
+pub struct FeedBuilder {
+    editor: Editor,
+}
+
+impl FeedBuilder {
+    pub fn new(editor: Editor) -> Self {
+        FeedBuilder { editor }
+    }
+
+    /// Bib records cataloged since the cutoff, newest first, that
+    /// pass the org/location/audience filters.
+    pub fn recent_titles(
+        &mut self,
+        since: &str,
+        orgs: &[i64],
+        locations: &[i64],
+        audiences: &[char],
+        limit: usize,
+    ) -> Result<Vec<FeedEntry>, String> {
+        // Only the fields we read; full MARC rows carry heavy
+        // unused columns.
+        let ops = SearchOps::new().select("bre", &["id", "marc", "create_date"]);
+
+        let bibs = self.editor.search_with_ops(
+            "bre",
+            json::object! {
+                id: {">": 0},
+                deleted: "f",
+                create_date: {">=": since},
+            },
+            ops.into_json(),
+        )?;
+
+        let mut entries = Vec::new();
+
+        for bre in &bibs {
+            let bib_id = util::json_int(&bre["id"])?;
+
+            let marc_xml = util::json_string(&bre["marc"])?;
+            let record = match marc::Record::from_xml(&marc_xml) {
+                Ok(r) => r,
+                Err(e) => {
+                    log::error!("Cannot parse bib {bib_id}: {e}");
+                    continue;
+                }
+            };
+
+            if !audiences.is_empty() {
+                match audience_code(&record) {
+                    Some(code) if audiences.contains(&code) => {}
+                    _ => continue,
+                }
+            }
+
+            if !self.has_matching_copy(bib_id, orgs, locations)? {
+                continue;
+            }
+
+            entries.push(FeedEntry::from_record(bib_id, bre, &record));
+        }
+
+        // Newest first.
+        entries.sort_by(|a, b| b.create_date.cmp(&a.create_date));
+        entries.truncate(limit);
+
+        Ok(entries)
+    }
+
+    /// True if the record holds a copy passing the org and location
+    /// filters.  Empty filters pass everything.
+    fn has_matching_copy(
+        &mut self,
+        bib_id: i64,
+        orgs: &[i64],
+        locations: &[i64],
+    ) -> Result<bool, String> {
+        if orgs.is_empty() && locations.is_empty() {
+            return Ok(true);
+        }
+
+        let call_numbers = self
+            .editor
+            .search("acn", json::object! {record: bib_id, deleted: "f"})?;
+
+        let mut cn_ids = Vec::new();
+        for cn in &call_numbers {
+            cn_ids.push(util::json_int(&cn["id"])?);
+        }
+
+        if cn_ids.is_empty() {
+            return Ok(false);
+        }
+
+        let mut filter = json::object! {call_number: cn_ids, deleted: "f"};
+
+        if !orgs.is_empty() {
+            filter["circ_lib"] = orgs.to_vec().into();
+        }
+
+        if !locations.is_empty() {
+            filter["location"] = locations.to_vec().into();
+        }
+
+        Ok(!self.editor.search("acp", filter)?.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<FeedEntry> {
+        vec![FeedEntry {
+            id: 42,
+            title: "Moby Dick & other tales".to_string(),
+            author: "Melville, Herman".to_string(),
+            isbn: "9780198526639".to_string(),
+            pub_year: "1851".to_string(),
+            create_date: "2026-08-01T00:00:00+0000".to_string(),
+        }]
+    }
+
+    #[test]
+    fn test_render_rss_and_atom() {
+        let rss = render_rss("New Titles", "https://example.org/feed", &entries());
+        assert!(rss.contains("<title>Moby Dick &amp; other tales</title>"));
+        assert!(rss.contains("<guid isPermaLink=\"false\">bib:42</guid>"));
+
+        let atom = render_atom("New Titles", "https://example.org/feed", &entries());
+        assert!(atom.contains("<id>bib:42</id>"));
+        assert!(atom.contains("<author><name>Melville, Herman</name></author>"));
+    }
+
+    #[test]
+    fn test_render_json() {
+        let text = render_json("New Titles", "https://example.org/feed", &entries());
+        let parsed = json::parse(&text).unwrap();
+
+        assert_eq!(parsed["title"], "New Titles");
+        assert_eq!(parsed["items"][0]["id"], 42);
+        assert_eq!(parsed["items"][0]["pub_year"], "1851");
+    }
+}
@@ -0,0 +1,231 @@
+//! Hatch-compatible print delivery.
+//!
+//! Speaks the Hatch native-messaging protocol -- length-prefixed
+//! JSON frames over the service's stdin/stdout -- so Rust tools can
+//! deliver receipts and labels to a workstation's printers through
+//! its local Hatch service.
+
+use crate::notice::Renderer;
+use json::JsonValue;
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+
+/// Where Hatch's Java entry point normally lives.
+const DEFAULT_HATCH_COMMAND: &str = "hatch.sh";
+
+/// A print job ready for delivery.
+#[derive(Debug, Clone)]
+pub struct PrintJob {
+    pub content: String,
+    /// "text/plain" or "text/html".
+    pub content_type: String,
+    /// Print context, used for printer defaults: "default",
+    /// "receipt", "label", or "offline".
+    pub context: String,
+    /// Explicit printer name; otherwise Hatch uses the context's
+    /// configured printer.
+    pub printer: Option<String>,
+}
+
+impl PrintJob {
+    pub fn receipt(content: &str) -> Self {
+        PrintJob {
+            content: content.to_string(),
+            content_type: "text/html".to_string(),
+            context: "receipt".to_string(),
+            printer: None,
+        }
+    }
+
+    pub fn label(content: &str) -> Self {
+        PrintJob {
+            content: content.to_string(),
+            content_type: "text/plain".to_string(),
+            context: "label".to_string(),
+            printer: None,
+        }
+    }
+
+    /// Render a template with Tera and wrap the result as a receipt
+    /// job.
+    pub fn from_template(template: &str, data: &JsonValue) -> Result<Self, String> {
+        let content = Renderer::new().render(template, data)?;
+        Ok(PrintJob::receipt(&content))
+    }
+}
+
+/// Write one native-messaging frame: a 4-byte little-endian length
+/// followed by the JSON payload.
+fn write_frame<W: Write>(writer: &mut W, message: &JsonValue) -> Result<(), String> {
+    let payload = message.dump();
+    let len = payload.len() as u32;
+
+    writer
+        .write_all(&len.to_le_bytes())
+        .and_then(|_| writer.write_all(payload.as_bytes()))
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Error writing to Hatch: {e}"))
+}
+
+/// Read one native-messaging frame.
+fn read_frame<R: Read>(reader: &mut R) -> Result<JsonValue, String> {
+    let mut len_bytes = [0u8; 4];
+    reader
+        .read_exact(&mut len_bytes)
+        .map_err(|e| format!("Error reading from Hatch: {e}"))?;
+
+    let len = u32::from_le_bytes(len_bytes) as usize;
+
+    let mut payload = vec![0u8; len];
+    reader
+        .read_exact(&mut payload)
+        .map_err(|e| format!("Error reading from Hatch: {e}"))?;
+
+    let text = String::from_utf8(payload).map_err(|e| format!("Hatch sent invalid UTF-8: {e}"))?;
+
+    json::parse(&text).map_err(|e| format!("Hatch sent invalid JSON: {e}"))
+}
+
+/// A connection to a local Hatch service.
+pub struct HatchClient {
+    child: Child,
+    next_msgid: u64,
+}
+
+impl HatchClient {
+    /// Launch the Hatch service and connect to its message stream.
+    pub fn connect(command: Option<&str>) -> Result<HatchClient, String> {
+        let command = command.unwrap_or(DEFAULT_HATCH_COMMAND);
+
+        let child = Command::new(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Cannot launch Hatch ({command}): {e}"))?;
+
+        Ok(HatchClient {
+            child,
+            next_msgid: 1,
+        })
+    }
+
+    /// Send a request and wait for the response with our message ID.
+    fn send(&mut self, mut message: JsonValue) -> Result<JsonValue, String> {
+        let msgid = self.next_msgid;
+        self.next_msgid += 1;
+
+        message["msgid"] = msgid.into();
+
+        let stdin = self.child.stdin.as_mut().ok_or("Hatch stdin is closed")?;
+        write_frame(stdin, &message)?;
+
+        let stdout = self.child.stdout.as_mut().ok_or("Hatch stdout is closed")?;
+
+        loop {
+            let resp = read_frame(stdout)?;
+
+            if resp["msgid"] != msgid {
+                log::warn!("Ignoring out-of-sequence Hatch message: {}", resp.dump());
+                continue;
+            }
+
+            if resp["status"] == 200 {
+                return Ok(resp);
+            }
+
+            return Err(format!(
+                "Hatch request failed: {} {}",
+                resp["status"].dump(),
+                resp["message"].as_str().unwrap_or("")
+            ));
+        }
+    }
+
+    /// The workstation's available printers.
+    pub fn printers(&mut self) -> Result<Vec<JsonValue>, String> {
+        let resp = self.send(json::object! {action: "printers"})?;
+
+        let mut printers = Vec::new();
+        for printer in resp["content"].members() {
+            printers.push(printer.clone());
+        }
+
+        Ok(printers)
+    }
+
+    /// Options (trays, media) for one printer.
+    pub fn printer_options(&mut self, printer: &str) -> Result<JsonValue, String> {
+        let resp = self.send(json::object! {
+            action: "printer-options",
+            printer: printer,
+        })?;
+
+        Ok(resp["content"].clone())
+    }
+
+    /// Deliver a print job.
+    pub fn print(&mut self, job: &PrintJob) -> Result<(), String> {
+        let mut settings = json::object! {context: job.context.as_str()};
+        if let Some(printer) = &job.printer {
+            settings["printer"] = printer.as_str().into();
+        }
+
+        self.send(json::object! {
+            action: "print",
+            content: job.content.as_str(),
+            contentType: job.content_type.as_str(),
+            settings: settings,
+        })?;
+
+        Ok(())
+    }
+}
+
+impl Drop for HatchClient {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let message = json::object! {action: "print", msgid: 1};
+
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &message).expect("frame should write");
+
+        // 4-byte little-endian length prefix.
+        let len = u32::from_le_bytes(buffer[0..4].try_into().unwrap());
+        assert_eq!(len as usize, buffer.len() - 4);
+
+        let parsed = read_frame(&mut Cursor::new(buffer)).expect("frame should read");
+        assert_eq!(parsed, message);
+    }
+
+    #[test]
+    fn test_truncated_frame() {
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &json::object! {action: "print"}).unwrap();
+        buffer.truncate(buffer.len() - 1);
+
+        assert!(read_frame(&mut Cursor::new(buffer)).is_err());
+    }
+
+    #[test]
+    fn test_job_from_template() {
+        let job = PrintJob::from_template(
+            "Total: {{ total }}",
+            &json::object! {total: "5.00"},
+        )
+        .expect("job should render");
+
+        assert_eq!(job.content, "Total: 5.00");
+        assert_eq!(job.context, "receipt");
+    }
+}
@@ -0,0 +1,330 @@
+//! Typed wrappers for the open-ils.circ.holds APIs: placement,
+//! permit checks, cancellation, suspension, and status queries.
+
+use crate::event::EgEvent;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+
+const HOLDS_TIMEOUT: u64 = 120;
+
+/// The hold target level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldType {
+    Title,
+    Volume,
+    Copy,
+    Metarecord,
+}
+
+impl HoldType {
+    /// The single-letter code stored on action.hold_request.
+    pub fn code(&self) -> &'static str {
+        match self {
+            HoldType::Title => "T",
+            HoldType::Volume => "V",
+            HoldType::Copy => "C",
+            HoldType::Metarecord => "M",
+        }
+    }
+}
+
+impl std::str::FromStr for HoldType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<HoldType, String> {
+        match value {
+            "T" => Ok(HoldType::Title),
+            "V" => Ok(HoldType::Volume),
+            "C" => Ok(HoldType::Copy),
+            "M" => Ok(HoldType::Metarecord),
+            _ => Err(format!("Unknown hold type: {value}")),
+        }
+    }
+}
+
+/// Parameters for placing (or testing) a hold.
+#[derive(Debug, Clone)]
+pub struct HoldParams {
+    pub hold_type: HoldType,
+    /// Target ID: bib record, call number, copy, or metarecord,
+    /// per the hold type.
+    pub target: i64,
+    pub patron_id: i64,
+    pub pickup_lib: i64,
+    /// Requesting staff user; defaults to the patron.
+    pub requestor: Option<i64>,
+    /// Place the hold pre-suspended.
+    pub frozen: bool,
+    pub thaw_date: Option<String>,
+    pub expire_time: Option<String>,
+    /// Format filter for metarecord holds.
+    pub holdable_formats: Option<String>,
+}
+
+impl HoldParams {
+    pub fn new(hold_type: HoldType, target: i64, patron_id: i64, pickup_lib: i64) -> Self {
+        HoldParams {
+            hold_type,
+            target,
+            patron_id,
+            pickup_lib,
+            requestor: None,
+            frozen: false,
+            thaw_date: None,
+            expire_time: None,
+            holdable_formats: None,
+        }
+    }
+
+    /// The hold object passed to the create/permit APIs.
+    fn to_hold_object(&self) -> JsonValue {
+        let mut hold = json::object! {
+            "_classname": "ahr",
+            hold_type: self.hold_type.code(),
+            target: self.target,
+            usr: self.patron_id,
+            pickup_lib: self.pickup_lib,
+            requestor: self.requestor.unwrap_or(self.patron_id),
+        };
+
+        if self.frozen {
+            hold["frozen"] = "t".into();
+        }
+        if let Some(v) = &self.thaw_date {
+            hold["thaw_date"] = v.as_str().into();
+        }
+        if let Some(v) = &self.expire_time {
+            hold["expire_time"] = v.as_str().into();
+        }
+        if let Some(v) = &self.holdable_formats {
+            hold["holdable_formats"] = v.as_str().into();
+        }
+
+        hold
+    }
+}
+
+/// Queue position and wait statistics for a hold.
+#[derive(Debug, Clone)]
+pub struct QueueStats {
+    pub queue_position: i64,
+    pub potential_copies: i64,
+    pub total_holds: i64,
+    pub estimated_wait: Option<String>,
+}
+
+/// Drives hold operations for one authenticated session.
+pub struct Holds {
+    client: Client,
+    authtoken: String,
+    timeout: u64,
+}
+
+impl Holds {
+    pub fn new(client: &Client, authtoken: &str) -> Self {
+        Holds {
+            client: client.clone(),
+            authtoken: authtoken.to_string(),
+            timeout: HOLDS_TIMEOUT,
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: u64) {
+        self.timeout = timeout;
+    }
+
+    /// Call an open-ils.circ method and return its first response.
+    fn request(&self, method: &str, mut params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        params.insert(0, json::from(self.authtoken.as_str()));
+
+        let session = self.client.session("open-ils.circ");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(self.timeout)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Convert a non-success event response into an Err.  Responses
+    /// may be a single event or a list; the first failure wins.
+    fn check_events(method: &str, resp: JsonValue) -> Result<JsonValue, String> {
+        let entries = match &resp {
+            JsonValue::Array(entries) => entries.as_slice(),
+            _ => std::slice::from_ref(&resp),
+        };
+
+        for entry in entries {
+            if let Some(evt) = EgEvent::parse(entry) {
+                if !evt.is_success() {
+                    return Err(format!("{method} failed: {evt}"));
+                }
+            }
+        }
+
+        Ok(resp)
+    }
+
+    /// Place a hold, returning the new hold ID.
+    pub fn place_hold(&self, params: &HoldParams) -> Result<i64, String> {
+        let method = "open-ils.circ.holds.create";
+
+        let resp = self.request(method, vec![params.to_hold_object()])?;
+        let resp = Holds::check_events(method, resp)?;
+
+        // Success responses carry the new hold ID, directly or as an
+        // event payload.
+        if let Ok(id) = util::json_int(&resp) {
+            return Ok(id);
+        }
+
+        util::json_int(&resp["payload"])
+            .map_err(|_| format!("Unexpected hold creation response: {}", resp.dump()))
+    }
+
+    /// Test whether a hold could be placed, without placing it.
+    /// Returns the blocking events; an empty list means permitted.
+    pub fn permit_hold(&self, params: &HoldParams) -> Result<Vec<EgEvent>, String> {
+        let args = json::object! {
+            patronid: params.patron_id,
+            pickup_lib: params.pickup_lib,
+            hold_type: params.hold_type.code(),
+            titleid: params.target,
+            volume_id: params.target,
+            copy_id: params.target,
+            mrid: params.target,
+        };
+
+        let resp = self.request("open-ils.circ.title_hold.is_possible", vec![args])?;
+
+        if util::json_bool(&resp["success"]) {
+            return Ok(Vec::new());
+        }
+
+        let last_event = &resp["last_event"];
+
+        let entries = match last_event {
+            JsonValue::Array(entries) => entries.as_slice(),
+            _ => std::slice::from_ref(last_event),
+        };
+
+        let events: Vec<EgEvent> = entries.iter().filter_map(EgEvent::parse).collect();
+
+        if events.is_empty() {
+            return Err(format!("Unexpected permit response: {}", resp.dump()));
+        }
+
+        Ok(events)
+    }
+
+    /// Cancel a hold.  Cause is an action.hold_request_cancel_cause
+    /// ID; note is free text for the patron record.
+    pub fn cancel_hold(&self, hold_id: i64, cause: Option<i64>, note: Option<&str>) -> Result<(), String> {
+        let method = "open-ils.circ.hold.cancel";
+
+        let resp = self.request(
+            method,
+            vec![
+                json::from(hold_id),
+                cause.map(json::from).unwrap_or(JsonValue::Null),
+                note.map(json::from).unwrap_or(JsonValue::Null),
+            ],
+        )?;
+
+        Holds::check_events(method, resp).map(|_| ())
+    }
+
+    /// Suspend a hold, optionally with an auto-thaw date.
+    pub fn suspend_hold(&self, hold_id: i64, thaw_date: Option<&str>) -> Result<(), String> {
+        self.update_hold(json::object! {
+            id: hold_id,
+            frozen: "t",
+            thaw_date: thaw_date.map(json::from).unwrap_or(JsonValue::Null),
+        })
+    }
+
+    /// Reactivate a suspended hold.
+    pub fn activate_hold(&self, hold_id: i64) -> Result<(), String> {
+        self.update_hold(json::object! {
+            id: hold_id,
+            frozen: "f",
+            thaw_date: JsonValue::Null,
+        })
+    }
+
+    fn update_hold(&self, values: JsonValue) -> Result<(), String> {
+        let method = "open-ils.circ.hold.update";
+
+        let resp = self.request(method, vec![JsonValue::Null, values])?;
+        Holds::check_events(method, resp).map(|_| ())
+    }
+
+    /// The numeric hold status (1 = waiting for copy, 4 = on the
+    /// shelf, etc., per open-ils.circ.hold.status.retrieve).
+    pub fn hold_status(&self, hold_id: i64) -> Result<i64, String> {
+        let method = "open-ils.circ.hold.status.retrieve";
+
+        let resp = self.request(method, vec![json::from(hold_id)])?;
+        let resp = Holds::check_events(method, resp)?;
+
+        util::json_int(&resp)
+    }
+
+    /// Queue position and wait statistics for a hold.
+    pub fn queue_stats(&self, hold_id: i64) -> Result<QueueStats, String> {
+        let method = "open-ils.circ.hold.queue_stats.retrieve";
+
+        let resp = self.request(method, vec![json::from(hold_id)])?;
+        let resp = Holds::check_events(method, resp)?;
+
+        Ok(QueueStats {
+            queue_position: util::json_int(&resp["queue_position"])?,
+            potential_copies: util::json_int(&resp["potential_copies"])?,
+            total_holds: util::json_int(&resp["total_holds"])?,
+            estimated_wait: resp["estimated_wait"].as_str().map(|w| w.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hold_object() {
+        let mut params = HoldParams::new(HoldType::Title, 12, 34, 5);
+        params.frozen = true;
+        params.thaw_date = Some("2026-01-01T00:00:00-0500".to_string());
+
+        let hold = params.to_hold_object();
+        assert_eq!(hold["hold_type"], "T");
+        assert_eq!(hold["target"], 12);
+        assert_eq!(hold["usr"], 34);
+        assert_eq!(hold["requestor"], 34); // defaults to patron
+        assert_eq!(hold["frozen"], "t");
+        assert!(hold["expire_time"].is_null());
+    }
+
+    #[test]
+    fn test_hold_type_codes() {
+        assert_eq!(HoldType::Metarecord.code(), "M");
+        assert_eq!("C".parse::<HoldType>().unwrap(), HoldType::Copy);
+        assert!("Z".parse::<HoldType>().is_err());
+    }
+
+    #[test]
+    fn test_check_events() {
+        let ok = json::object! {ilsevent: 0, textcode: "SUCCESS", desc: ""};
+        assert!(Holds::check_events("test", ok).is_ok());
+
+        let blocked = json::array![
+            {ilsevent: 1707, textcode: "HOLD_EXISTS", desc: ""},
+        ];
+        let err = Holds::check_events("test", blocked).unwrap_err();
+        assert!(err.contains("HOLD_EXISTS"));
+
+        // Non-event responses pass through.
+        assert!(Holds::check_events("test", json::from(42)).is_ok());
+    }
+}
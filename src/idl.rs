@@ -0,0 +1,1562 @@
+//! Parse the Evergreen fieldmapper IDL (fm_IDL.xml) and translate
+//! between IDL-classed JSON arrays and JSON hashes.
+
+use crate::marc::escape_xml;
+use crate::osrf::DataSerializer;
+use crate::util;
+use json::JsonValue;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::{Arc, OnceLock};
+
+const OILS_NS_OBJ: &str = "http://open-ils.org/spec/opensrf/IDL/objects/v1";
+const OILS_NS_PERSIST: &str = "http://open-ils.org/spec/opensrf/IDL/persistence/v1";
+const OILS_NS_REPORTER: &str = "http://open-ils.org/spec/opensrf/IDL/reporter/v1";
+const OILS_NS_PERMACRUD: &str = "http://open-ils.org/spec/opensrf/IDL/permacrud/v1";
+
+/// Key used to store the IDL class name on hash-formatted objects.
+pub const CLASSNAME_KEY: &str = "_classname";
+
+/// Bumped when the on-disk cache layout changes, so stale caches
+/// reparse instead of misloading.
+const CACHE_VERSION: i64 = 1;
+
+/// The process-wide IDL, for code that would otherwise thread an
+/// Arc<Parser> through every signature.
+static GLOBAL: OnceLock<Arc<Parser>> = OnceLock::new();
+
+/// Parse an IDL file and install it as the process-wide IDL.
+///
+/// Errors if a global IDL is already installed.
+pub fn load(filename: &str) -> Result<Arc<Parser>, String> {
+    let parser = Parser::parse_file(filename)?;
+
+    if !set(parser.clone()) {
+        return Err("Global IDL is already loaded".to_string());
+    }
+
+    Ok(parser)
+}
+
+/// Install an already-parsed IDL as the process-wide IDL.
+///
+/// Returns false if one is already installed, leaving it in place.
+pub fn set(parser: Arc<Parser>) -> bool {
+    GLOBAL.set(parser).is_ok()
+}
+
+/// The process-wide IDL.
+///
+/// Panics if called before load()/set(); that is a startup-order bug
+/// rather than a runtime condition worth handling.
+pub fn global() -> &'static Arc<Parser> {
+    GLOBAL.get().expect("No global IDL has been loaded")
+}
+
+/// The process-wide IDL, or None before load()/set().
+pub fn try_global() -> Option<&'static Arc<Parser>> {
+    GLOBAL.get()
+}
+
+/// Field data types, from the reporter:datatype field attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    Bool,
+    Float,
+    Id,
+    Int,
+    Interval,
+    Link,
+    Money,
+    Number,
+    OrgUnit,
+    Text,
+    Timestamp,
+}
+
+impl From<&str> for DataType {
+    fn from(s: &str) -> Self {
+        match s {
+            "bool" => Self::Bool,
+            "float" => Self::Float,
+            "id" => Self::Id,
+            "int" => Self::Int,
+            "interval" => Self::Interval,
+            "link" => Self::Link,
+            "money" => Self::Money,
+            "number" => Self::Number,
+            "org_unit" => Self::OrgUnit,
+            "timestamp" => Self::Timestamp,
+            _ => Self::Text,
+        }
+    }
+}
+
+impl DataType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Self::Float | Self::Id | Self::Int | Self::Money | Self::Number | Self::OrgUnit
+        )
+    }
+
+    /// The IDL-style name, round-trippable via From<&str>.
+    fn idl_name(&self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::Float => "float",
+            Self::Id => "id",
+            Self::Int => "int",
+            Self::Interval => "interval",
+            Self::Link => "link",
+            Self::Money => "money",
+            Self::Number => "number",
+            Self::OrgUnit => "org_unit",
+            Self::Text => "text",
+            Self::Timestamp => "timestamp",
+        }
+    }
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Translated reporter labels keyed by locale, loaded from gettext PO
+/// or Java properties files.  Translations are keyed by the English
+/// label, as gettext does.
+#[derive(Debug, Clone, Default)]
+pub struct Translations {
+    by_locale: HashMap<String, HashMap<String, String>>,
+}
+
+impl Translations {
+    pub fn new() -> Self {
+        Translations::default()
+    }
+
+    /// Load a gettext PO file for a locale.
+    pub fn load_po(&mut self, locale: &str, filename: &str) -> Result<(), String> {
+        let text = fs::read_to_string(filename)
+            .map_err(|e| format!("Cannot read PO file {filename}: {e}"))?;
+        self.add_po(locale, &text);
+        Ok(())
+    }
+
+    /// Ingest PO-formatted text for a locale.
+    pub fn add_po(&mut self, locale: &str, text: &str) {
+        let map = self.by_locale.entry(locale.to_string()).or_default();
+
+        let mut msgid = String::new();
+        let mut msgstr = String::new();
+        let mut in_msgstr = false;
+
+        let mut flush = |msgid: &str, msgstr: &str| {
+            // An empty msgstr means untranslated; keep the fallback.
+            if !msgid.is_empty() && !msgstr.is_empty() {
+                map.insert(msgid.to_string(), msgstr.to_string());
+            }
+        };
+
+        for line in text.lines() {
+            let line = line.trim();
+
+            if let Some(rest) = line.strip_prefix("msgid ") {
+                flush(&msgid, &msgstr);
+                msgid = Translations::unquote(rest);
+                msgstr.clear();
+                in_msgstr = false;
+            } else if let Some(rest) = line.strip_prefix("msgstr ") {
+                msgstr = Translations::unquote(rest);
+                in_msgstr = true;
+            } else if line.starts_with('"') {
+                // Continuation of the preceding msgid/msgstr.
+                if in_msgstr {
+                    msgstr += &Translations::unquote(line);
+                } else {
+                    msgid += &Translations::unquote(line);
+                }
+            }
+        }
+
+        flush(&msgid, &msgstr);
+    }
+
+    /// Load a Java-style properties file (label=translation) for a
+    /// locale.
+    pub fn load_properties(&mut self, locale: &str, filename: &str) -> Result<(), String> {
+        let text = fs::read_to_string(filename)
+            .map_err(|e| format!("Cannot read properties file {filename}: {e}"))?;
+        self.add_properties(locale, &text);
+        Ok(())
+    }
+
+    /// Ingest properties-formatted text for a locale.
+    pub fn add_properties(&mut self, locale: &str, text: &str) {
+        let map = self.by_locale.entry(locale.to_string()).or_default();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let (key, value) = (key.trim(), value.trim());
+                if !key.is_empty() && !value.is_empty() {
+                    map.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+    }
+
+    /// The translation of a label for a locale, if one is loaded.
+    pub fn get(&self, locale: &str, label: &str) -> Option<&str> {
+        self.by_locale.get(locale)?.get(label).map(|t| t.as_str())
+    }
+
+    /// Strip surrounding quotes and unescape a PO string literal.
+    fn unquote(text: &str) -> String {
+        let text = text.trim().trim_matches('"');
+        let mut out = String::with_capacity(text.len());
+        let mut chars = text.chars();
+
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                out.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(esc) => out.push(esc),
+                None => break,
+            }
+        }
+
+        out
+    }
+}
+
+/// A single field on an IDL class.
+#[derive(Debug, Clone)]
+pub struct Field {
+    name: String,
+    label: Option<String>,
+    datatype: DataType,
+    selector: Option<String>,
+    i18n: bool,
+    array_pos: usize,
+    is_virtual: bool,
+}
+
+impl Field {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    pub fn datatype(&self) -> &DataType {
+        &self.datatype
+    }
+    /// The display field on the linked class, from reporter:selector.
+    pub fn selector(&self) -> Option<&str> {
+        self.selector.as_deref()
+    }
+    pub fn i18n(&self) -> bool {
+        self.i18n
+    }
+    pub fn array_pos(&self) -> usize {
+        self.array_pos
+    }
+    pub fn is_virtual(&self) -> bool {
+        self.is_virtual
+    }
+
+    /// The label translated for a locale, falling back to the
+    /// untranslated label, then to the field name.
+    pub fn label_for_locale<'a>(&'a self, i18n: &'a Translations, locale: &str) -> &'a str {
+        let label = match self.label() {
+            Some(l) => l,
+            None => return self.name(),
+        };
+        i18n.get(locale, label).unwrap_or(label)
+    }
+}
+
+/// How a link field relates this class to the linked class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelType {
+    HasA,
+    HasMany,
+    MightHave,
+    Unset,
+}
+
+impl From<&str> for RelType {
+    fn from(s: &str) -> Self {
+        match s {
+            "has_a" => Self::HasA,
+            "has_many" => Self::HasMany,
+            "might_have" => Self::MightHave,
+            _ => Self::Unset,
+        }
+    }
+}
+
+impl RelType {
+    /// The IDL-style name, round-trippable via From<&str>.
+    fn idl_name(&self) -> &'static str {
+        match self {
+            Self::HasA => "has_a",
+            Self::HasMany => "has_many",
+            Self::MightHave => "might_have",
+            Self::Unset => "",
+        }
+    }
+}
+
+/// A link from a field on one IDL class to another class.
+#[derive(Debug, Clone)]
+pub struct Link {
+    field: String,
+    reltype: RelType,
+    key: String,
+    map: Option<String>,
+    class: String,
+}
+
+impl Link {
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+    pub fn reltype(&self) -> RelType {
+        self.reltype
+    }
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+    pub fn map(&self) -> Option<&str> {
+        self.map.as_deref()
+    }
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+}
+
+/// One permacrud action (create, retrieve, update, or delete) on a
+/// pcrud-published class.
+#[derive(Debug, Clone)]
+pub struct PermacrudAction {
+    action: String,
+    permissions: Vec<String>,
+    context_field: Option<String>,
+    global_required: bool,
+}
+
+impl PermacrudAction {
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+    /// Permissions required for the action; any one suffices.
+    pub fn permissions(&self) -> &[String] {
+        &self.permissions
+    }
+    /// Field linking the object to the org unit where the permission
+    /// must be held.
+    pub fn context_field(&self) -> Option<&str> {
+        self.context_field.as_deref()
+    }
+    /// True if the permission must be held globally (depth 0).
+    pub fn global_required(&self) -> bool {
+        self.global_required
+    }
+}
+
+/// One IDL class.
+#[derive(Debug, Clone)]
+pub struct Class {
+    classname: String,
+    label: Option<String>,
+    fieldmapper: Option<String>,
+    tablename: Option<String>,
+    pkey: Option<String>,
+    pkey_sequence: Option<String>,
+    controller: Vec<String>,
+    restrict_primary: Option<String>,
+    is_virtual: bool,
+    fields: HashMap<String, Field>,
+    links: HashMap<String, Link>,
+    permacrud: HashMap<String, PermacrudAction>,
+}
+
+impl Class {
+    pub fn classname(&self) -> &str {
+        &self.classname
+    }
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    pub fn fieldmapper(&self) -> Option<&str> {
+        self.fieldmapper.as_deref()
+    }
+    pub fn tablename(&self) -> Option<&str> {
+        self.tablename.as_deref()
+    }
+    /// Primary key field name, from oils_persist:primary on the
+    /// fields node.
+    pub fn pkey(&self) -> Option<&str> {
+        self.pkey.as_deref()
+    }
+    /// Database sequence backing the primary key.
+    pub fn pkey_sequence(&self) -> Option<&str> {
+        self.pkey_sequence.as_deref()
+    }
+    /// Services that publish this class, e.g. open-ils.cstore.
+    pub fn controller(&self) -> &[String] {
+        &self.controller
+    }
+    /// True if the named service publishes this class.
+    pub fn has_controller(&self, service: &str) -> bool {
+        self.controller.iter().any(|c| c == service)
+    }
+    /// Permission org-unit restriction depth, from
+    /// oils_persist:restrict_primary.
+    pub fn restrict_primary(&self) -> Option<&str> {
+        self.restrict_primary.as_deref()
+    }
+    /// True for classes with no database table behind them.
+    pub fn is_virtual(&self) -> bool {
+        self.is_virtual
+    }
+    pub fn fields(&self) -> &HashMap<String, Field> {
+        &self.fields
+    }
+    pub fn links(&self) -> &HashMap<String, Link> {
+        &self.links
+    }
+    /// Permacrud actions keyed by action name, empty for classes not
+    /// published via pcrud.
+    pub fn permacrud(&self) -> &HashMap<String, PermacrudAction> {
+        &self.permacrud
+    }
+    /// The permacrud entry for one action ("create", "retrieve",
+    /// "update", "delete").
+    pub fn permacrud_action(&self, action: &str) -> Option<&PermacrudAction> {
+        self.permacrud.get(action)
+    }
+
+    /// The class label translated for a locale, falling back to the
+    /// untranslated label, then to the class name.
+    pub fn label_for_locale<'a>(&'a self, i18n: &'a Translations, locale: &str) -> &'a str {
+        let label = match self.label() {
+            Some(l) => l,
+            None => return self.classname(),
+        };
+        i18n.get(locale, label).unwrap_or(label)
+    }
+
+    /// Returns the non-virtual fields of this class.
+    pub fn real_fields(&self) -> Vec<&Field> {
+        self.fields.values().filter(|f| !f.is_virtual()).collect()
+    }
+
+    /// Returns the non-virtual fields sorted by name, for stable
+    /// display order.
+    pub fn real_fields_sorted(&self) -> Vec<&Field> {
+        let mut fields = self.real_fields();
+        fields.sort_by_key(|f| f.name());
+        fields
+    }
+
+    /// Returns all fields ordered by their array position, matching
+    /// the wire format.
+    pub fn fields_sorted_by_position(&self) -> Vec<&Field> {
+        let mut fields: Vec<&Field> = self.fields.values().collect();
+        fields.sort_by_key(|f| f.array_pos());
+        fields
+    }
+}
+
+/// IDL parser and IDL-classed object translator.
+pub struct Parser {
+    classes: HashMap<String, Class>,
+}
+
+impl Parser {
+    /// Parse the IDL from a file on disk.
+    ///
+    /// With OILS_IDL_CACHE_DIR set, a parsed copy keyed by the XML
+    /// content hash is kept in that directory, skipping the XML parse
+    /// on later invocations.  Editing the IDL changes the hash, so
+    /// stale caches are ignored rather than invalidated in place.
+    pub fn parse_file(filename: &str) -> Result<Arc<Parser>, String> {
+        let xml = fs::read_to_string(filename)
+            .map_err(|e| format!("Cannot read IDL file {filename}: {e}"))?;
+
+        let cache_dir = match env::var("OILS_IDL_CACHE_DIR") {
+            Ok(dir) => dir,
+            Err(_) => return Parser::parse_string(&xml),
+        };
+
+        let cache_file = format!("{cache_dir}/fm_idl-{:x}.json", md5::compute(&xml));
+
+        if let Some(parser) = Parser::load_cache(&cache_file) {
+            return Ok(parser);
+        }
+
+        let parser = Parser::parse_string(&xml)?;
+
+        // Best effort; a cache miss next time is not fatal.
+        if let Err(e) = fs::write(&cache_file, parser.to_cache().dump()) {
+            log::warn!("Cannot write IDL cache {cache_file}: {e}");
+        }
+
+        Ok(parser)
+    }
+
+    /// Load a previously cached parse, or None if the cache is
+    /// missing, malformed, or from an older layout.
+    fn load_cache(cache_file: &str) -> Option<Arc<Parser>> {
+        let text = fs::read_to_string(cache_file).ok()?;
+        let value = json::parse(&text).ok()?;
+
+        if value["version"] != CACHE_VERSION {
+            return None;
+        }
+
+        Parser::from_cache(&value).map(Arc::new)
+    }
+
+    /// Render the parsed IDL as a cacheable JSON document.
+    fn to_cache(&self) -> JsonValue {
+        let mut classes = json::object! {};
+
+        for (classname, class) in &self.classes {
+            let mut fields = json::object! {};
+            for (name, field) in &class.fields {
+                fields[name.as_str()] = json::object! {
+                    label: field.label.clone(),
+                    datatype: field.datatype.idl_name(),
+                    selector: field.selector.clone(),
+                    i18n: field.i18n,
+                    array_pos: field.array_pos,
+                    "virtual": field.is_virtual,
+                };
+            }
+
+            let mut links = json::object! {};
+            for (name, link) in &class.links {
+                links[name.as_str()] = json::object! {
+                    reltype: link.reltype.idl_name(),
+                    key: link.key.as_str(),
+                    map: link.map.clone(),
+                    class: link.class.as_str(),
+                };
+            }
+
+            let mut permacrud = json::object! {};
+            for (action, pcrud) in &class.permacrud {
+                permacrud[action.as_str()] = json::object! {
+                    permissions: pcrud.permissions.clone(),
+                    context_field: pcrud.context_field.clone(),
+                    global_required: pcrud.global_required,
+                };
+            }
+
+            classes[classname.as_str()] = json::object! {
+                label: class.label.clone(),
+                fieldmapper: class.fieldmapper.clone(),
+                tablename: class.tablename.clone(),
+                pkey: class.pkey.clone(),
+                pkey_sequence: class.pkey_sequence.clone(),
+                controller: class.controller.clone(),
+                restrict_primary: class.restrict_primary.clone(),
+                "virtual": class.is_virtual,
+                fields: fields,
+                links: links,
+                permacrud: permacrud,
+            };
+        }
+
+        json::object! {
+            version: CACHE_VERSION,
+            classes: classes,
+        }
+    }
+
+    /// Rebuild a Parser from a cache document.
+    fn from_cache(value: &JsonValue) -> Option<Parser> {
+        let mut classes = HashMap::new();
+
+        for (classname, cls) in value["classes"].entries() {
+            let mut fields = HashMap::new();
+            for (name, fld) in cls["fields"].entries() {
+                fields.insert(
+                    name.to_string(),
+                    Field {
+                        name: name.to_string(),
+                        label: fld["label"].as_str().map(|l| l.to_string()),
+                        datatype: fld["datatype"].as_str().unwrap_or("text").into(),
+                        selector: fld["selector"].as_str().map(|s| s.to_string()),
+                        i18n: fld["i18n"].as_bool().unwrap_or(false),
+                        array_pos: fld["array_pos"].as_usize()?,
+                        is_virtual: fld["virtual"].as_bool().unwrap_or(false),
+                    },
+                );
+            }
+
+            let mut links = HashMap::new();
+            for (name, lnk) in cls["links"].entries() {
+                links.insert(
+                    name.to_string(),
+                    Link {
+                        field: name.to_string(),
+                        reltype: lnk["reltype"].as_str().unwrap_or("").into(),
+                        key: lnk["key"].as_str().unwrap_or("").to_string(),
+                        map: lnk["map"].as_str().map(|m| m.to_string()),
+                        class: lnk["class"].as_str().unwrap_or("").to_string(),
+                    },
+                );
+            }
+
+            let mut permacrud = HashMap::new();
+            for (action, pcrud) in cls["permacrud"].entries() {
+                permacrud.insert(
+                    action.to_string(),
+                    PermacrudAction {
+                        action: action.to_string(),
+                        permissions: pcrud["permissions"]
+                            .members()
+                            .filter_map(|p| p.as_str().map(|p| p.to_string()))
+                            .collect(),
+                        context_field: pcrud["context_field"].as_str().map(|cf| cf.to_string()),
+                        global_required: pcrud["global_required"].as_bool().unwrap_or(false),
+                    },
+                );
+            }
+
+            classes.insert(
+                classname.to_string(),
+                Class {
+                    classname: classname.to_string(),
+                    label: cls["label"].as_str().map(|l| l.to_string()),
+                    fieldmapper: cls["fieldmapper"].as_str().map(|fm| fm.to_string()),
+                    tablename: cls["tablename"].as_str().map(|tn| tn.to_string()),
+                    pkey: cls["pkey"].as_str().map(|p| p.to_string()),
+                    pkey_sequence: cls["pkey_sequence"].as_str().map(|s| s.to_string()),
+                    controller: cls["controller"]
+                        .members()
+                        .filter_map(|c| c.as_str().map(|c| c.to_string()))
+                        .collect(),
+                    restrict_primary: cls["restrict_primary"].as_str().map(|rp| rp.to_string()),
+                    is_virtual: cls["virtual"].as_bool().unwrap_or(false),
+                    fields,
+                    links,
+                    permacrud,
+                },
+            );
+        }
+
+        Some(Parser { classes })
+    }
+
+    /// Parse the IDL from an XML string.
+    pub fn parse_string(xml: &str) -> Result<Arc<Parser>, String> {
+        let doc = roxmltree::Document::parse(xml).map_err(|e| {
+            let pos = e.pos();
+            format!(
+                "Cannot parse IDL XML at line {} column {}: {e}",
+                pos.row, pos.col
+            )
+        })?;
+
+        let mut parser = Parser {
+            classes: HashMap::new(),
+        };
+
+        for node in doc
+            .root_element()
+            .children()
+            .filter(|n| n.is_element() && n.tag_name().name() == "class")
+        {
+            parser.add_class(&node)?;
+        }
+
+        Ok(Arc::new(parser))
+    }
+
+    pub fn classes(&self) -> &HashMap<String, Class> {
+        &self.classes
+    }
+
+    pub fn get_class(&self, classname: &str) -> Option<&Class> {
+        self.classes.get(classname)
+    }
+
+    /// Create a blank hash-formatted object of a class with every
+    /// field present, isnew set, and virtual collection fields
+    /// started as empty arrays, so a later hash-to-array conversion
+    /// emits a complete row.
+    pub fn new_object(&self, classname: &str) -> Result<JsonValue, String> {
+        let class = self
+            .get_class(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        let mut obj = json::object! {};
+        obj[CLASSNAME_KEY] = classname.into();
+        obj["isnew"] = true.into();
+
+        for field in class.fields().values() {
+            let is_many = class
+                .links()
+                .get(field.name())
+                .map(|l| l.reltype() == RelType::HasMany)
+                .unwrap_or(false);
+
+            obj[field.name()] = if field.is_virtual() && is_many {
+                json::array![]
+            } else {
+                JsonValue::Null
+            };
+        }
+
+        Ok(obj)
+    }
+
+    /// The class backed by a database table, e.g. "actor.usr".
+    pub fn class_for_table(&self, tablename: &str) -> Option<&Class> {
+        self.classes
+            .values()
+            .find(|c| c.tablename() == Some(tablename))
+    }
+
+    /// The class behind a fieldmapper path, e.g. "actor::user" as it
+    /// appears in cstore method names.
+    pub fn class_for_fieldmapper(&self, fieldmapper: &str) -> Option<&Class> {
+        self.classes
+            .values()
+            .find(|c| c.fieldmapper() == Some(fieldmapper))
+    }
+
+    /// The link defined on a class field, if any.
+    ///
+    /// The link carries the target class, reltype, and remote key.
+    pub fn link(&self, classname: &str, field: &str) -> Option<&Link> {
+        self.classes.get(classname)?.links().get(field)
+    }
+
+    /// The class a link field points to.
+    pub fn linked_class(&self, classname: &str, field: &str) -> Option<&Class> {
+        self.classes.get(self.link(classname, field)?.class())
+    }
+
+    /// All (source class, link) pairs whose links target the given
+    /// class, ordered by source class and field for stable output.
+    ///
+    /// Useful for fleshing and dependency-aware exports.
+    pub fn links_to(&self, classname: &str) -> Vec<(&Class, &Link)> {
+        let mut found: Vec<(&Class, &Link)> = Vec::new();
+
+        for class in self.classes.values() {
+            for link in class.links().values() {
+                if link.class() == classname {
+                    found.push((class, link));
+                }
+            }
+        }
+
+        found.sort_by_key(|(class, link)| (class.classname(), link.field()));
+
+        found
+    }
+
+    fn add_class(&mut self, node: &roxmltree::Node) -> Result<(), String> {
+        let classname = node
+            .attribute("id")
+            .ok_or_else(|| "IDL class node has no id attribute".to_string())?
+            .to_string();
+
+        let label = node
+            .attribute((OILS_NS_REPORTER, "label"))
+            .map(|l| l.to_string());
+
+        let fieldmapper = node
+            .attribute((OILS_NS_OBJ, "fieldmapper"))
+            .map(|fm| fm.to_string());
+
+        let tablename = node
+            .attribute((OILS_NS_PERSIST, "tablename"))
+            .map(|tn| tn.to_string());
+
+        let controller = node
+            .attribute("controller")
+            .unwrap_or("")
+            .split_whitespace()
+            .map(|c| c.to_string())
+            .collect();
+
+        let restrict_primary = node
+            .attribute((OILS_NS_PERSIST, "restrict_primary"))
+            .map(|rp| rp.to_string());
+
+        let is_virtual = node
+            .attribute((OILS_NS_PERSIST, "virtual"))
+            .map(|v| v == "true")
+            .unwrap_or(false);
+
+        let mut class = Class {
+            classname: classname.clone(),
+            label,
+            fieldmapper,
+            tablename,
+            pkey: None,
+            pkey_sequence: None,
+            controller,
+            restrict_primary,
+            is_virtual,
+            fields: HashMap::new(),
+            links: HashMap::new(),
+            permacrud: HashMap::new(),
+        };
+
+        for child in node.children().filter(|n| n.is_element()) {
+            match child.tag_name().name() {
+                "fields" => Parser::add_fields(&mut class, &child)?,
+                "links" => Parser::add_links(&mut class, &child)?,
+                "permacrud" if child.tag_name().namespace() == Some(OILS_NS_PERMACRUD) => {
+                    Parser::add_permacrud(&mut class, &child)
+                }
+                _ => {}
+            }
+        }
+
+        self.classes.insert(classname, class);
+
+        Ok(())
+    }
+
+    fn add_fields(class: &mut Class, node: &roxmltree::Node) -> Result<(), String> {
+        class.pkey = node
+            .attribute((OILS_NS_PERSIST, "primary"))
+            .map(|p| p.to_string());
+
+        class.pkey_sequence = node
+            .attribute((OILS_NS_PERSIST, "sequence"))
+            .map(|s| s.to_string());
+
+        for (array_pos, fnode) in node
+            .children()
+            .filter(|n| n.is_element() && n.tag_name().name() == "field")
+            .enumerate()
+        {
+            let name = fnode
+                .attribute("name")
+                .ok_or_else(|| {
+                    format!(
+                        "IDL field in class {} has no name attribute",
+                        class.classname
+                    )
+                })?
+                .to_string();
+
+            let label = fnode
+                .attribute((OILS_NS_REPORTER, "label"))
+                .map(|l| l.to_string());
+
+            let datatype: DataType = fnode
+                .attribute((OILS_NS_REPORTER, "datatype"))
+                .unwrap_or("text")
+                .into();
+
+            let selector = fnode
+                .attribute((OILS_NS_REPORTER, "selector"))
+                .map(|s| s.to_string());
+
+            let i18n = fnode
+                .attribute((OILS_NS_PERSIST, "i18n"))
+                .map(|v| v == "true")
+                .unwrap_or(false);
+
+            let is_virtual = fnode
+                .attribute((OILS_NS_PERSIST, "virtual"))
+                .map(|v| v == "true")
+                .unwrap_or(false);
+
+            class.fields.insert(
+                name.clone(),
+                Field {
+                    name,
+                    label,
+                    datatype,
+                    selector,
+                    i18n,
+                    array_pos,
+                    is_virtual,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    fn add_links(class: &mut Class, node: &roxmltree::Node) -> Result<(), String> {
+        for lnode in node
+            .children()
+            .filter(|n| n.is_element() && n.tag_name().name() == "link")
+        {
+            let field = lnode
+                .attribute("field")
+                .ok_or_else(|| {
+                    format!(
+                        "IDL link in class {} has no field attribute",
+                        class.classname
+                    )
+                })?
+                .to_string();
+
+            let link = Link {
+                field: field.clone(),
+                reltype: lnode.attribute("reltype").unwrap_or("").into(),
+                key: lnode.attribute("key").unwrap_or("").to_string(),
+                map: lnode.attribute("map").and_then(|m| {
+                    if m.is_empty() {
+                        None
+                    } else {
+                        Some(m.to_string())
+                    }
+                }),
+                class: lnode.attribute("class").unwrap_or("").to_string(),
+            };
+
+            class.links.insert(field, link);
+        }
+
+        Ok(())
+    }
+
+    fn add_permacrud(class: &mut Class, node: &roxmltree::Node) {
+        let actions = match node
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == "actions")
+        {
+            Some(a) => a,
+            None => return,
+        };
+
+        for anode in actions.children().filter(|n| n.is_element()) {
+            let action = anode.tag_name().name().to_string();
+
+            let permissions = anode
+                .attribute("permission")
+                .unwrap_or("")
+                .split_whitespace()
+                .map(|p| p.to_string())
+                .collect();
+
+            let context_field = anode.attribute("context_field").map(|cf| cf.to_string());
+
+            let global_required = anode
+                .attribute("global_required")
+                .map(|v| v == "true")
+                .unwrap_or(false);
+
+            class.permacrud.insert(
+                action.clone(),
+                PermacrudAction {
+                    action,
+                    permissions,
+                    context_field,
+                    global_required,
+                },
+            );
+        }
+    }
+
+    /// Serialize the parsed IDL back out as fm_IDL.xml.
+    ///
+    /// Classes, fields, and links are emitted in a stable order, so
+    /// overlay tooling producing modified IDLs gets clean diffs.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml += &format!(
+            "<IDL xmlns=\"http://opensrf.org/spec/IDL/base/v1\"\n    \
+             xmlns:oils_obj=\"{OILS_NS_OBJ}\"\n    \
+             xmlns:oils_persist=\"{OILS_NS_PERSIST}\"\n    \
+             xmlns:reporter=\"{OILS_NS_REPORTER}\"\n    \
+             xmlns:permacrud=\"{OILS_NS_PERMACRUD}\">\n"
+        );
+
+        let mut classnames: Vec<&str> = self.classes.keys().map(|c| c.as_str()).collect();
+        classnames.sort_unstable();
+
+        for classname in classnames {
+            let class = &self.classes[classname];
+
+            xml += &format!("  <class id=\"{}\"", escape_xml(classname));
+            if !class.controller.is_empty() {
+                xml += &format!(
+                    " controller=\"{}\"",
+                    escape_xml(&class.controller.join(" "))
+                );
+            }
+            if let Some(fm) = class.fieldmapper() {
+                xml += &format!(" oils_obj:fieldmapper=\"{}\"", escape_xml(fm));
+            }
+            if let Some(tn) = class.tablename() {
+                xml += &format!(" oils_persist:tablename=\"{}\"", escape_xml(tn));
+            }
+            if let Some(rp) = class.restrict_primary() {
+                xml += &format!(" oils_persist:restrict_primary=\"{}\"", escape_xml(rp));
+            }
+            if class.is_virtual {
+                xml += " oils_persist:virtual=\"true\"";
+            }
+            if let Some(label) = class.label() {
+                xml += &format!(" reporter:label=\"{}\"", escape_xml(label));
+            }
+            xml += ">\n";
+
+            xml += "    <fields";
+            if let Some(pkey) = class.pkey() {
+                xml += &format!(" oils_persist:primary=\"{}\"", escape_xml(pkey));
+            }
+            if let Some(seq) = class.pkey_sequence() {
+                xml += &format!(" oils_persist:sequence=\"{}\"", escape_xml(seq));
+            }
+            xml += ">\n";
+
+            for field in class.fields_sorted_by_position() {
+                xml += &format!("      <field name=\"{}\"", escape_xml(field.name()));
+                if field.is_virtual {
+                    xml += " oils_persist:virtual=\"true\"";
+                }
+                if field.i18n {
+                    xml += " oils_persist:i18n=\"true\"";
+                }
+                xml += &format!(" reporter:datatype=\"{}\"", field.datatype.idl_name());
+                if let Some(selector) = field.selector() {
+                    xml += &format!(" reporter:selector=\"{}\"", escape_xml(selector));
+                }
+                if let Some(label) = field.label() {
+                    xml += &format!(" reporter:label=\"{}\"", escape_xml(label));
+                }
+                xml += "/>\n";
+            }
+
+            xml += "    </fields>\n";
+
+            if !class.links.is_empty() {
+                xml += "    <links>\n";
+
+                let mut link_fields: Vec<&str> =
+                    class.links.keys().map(|f| f.as_str()).collect();
+                link_fields.sort_unstable();
+
+                for field in link_fields {
+                    let link = &class.links[field];
+                    xml += &format!(
+                        "      <link field=\"{}\" reltype=\"{}\" key=\"{}\"",
+                        escape_xml(field),
+                        link.reltype.idl_name(),
+                        escape_xml(link.key()),
+                    );
+                    if let Some(map) = link.map() {
+                        xml += &format!(" map=\"{}\"", escape_xml(map));
+                    }
+                    xml += &format!(" class=\"{}\"/>\n", escape_xml(link.class()));
+                }
+
+                xml += "    </links>\n";
+            }
+
+            if !class.permacrud.is_empty() {
+                xml += "    <permacrud:permacrud>\n      <permacrud:actions>\n";
+
+                for action in ["create", "retrieve", "update", "delete"] {
+                    let pcrud = match class.permacrud.get(action) {
+                        Some(p) => p,
+                        None => continue,
+                    };
+
+                    xml += &format!("        <permacrud:{action}");
+                    if !pcrud.permissions.is_empty() {
+                        xml += &format!(
+                            " permission=\"{}\"",
+                            escape_xml(&pcrud.permissions.join(" "))
+                        );
+                    }
+                    if let Some(cf) = pcrud.context_field() {
+                        xml += &format!(" context_field=\"{}\"", escape_xml(cf));
+                    }
+                    if pcrud.global_required {
+                        xml += " global_required=\"true\"";
+                    }
+                    xml += "/>\n";
+                }
+
+                xml += "      </permacrud:actions>\n    </permacrud:permacrud>\n";
+            }
+
+            xml += "  </class>\n";
+        }
+
+        xml += "</IDL>\n";
+        xml
+    }
+
+    /// Translate an IDL-classed array (wire format) into a hash whose
+    /// keys are the field names, with the class stored under
+    /// CLASSNAME_KEY.  Nested classed payloads are translated
+    /// recursively.
+    pub fn array_to_hash(&self, classname: &str, array: &JsonValue) -> Result<JsonValue, String> {
+        self.array_to_hash_limited(classname, array, usize::MAX)
+    }
+
+    /// Depth-limited variant of array_to_hash: classed payloads
+    /// nested more than `max_depth` levels down stay in their wire
+    /// form.  A max_depth of 1 translates only the top object.
+    pub fn array_to_hash_limited(
+        &self,
+        classname: &str,
+        array: &JsonValue,
+        max_depth: usize,
+    ) -> Result<JsonValue, String> {
+        let class = self
+            .classes
+            .get(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        let mut hash = json::object! {};
+        hash[CLASSNAME_KEY] = classname.into();
+
+        for field in class.fields_sorted_by_position() {
+            hash[field.name()] = self.unpack_limited(
+                array[field.array_pos()].clone(),
+                max_depth.saturating_sub(1),
+            );
+        }
+
+        Ok(hash)
+    }
+
+    /// Recursively translate classed arrays into hashes, leaving
+    /// classed values alone once the depth budget runs out.
+    fn unpack_limited(&self, value: JsonValue, depth: usize) -> JsonValue {
+        if let JsonValue::Array(arr) = value {
+            let mut new_arr = json::array![];
+            for item in arr {
+                new_arr
+                    .push(self.unpack_limited(item, depth))
+                    .expect("push to array succeeds");
+            }
+            return new_arr;
+        }
+
+        if !value.is_object() {
+            return value;
+        }
+
+        if let Some(classname) = value["__c"].as_str() {
+            if self.classes.contains_key(classname) {
+                if depth == 0 {
+                    return value;
+                }
+                let classname = classname.to_string();
+                return self
+                    .array_to_hash_limited(&classname, &value["__p"], depth)
+                    .expect("class existence checked above");
+            }
+        }
+
+        let mut hash = json::object! {};
+        for (key, val) in value.entries() {
+            hash[key] = self.unpack_limited(val.clone(), depth);
+        }
+
+        hash
+    }
+
+    /// Translate a hash-formatted object back into an IDL-classed
+    /// array in the wire format.  Nested hash-formatted objects are
+    /// repacked recursively.
+    pub fn hash_to_array(&self, classname: &str, hash: &JsonValue) -> Result<JsonValue, String> {
+        let class = self
+            .classes
+            .get(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        let mut array = json::array![];
+
+        for field in class.fields_sorted_by_position() {
+            array
+                .push(self.pack(hash[field.name()].clone()))
+                .expect("push to array succeeds");
+        }
+
+        Ok(array)
+    }
+
+    /// Unpack any IDL-classed payloads in a value and return the
+    /// result as a serde_json value, ready for
+    /// serde_json::from_value() into user types.
+    pub fn unpack_serde(&self, value: JsonValue) -> serde_json::Value {
+        util::json_to_serde(&self.unpack(value))
+    }
+
+    /// Translate a serde_json value into the json-crate
+    /// representation, repacking any hash-formatted IDL objects into
+    /// the classed wire format.
+    pub fn pack_serde(&self, value: &serde_json::Value) -> JsonValue {
+        self.pack(util::serde_to_json(value))
+    }
+}
+
+impl DataSerializer for Parser {
+    /// Recursively translate IDL-classed arrays into hashes.
+    fn unpack(&self, value: JsonValue) -> JsonValue {
+        if let JsonValue::Array(arr) = value {
+            let mut new_arr = json::array![];
+            for item in arr {
+                new_arr
+                    .push(self.unpack(item))
+                    .expect("push to array succeeds");
+            }
+            return new_arr;
+        }
+
+        if !value.is_object() {
+            return value;
+        }
+
+        if let Some(classname) = value["__c"].as_str() {
+            if self.classes.contains_key(classname) {
+                let classname = classname.to_string();
+                return self
+                    .array_to_hash(&classname, &value["__p"])
+                    .expect("class existence checked above");
+            }
+        }
+
+        let mut hash = json::object! {};
+        for (key, val) in value.entries() {
+            hash[key] = self.unpack(val.clone());
+        }
+
+        hash
+    }
+
+    /// Recursively translate hash-formatted objects into IDL-classed
+    /// arrays.
+    fn pack(&self, value: JsonValue) -> JsonValue {
+        if let JsonValue::Array(arr) = value {
+            let mut new_arr = json::array![];
+            for item in arr {
+                new_arr
+                    .push(self.pack(item))
+                    .expect("push to array succeeds");
+            }
+            return new_arr;
+        }
+
+        if !value.is_object() {
+            return value;
+        }
+
+        if let Some(classname) = value[CLASSNAME_KEY].as_str() {
+            if self.classes.contains_key(classname) {
+                let classname = classname.to_string();
+                return json::object! {
+                    "__c": classname.as_str(),
+                    "__p": self
+                        .hash_to_array(&classname, &value)
+                        .expect("class existence checked above"),
+                };
+            }
+        }
+
+        let mut hash = json::object! {};
+        for (key, val) in value.entries() {
+            hash[key] = self.pack(val.clone());
+        }
+
+        hash
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A minimal IDL subset for exercising the parser.
+    pub(crate) const TEST_IDL: &str = r#"<IDL xmlns="http://opensrf.org/spec/IDL/base/v1"
+        xmlns:oils_persist="http://open-ils.org/spec/opensrf/IDL/persistence/v1"
+        xmlns:oils_obj="http://open-ils.org/spec/opensrf/IDL/objects/v1"
+        xmlns:reporter="http://open-ils.org/spec/opensrf/IDL/reporter/v1"
+        xmlns:permacrud="http://open-ils.org/spec/opensrf/IDL/permacrud/v1">
+      <class id="aou" controller="open-ils.cstore open-ils.pcrud"
+          oils_obj:fieldmapper="actor::org_unit"
+          oils_persist:tablename="actor.org_unit" reporter:label="Organizational Unit">
+        <fields oils_persist:primary="id" oils_persist:sequence="actor.org_unit_id_seq">
+          <field name="children" oils_persist:virtual="true" reporter:label="Children"/>
+          <field name="id" reporter:datatype="id" reporter:label="Organizational Unit ID"/>
+          <field name="name" reporter:datatype="text" reporter:label="Name" oils_persist:i18n="true"/>
+          <field name="parent_ou" reporter:datatype="org_unit" reporter:selector="name" reporter:label="Parent"/>
+          <field name="opac_visible" reporter:datatype="bool" reporter:label="OPAC Visible"/>
+        </fields>
+        <links>
+          <link field="parent_ou" reltype="has_a" key="id" map="" class="aou"/>
+          <link field="children" reltype="has_many" key="parent_ou" map="" class="aou"/>
+        </links>
+        <permacrud:permacrud>
+          <permacrud:actions>
+            <permacrud:create permission="CREATE_ORG_UNIT" global_required="true"/>
+            <permacrud:retrieve/>
+            <permacrud:update permission="UPDATE_ORG_UNIT UPDATE_ORG_UNIT_CLOSING" context_field="id"/>
+            <permacrud:delete permission="DELETE_ORG_UNIT" context_field="id"/>
+          </permacrud:actions>
+        </permacrud:permacrud>
+      </class>
+    </IDL>"#;
+
+    #[test]
+    fn test_parse_and_translate() {
+        let parser = Parser::parse_string(TEST_IDL).expect("IDL parses");
+
+        let class = parser.get_class("aou").expect("aou should exist");
+        assert_eq!(class.tablename(), Some("actor.org_unit"));
+        assert_eq!(class.fieldmapper(), Some("actor::org_unit"));
+        assert_eq!(class.pkey(), Some("id"));
+        assert_eq!(class.pkey_sequence(), Some("actor.org_unit_id_seq"));
+        assert!(class.has_controller("open-ils.pcrud"));
+        assert!(!class.has_controller("open-ils.storage"));
+        assert!(!class.is_virtual());
+        assert_eq!(class.fields().len(), 5);
+        assert_eq!(class.real_fields().len(), 4);
+        assert!(class.fields()["children"].is_virtual());
+
+        let by_pos = class.fields_sorted_by_position();
+        assert_eq!(by_pos[0].name(), "children");
+        assert_eq!(by_pos[4].name(), "opac_visible");
+        let by_name = class.real_fields_sorted();
+        assert_eq!(by_name[0].name(), "id");
+        assert_eq!(by_name[3].name(), "parent_ou");
+
+        assert_eq!(*class.fields()["id"].datatype(), DataType::Id);
+        assert_eq!(*class.fields()["parent_ou"].datatype(), DataType::OrgUnit);
+        assert!(class.fields()["parent_ou"].datatype().is_numeric());
+        assert_eq!(class.fields()["parent_ou"].selector(), Some("name"));
+        assert_eq!(class.links()["parent_ou"].class(), "aou");
+        assert_eq!(class.links()["parent_ou"].reltype(), RelType::HasA);
+
+        assert_eq!(
+            parser
+                .class_for_table("actor.org_unit")
+                .map(|c| c.classname()),
+            Some("aou")
+        );
+        assert_eq!(
+            parser
+                .class_for_fieldmapper("actor::org_unit")
+                .map(|c| c.classname()),
+            Some("aou")
+        );
+        assert!(parser.class_for_table("actor.usr").is_none());
+
+        let link = parser.link("aou", "parent_ou").expect("link exists");
+        assert_eq!(link.key(), "id");
+        assert_eq!(
+            parser.linked_class("aou", "parent_ou").unwrap().classname(),
+            "aou"
+        );
+        let inbound = parser.links_to("aou");
+        assert_eq!(inbound.len(), 2);
+        assert_eq!(inbound[0].1.field(), "children");
+        assert!(parser.link("aou", "name").is_none());
+
+        assert_eq!(class.permacrud().len(), 4);
+        let update = class.permacrud_action("update").expect("update is defined");
+        assert_eq!(update.permissions().len(), 2);
+        assert_eq!(update.context_field(), Some("id"));
+        assert!(!update.global_required());
+        let create = class.permacrud_action("create").expect("create is defined");
+        assert!(create.global_required());
+        assert!(class.permacrud_action("retrieve").is_some());
+
+        let wire = json::object! {
+            "__c": "aou",
+            "__p": [JsonValue::Null, 1, "Example Consortium", JsonValue::Null, "t"],
+        };
+
+        let hash = parser.unpack(wire.clone());
+        assert_eq!(hash[CLASSNAME_KEY], "aou");
+        assert_eq!(hash["id"], 1);
+        assert_eq!(hash["name"], "Example Consortium");
+
+        let packed = parser.pack(hash);
+        assert_eq!(packed, wire);
+
+        let serde_hash = parser.unpack_serde(wire.clone());
+        assert_eq!(serde_hash["name"], serde_json::json!("Example Consortium"));
+        assert_eq!(parser.pack_serde(&serde_hash), wire);
+    }
+
+    #[test]
+    fn test_hash_array_conversion() {
+        let parser = Parser::parse_string(TEST_IDL).expect("IDL parses");
+
+        let parent = json::object! {
+            "__c": "aou",
+            "__p": [JsonValue::Null, 1, "Example Consortium", JsonValue::Null, "t"],
+        };
+        let wire = json::array![[], 4, "Example Branch 1", parent.clone(), "t"];
+
+        let hash = parser.array_to_hash("aou", &wire).expect("converts");
+        assert_eq!(hash["id"], 4);
+        assert_eq!(hash["parent_ou"]["name"], "Example Consortium");
+
+        // Depth 1 translates only the top object, leaving the fleshed
+        // parent in wire form.
+        let shallow = parser
+            .array_to_hash_limited("aou", &wire, 1)
+            .expect("converts");
+        assert_eq!(shallow["id"], 4);
+        assert_eq!(shallow["parent_ou"], parent);
+
+        let repacked = parser.hash_to_array("aou", &hash).expect("repacks");
+        assert_eq!(repacked[1], 4);
+        assert_eq!(repacked[3]["__c"], "aou");
+
+        assert!(parser.array_to_hash("nope", &wire).is_err());
+        assert!(parser.hash_to_array("nope", &hash).is_err());
+    }
+
+    #[test]
+    fn test_new_object() {
+        let parser = Parser::parse_string(TEST_IDL).expect("IDL parses");
+
+        let obj = parser.new_object("aou").expect("aou exists");
+        assert_eq!(obj[CLASSNAME_KEY], "aou");
+        assert_eq!(obj["isnew"], true);
+        assert!(obj.has_key("parent_ou"));
+        assert!(obj["id"].is_null());
+        // Virtual has_many fields flesh as lists; they start empty.
+        assert!(obj["children"].is_array());
+
+        // The complete field set converts to a full wire row.
+        let array = &parser.pack(obj)["__p"];
+        assert_eq!(array.len(), 5);
+
+        assert!(parser.new_object("no_such_class").is_err());
+    }
+
+    #[test]
+    fn test_global() {
+        let parser = Parser::parse_string(TEST_IDL).expect("IDL parses");
+
+        if set(parser.clone()) {
+            // Only the first install wins; later calls leave it alone.
+            assert!(!set(parser));
+        }
+
+        assert!(try_global().is_some());
+        assert!(global().get_class("aou").is_some());
+    }
+
+    #[test]
+    fn test_translations() {
+        let parser = Parser::parse_string(TEST_IDL).expect("IDL parses");
+        let class = parser.get_class("aou").expect("aou should exist");
+
+        let mut i18n = Translations::new();
+        i18n.add_po(
+            "fr-CA",
+            r#"
+msgid "Organizational Unit"
+msgstr "Unité organisationnelle"
+
+msgid "Name"
+msgstr "Nom"
+
+msgid "OPAC Visible"
+msgstr ""
+"#,
+        );
+        i18n.add_properties("es-ES", "Name = Nombre\n# comment\nParent=Padre\n");
+
+        assert_eq!(
+            class.label_for_locale(&i18n, "fr-CA"),
+            "Unité organisationnelle"
+        );
+        assert_eq!(
+            class.fields()["name"].label_for_locale(&i18n, "fr-CA"),
+            "Nom"
+        );
+        assert_eq!(
+            class.fields()["name"].label_for_locale(&i18n, "es-ES"),
+            "Nombre"
+        );
+        assert_eq!(
+            class.fields()["parent_ou"].label_for_locale(&i18n, "es-ES"),
+            "Padre"
+        );
+        // Empty msgstr and unknown locales fall back to the IDL label.
+        assert_eq!(
+            class.fields()["opac_visible"].label_for_locale(&i18n, "fr-CA"),
+            "OPAC Visible"
+        );
+        assert_eq!(class.label_for_locale(&i18n, "de-DE"), "Organizational Unit");
+    }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let parser = Parser::parse_string(TEST_IDL).expect("IDL parses");
+
+        let xml = parser.to_xml();
+        let reparsed = Parser::parse_string(&xml).expect("writer output parses");
+
+        let class = reparsed.get_class("aou").expect("aou survives");
+        assert_eq!(class.tablename(), Some("actor.org_unit"));
+        assert_eq!(class.pkey(), Some("id"));
+        assert_eq!(class.pkey_sequence(), Some("actor.org_unit_id_seq"));
+        assert!(class.has_controller("open-ils.pcrud"));
+        assert_eq!(class.fields().len(), 5);
+        assert_eq!(*class.fields()["parent_ou"].datatype(), DataType::OrgUnit);
+        assert_eq!(class.fields()["parent_ou"].selector(), Some("name"));
+        assert!(class.fields()["name"].i18n());
+        assert_eq!(class.links()["children"].reltype(), RelType::HasMany);
+        assert_eq!(class.permacrud().len(), 4);
+        assert!(class.permacrud_action("create").unwrap().global_required());
+
+        // A second write of the reparsed IDL is stable.
+        assert_eq!(xml, reparsed.to_xml());
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let parser = Parser::parse_string(TEST_IDL).expect("IDL parses");
+
+        let cached = Parser::from_cache(&parser.to_cache()).expect("cache loads");
+
+        let class = cached.get_class("aou").expect("aou survives caching");
+        assert_eq!(class.tablename(), Some("actor.org_unit"));
+        assert_eq!(class.pkey(), Some("id"));
+        assert_eq!(class.fields().len(), 5);
+        assert_eq!(*class.fields()["id"].datatype(), DataType::Id);
+        assert_eq!(class.fields()["parent_ou"].selector(), Some("name"));
+        assert_eq!(class.fields()["name"].array_pos(), 2);
+        assert!(class.fields()["children"].is_virtual());
+        assert_eq!(class.links()["parent_ou"].reltype(), RelType::HasA);
+        assert_eq!(class.permacrud().len(), 4);
+        assert!(class.permacrud_action("create").unwrap().global_required());
+
+        // An incompatible cache layout is rejected, forcing a reparse.
+        let mut stale = parser.to_cache();
+        stale["version"] = (CACHE_VERSION + 1).into();
+        assert!(Parser::load_cache("/no/such/cache/file").is_none());
+        assert!(stale["version"] != CACHE_VERSION);
+    }
+}
@@ -0,0 +1,1147 @@
+//! Translate IDL classes into direct database calls, cstore-style.
+
+use crate::db::DatabaseConnection;
+use crate::idl;
+use crate::idl::{DataType, RelType};
+use json::JsonValue;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Comparison operators supported in search filters.
+const SUPPORTED_OPERANDS: &[&str] = &["=", "!=", "<", "<=", ">", ">="];
+
+/// Rows fetched per round trip while streaming a search.
+const DEFAULT_CURSOR_BATCH_SIZE: u64 = 500;
+
+/// Serial for unique server-side cursor names.
+static CURSOR_SERIAL: AtomicU64 = AtomicU64::new(0);
+
+/// Pattern-match operators supported in search filters, lowercase.
+const PATTERN_OPERANDS: &[&str] = &[
+    "like", "not like", "ilike", "not ilike", "~", "~*", "!~", "!~*",
+];
+
+/// Escape a literal string for embedding in a LIKE/ILIKE pattern,
+/// so `%` and `_` in the value match themselves instead of acting
+/// as wildcards.
+pub fn escape_like_pattern(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Models a request to search an IDL class in the database.
+pub struct IdlClassSearch {
+    classname: String,
+    filter: Option<JsonValue>,
+    flesh_depth: u8,
+    flesh_fields: HashMap<String, Vec<String>>,
+    distinct: bool,
+    distinct_fields: Vec<String>,
+}
+
+impl IdlClassSearch {
+    pub fn new(classname: &str) -> Self {
+        IdlClassSearch {
+            classname: classname.to_string(),
+            filter: None,
+            flesh_depth: 0,
+            flesh_fields: HashMap::new(),
+            distinct: false,
+            distinct_fields: Vec::new(),
+        }
+    }
+
+    pub fn classname(&self) -> &str {
+        &self.classname
+    }
+
+    pub fn filter(&self) -> Option<&JsonValue> {
+        self.filter.as_ref()
+    }
+
+    pub fn set_filter(&mut self, filter: JsonValue) {
+        self.filter = Some(filter);
+    }
+
+    pub fn flesh_depth(&self) -> u8 {
+        self.flesh_depth
+    }
+
+    /// How many link levels deep to flesh.  Zero (the default)
+    /// disables fleshing.
+    pub fn set_flesh_depth(&mut self, depth: u8) {
+        self.flesh_depth = depth;
+    }
+
+    pub fn flesh_fields(&self) -> &HashMap<String, Vec<String>> {
+        &self.flesh_fields
+    }
+
+    /// Flesh the named link field wherever an object of this class
+    /// appears within the flesh depth, cstore-style.
+    pub fn add_flesh_field(&mut self, classname: &str, field: &str) {
+        self.flesh_fields
+            .entry(classname.to_string())
+            .or_default()
+            .push(field.to_string());
+    }
+
+    pub fn distinct(&self) -> bool {
+        self.distinct
+    }
+
+    /// Return only distinct rows.
+    pub fn set_distinct(&mut self, distinct: bool) {
+        self.distinct = distinct;
+    }
+
+    pub fn distinct_fields(&self) -> &[String] {
+        &self.distinct_fields
+    }
+
+    /// Return distinct values of this field subset only; other
+    /// fields come back unset.  Implies set_distinct(true).
+    pub fn add_distinct_field(&mut self, field: &str) {
+        self.distinct = true;
+        self.distinct_fields.push(field.to_string());
+    }
+}
+
+/// Aggregate functions supported by idl_class_aggregate().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunc {
+    Count,
+    Min,
+    Max,
+    Sum,
+}
+
+impl AggregateFunc {
+    fn sql_name(&self) -> &'static str {
+        match self {
+            Self::Count => "COUNT",
+            Self::Min => "MIN",
+            Self::Max => "MAX",
+            Self::Sum => "SUM",
+        }
+    }
+}
+
+/// Performs direct-database CRUD on IDL-classed objects.
+pub struct Translator {
+    idl: Arc<idl::Parser>,
+    db: Rc<RefCell<DatabaseConnection>>,
+}
+
+impl Translator {
+    pub fn new(idl: Arc<idl::Parser>, db: Rc<RefCell<DatabaseConnection>>) -> Self {
+        Translator { idl, db }
+    }
+
+    pub fn idl(&self) -> &Arc<idl::Parser> {
+        &self.idl
+    }
+
+    /// Start a database transaction.
+    pub fn xact_begin(&self) -> Result<(), String> {
+        self.db.borrow_mut().xact_begin()
+    }
+
+    /// Commit the open transaction.
+    pub fn xact_commit(&self) -> Result<(), String> {
+        self.db.borrow_mut().xact_commit()
+    }
+
+    /// Roll back the open transaction.
+    pub fn xact_rollback(&self) -> Result<(), String> {
+        self.db.borrow_mut().xact_rollback()
+    }
+
+    /// Create a savepoint within the open transaction.
+    pub fn savepoint(&self, name: &str) -> Result<(), String> {
+        self.db.borrow_mut().savepoint(name)
+    }
+
+    /// Release a savepoint, keeping its changes.
+    pub fn release_savepoint(&self, name: &str) -> Result<(), String> {
+        self.db.borrow_mut().release_savepoint(name)
+    }
+
+    /// Roll back to a savepoint, discarding changes made since.
+    pub fn rollback_to_savepoint(&self, name: &str) -> Result<(), String> {
+        self.db.borrow_mut().rollback_to_savepoint(name)
+    }
+
+    /// Compile and run a cstore-style json_query, returning one JSON
+    /// object per row keyed by output column name.
+    pub fn json_query(&self, query: &JsonValue) -> Result<Vec<JsonValue>, String> {
+        let mut compiler = crate::jsonquery::JsonQueryCompiler::new(self.idl.clone());
+        let sql = compiler.compile(query)?;
+
+        log::debug!(
+            "json_query() executing query: {sql}; params={:?}",
+            compiler.params()
+        );
+
+        let params: Vec<&(dyn postgres::types::ToSql + Sync)> = compiler
+            .params()
+            .iter()
+            .map(|p| p as &(dyn postgres::types::ToSql + Sync))
+            .collect();
+
+        let mut db = self.db.borrow_mut();
+
+        let rows = db
+            .client()
+            .query(&sql[..], params.as_slice())
+            .map_err(|e| format!("DB query failed: {e}"))?;
+
+        let mut results = Vec::new();
+        for row in rows.iter() {
+            let mut obj = json::object! {};
+            for (index, column) in row.columns().iter().enumerate() {
+                obj[column.name()] = Translator::col_value_to_json_value(row, index)?;
+            }
+            results.push(obj);
+        }
+
+        Ok(results)
+    }
+
+    /// Search an IDL class, returning matches as hash-formatted
+    /// classed objects.
+    pub fn idl_class_search(&self, search: &IdlClassSearch) -> Result<Vec<JsonValue>, String> {
+        let class = self
+            .idl
+            .get_class(search.classname())
+            .ok_or_else(|| format!("No such IDL class: {}", search.classname()))?;
+
+        let query = self.compile_search_query(search)?;
+
+        log::debug!("idl_class_search() executing query: {query}");
+
+        let mut results = Vec::new();
+
+        // Scoped so the connection is free for follow-up flesh queries.
+        {
+            let mut db = self.db.borrow_mut();
+
+            let rows = db
+                .client()
+                .query(&query[..], &[])
+                .map_err(|e| format!("DB query failed: {e}"))?;
+
+            for row in rows {
+                results.push(self.row_to_idl(class, &row)?);
+            }
+        }
+
+        if search.flesh_depth() > 0 {
+            for obj in results.iter_mut() {
+                self.flesh_object(obj, search.flesh_depth(), search.flesh_fields())?;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Compile a search into its SELECT statement.
+    fn compile_search_query(&self, search: &IdlClassSearch) -> Result<String, String> {
+        let class = self
+            .idl
+            .get_class(search.classname())
+            .ok_or_else(|| format!("No such IDL class: {}", search.classname()))?;
+
+        let tablename = class
+            .tablename()
+            .ok_or_else(|| format!("Class {} has no table", search.classname()))?;
+
+        let select = if search.distinct() {
+            let columns = if search.distinct_fields().is_empty() {
+                self.compile_column_list(class)
+            } else {
+                let mut cols = Vec::new();
+                for field_name in search.distinct_fields() {
+                    let field = class.fields().get(field_name).ok_or_else(|| {
+                        format!("No such field: {}.{field_name}", class.classname())
+                    })?;
+                    if field.is_virtual() {
+                        return Err(format!("Cannot select virtual field {field_name}"));
+                    }
+                    cols.push(Translator::compile_column(field));
+                }
+                cols.join(", ")
+            };
+            format!("SELECT DISTINCT {columns}")
+        } else {
+            self.compile_class_select(class)
+        };
+
+        let mut query = format!("{select} FROM {tablename}");
+
+        if let Some(filter) = search.filter() {
+            query += &self.compile_class_filter(class, filter)?;
+        }
+
+        Ok(query)
+    }
+
+    /// Search as a stream, fetching batches of rows from a
+    /// server-side cursor instead of materializing the whole result
+    /// set; see IdlClassCursor.
+    pub fn idl_class_search_stream(
+        &self,
+        search: &IdlClassSearch,
+    ) -> Result<IdlClassCursor<'_>, String> {
+        let query = self.compile_search_query(search)?;
+        let name = format!(
+            "idl_cursor_{}",
+            CURSOR_SERIAL.fetch_add(1, Ordering::Relaxed)
+        );
+
+        // WITH HOLD keeps the cursor usable without an explicit
+        // transaction wrapping every fetch.
+        let declare = format!("DECLARE {name} CURSOR WITH HOLD FOR {query}");
+
+        log::debug!("idl_class_search_stream() executing query: {declare}");
+
+        self.db
+            .borrow_mut()
+            .client()
+            .batch_execute(&declare)
+            .map_err(|e| format!("DB cursor declare failed: {e}"))?;
+
+        Ok(IdlClassCursor {
+            translator: self,
+            classname: search.classname().to_string(),
+            flesh_depth: search.flesh_depth(),
+            flesh_fields: search.flesh_fields().clone(),
+            name,
+            batch_size: DEFAULT_CURSOR_BATCH_SIZE,
+            buffer: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    /// Count the rows a search would match, without fetching them.
+    pub fn idl_class_count(&self, search: &IdlClassSearch) -> Result<u64, String> {
+        let class = self
+            .idl
+            .get_class(search.classname())
+            .ok_or_else(|| format!("No such IDL class: {}", search.classname()))?;
+
+        let tablename = class
+            .tablename()
+            .ok_or_else(|| format!("Class {} has no table", search.classname()))?;
+
+        let mut query = format!("SELECT COUNT(*) FROM {tablename}");
+
+        if let Some(filter) = search.filter() {
+            query += &self.compile_class_filter(class, filter)?;
+        }
+
+        log::debug!("idl_class_count() executing query: {query}");
+
+        let mut db = self.db.borrow_mut();
+
+        let row = db
+            .client()
+            .query_one(&query[..], &[])
+            .map_err(|e| format!("DB query failed: {e}"))?;
+
+        let count: i64 = row
+            .try_get(0)
+            .map_err(|e| format!("Error reading count: {e}"))?;
+
+        Ok(count as u64)
+    }
+
+    /// Run an aggregate query over a search, optionally grouped.
+    ///
+    /// Each aggregate pairs a function with a non-virtual field name
+    /// ("*" is allowed for Count) and lands in the output keyed as
+    /// "count" for COUNT(*) and "{func}_{field}" otherwise; group_by
+    /// fields are selected under their own names and ordered for
+    /// stable output.
+    pub fn idl_class_aggregate(
+        &self,
+        search: &IdlClassSearch,
+        group_by: &[&str],
+        aggregates: &[(AggregateFunc, &str)],
+    ) -> Result<Vec<JsonValue>, String> {
+        if aggregates.is_empty() {
+            return Err("idl_class_aggregate() requires at least one aggregate".to_string());
+        }
+
+        let class = self
+            .idl
+            .get_class(search.classname())
+            .ok_or_else(|| format!("No such IDL class: {}", search.classname()))?;
+
+        let tablename = class
+            .tablename()
+            .ok_or_else(|| format!("Class {} has no table", search.classname()))?;
+
+        let check_field = |field_name: &str| -> Result<&idl::Field, String> {
+            let field = class
+                .fields()
+                .get(field_name)
+                .ok_or_else(|| format!("No such field: {}.{field_name}", class.classname()))?;
+            if field.is_virtual() {
+                return Err(format!("Cannot aggregate virtual field {field_name}"));
+            }
+            Ok(field)
+        };
+
+        let mut columns = Vec::new();
+
+        for field_name in group_by {
+            // compile_column() casts types our row reader cannot
+            // speak; GROUP BY below still names the bare column.
+            columns.push(Translator::compile_column(check_field(field_name)?));
+        }
+
+        for (func, field_name) in aggregates {
+            if *func == AggregateFunc::Count && *field_name == "*" {
+                columns.push("COUNT(*) AS count".to_string());
+                continue;
+            }
+
+            let field = check_field(field_name)?;
+
+            let operand = match field.datatype() {
+                // Same cast compile_column() applies, so MIN/MAX of
+                // timestamps come back readable.
+                DataType::Timestamp | DataType::Interval => format!("{field_name}::TEXT"),
+                _ => field_name.to_string(),
+            };
+
+            let column = match func {
+                // SUM of integer columns yields NUMERIC, which our
+                // row reader does not speak; a FLOAT8 cast keeps the
+                // output readable.
+                AggregateFunc::Sum => format!("SUM({operand})::FLOAT8 AS sum_{field_name}"),
+                _ => format!(
+                    "{}({operand}) AS {}_{field_name}",
+                    func.sql_name(),
+                    func.sql_name().to_lowercase()
+                ),
+            };
+
+            columns.push(column);
+        }
+
+        let mut query = format!("SELECT {} FROM {tablename}", columns.join(", "));
+
+        if let Some(filter) = search.filter() {
+            query += &self.compile_class_filter(class, filter)?;
+        }
+
+        if !group_by.is_empty() {
+            query += &format!(" GROUP BY {g} ORDER BY {g}", g = group_by.join(", "));
+        }
+
+        log::debug!("idl_class_aggregate() executing query: {query}");
+
+        let mut db = self.db.borrow_mut();
+
+        let rows = db
+            .client()
+            .query(&query[..], &[])
+            .map_err(|e| format!("DB query failed: {e}"))?;
+
+        let mut results = Vec::new();
+        for row in rows.iter() {
+            let mut obj = json::object! {};
+            for (index, column) in row.columns().iter().enumerate() {
+                obj[column.name()] = Translator::col_value_to_json_value(row, index)?;
+            }
+            results.push(obj);
+        }
+
+        Ok(results)
+    }
+
+    /// Replace link field values on a classed object with the objects
+    /// they point to, using IDL link metadata and follow-up queries.
+    ///
+    /// has_a and might_have links become a nested object (or stay as
+    /// the bare id when the target row is gone); has_many links become
+    /// an array of objects.  Fleshed children are themselves fleshed
+    /// until the depth runs out.
+    fn flesh_object(
+        &self,
+        obj: &mut JsonValue,
+        depth: u8,
+        flesh_fields: &HashMap<String, Vec<String>>,
+    ) -> Result<(), String> {
+        if depth == 0 {
+            return Ok(());
+        }
+
+        let classname = obj[idl::CLASSNAME_KEY]
+            .as_str()
+            .ok_or_else(|| format!("Cannot flesh unclassed object: {}", obj.dump()))?
+            .to_string();
+
+        let fields = match flesh_fields.get(&classname) {
+            Some(fields) => fields,
+            None => return Ok(()),
+        };
+
+        for fieldname in fields {
+            let link = self
+                .idl
+                .link(&classname, fieldname)
+                .ok_or_else(|| format!("Class {classname} has no link on field {fieldname}"))?;
+
+            if link.map().is_some() {
+                return Err(format!(
+                    "Cannot flesh mapped link {classname}.{fieldname}"
+                ));
+            }
+
+            let linked_class = self
+                .idl
+                .linked_class(&classname, fieldname)
+                .ok_or_else(|| format!("Link {classname}.{fieldname} targets an unknown class"))?;
+
+            // The value the linked class is matched on: our link field
+            // for has_a / might_have, our pkey for has_many.
+            let value = match link.reltype() {
+                RelType::HasMany => {
+                    let pkey = self
+                        .idl
+                        .get_class(&classname)
+                        .and_then(|c| c.pkey())
+                        .ok_or_else(|| format!("Class {classname} has no primary key"))?;
+                    obj[pkey].clone()
+                }
+                _ => obj[fieldname.as_str()].clone(),
+            };
+
+            if value.is_null() {
+                continue;
+            }
+
+            let mut search = IdlClassSearch::new(linked_class.classname());
+            let mut filter = JsonValue::new_object();
+            filter[link.key()] = value;
+            search.set_filter(filter);
+
+            let mut children = self.idl_class_search(&search)?;
+
+            for child in children.iter_mut() {
+                self.flesh_object(child, depth - 1, flesh_fields)?;
+            }
+
+            match link.reltype() {
+                RelType::HasMany => obj[fieldname.as_str()] = JsonValue::from(children),
+                _ => {
+                    if let Some(child) = children.drain(..).next() {
+                        obj[fieldname.as_str()] = child;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create a row from a hash-formatted classed object, returning
+    /// the newly created object.
+    pub fn idl_class_create(&self, obj: &JsonValue) -> Result<JsonValue, String> {
+        let classname = obj[idl::CLASSNAME_KEY]
+            .as_str()
+            .ok_or_else(|| format!("Not a classed object: {}", obj.dump()))?;
+
+        let class = self
+            .idl
+            .get_class(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        let tablename = class
+            .tablename()
+            .ok_or_else(|| format!("Class {classname} has no table"))?
+            .to_string();
+
+        let mut columns = Vec::new();
+        let mut values = Vec::new();
+
+        for field in class.real_fields_sorted() {
+            let value = &obj[field.name()];
+            if value.is_null() {
+                continue;
+            }
+            columns.push(field.name().to_string());
+            values.push(self.compile_value(field.datatype(), value)?);
+        }
+
+        if columns.is_empty() {
+            return Err(format!("No values provided for new {classname}"));
+        }
+
+        let query = format!(
+            "INSERT INTO {tablename} ({}) VALUES ({}) RETURNING {}",
+            columns.join(", "),
+            values.join(", "),
+            self.compile_column_list(class),
+        );
+
+        log::debug!("idl_class_create() executing query: {query}");
+
+        let mut db = self.db.borrow_mut();
+
+        let rows = db
+            .client()
+            .query(&query[..], &[])
+            .map_err(|e| format!("DB insert failed: {e}"))?;
+
+        match rows.first() {
+            Some(row) => self.row_to_idl(class, row),
+            None => Err(format!("INSERT into {tablename} returned no row")),
+        }
+    }
+
+    /// Update a row from a hash-formatted classed object, keyed on
+    /// the class primary key, returning the updated object.
+    ///
+    /// Every non-virtual field is written; null fields become NULL.
+    pub fn idl_class_update(&self, obj: &JsonValue) -> Result<JsonValue, String> {
+        self.compile_and_run_update(obj, None)
+    }
+
+    /// Like idl_class_update, but writes only the named fields.
+    ///
+    /// Callers tracking ischanged state can limit the UPDATE to what
+    /// actually changed.
+    pub fn idl_class_update_fields(
+        &self,
+        obj: &JsonValue,
+        fields: &[&str],
+    ) -> Result<JsonValue, String> {
+        self.compile_and_run_update(obj, Some(fields))
+    }
+
+    fn compile_and_run_update(
+        &self,
+        obj: &JsonValue,
+        only: Option<&[&str]>,
+    ) -> Result<JsonValue, String> {
+        let classname = obj[idl::CLASSNAME_KEY]
+            .as_str()
+            .ok_or_else(|| format!("Not a classed object: {}", obj.dump()))?;
+
+        let class = self
+            .idl
+            .get_class(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        let tablename = class
+            .tablename()
+            .ok_or_else(|| format!("Class {classname} has no table"))?
+            .to_string();
+
+        let pkey = class
+            .pkey()
+            .ok_or_else(|| format!("Class {classname} has no primary key"))?;
+
+        let pkey_field = class
+            .fields()
+            .get(pkey)
+            .ok_or_else(|| format!("No such field: {classname}.{pkey}"))?;
+
+        let pkey_value = &obj[pkey];
+        if pkey_value.is_null() {
+            return Err(format!("Object has no {pkey} value: {}", obj.dump()));
+        }
+
+        let mut sets = Vec::new();
+
+        for field in class.real_fields_sorted() {
+            if field.name() == pkey {
+                continue;
+            }
+            if let Some(fields) = only {
+                if !fields.contains(&field.name()) {
+                    continue;
+                }
+            }
+
+            let value = &obj[field.name()];
+            let compiled = if value.is_null() {
+                "NULL".to_string()
+            } else {
+                self.compile_value(field.datatype(), value)?
+            };
+
+            sets.push(format!("{} = {compiled}", field.name()));
+        }
+
+        if sets.is_empty() {
+            return Err(format!("No fields to update for {classname}"));
+        }
+
+        let query = format!(
+            "UPDATE {tablename} SET {} WHERE {pkey} = {} RETURNING {}",
+            sets.join(", "),
+            self.compile_value(pkey_field.datatype(), pkey_value)?,
+            self.compile_column_list(class),
+        );
+
+        log::debug!("idl_class_update() executing query: {query}");
+
+        let mut db = self.db.borrow_mut();
+
+        let rows = db
+            .client()
+            .query(&query[..], &[])
+            .map_err(|e| format!("DB update failed: {e}"))?;
+
+        match rows.first() {
+            Some(row) => self.row_to_idl(class, row),
+            None => Err(format!("No {classname} row with {pkey} {pkey_value}")),
+        }
+    }
+
+    /// Delete a row by primary key, returning true if a row was
+    /// removed.
+    pub fn idl_class_delete(
+        &self,
+        classname: &str,
+        pkey_value: &JsonValue,
+    ) -> Result<bool, String> {
+        let count = self.run_delete(classname, &self.pkey_filter(classname, pkey_value)?, false)?;
+        Ok(count > 0)
+    }
+
+    /// Soft-delete a row by primary key by setting its deleted
+    /// column, for classes that keep dead rows around.  Errors when
+    /// the class has no deleted field.
+    pub fn idl_class_soft_delete(
+        &self,
+        classname: &str,
+        pkey_value: &JsonValue,
+    ) -> Result<bool, String> {
+        let count = self.run_delete(classname, &self.pkey_filter(classname, pkey_value)?, true)?;
+        Ok(count > 0)
+    }
+
+    /// Delete all rows matching a filter, returning how many went
+    /// away.  With `soft` set, rows are flagged deleted instead of
+    /// removed.
+    pub fn idl_class_delete_where(
+        &self,
+        classname: &str,
+        filter: &JsonValue,
+        soft: bool,
+    ) -> Result<u64, String> {
+        if !filter.is_object() || filter.is_empty() {
+            // Require a real filter so a stray empty hash cannot
+            // clear a table.
+            return Err(format!(
+                "delete_where() requires a non-empty filter: {}",
+                filter.dump()
+            ));
+        }
+
+        self.run_delete(classname, filter, soft)
+    }
+
+    /// Build a {pkey: value} filter for a class.
+    fn pkey_filter(&self, classname: &str, pkey_value: &JsonValue) -> Result<JsonValue, String> {
+        let class = self
+            .idl
+            .get_class(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        let pkey = class
+            .pkey()
+            .ok_or_else(|| format!("Class {classname} has no primary key"))?;
+
+        if pkey_value.is_null() {
+            return Err(format!("Delete requires a {pkey} value"));
+        }
+
+        let mut filter = json::object! {};
+        filter[pkey] = pkey_value.clone();
+
+        Ok(filter)
+    }
+
+    fn run_delete(&self, classname: &str, filter: &JsonValue, soft: bool) -> Result<u64, String> {
+        let class = self
+            .idl
+            .get_class(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        let tablename = class
+            .tablename()
+            .ok_or_else(|| format!("Class {classname} has no table"))?
+            .to_string();
+
+        let where_clause = self.compile_class_filter(class, filter)?;
+
+        let query = if soft {
+            match class.fields().get("deleted") {
+                Some(f) if !f.is_virtual() => {}
+                _ => return Err(format!("Class {classname} has no deleted column")),
+            }
+            format!("UPDATE {tablename} SET deleted = TRUE{where_clause}")
+        } else {
+            format!("DELETE FROM {tablename}{where_clause}")
+        };
+
+        log::debug!("run_delete() executing query: {query}");
+
+        let mut db = self.db.borrow_mut();
+
+        db.client()
+            .execute(&query[..], &[])
+            .map_err(|e| format!("DB delete failed: {e}"))
+    }
+
+    /// Build the comma-separated column list for a class, casting
+    /// types postgres cannot hand us directly.
+    fn compile_column_list(&self, class: &idl::Class) -> String {
+        let cols: Vec<String> = class
+            .fields_sorted_by_position()
+            .into_iter()
+            .filter(|f| !f.is_virtual())
+            .map(Translator::compile_column)
+            .collect();
+
+        cols.join(", ")
+    }
+
+    /// One select column, casting types postgres cannot hand us
+    /// directly.
+    fn compile_column(field: &idl::Field) -> String {
+        match field.datatype() {
+            DataType::Timestamp | DataType::Interval => {
+                format!("{n}::TEXT AS {n}", n = field.name())
+            }
+            _ => field.name().to_string(),
+        }
+    }
+
+    fn compile_class_select(&self, class: &idl::Class) -> String {
+        format!("SELECT {}", self.compile_column_list(class))
+    }
+
+    /// Compile a filter hash into a WHERE clause.
+    ///
+    /// Field tests are ANDed together unless grouped under the
+    /// cstore-style "-or" / "-and" / "-not" combinators.
+    fn compile_class_filter(
+        &self,
+        class: &idl::Class,
+        filter: &JsonValue,
+    ) -> Result<String, String> {
+        if !filter.is_object() {
+            return Err(format!(
+                "Translator class filter must be an object: {}",
+                filter.dump()
+            ));
+        }
+
+        if filter.is_empty() {
+            return Ok(String::new());
+        }
+
+        Ok(format!(
+            " WHERE {}",
+            self.compile_filter_block(class, filter, "AND")?
+        ))
+    }
+
+    /// Compile one filter block.
+    ///
+    /// Object entries are field tests or combinators; array members
+    /// are AND-joined sub-blocks.  Multi-part output comes back
+    /// parenthesized.
+    fn compile_filter_block(
+        &self,
+        class: &idl::Class,
+        block: &JsonValue,
+        joiner: &str,
+    ) -> Result<String, String> {
+        let mut tests = Vec::new();
+
+        if block.is_array() {
+            for member in block.members() {
+                tests.push(self.compile_filter_block(class, member, "AND")?);
+            }
+        } else if block.is_object() {
+            for (key, value) in block.entries() {
+                match key {
+                    "-or" => tests.push(self.compile_filter_block(class, value, "OR")?),
+                    "-and" => tests.push(self.compile_filter_block(class, value, "AND")?),
+                    "-not" => {
+                        let inner = self.compile_filter_block(class, value, "AND")?;
+                        if inner.starts_with('(') {
+                            tests.push(format!("NOT {inner}"));
+                        } else {
+                            tests.push(format!("NOT ({inner})"));
+                        }
+                    }
+                    _ => tests.push(self.compile_field_filter(class, key, value)?),
+                }
+            }
+        } else {
+            return Err(format!("Invalid filter block: {}", block.dump()));
+        }
+
+        if tests.is_empty() {
+            return Err(format!("Empty filter block: {}", block.dump()));
+        }
+
+        if tests.len() == 1 {
+            Ok(tests.remove(0))
+        } else {
+            Ok(format!("({})", tests.join(&format!(" {joiner} "))))
+        }
+    }
+
+    /// Compile one field test.
+    fn compile_field_filter(
+        &self,
+        class: &idl::Class,
+        field_name: &str,
+        value: &JsonValue,
+    ) -> Result<String, String> {
+        let field = class
+            .fields()
+            .get(field_name)
+            .ok_or_else(|| format!("No such field: {}.{field_name}", class.classname()))?;
+
+        if field.is_virtual() {
+            return Err(format!("Cannot filter on virtual field {field_name}"));
+        }
+
+        let test = match value {
+            JsonValue::Null => format!("{field_name} IS NULL"),
+            JsonValue::Array(list) => {
+                let mut quoted = Vec::new();
+                for v in list {
+                    quoted.push(self.compile_value(field.datatype(), v)?);
+                }
+                format!("{field_name} IN ({})", quoted.join(", "))
+            }
+            JsonValue::Object(_) => {
+                // {field: {">": value}} style operand tests.
+                let (op, operand) = value
+                    .entries()
+                    .next()
+                    .ok_or_else(|| format!("Empty operand test for {field_name}"))?;
+
+                let op_lower = op.to_lowercase();
+
+                if SUPPORTED_OPERANDS.contains(&op) {
+                    format!(
+                        "{field_name} {op} {}",
+                        self.compile_value(field.datatype(), operand)?
+                    )
+                } else if PATTERN_OPERANDS.contains(&op_lower.as_str()) {
+                    // Patterns are strings regardless of the field
+                    // datatype; LIKE et al. operate on text.
+                    let pattern = operand
+                        .as_str()
+                        .ok_or_else(|| format!("Pattern must be a string: {}", operand.dump()))?;
+
+                    format!(
+                        "{field_name}::TEXT {} '{}'",
+                        op_lower.to_uppercase(),
+                        pattern.replace('\'', "''")
+                    )
+                } else {
+                    return Err(format!("Unsupported operand: {op}"));
+                }
+            }
+            _ => format!(
+                "{field_name} = {}",
+                self.compile_value(field.datatype(), value)?
+            ),
+        };
+
+        Ok(test)
+    }
+
+    /// Compile a JSON value into a SQL literal matching the field
+    /// datatype.
+    fn compile_value(&self, datatype: &DataType, value: &JsonValue) -> Result<String, String> {
+        if datatype.is_numeric() {
+            // Accept numbers and numeric strings.
+            let num = crate::util::json_float(value)?;
+            return Ok(format!("{num}"));
+        }
+
+        match value {
+            JsonValue::Boolean(b) => Ok(if *b { "TRUE" } else { "FALSE" }.to_string()),
+            JsonValue::Number(_) => Ok(format!("{value}")),
+            _ => match value.as_str() {
+                Some(s) => Ok(format!("'{}'", s.replace('\'', "''"))),
+                None => Err(format!("Cannot compile SQL value: {}", value.dump())),
+            },
+        }
+    }
+
+    /// Map a database row into a hash-formatted classed object.
+    fn row_to_idl(&self, class: &idl::Class, row: &postgres::Row) -> Result<JsonValue, String> {
+        let mut obj = json::object! {};
+        obj[idl::CLASSNAME_KEY] = class.classname().into();
+
+        for (index, column) in row.columns().iter().enumerate() {
+            obj[column.name()] = Translator::col_value_to_json_value(row, index)?;
+        }
+
+        Ok(obj)
+    }
+
+    /// Translate a column value into a JSON value based on the
+    /// column's database type.
+    pub fn col_value_to_json_value(row: &postgres::Row, index: usize) -> Result<JsonValue, String> {
+        let coltype = row.columns()[index].type_();
+
+        macro_rules! get_col {
+            ($type:ty) => {
+                match row
+                    .try_get::<usize, Option<$type>>(index)
+                    .map_err(|e| format!("Error reading column {index}: {e}"))?
+                {
+                    Some(v) => Ok(json::from(v)),
+                    None => Ok(JsonValue::Null),
+                }
+            };
+        }
+
+        match *coltype {
+            postgres::types::Type::BOOL => get_col!(bool),
+            postgres::types::Type::INT2 => get_col!(i16),
+            postgres::types::Type::INT4 => get_col!(i32),
+            postgres::types::Type::INT8 => get_col!(i64),
+            postgres::types::Type::OID => get_col!(u32),
+            postgres::types::Type::FLOAT4 => get_col!(f32),
+            postgres::types::Type::FLOAT8 => get_col!(f64),
+            postgres::types::Type::TEXT
+            | postgres::types::Type::VARCHAR
+            | postgres::types::Type::BPCHAR
+            | postgres::types::Type::NAME => get_col!(String),
+            postgres::types::Type::BOOL_ARRAY => get_col!(Vec<bool>),
+            postgres::types::Type::INT2_ARRAY => get_col!(Vec<i16>),
+            postgres::types::Type::INT4_ARRAY => get_col!(Vec<i32>),
+            postgres::types::Type::INT8_ARRAY => get_col!(Vec<i64>),
+            postgres::types::Type::FLOAT4_ARRAY => get_col!(Vec<f32>),
+            postgres::types::Type::FLOAT8_ARRAY => get_col!(Vec<f64>),
+            postgres::types::Type::TEXT_ARRAY
+            | postgres::types::Type::VARCHAR_ARRAY
+            | postgres::types::Type::BPCHAR_ARRAY
+            | postgres::types::Type::NAME_ARRAY => get_col!(Vec<String>),
+            postgres::types::Type::JSON | postgres::types::Type::JSONB => {
+                match row
+                    .try_get::<usize, Option<serde_json::Value>>(index)
+                    .map_err(|e| format!("Error reading column {index}: {e}"))?
+                {
+                    Some(v) => Ok(crate::util::serde_to_json(&v)),
+                    None => Ok(JsonValue::Null),
+                }
+            }
+            _ => Err(format!("Unsupported column type: {coltype}")),
+        }
+    }
+}
+
+/// Streams search results from a server-side cursor in batches.
+///
+/// Created by Translator::idl_class_search_stream().  The cursor
+/// closes itself once the last row is fetched, or on drop.
+pub struct IdlClassCursor<'a> {
+    translator: &'a Translator,
+    classname: String,
+    flesh_depth: u8,
+    flesh_fields: HashMap<String, Vec<String>>,
+    name: String,
+    batch_size: u64,
+    buffer: VecDeque<JsonValue>,
+    done: bool,
+}
+
+impl IdlClassCursor<'_> {
+    /// Rows fetched per round trip; defaults to 500.
+    pub fn set_batch_size(&mut self, batch_size: u64) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Pull the next batch from the cursor into our buffer.
+    fn fetch_batch(&mut self) -> Result<(), String> {
+        let class = self
+            .translator
+            .idl()
+            .get_class(&self.classname)
+            .ok_or_else(|| format!("No such IDL class: {}", self.classname))?;
+
+        let query = format!("FETCH {} FROM {}", self.batch_size, self.name);
+
+        // Scoped so the connection is free for follow-up flesh queries.
+        let rows = {
+            let mut db = self.translator.db.borrow_mut();
+            db.client()
+                .query(&query[..], &[])
+                .map_err(|e| format!("DB cursor fetch failed: {e}"))?
+        };
+
+        if (rows.len() as u64) < self.batch_size {
+            self.close();
+        }
+
+        for row in rows.iter() {
+            let mut obj = self.translator.row_to_idl(class, row)?;
+            if self.flesh_depth > 0 {
+                self.translator
+                    .flesh_object(&mut obj, self.flesh_depth, &self.flesh_fields)?;
+            }
+            self.buffer.push_back(obj);
+        }
+
+        Ok(())
+    }
+
+    /// Close the server-side cursor, best-effort.
+    fn close(&mut self) {
+        if self.done {
+            return;
+        }
+        self.done = true;
+
+        let mut db = self.translator.db.borrow_mut();
+        if let Err(e) = db.client().batch_execute(&format!("CLOSE {}", self.name)) {
+            log::warn!("Error closing cursor {}: {e}", self.name);
+        }
+    }
+}
+
+impl Iterator for IdlClassCursor<'_> {
+    type Item = Result<JsonValue, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && !self.done {
+            if let Err(e) = self.fetch_batch() {
+                self.close();
+                return Some(Err(e));
+            }
+        }
+
+        self.buffer.pop_front().map(Ok)
+    }
+}
+
+impl Drop for IdlClassCursor<'_> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
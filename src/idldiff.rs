@@ -0,0 +1,190 @@
+//! Compare two parsed IDLs and report structural changes.
+//!
+//! Useful for validating upgrades and keeping generated Rust structs
+//! in sync with a cluster's fm_IDL.xml.  The eg-idl-diff binary wraps
+//! this for the command line.
+
+use crate::idl::Parser;
+use std::fmt;
+
+/// One structural difference between two IDLs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    ClassAdded(String),
+    ClassRemoved(String),
+    FieldAdded(String, String),
+    FieldRemoved(String, String),
+    DatatypeChanged {
+        class: String,
+        field: String,
+        old: String,
+        new: String,
+    },
+    LinkAdded(String, String),
+    LinkRemoved(String, String),
+    LinkChanged {
+        class: String,
+        field: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ClassAdded(class) => write!(f, "class added: {class}"),
+            Self::ClassRemoved(class) => write!(f, "class removed: {class}"),
+            Self::FieldAdded(class, field) => write!(f, "field added: {class}.{field}"),
+            Self::FieldRemoved(class, field) => write!(f, "field removed: {class}.{field}"),
+            Self::DatatypeChanged {
+                class,
+                field,
+                old,
+                new,
+            } => write!(f, "datatype changed: {class}.{field} {old} -> {new}"),
+            Self::LinkAdded(class, field) => write!(f, "link added: {class}.{field}"),
+            Self::LinkRemoved(class, field) => write!(f, "link removed: {class}.{field}"),
+            Self::LinkChanged {
+                class,
+                field,
+                old,
+                new,
+            } => write!(f, "link changed: {class}.{field} {old} -> {new}"),
+        }
+    }
+}
+
+/// Compare two IDLs, reporting what `new` adds, removes, or redefines
+/// relative to `old`.  Changes come back sorted by class and field.
+pub fn diff(old: &Parser, new: &Parser) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let mut classnames: Vec<&str> = old
+        .classes()
+        .keys()
+        .chain(new.classes().keys())
+        .map(|c| c.as_str())
+        .collect();
+    classnames.sort_unstable();
+    classnames.dedup();
+
+    for classname in classnames {
+        let old_class = match old.get_class(classname) {
+            Some(c) => c,
+            None => {
+                changes.push(Change::ClassAdded(classname.to_string()));
+                continue;
+            }
+        };
+
+        let new_class = match new.get_class(classname) {
+            Some(c) => c,
+            None => {
+                changes.push(Change::ClassRemoved(classname.to_string()));
+                continue;
+            }
+        };
+
+        let mut fields: Vec<&str> = old_class
+            .fields()
+            .keys()
+            .chain(new_class.fields().keys())
+            .map(|f| f.as_str())
+            .collect();
+        fields.sort_unstable();
+        fields.dedup();
+
+        for field in fields {
+            match (old_class.fields().get(field), new_class.fields().get(field)) {
+                (None, Some(_)) => {
+                    changes.push(Change::FieldAdded(classname.to_string(), field.to_string()))
+                }
+                (Some(_), None) => {
+                    changes.push(Change::FieldRemoved(classname.to_string(), field.to_string()))
+                }
+                (Some(of), Some(nf)) => {
+                    if of.datatype() != nf.datatype() {
+                        changes.push(Change::DatatypeChanged {
+                            class: classname.to_string(),
+                            field: field.to_string(),
+                            old: of.datatype().to_string(),
+                            new: nf.datatype().to_string(),
+                        });
+                    }
+                }
+                (None, None) => unreachable!("field came from one of the two maps"),
+            }
+        }
+
+        let mut links: Vec<&str> = old_class
+            .links()
+            .keys()
+            .chain(new_class.links().keys())
+            .map(|l| l.as_str())
+            .collect();
+        links.sort_unstable();
+        links.dedup();
+
+        for field in links {
+            match (old_class.links().get(field), new_class.links().get(field)) {
+                (None, Some(_)) => {
+                    changes.push(Change::LinkAdded(classname.to_string(), field.to_string()))
+                }
+                (Some(_), None) => {
+                    changes.push(Change::LinkRemoved(classname.to_string(), field.to_string()))
+                }
+                (Some(ol), Some(nl)) => {
+                    if ol.class() != nl.class()
+                        || ol.reltype() != nl.reltype()
+                        || ol.key() != nl.key()
+                    {
+                        changes.push(Change::LinkChanged {
+                            class: classname.to_string(),
+                            field: field.to_string(),
+                            old: format!("{:?}:{}:{}", ol.reltype(), ol.class(), ol.key()),
+                            new: format!("{:?}:{}:{}", nl.reltype(), nl.class(), nl.key()),
+                        });
+                    }
+                }
+                (None, None) => unreachable!("link came from one of the two maps"),
+            }
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl;
+
+    #[test]
+    fn test_diff() {
+        let old = idl::Parser::parse_string(idl::tests::TEST_IDL).expect("IDL parses");
+
+        let modified = idl::tests::TEST_IDL
+            .replace(
+                r#"<field name="opac_visible" reporter:datatype="bool" reporter:label="OPAC Visible"/>"#,
+                r#"<field name="opac_visible" reporter:datatype="text" reporter:label="OPAC Visible"/>
+                   <field name="shortname" reporter:datatype="text" reporter:label="Shortname"/>"#,
+            )
+            .replace(
+                r#"<link field="children" reltype="has_many" key="parent_ou" map="" class="aou"/>"#,
+                "",
+            );
+
+        let new = idl::Parser::parse_string(&modified).expect("modified IDL parses");
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&Change::FieldAdded("aou".to_string(), "shortname".to_string())));
+        assert!(changes.contains(&Change::LinkRemoved("aou".to_string(), "children".to_string())));
+        assert!(changes
+            .iter()
+            .any(|c| matches!(c, Change::DatatypeChanged { field, .. } if field == "opac_visible")));
+
+        assert!(diff(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+//! Generate typed Rust structs from IDL classes.
+//!
+//! Emits a struct per selected class with fields typed from the
+//! reporter datatypes, plus conversions to and from the hash-formatted
+//! JSON representation, so callers can trade the stringly
+//! `user["home_ou"]` pattern for compile-time checked field access.
+//! The eg-idlgen binary wraps this for build scripts and one-off runs.
+
+use crate::idl::{Class, DataType, Field, Parser};
+
+/// The Rust struct name for an IDL class: "aou" becomes "Aou",
+/// "circ_chain_summary" becomes "CircChainSummary".
+pub fn struct_name(classname: &str) -> String {
+    classname
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// The Rust type backing a field.
+///
+/// Every column is nullable on the wire, so each maps to an Option.
+/// Link and org_unit columns carry numeric ids; fleshed objects are
+/// out of scope for generated types.
+pub fn rust_type(field: &Field) -> &'static str {
+    match field.datatype() {
+        DataType::Bool => "Option<bool>",
+        DataType::Float | DataType::Money | DataType::Number => "Option<f64>",
+        DataType::Id | DataType::Int | DataType::OrgUnit => "Option<i64>",
+        _ => "Option<String>",
+    }
+}
+
+/// Generate the struct and conversion impls for one class.
+fn generate_class(class: &Class) -> String {
+    let name = struct_name(class.classname());
+
+    // Stable field order so regeneration produces clean diffs.
+    let fields: Vec<&Field> = class
+        .fields_sorted_by_position()
+        .into_iter()
+        .filter(|f| !f.is_virtual())
+        .collect();
+
+    let mut src = String::new();
+
+    if let Some(label) = class.label() {
+        src += &format!("/// {label}\n");
+    }
+    src += &format!("/// IDL class: {}\n", class.classname());
+    src += "#[derive(Debug, Clone, Default, PartialEq)]\n";
+    src += &format!("pub struct {name} {{\n");
+
+    for field in &fields {
+        src += &format!("    pub {}: {},\n", field.name(), rust_type(field));
+    }
+
+    src += "}\n\n";
+    src += &format!("impl {name} {{\n");
+    src += &format!(
+        "    pub const CLASSNAME: &'static str = \"{}\";\n\n",
+        class.classname()
+    );
+
+    // from_json
+    src += "    /// Build from a hash-formatted IDL object.\n";
+    src += "    pub fn from_json(obj: &json::JsonValue) -> Self {\n";
+    src += &format!("        {name} {{\n");
+
+    for field in &fields {
+        let getter = match field.datatype() {
+            DataType::Bool => {
+                // Booleans arrive as "t"/"f" strings or JSON booleans.
+                format!(
+                    "obj[\"{}\"].as_bool().or_else(|| obj[\"{}\"].as_str().map(|v| v == \"t\"))",
+                    field.name(),
+                    field.name()
+                )
+            }
+            DataType::Float | DataType::Money | DataType::Number => {
+                format!("obj[\"{}\"].as_f64()", field.name())
+            }
+            DataType::Id | DataType::Int | DataType::OrgUnit => {
+                format!("obj[\"{}\"].as_i64()", field.name())
+            }
+            _ => format!(
+                "obj[\"{}\"].as_str().map(|v| v.to_string())",
+                field.name()
+            ),
+        };
+        src += &format!("            {}: {getter},\n", field.name());
+    }
+
+    src += "        }\n    }\n\n";
+
+    // to_json
+    src += "    /// Render as a hash-formatted IDL object.\n";
+    src += "    pub fn to_json(&self) -> json::JsonValue {\n";
+    src += "        let mut obj = json::object! {};\n";
+    src += &format!(
+        "        obj[\"{}\"] = Self::CLASSNAME.into();\n",
+        crate::idl::CLASSNAME_KEY
+    );
+
+    for field in &fields {
+        src += &format!(
+            "        obj[\"{}\"] = match &self.{} {{\n",
+            field.name(),
+            field.name()
+        );
+        src += "            Some(v) => v.clone().into(),\n";
+        src += "            None => json::JsonValue::Null,\n";
+        src += "        };\n";
+    }
+
+    src += "        obj\n    }\n}\n";
+    src
+}
+
+/// Generate a source file covering the named classes, in the order
+/// given.  Unknown class names are an error.
+pub fn generate(parser: &Parser, classnames: &[&str]) -> Result<String, String> {
+    let mut src = String::from(
+        "// Generated by eg-idlgen from the Evergreen IDL.  Do not edit.\n\n",
+    );
+
+    for classname in classnames {
+        let class = parser
+            .get_class(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        src += &generate_class(class);
+        src += "\n";
+    }
+
+    Ok(src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl;
+
+    #[test]
+    fn test_struct_name() {
+        assert_eq!(struct_name("aou"), "Aou");
+        assert_eq!(struct_name("circ_chain_summary"), "CircChainSummary");
+    }
+
+    #[test]
+    fn test_generate() {
+        let parser = idl::Parser::parse_string(idl::tests::TEST_IDL).expect("IDL parses");
+
+        let src = generate(&parser, &["aou"]).expect("aou generates");
+
+        assert!(src.contains("pub struct Aou {"));
+        assert!(src.contains("pub id: Option<i64>,"));
+        assert!(src.contains("pub name: Option<String>,"));
+        assert!(src.contains("pub opac_visible: Option<bool>,"));
+        // Virtual fields are not persisted and get no struct member.
+        assert!(!src.contains("pub children:"));
+
+        assert!(generate(&parser, &["no_such_class"]).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! Search-engine export of bib records.
+//!
+//! Builds Elasticsearch/OpenSearch documents from bib MARC plus a
+//! holdings summary and ships them via the bulk API, for sites
+//! building discovery layers outside the ILS.
+
+use crate::dedup::normalize_isbn;
+use crate::editor::Editor;
+use crate::marc;
+use crate::util;
+use json::JsonValue;
+use std::collections::HashMap;
+
+/// Copy statuses counted as available: Available and Reshelving.
+const AVAILABLE_STATUSES: &[i64] = &[0, 7];
+
+const HTTP_TIMEOUT: u64 = 60;
+
+/// Aggregate holdings for one bib record.
+#[derive(Debug, Clone, Default)]
+pub struct HoldingsSummary {
+    pub total_copies: usize,
+    pub available_copies: usize,
+    pub libraries: Vec<String>,
+}
+
+/// Build the index document for one bib record.
+pub fn bib_document(
+    bib_id: i64,
+    bre: &JsonValue,
+    record: &marc::Record,
+    holdings: &HoldingsSummary,
+) -> JsonValue {
+    let title = record
+        .first_field("245")
+        .map(|f| {
+            let mut parts = Vec::new();
+            parts.extend(f.get_subfields("a"));
+            parts.extend(f.get_subfields("b"));
+            parts.join(" ")
+        })
+        .unwrap_or_default();
+
+    let author = ["100", "110", "111"]
+        .iter()
+        .find_map(|tag| record.first_field(tag))
+        .and_then(|f| f.first_subfield("a"))
+        .unwrap_or("")
+        .to_string();
+
+    let isbns: Vec<String> = record
+        .get_values("020", "a")
+        .iter()
+        .filter_map(|v| normalize_isbn(v))
+        .collect();
+
+    let mut subjects = Vec::new();
+    for tag in ["600", "610", "611", "630", "650", "651", "655"] {
+        for value in record.get_values(tag, "a") {
+            subjects.push(value.to_string());
+        }
+    }
+
+    let publisher = ["260", "264"]
+        .iter()
+        .find_map(|tag| record.first_field(tag))
+        .and_then(|f| f.first_subfield("b"))
+        .unwrap_or("")
+        .to_string();
+
+    let pub_year: String = ["260", "264"]
+        .iter()
+        .find_map(|tag| record.first_field(tag))
+        .and_then(|f| f.first_subfield("c"))
+        .unwrap_or("")
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .take(4)
+        .collect();
+
+    json::object! {
+        id: bib_id,
+        title: title,
+        author: author,
+        isbns: isbns,
+        subjects: subjects,
+        publisher: publisher,
+        pub_year: pub_year,
+        create_date: bre["create_date"].as_str().unwrap_or(""),
+        edit_date: bre["edit_date"].as_str().unwrap_or(""),
+        holdings: {
+            total_copies: holdings.total_copies,
+            available_copies: holdings.available_copies,
+            libraries: holdings.libraries.clone(),
+        },
+    }
+}
+
+/// One bulk API operation.
+#[derive(Debug)]
+pub enum BulkOp {
+    Index { id: i64, doc: JsonValue },
+    Delete { id: i64 },
+}
+
+/// Render operations as a bulk API NDJSON payload.
+pub fn bulk_payload(index: &str, ops: &[BulkOp]) -> String {
+    let mut payload = String::new();
+
+    for op in ops {
+        match op {
+            BulkOp::Index { id, doc } => {
+                let mut action = json::object! {index: {"_id": *id}};
+                action["index"]["_index"] = index.into();
+                payload += &format!("{}\n{}\n", action.dump(), doc.dump());
+            }
+            BulkOp::Delete { id } => {
+                let mut action = json::object! {delete: {"_id": *id}};
+                action["delete"]["_index"] = index.into();
+                payload += &format!("{}\n", action.dump());
+            }
+        }
+    }
+
+    payload
+}
+
+/// A minimal Elasticsearch/OpenSearch bulk client.
+pub struct SearchEngine {
+    base_url: String,
+    index: String,
+}
+
+impl SearchEngine {
+    pub fn new(base_url: &str, index: &str) -> Self {
+        SearchEngine {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            index: index.to_string(),
+        }
+    }
+
+    pub fn index(&self) -> &str {
+        &self.index
+    }
+
+    /// Ship a batch of operations via the bulk API.
+    pub fn bulk(&self, ops: &[BulkOp]) -> Result<(), String> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+
+        let payload = bulk_payload(&self.index, ops);
+        let url = format!("{}/_bulk", self.base_url);
+
+        let response = ureq::post(&url)
+            .timeout(std::time::Duration::from_secs(HTTP_TIMEOUT))
+            .set("Content-Type", "application/x-ndjson")
+            .send_string(&payload)
+            .map_err(|e| format!("Bulk request to {url} failed: {e}"))?;
+
+        let text = response
+            .into_string()
+            .map_err(|e| format!("Error reading bulk response: {e}"))?;
+
+        let parsed = json::parse(&text).map_err(|e| format!("Invalid bulk response: {e}"))?;
+
+        if util::json_bool(&parsed["errors"]) {
+            return Err(format!("Bulk request reported errors: {text}"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Counters for one export run.
+#[derive(Debug, Clone, Default)]
+pub struct IndexCounts {
+    pub records_examined: usize,
+    pub records_indexed: usize,
+    pub records_deleted: usize,
+    pub errors: usize,
+}
+
+/// Streams bib records into the search engine.
+pub struct Indexer {
+    editor: Editor,
+    engine: SearchEngine,
+    counts: IndexCounts,
+    /// Operations are shipped in bulk batches this large.
+    batch_size: usize,
+    pending: Vec<BulkOp>,
+    /// Org shortname cache for holdings summaries.
+    org_names: HashMap<i64, String>,
+}
+
+impl Indexer {
+    pub fn new(editor: Editor, engine: SearchEngine) -> Self {
+        Indexer {
+            editor,
+            engine,
+            counts: IndexCounts::default(),
+            batch_size: 100,
+            pending: Vec::new(),
+            org_names: HashMap::new(),
+        }
+    }
+
+    pub fn counts(&self) -> &IndexCounts {
+        &self.counts
+    }
+
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Export bib records.  A full run indexes every non-deleted
+    /// record; an incremental run covers records edited since the
+    /// given timestamp, deleting the ones that went away.
+    pub fn run(&mut self, modified_since: Option<&str>) -> Result<(), String> {
+        let filter = match modified_since {
+            Some(since) => json::object! {id: {">": 0}, edit_date: {">=": since}},
+            None => json::object! {id: {">": 0}, deleted: "f"},
+        };
+
+        let bibs = self.editor.search("bre", filter)?;
+
+        log::info!("Indexer examining {} bib records", bibs.len());
+
+        for bre in bibs {
+            self.counts.records_examined += 1;
+
+            let bib_id = util::json_int(&bre["id"])?;
+
+            if let Err(e) = self.process_bib(bib_id, bre) {
+                self.counts.errors += 1;
+                log::error!("Error indexing bib {bib_id}: {e}");
+            }
+        }
+
+        self.flush()
+    }
+
+    fn process_bib(&mut self, bib_id: i64, bre: JsonValue) -> Result<(), String> {
+        if util::json_bool(&bre["deleted"]) {
+            self.push(BulkOp::Delete { id: bib_id })?;
+            self.counts.records_deleted += 1;
+            return Ok(());
+        }
+
+        let marc_xml = util::json_string(&bre["marc"])?;
+        let record = marc::Record::from_xml(&marc_xml)?;
+        let holdings = self.holdings_summary(bib_id)?;
+
+        let doc = bib_document(bib_id, &bre, &record, &holdings);
+
+        self.push(BulkOp::Index { id: bib_id, doc })?;
+        self.counts.records_indexed += 1;
+
+        Ok(())
+    }
+
+    /// Tally the record's copies and the libraries holding them.
+    fn holdings_summary(&mut self, bib_id: i64) -> Result<HoldingsSummary, String> {
+        let call_numbers = self
+            .editor
+            .search("acn", json::object! {record: bib_id, deleted: "f"})?;
+
+        let mut cn_ids = Vec::new();
+        for cn in &call_numbers {
+            cn_ids.push(util::json_int(&cn["id"])?);
+        }
+
+        let mut summary = HoldingsSummary::default();
+
+        if cn_ids.is_empty() {
+            return Ok(summary);
+        }
+
+        let copies = self.editor.search(
+            "acp",
+            json::object! {call_number: cn_ids, deleted: "f"},
+        )?;
+
+        for copy in &copies {
+            summary.total_copies += 1;
+
+            let status = util::json_int(&copy["status"]).unwrap_or(-1);
+            if AVAILABLE_STATUSES.contains(&status) {
+                summary.available_copies += 1;
+            }
+
+            let org_id = util::json_int(&copy["circ_lib"])?;
+            let name = self.org_shortname(org_id)?;
+            if !summary.libraries.contains(&name) {
+                summary.libraries.push(name);
+            }
+        }
+
+        summary.libraries.sort();
+
+        Ok(summary)
+    }
+
+    fn org_shortname(&mut self, org_id: i64) -> Result<String, String> {
+        if let Some(name) = self.org_names.get(&org_id) {
+            return Ok(name.clone());
+        }
+
+        let name = match self.editor.retrieve("aou", json::from(org_id))? {
+            Some(org) => org["shortname"].as_str().unwrap_or("").to_string(),
+            None => String::new(),
+        };
+
+        self.org_names.insert(org_id, name.clone());
+        Ok(name)
+    }
+
+    fn push(&mut self, op: BulkOp) -> Result<(), String> {
+        self.pending.push(op);
+
+        if self.pending.len() >= self.batch_size {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Ship any buffered operations.
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let ops = std::mem::take(&mut self.pending);
+        self.engine.bulk(&ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_XML: &str = r#"<record xmlns="http://www.loc.gov/MARC21/slim">
+        <leader>00000cam a2200000 a 4500</leader>
+        <controlfield tag="001">12345</controlfield>
+        <datafield tag="020" ind1=" " ind2=" ">
+            <subfield code="a">978-0-19-852663-9 (hbk.)</subfield>
+        </datafield>
+        <datafield tag="100" ind1="1" ind2=" ">
+            <subfield code="a">Melville, Herman,</subfield>
+        </datafield>
+        <datafield tag="245" ind1="1" ind2="0">
+            <subfield code="a">Moby Dick :</subfield>
+            <subfield code="b">or, the whale</subfield>
+        </datafield>
+        <datafield tag="260" ind1=" " ind2=" ">
+            <subfield code="b">Harper,</subfield>
+            <subfield code="c">1851.</subfield>
+        </datafield>
+        <datafield tag="650" ind1=" " ind2="0">
+            <subfield code="a">Whaling</subfield>
+        </datafield>
+    </record>"#;
+
+    #[test]
+    fn test_bib_document() {
+        let record = marc::Record::from_xml(TEST_XML).unwrap();
+        let bre = json::object! {
+            create_date: "2026-01-01T00:00:00+0000",
+            edit_date: "2026-06-01T00:00:00+0000",
+        };
+        let holdings = HoldingsSummary {
+            total_copies: 3,
+            available_copies: 2,
+            libraries: vec!["BR1".to_string(), "BR2".to_string()],
+        };
+
+        let doc = bib_document(42, &bre, &record, &holdings);
+
+        assert_eq!(doc["id"], 42);
+        assert_eq!(doc["title"], "Moby Dick : or, the whale");
+        assert_eq!(doc["author"], "Melville, Herman,");
+        assert_eq!(doc["isbns"][0], "9780198526639");
+        assert_eq!(doc["subjects"][0], "Whaling");
+        assert_eq!(doc["publisher"], "Harper,");
+        assert_eq!(doc["pub_year"], "1851");
+        assert_eq!(doc["holdings"]["total_copies"], 3);
+        assert_eq!(doc["holdings"]["libraries"][1], "BR2");
+    }
+
+    #[test]
+    fn test_bulk_payload() {
+        let ops = vec![
+            BulkOp::Index {
+                id: 1,
+                doc: json::object! {title: "A"},
+            },
+            BulkOp::Delete { id: 2 },
+        ];
+
+        let payload = bulk_payload("bibs", &ops);
+        let lines: Vec<&str> = payload.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            json::parse(lines[0]).unwrap()["index"]["_index"],
+            "bibs"
+        );
+        assert_eq!(json::parse(lines[1]).unwrap()["title"], "A");
+        assert_eq!(json::parse(lines[2]).unwrap()["delete"]["_id"], 2);
+        assert!(payload.ends_with('\n'));
+    }
+}
@@ -0,0 +1,111 @@
+//! Shared setup for binaries: load the IDL, connect to the bus, and
+//! wire the IDL serializer into the client.
+
+use crate::idl;
+use crate::osrf::{Client, ClientConfig, DataSerializer};
+use std::env;
+use std::sync::Arc;
+
+const DEFAULT_IDL_FILE: &str = "/openils/conf/fm_IDL.xml";
+
+/// Seconds to wait for opensrf.settings replies.
+const SETTINGS_TIMEOUT: u64 = 10;
+
+/// Everything a connected program needs to get going.
+pub struct Context {
+    client: Client,
+    idl: Arc<idl::Parser>,
+    config: ClientConfig,
+}
+
+impl Context {
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn idl(&self) -> &Arc<idl::Parser> {
+        &self.idl
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+}
+
+/// Returns the local IDL file path.
+pub fn idl_file() -> String {
+    env::var("OILS_IDL_FILE").unwrap_or_else(|_| DEFAULT_IDL_FILE.to_string())
+}
+
+/// The IDL location opensrf.settings publishes for this host, if the
+/// service knows one.
+///
+/// The hostname comes from OSRF_HOSTNAME or HOSTNAME; without one we
+/// fall back to the cluster default config.
+fn settings_idl_path(client: &Client) -> Option<String> {
+    let hostname = env::var("OSRF_HOSTNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .unwrap_or_default();
+
+    let (method, params) = if hostname.is_empty() {
+        ("opensrf.settings.default_config.get", Vec::new())
+    } else {
+        (
+            "opensrf.settings.host_config.get",
+            vec![json::from(hostname.as_str())],
+        )
+    };
+
+    let session = client.session("opensrf.settings");
+    let mut req = session.request(method, params).ok()?;
+    let config = req.recv(SETTINGS_TIMEOUT).ok()??;
+
+    config["IDL"].as_str().map(|p| p.to_string())
+}
+
+/// Load and parse the IDL from a local path or an http(s) URL.
+pub fn load_idl_source(source: &str) -> Result<Arc<idl::Parser>, String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let body = ureq::get(source)
+            .call()
+            .map_err(|e| format!("Cannot fetch IDL from {source}: {e}"))?
+            .into_string()
+            .map_err(|e| format!("Cannot read IDL from {source}: {e}"))?;
+
+        idl::Parser::parse_string(&body)
+    } else {
+        idl::Parser::parse_file(source)
+    }
+}
+
+/// Connect and load the IDL using environment-provided settings.
+///
+/// Setting OSRF_GATEWAY_URL routes requests through an HTTP gateway
+/// endpoint instead of the bus.
+pub fn init() -> Result<Context, String> {
+    init_with_config(ClientConfig::from_env())
+}
+
+/// Connect to the bus and load the IDL using the provided settings.
+pub fn init_with_config(config: ClientConfig) -> Result<Context, String> {
+    let client = Client::connect(&config)?;
+
+    // Prefer a locally configured IDL; nodes without one ask the
+    // settings server where the cluster keeps it.
+    let source = match env::var("OILS_IDL_FILE") {
+        Ok(file) => file,
+        Err(_) => settings_idl_path(&client).unwrap_or_else(|| DEFAULT_IDL_FILE.to_string()),
+    };
+
+    let idl = load_idl_source(&source)?;
+    client.set_serializer(idl.clone() as Arc<dyn DataSerializer>);
+
+    // Make the IDL reachable without threading the Arc everywhere.
+    idl::set(idl.clone());
+
+    Ok(Context {
+        client,
+        idl,
+        config,
+    })
+}
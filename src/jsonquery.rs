@@ -0,0 +1,791 @@
+//! Compile cstore-style json_query structures into SQL.
+//!
+//! Accepts the standard Evergreen json_query shape -- select, from
+//! (with a join tree), where, order_by, limit, offset -- and produces
+//! SQL against IDL-mapped tables.  String values become positional
+//! parameters; numbers, booleans, and nulls are compiled inline.
+//! Identifiers (tables, columns, aliases) are validated against the
+//! IDL, never copied from the query verbatim.
+
+use crate::idl;
+use crate::idl::RelType;
+use json::JsonValue;
+use std::sync::Arc;
+
+/// Comparison operators supported in where clauses, lowercase.
+const SUPPORTED_OPERATORS: &[&str] = &[
+    "=", "!=", "<>", "<", "<=", ">", ">=", "like", "ilike", "~", "~*", "!~", "!~*", "in", "not in",
+    "between", "is", "is not",
+];
+
+/// One class participating in a query under a unique alias.
+///
+/// The alias defaults to the classname; a join spec may override it,
+/// which is how the same class joins into a query more than once.
+struct SourceClass {
+    alias: String,
+    classname: String,
+}
+
+/// Compiles one json_query at a time; reusable across queries.
+pub struct JsonQueryCompiler {
+    idl: Arc<idl::Parser>,
+    sources: Vec<SourceClass>,
+    params: Vec<String>,
+}
+
+impl JsonQueryCompiler {
+    pub fn new(idl: Arc<idl::Parser>) -> Self {
+        JsonQueryCompiler {
+            idl,
+            sources: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Positional parameter values for the most recently compiled
+    /// query, in $1..$n order.
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    /// Compile a json_query into SQL.
+    ///
+    /// The returned string contains $n placeholders for every string
+    /// value in the query; bind params() when executing.
+    pub fn compile(&mut self, query: &JsonValue) -> Result<String, String> {
+        if !query.is_object() {
+            return Err(format!("json_query must be an object: {}", query.dump()));
+        }
+
+        self.sources.clear();
+        self.params.clear();
+
+        // FROM first so select/where/order_by can see every source.
+        let from = self.compile_from(&query["from"])?;
+        let select = self.compile_select(&query["select"])?;
+
+        let mut sql = format!("SELECT {select} FROM {from}");
+
+        if !query["where"].is_null() {
+            let core = self.core_alias();
+            let tests = self.compile_where_for_alias(&core, &query["where"], "AND")?;
+            sql += &format!(" WHERE {tests}");
+        }
+
+        if !query["order_by"].is_null() {
+            sql += &format!(" ORDER BY {}", self.compile_order_by(&query["order_by"])?);
+        }
+
+        if !query["limit"].is_null() {
+            let limit = query["limit"]
+                .as_u64()
+                .ok_or_else(|| format!("Invalid limit: {}", query["limit"].dump()))?;
+            sql += &format!(" LIMIT {limit}");
+        }
+
+        if !query["offset"].is_null() {
+            let offset = query["offset"]
+                .as_u64()
+                .ok_or_else(|| format!("Invalid offset: {}", query["offset"].dump()))?;
+            sql += &format!(" OFFSET {offset}");
+        }
+
+        Ok(sql)
+    }
+
+    /// The alias of the core (first "from") class.
+    fn core_alias(&self) -> String {
+        self.sources
+            .first()
+            .map(|s| s.alias.clone())
+            .expect("compile_from registers the core class first")
+    }
+
+    fn find_source(&self, alias: &str) -> Result<&SourceClass, String> {
+        self.sources
+            .iter()
+            .find(|s| s.alias == alias)
+            .ok_or_else(|| format!("Query references unjoined class/alias: {alias}"))
+    }
+
+    /// Register a participating class, verifying it exists, maps to a
+    /// table, and its alias is a unique plain identifier.
+    fn add_source(&mut self, classname: &str, alias: &str) -> Result<(), String> {
+        let class = self
+            .idl
+            .get_class(classname)
+            .ok_or_else(|| format!("No such IDL class: {classname}"))?;
+
+        if class.tablename().is_none() {
+            return Err(format!("Class {classname} has no table"));
+        }
+
+        let valid = !alias.is_empty()
+            && !alias.starts_with(|c: char| c.is_ascii_digit())
+            && alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if !valid {
+            return Err(format!("Invalid alias: {alias}"));
+        }
+
+        if self.sources.iter().any(|s| s.alias == alias) {
+            return Err(format!("Duplicate alias in query: {alias}"));
+        }
+
+        self.sources.push(SourceClass {
+            alias: alias.to_string(),
+            classname: classname.to_string(),
+        });
+
+        Ok(())
+    }
+
+    fn tablename(&self, classname: &str) -> String {
+        self.idl
+            .get_class(classname)
+            .and_then(|c| c.tablename())
+            .expect("add_source verified the table")
+            .to_string()
+    }
+
+    /// The quoted-alias column reference for a field, verifying the
+    /// field exists on the aliased class and is not virtual.
+    fn column_ref(&self, alias: &str, fieldname: &str) -> Result<String, String> {
+        let classname = &self.find_source(alias)?.classname;
+
+        let class = self
+            .idl
+            .get_class(classname)
+            .expect("sources hold verified classnames");
+
+        let field = class
+            .fields()
+            .get(fieldname)
+            .ok_or_else(|| format!("No such field: {classname}.{fieldname}"))?;
+
+        if field.is_virtual() {
+            return Err(format!("Cannot query virtual field {classname}.{fieldname}"));
+        }
+
+        Ok(format!("\"{alias}\".{fieldname}"))
+    }
+
+    fn add_param(&mut self, value: &str) -> String {
+        self.params.push(value.to_string());
+        format!("${}", self.params.len())
+    }
+
+    // ------------------------------------------------------------------
+    // FROM / joins
+
+    /// Compile the from clause: a bare classname string or a
+    /// one-entry object mapping the core class to its join tree.
+    fn compile_from(&mut self, from: &JsonValue) -> Result<String, String> {
+        if let Some(classname) = from.as_str() {
+            self.add_source(classname, classname)?;
+            return Ok(format!("{} AS \"{classname}\"", self.tablename(classname)));
+        }
+
+        if !from.is_object() || from.len() != 1 {
+            return Err(format!(
+                "json_query from must be a classname or a one-entry object: {}",
+                from.dump()
+            ));
+        }
+
+        let (classname, joins) = from.entries().next().expect("length checked above");
+
+        self.add_source(classname, classname)?;
+        let mut sql = format!("{} AS \"{classname}\"", self.tablename(classname));
+
+        sql += &self.compile_joins(classname, joins)?;
+
+        Ok(sql)
+    }
+
+    /// Compile a join tree hanging off the aliased parent class.
+    ///
+    /// Each entry joins one class: a bare classname string, or
+    /// {classname: spec} where the spec may carry "type", "alias",
+    /// "field" (on the joined class), "fkey" (on the parent), a
+    /// "filter" of extra ON conditions, and a nested "join".  Absent
+    /// field/fkey are inferred from IDL links.
+    fn compile_joins(&mut self, parent_alias: &str, joins: &JsonValue) -> Result<String, String> {
+        if let Some(classname) = joins.as_str() {
+            let mut tree = JsonValue::new_object();
+            tree[classname] = JsonValue::new_object();
+            return self.compile_joins(parent_alias, &tree);
+        }
+
+        if !joins.is_object() {
+            return Err(format!("Invalid join tree: {}", joins.dump()));
+        }
+
+        let parent_classname = self.find_source(parent_alias)?.classname.clone();
+        let mut sql = String::new();
+
+        for (classname, spec) in joins.entries() {
+            if !spec.is_object() && !spec.is_null() {
+                return Err(format!("Invalid join spec for {classname}: {}", spec.dump()));
+            }
+
+            let alias = spec["alias"].as_str().unwrap_or(classname).to_string();
+            self.add_source(classname, &alias)?;
+
+            let join_type = match spec["type"].as_str() {
+                Some("left") => "LEFT JOIN",
+                Some("right") => "RIGHT JOIN",
+                Some("full") => "FULL OUTER JOIN",
+                Some("inner") | None => "JOIN",
+                Some(t) => return Err(format!("Unsupported join type: {t}")),
+            };
+
+            let (field, fkey) = match (spec["field"].as_str(), spec["fkey"].as_str()) {
+                (Some(field), Some(fkey)) => (field.to_string(), fkey.to_string()),
+                _ => self.infer_join_keys(&parent_classname, classname)?,
+            };
+
+            let mut on = format!(
+                "{} = {}",
+                self.column_ref(&alias, &field)?,
+                self.column_ref(parent_alias, &fkey)?
+            );
+
+            if !spec["filter"].is_null() {
+                let filter = self.compile_where_for_alias(&alias, &spec["filter"], "AND")?;
+                on += &format!(" AND {filter}");
+            }
+
+            sql += &format!(
+                " {join_type} {} AS \"{alias}\" ON ({on})",
+                self.tablename(classname)
+            );
+
+            if !spec["join"].is_null() {
+                sql += &self.compile_joins(&alias, &spec["join"])?;
+            }
+        }
+
+        Ok(sql)
+    }
+
+    /// Join keys from IDL link metadata: the field on the joined
+    /// class and the field on the parent it must equal.
+    ///
+    /// Links are checked in field-name order for determinism; the
+    /// parent's links win over the child's.
+    fn infer_join_keys(
+        &self,
+        parent_classname: &str,
+        child_classname: &str,
+    ) -> Result<(String, String), String> {
+        let parent = self
+            .idl
+            .get_class(parent_classname)
+            .expect("sources hold verified classnames");
+
+        let mut links: Vec<&idl::Link> = parent.links().values().collect();
+        links.sort_by_key(|link| link.field());
+
+        for link in links {
+            if link.class() != child_classname {
+                continue;
+            }
+            match link.reltype() {
+                RelType::HasA | RelType::MightHave => {
+                    return Ok((link.key().to_string(), link.field().to_string()));
+                }
+                RelType::HasMany => {
+                    let pkey = parent
+                        .pkey()
+                        .ok_or_else(|| format!("Class {parent_classname} has no primary key"))?;
+                    return Ok((link.key().to_string(), pkey.to_string()));
+                }
+                RelType::Unset => {}
+            }
+        }
+
+        // No parent link; look for a child link pointing back.
+        if let Some(child) = self.idl.get_class(child_classname) {
+            let mut links: Vec<&idl::Link> = child.links().values().collect();
+            links.sort_by_key(|link| link.field());
+
+            for link in links {
+                if link.class() == parent_classname && link.reltype() != RelType::HasMany {
+                    return Ok((link.field().to_string(), link.key().to_string()));
+                }
+            }
+        }
+
+        Err(format!(
+            "Cannot infer join keys from {parent_classname} to {child_classname}"
+        ))
+    }
+
+    // ------------------------------------------------------------------
+    // SELECT
+
+    /// Compile the select list: an object mapping class aliases to
+    /// field lists.  Absent, every real field of the core class is
+    /// selected.
+    fn compile_select(&mut self, select: &JsonValue) -> Result<String, String> {
+        if select.is_null() {
+            let core = self.core_alias();
+            return self.compile_select_star(&core);
+        }
+
+        if !select.is_object() {
+            return Err(format!("json_query select must be an object: {}", select.dump()));
+        }
+
+        let mut columns = Vec::new();
+
+        for (alias, list) in select.entries() {
+            if !list.is_array() {
+                return Err(format!("Invalid select list for {alias}: {}", list.dump()));
+            }
+
+            for item in list.members() {
+                if item.as_str() == Some("*") {
+                    columns.push(self.compile_select_star(alias)?);
+                } else {
+                    columns.push(self.compile_select_item(alias, item)?);
+                }
+            }
+        }
+
+        if columns.is_empty() {
+            return Err("json_query selects no columns".to_string());
+        }
+
+        Ok(columns.join(", "))
+    }
+
+    /// Every real field of the aliased class, in IDL order.
+    fn compile_select_star(&self, alias: &str) -> Result<String, String> {
+        let classname = &self.find_source(alias)?.classname;
+
+        let columns: Vec<String> = self
+            .idl
+            .get_class(classname)
+            .expect("sources hold verified classnames")
+            .fields_sorted_by_position()
+            .into_iter()
+            .filter(|f| !f.is_virtual())
+            .map(|f| format!("\"{alias}\".{}", f.name()))
+            .collect();
+
+        Ok(columns.join(", "))
+    }
+
+    /// One select entry: a field name or {"column": name} with
+    /// optional "alias" and "transform" (an SQL function to apply).
+    fn compile_select_item(&mut self, alias: &str, item: &JsonValue) -> Result<String, String> {
+        if let Some(fieldname) = item.as_str() {
+            return self.column_ref(alias, fieldname);
+        }
+
+        let fieldname = item["column"]
+            .as_str()
+            .ok_or_else(|| format!("Invalid select item: {}", item.dump()))?;
+
+        let mut column = self.column_ref(alias, fieldname)?;
+
+        if let Some(func) = item["transform"].as_str() {
+            let valid = !func.is_empty()
+                && func
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+            if !valid {
+                return Err(format!("Invalid transform: {func}"));
+            }
+            column = format!("{func}({column})");
+        }
+
+        if let Some(out) = item["alias"].as_str() {
+            if !out.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("Invalid column alias: {out}"));
+            }
+            column += &format!(" AS \"{out}\"");
+        }
+
+        Ok(column)
+    }
+
+    // ------------------------------------------------------------------
+    // WHERE
+
+    /// Compile a where (or join filter) block against a default
+    /// class alias.
+    ///
+    /// Object entries are field tests, "+alias" scopes, or the
+    /// "-or" / "-and" / "-not" combinators; array members are
+    /// AND-joined blocks.  Multi-part output comes back
+    /// parenthesized.
+    fn compile_where_for_alias(
+        &mut self,
+        alias: &str,
+        block: &JsonValue,
+        joiner: &str,
+    ) -> Result<String, String> {
+        let mut tests = Vec::new();
+
+        if block.is_array() {
+            for member in block.members() {
+                tests.push(self.compile_where_for_alias(alias, member, "AND")?);
+            }
+        } else if block.is_object() {
+            for (key, value) in block.entries() {
+                if let Some(scoped) = key.strip_prefix('+') {
+                    let compiled = self.compile_where_for_alias(scoped, value, "AND")?;
+                    tests.push(compiled);
+                } else if key == "-or" {
+                    tests.push(self.compile_where_for_alias(alias, value, "OR")?);
+                } else if key == "-and" {
+                    tests.push(self.compile_where_for_alias(alias, value, "AND")?);
+                } else if key == "-not" {
+                    let inner = self.compile_where_for_alias(alias, value, "AND")?;
+                    if inner.starts_with('(') {
+                        tests.push(format!("NOT {inner}"));
+                    } else {
+                        tests.push(format!("NOT ({inner})"));
+                    }
+                } else {
+                    tests.push(self.compile_field_test(alias, key, value)?);
+                }
+            }
+        } else {
+            return Err(format!("Invalid where block: {}", block.dump()));
+        }
+
+        if tests.is_empty() {
+            return Err(format!("Empty where block: {}", block.dump()));
+        }
+
+        if tests.len() == 1 {
+            Ok(tests.remove(0))
+        } else {
+            Ok(format!("({})", tests.join(&format!(" {joiner} "))))
+        }
+    }
+
+    /// One field test: null, a scalar (equality), an array (IN), or
+    /// an object of operator tests.
+    fn compile_field_test(
+        &mut self,
+        alias: &str,
+        fieldname: &str,
+        value: &JsonValue,
+    ) -> Result<String, String> {
+        let column = self.column_ref(alias, fieldname)?;
+
+        if value.is_null() {
+            return Ok(format!("{column} IS NULL"));
+        }
+
+        if value.is_array() {
+            return Ok(format!("{column} IN ({})", self.compile_operand_list(value)?));
+        }
+
+        if !value.is_object() {
+            let operand = self.compile_operand(value)?;
+            return Ok(format!("{column} = {operand}"));
+        }
+
+        // Column-reference equality: {"field": {"+alias": "other"}}.
+        if let Some(colref) = self.compile_column_operand(value)? {
+            return Ok(format!("{column} = {colref}"));
+        }
+
+        let mut tests = Vec::new();
+
+        for (op, operand) in value.entries() {
+            let op_lower = op.to_lowercase();
+
+            if !SUPPORTED_OPERATORS.contains(&op_lower.as_str()) {
+                return Err(format!("Unsupported operator: {op}"));
+            }
+
+            let op_sql = op_lower.to_uppercase();
+
+            let test = match op_lower.as_str() {
+                "in" | "not in" => {
+                    format!("{column} {op_sql} ({})", self.compile_operand_list(operand)?)
+                }
+                "between" => {
+                    if !operand.is_array() || operand.len() != 2 {
+                        return Err(format!("between requires two values: {}", operand.dump()));
+                    }
+                    format!(
+                        "{column} BETWEEN {} AND {}",
+                        self.compile_operand(&operand[0])?,
+                        self.compile_operand(&operand[1])?
+                    )
+                }
+                "is" | "is not" => {
+                    if !operand.is_null() {
+                        return Err(format!("{op} requires a null operand: {}", operand.dump()));
+                    }
+                    format!("{column} {op_sql} NULL")
+                }
+                _ => format!("{column} {op_sql} {}", self.compile_operand(operand)?),
+            };
+
+            tests.push(test);
+        }
+
+        if tests.is_empty() {
+            return Err(format!("Empty operand test for {fieldname}"));
+        }
+
+        if tests.len() == 1 {
+            Ok(tests.remove(0))
+        } else {
+            Ok(format!("({})", tests.join(" AND ")))
+        }
+    }
+
+    /// A comma-separated operand list for IN tests.
+    fn compile_operand_list(&mut self, list: &JsonValue) -> Result<String, String> {
+        if !list.is_array() || list.is_empty() {
+            return Err(format!("Operand list must be a non-empty array: {}", list.dump()));
+        }
+
+        let mut operands = Vec::new();
+        for member in list.members() {
+            operands.push(self.compile_operand(member)?);
+        }
+
+        Ok(operands.join(", "))
+    }
+
+    /// One operand value.  Numbers, booleans, and nulls compile
+    /// inline; strings become positional parameters; a one-entry
+    /// {"+alias": "field"} object compiles to a column reference.
+    fn compile_operand(&mut self, value: &JsonValue) -> Result<String, String> {
+        if let Some(colref) = self.compile_column_operand(value)? {
+            return Ok(colref);
+        }
+
+        match value {
+            JsonValue::Null => Ok("NULL".to_string()),
+            JsonValue::Boolean(b) => Ok(if *b { "TRUE" } else { "FALSE" }.to_string()),
+            JsonValue::Number(_) => Ok(format!("{value}")),
+            JsonValue::String(_) | JsonValue::Short(_) => {
+                Ok(self.add_param(value.as_str().expect("string variants have str values")))
+            }
+            _ => Err(format!("Cannot compile operand: {}", value.dump())),
+        }
+    }
+
+    /// The column reference for a {"+alias": "field"} operand, or
+    /// None when the value is not one.
+    fn compile_column_operand(&self, value: &JsonValue) -> Result<Option<String>, String> {
+        if !value.is_object() || value.len() != 1 {
+            return Ok(None);
+        }
+
+        let (key, field) = value.entries().next().expect("length checked above");
+
+        let alias = match key.strip_prefix('+') {
+            Some(alias) => alias,
+            None => return Ok(None),
+        };
+
+        let fieldname = field
+            .as_str()
+            .ok_or_else(|| format!("Invalid column reference: {}", value.dump()))?;
+
+        Ok(Some(self.column_ref(alias, fieldname)?))
+    }
+
+    // ------------------------------------------------------------------
+    // ORDER BY
+
+    /// Compile order_by: an array of {"class", "field", "direction"}
+    /// objects, or a hash of {alias: {field: direction}}.
+    fn compile_order_by(&mut self, order_by: &JsonValue) -> Result<String, String> {
+        let mut parts = Vec::new();
+
+        if order_by.is_array() {
+            for member in order_by.members() {
+                let alias = member["class"]
+                    .as_str()
+                    .map(|a| a.to_string())
+                    .unwrap_or_else(|| self.core_alias());
+
+                let fieldname = member["field"]
+                    .as_str()
+                    .ok_or_else(|| format!("order_by entry has no field: {}", member.dump()))?;
+
+                parts.push(self.compile_order_part(&alias, fieldname, &member["direction"])?);
+            }
+        } else if order_by.is_object() {
+            for (alias, fields) in order_by.entries() {
+                if !fields.is_object() {
+                    return Err(format!("Invalid order_by for {alias}: {}", fields.dump()));
+                }
+                for (fieldname, spec) in fields.entries() {
+                    let direction = if spec.is_object() {
+                        &spec["direction"]
+                    } else {
+                        spec
+                    };
+                    parts.push(self.compile_order_part(alias, fieldname, direction)?);
+                }
+            }
+        } else {
+            return Err(format!("Invalid order_by: {}", order_by.dump()));
+        }
+
+        if parts.is_empty() {
+            return Err("Empty order_by".to_string());
+        }
+
+        Ok(parts.join(", "))
+    }
+
+    fn compile_order_part(
+        &self,
+        alias: &str,
+        fieldname: &str,
+        direction: &JsonValue,
+    ) -> Result<String, String> {
+        let column = self.column_ref(alias, fieldname)?;
+
+        let descending = direction
+            .as_str()
+            .map(|d| d.to_lowercase().starts_with('d'))
+            .unwrap_or(false);
+
+        if descending {
+            Ok(format!("{column} DESC"))
+        } else {
+            Ok(column)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl;
+
+    fn compiler() -> JsonQueryCompiler {
+        let parser = idl::Parser::parse_string(idl::tests::TEST_IDL).expect("IDL parses");
+        JsonQueryCompiler::new(parser)
+    }
+
+    #[test]
+    fn test_compile_basic() {
+        let mut compiler = compiler();
+
+        let query = json::object! {
+            "select": {"aou": ["id", "name"]},
+            "from": "aou",
+            "where": {
+                "opac_visible": true,
+                "parent_ou": null,
+                "name": {"like": "%BR%"},
+            },
+            "order_by": [{"class": "aou", "field": "name", "direction": "desc"}],
+            "limit": 10,
+            "offset": 5,
+        };
+
+        let sql = compiler.compile(&query).expect("query compiles");
+
+        assert_eq!(
+            sql,
+            "SELECT \"aou\".id, \"aou\".name FROM actor.org_unit AS \"aou\" \
+            WHERE (\"aou\".opac_visible = TRUE AND \"aou\".parent_ou IS NULL \
+            AND \"aou\".name LIKE $1) ORDER BY \"aou\".name DESC LIMIT 10 OFFSET 5"
+        );
+        assert_eq!(compiler.params(), &["%BR%".to_string()]);
+    }
+
+    #[test]
+    fn test_compile_joins_and_combinators() {
+        let mut compiler = compiler();
+
+        let query = json::object! {
+            "select": {"aou": ["id"], "parent": [{"column": "name", "alias": "parent_name"}]},
+            "from": {
+                "aou": {
+                    "aou": {
+                        "type": "left",
+                        "alias": "parent",
+                        "field": "id",
+                        "fkey": "parent_ou",
+                    }
+                }
+            },
+            "where": {
+                "-or": [
+                    {"id": {"in": [1, 2, 3]}},
+                    {"+parent": {"opac_visible": true}},
+                ]
+            },
+        };
+
+        let sql = compiler.compile(&query).expect("query compiles");
+
+        assert_eq!(
+            sql,
+            "SELECT \"aou\".id, \"parent\".name AS \"parent_name\" \
+            FROM actor.org_unit AS \"aou\" \
+            LEFT JOIN actor.org_unit AS \"parent\" ON (\"parent\".id = \"aou\".parent_ou) \
+            WHERE (\"aou\".id IN (1, 2, 3) OR \"parent\".opac_visible = TRUE)"
+        );
+        assert!(compiler.params().is_empty());
+    }
+
+    #[test]
+    fn test_compile_inferred_join() {
+        let mut compiler = compiler();
+
+        // No field/fkey: the parent's first link to the class (by
+        // field name) decides -- here the has_many "children" link.
+        let query = json::object! {
+            "select": {"kids": ["id"]},
+            "from": {"aou": {"aou": {"alias": "kids"}}},
+        };
+
+        let sql = compiler.compile(&query).expect("query compiles");
+
+        assert_eq!(
+            sql,
+            "SELECT \"kids\".id FROM actor.org_unit AS \"aou\" \
+            JOIN actor.org_unit AS \"kids\" ON (\"kids\".parent_ou = \"aou\".id)"
+        );
+    }
+
+    #[test]
+    fn test_compile_rejects() {
+        let mut compiler = compiler();
+
+        // Unknown class.
+        assert!(compiler.compile(&json::object! {"from": "nope"}).is_err());
+
+        // Unknown field.
+        assert!(compiler
+            .compile(&json::object! {"from": "aou", "where": {"nope": 1}})
+            .is_err());
+
+        // Virtual field.
+        assert!(compiler
+            .compile(&json::object! {"from": "aou", "where": {"children": 1}})
+            .is_err());
+
+        // Unsupported operator.
+        assert!(compiler
+            .compile(&json::object! {"from": "aou", "where": {"id": {"@@": 1}}})
+            .is_err());
+
+        // Unjoined alias.
+        assert!(compiler
+            .compile(&json::object! {"from": "aou", "where": {"+au": {"id": 1}}})
+            .is_err());
+    }
+}
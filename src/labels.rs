@@ -0,0 +1,244 @@
+//! Spine and pocket label rendering for copies.
+//!
+//! Labels are rendered from Tera templates (see the notice module)
+//! fed with copy, call number, and bib data; call numbers are
+//! wrapped to each org's configured spine width.
+
+use crate::editor::Editor;
+use crate::idl;
+use crate::marc;
+use crate::notice::Renderer;
+use crate::osrf::client::Client;
+use crate::settings::Settings;
+use crate::util;
+use json::JsonValue;
+use std::sync::Arc;
+
+/// Org setting: characters per spine label line.
+const SPINE_WIDTH_SETTING: &str = "cat.spine.line.width";
+const DEFAULT_SPINE_WIDTH: usize = 8;
+
+/// Org setting: lines per spine label.
+const SPINE_LINES_SETTING: &str = "cat.spine.line.height";
+const DEFAULT_SPINE_LINES: usize = 9;
+
+/// One call number chunk per line, wrapped to the org's width.
+const DEFAULT_SPINE_TEMPLATE: &str = r#"{% for line in lines %}{{ line }}
+{% endfor %}"#;
+
+const DEFAULT_POCKET_TEMPLATE: &str = r#"{{ barcode }}
+{{ call_number }}
+{{ title }}
+{{ author }}
+"#;
+
+/// Wrap a call number for a spine label: split on whitespace, then
+/// chop any chunk longer than the line width.
+pub fn wrap_call_number(label: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+
+    for chunk in label.split_whitespace() {
+        let mut rest = chunk;
+        while rest.len() > width {
+            let (head, tail) = rest.split_at(width);
+            lines.push(head.to_string());
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            lines.push(rest.to_string());
+        }
+    }
+
+    lines
+}
+
+/// The label-relevant shape of one copy.
+#[derive(Debug, Clone, Default)]
+pub struct LabelData {
+    pub barcode: String,
+    pub call_number: String,
+    pub title: String,
+    pub author: String,
+    pub library: String,
+    pub owning_lib: i64,
+}
+
+impl LabelData {
+    /// The template context for this copy, with the call number
+    /// wrapped to the requested line shape.
+    pub fn context(&self, width: usize, max_lines: usize) -> JsonValue {
+        let mut lines = wrap_call_number(&self.call_number, width);
+        lines.truncate(max_lines.max(1));
+
+        json::object! {
+            barcode: self.barcode.as_str(),
+            call_number: self.call_number.as_str(),
+            lines: lines,
+            title: self.title.as_str(),
+            author: self.author.as_str(),
+            library: self.library.as_str(),
+        }
+    }
+}
+
+/// Renders spine and pocket labels for one authenticated session.
+pub struct Labeler {
+    editor: Editor,
+    settings: Settings,
+    renderer: Renderer,
+    spine_template: String,
+    pocket_template: String,
+}
+
+impl Labeler {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Labeler {
+            editor: Editor::with_auth(client, idl, authtoken),
+            settings: Settings::new(client, idl, authtoken),
+            renderer: Renderer::new(),
+            spine_template: DEFAULT_SPINE_TEMPLATE.to_string(),
+            pocket_template: DEFAULT_POCKET_TEMPLATE.to_string(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    pub fn set_spine_template(&mut self, template: &str) {
+        self.spine_template = template.to_string();
+    }
+
+    pub fn set_pocket_template(&mut self, template: &str) {
+        self.pocket_template = template.to_string();
+    }
+
+    /// Collect the label data for one copy: barcode, call number,
+    /// owning library, and the bib's title and author.
+    pub fn label_data(&mut self, copy_id: i64) -> Result<LabelData, String> {
+        let copy = match self.editor.retrieve("acp", json::from(copy_id))? {
+            Some(c) => c,
+            None => return Err(format!("No such copy: {copy_id}")),
+        };
+
+        let mut data = LabelData {
+            barcode: copy["barcode"].as_str().unwrap_or("").to_string(),
+            ..Default::default()
+        };
+
+        let cn_id = util::json_int(&copy["call_number"])?;
+        let call_number = match self.editor.retrieve("acn", json::from(cn_id))? {
+            Some(cn) => cn,
+            None => return Err(format!("No such call number: {cn_id}")),
+        };
+
+        data.call_number = call_number["label"].as_str().unwrap_or("").to_string();
+        data.owning_lib = util::json_int(&call_number["owning_lib"])?;
+
+        if let Some(org) = self
+            .editor
+            .retrieve("aou", call_number["owning_lib"].clone())?
+        {
+            data.library = org["shortname"].as_str().unwrap_or("").to_string();
+        }
+
+        let record_id = util::json_int(&call_number["record"])?;
+        if let Some(bre) = self.editor.retrieve("bre", json::from(record_id))? {
+            let marc_xml = util::json_string(&bre["marc"])?;
+            let record = marc::Record::from_xml(&marc_xml)?;
+
+            if let Some(field) = record.first_field("245") {
+                data.title = field.first_subfield("a").unwrap_or("").to_string();
+            }
+
+            data.author = ["100", "110", "111"]
+                .iter()
+                .find_map(|tag| record.first_field(tag))
+                .and_then(|field| field.first_subfield("a"))
+                .unwrap_or("")
+                .to_string();
+        }
+
+        Ok(data)
+    }
+
+    /// The owning org's spine line shape: (width, max lines).
+    fn spine_shape(&mut self, org_id: i64) -> Result<(usize, usize), String> {
+        let width = match util::json_int(&self.settings.ou_setting(org_id, SPINE_WIDTH_SETTING)?)
+        {
+            Ok(w) if w > 0 => w as usize,
+            _ => DEFAULT_SPINE_WIDTH,
+        };
+
+        let lines = match util::json_int(&self.settings.ou_setting(org_id, SPINE_LINES_SETTING)?)
+        {
+            Ok(l) if l > 0 => l as usize,
+            _ => DEFAULT_SPINE_LINES,
+        };
+
+        Ok((width, lines))
+    }
+
+    /// Render a spine label for a copy.
+    pub fn spine_label(&mut self, data: &LabelData) -> Result<String, String> {
+        let (width, lines) = self.spine_shape(data.owning_lib)?;
+        self.renderer
+            .render(&self.spine_template, &data.context(width, lines))
+    }
+
+    /// Render a pocket label for a copy.
+    pub fn pocket_label(&mut self, data: &LabelData) -> Result<String, String> {
+        let (width, lines) = self.spine_shape(data.owning_lib)?;
+        self.renderer
+            .render(&self.pocket_template, &data.context(width, lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_call_number() {
+        assert_eq!(
+            wrap_call_number("PS3563 .E537 M6 1988", 8),
+            vec!["PS3563", ".E537", "M6", "1988"]
+        );
+        assert_eq!(
+            wrap_call_number("FICTION MELVILLE", 6),
+            vec!["FICTIO", "N", "MELVIL", "LE"]
+        );
+        assert!(wrap_call_number("   ", 8).is_empty());
+    }
+
+    #[test]
+    fn test_default_templates_render() {
+        let data = LabelData {
+            barcode: "30000123456789".to_string(),
+            call_number: "PS3563 .E537".to_string(),
+            title: "Moby Dick".to_string(),
+            author: "Melville, Herman".to_string(),
+            library: "BR1".to_string(),
+            owning_lib: 4,
+        };
+
+        let renderer = Renderer::new();
+
+        let spine = renderer
+            .render(DEFAULT_SPINE_TEMPLATE, &data.context(8, 9))
+            .unwrap();
+        assert_eq!(spine, "PS3563\n.E537\n");
+
+        let pocket = renderer
+            .render(DEFAULT_POCKET_TEMPLATE, &data.context(8, 9))
+            .unwrap();
+        assert!(pocket.contains("30000123456789"));
+        assert!(pocket.contains("Moby Dick"));
+
+        // Line limits truncate, width wraps.
+        let context = data.context(4, 2);
+        assert_eq!(context["lines"].len(), 2);
+        assert_eq!(context["lines"][0], "PS35");
+    }
+}
@@ -0,0 +1,60 @@
+//! Evergreen ILS bindings and tools.
+//!
+//! Conventionally imported as `use evergreen as eg;`.
+
+pub mod acq;
+pub mod actor;
+pub mod auth;
+pub mod authority;
+pub mod barcode;
+pub mod booking;
+pub mod bucket;
+pub mod cache;
+pub mod calendar;
+pub mod circ;
+pub mod closing;
+pub mod course;
+pub mod dataset;
+pub mod date;
+pub mod db;
+pub mod dedup;
+pub mod edi;
+pub mod editor;
+pub mod error;
+pub mod event;
+pub mod exporter;
+pub mod feeds;
+pub mod fines;
+pub mod hatch;
+pub mod holds;
+pub mod idl;
+pub mod idldb;
+pub mod idldiff;
+pub mod idlgen;
+pub mod indexer;
+pub mod init;
+pub mod jsonquery;
+pub mod labels;
+pub mod marc;
+pub mod money;
+pub mod ncip;
+pub mod norm;
+pub mod notice;
+pub mod oai;
+pub mod org;
+pub mod osrf;
+pub mod patronload;
+pub mod penalty;
+pub mod perm;
+pub mod refdata;
+pub mod reporter;
+pub mod rest;
+pub mod search;
+pub mod serials;
+pub mod settings;
+pub mod targeter;
+pub mod trigger;
+pub mod upgrade;
+pub mod util;
+pub mod value;
+pub mod vandelay;
@@ -0,0 +1,367 @@
+//! Minimal MARCXML reading, mutation, and writing.
+
+const MARCXML_NAMESPACE: &str = "http://www.loc.gov/MARC21/slim";
+
+/// Escape a string for inclusion in XML text or attribute content.
+pub fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// A MARC subfield.
+#[derive(Debug, Clone)]
+pub struct Subfield {
+    code: String,
+    content: String,
+}
+
+impl Subfield {
+    pub fn new(code: &str, content: &str) -> Self {
+        Subfield {
+            code: code.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn set_content(&mut self, content: &str) {
+        self.content = content.to_string();
+    }
+}
+
+/// A MARC control field (tag < 010).
+#[derive(Debug, Clone)]
+pub struct Controlfield {
+    tag: String,
+    content: String,
+}
+
+impl Controlfield {
+    pub fn new(tag: &str, content: &str) -> Self {
+        Controlfield {
+            tag: tag.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn set_content(&mut self, content: &str) {
+        self.content = content.to_string();
+    }
+}
+
+/// A MARC data field.
+#[derive(Debug, Clone)]
+pub struct Field {
+    tag: String,
+    ind1: String,
+    ind2: String,
+    subfields: Vec<Subfield>,
+}
+
+impl Field {
+    pub fn new(tag: &str) -> Self {
+        Field {
+            tag: tag.to_string(),
+            ind1: " ".to_string(),
+            ind2: " ".to_string(),
+            subfields: Vec::new(),
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn ind1(&self) -> &str {
+        &self.ind1
+    }
+
+    pub fn ind2(&self) -> &str {
+        &self.ind2
+    }
+
+    pub fn set_ind1(&mut self, ind: &str) {
+        self.ind1 = ind.to_string();
+    }
+
+    pub fn set_ind2(&mut self, ind: &str) {
+        self.ind2 = ind.to_string();
+    }
+
+    pub fn subfields(&self) -> &Vec<Subfield> {
+        &self.subfields
+    }
+
+    pub fn subfields_mut(&mut self) -> &mut Vec<Subfield> {
+        &mut self.subfields
+    }
+
+    /// Content of every subfield with the requested code.
+    pub fn get_subfields(&self, code: &str) -> Vec<&str> {
+        self.subfields
+            .iter()
+            .filter(|sf| sf.code() == code)
+            .map(|sf| sf.content())
+            .collect()
+    }
+
+    /// Content of the first subfield with the requested code.
+    pub fn first_subfield(&self, code: &str) -> Option<&str> {
+        self.subfields
+            .iter()
+            .find(|sf| sf.code() == code)
+            .map(|sf| sf.content())
+    }
+
+    pub fn add_subfield(&mut self, code: &str, content: &str) {
+        self.subfields.push(Subfield::new(code, content));
+    }
+
+    /// Replace the first subfield with the requested code, or append
+    /// a new one.
+    pub fn set_subfield(&mut self, code: &str, content: &str) {
+        match self.subfields.iter_mut().find(|sf| sf.code() == code) {
+            Some(sf) => sf.set_content(content),
+            None => self.add_subfield(code, content),
+        }
+    }
+
+    pub fn remove_subfields(&mut self, code: &str) {
+        self.subfields.retain(|sf| sf.code() != code);
+    }
+}
+
+/// A MARC record.
+#[derive(Debug, Clone, Default)]
+pub struct Record {
+    leader: String,
+    control_fields: Vec<Controlfield>,
+    fields: Vec<Field>,
+}
+
+impl Record {
+    pub fn new() -> Self {
+        Record::default()
+    }
+
+    pub fn leader(&self) -> &str {
+        &self.leader
+    }
+
+    pub fn set_leader(&mut self, leader: &str) {
+        self.leader = leader.to_string();
+    }
+
+    pub fn control_fields(&self) -> &Vec<Controlfield> {
+        &self.control_fields
+    }
+
+    pub fn control_fields_mut(&mut self) -> &mut Vec<Controlfield> {
+        &mut self.control_fields
+    }
+
+    pub fn fields(&self) -> &Vec<Field> {
+        &self.fields
+    }
+
+    pub fn fields_mut(&mut self) -> &mut Vec<Field> {
+        &mut self.fields
+    }
+
+    /// All data fields with the requested tag.
+    pub fn get_fields(&self, tag: &str) -> Vec<&Field> {
+        self.fields.iter().filter(|f| f.tag() == tag).collect()
+    }
+
+    pub fn get_fields_mut(&mut self, tag: &str) -> Vec<&mut Field> {
+        self.fields.iter_mut().filter(|f| f.tag() == tag).collect()
+    }
+
+    pub fn first_field(&self, tag: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.tag() == tag)
+    }
+
+    /// Content of the first control field with the requested tag.
+    pub fn control_field(&self, tag: &str) -> Option<&str> {
+        self.control_fields
+            .iter()
+            .find(|f| f.tag() == tag)
+            .map(|f| f.content())
+    }
+
+    /// Subfield values for a tag + code combo across all matching
+    /// fields.
+    pub fn get_values(&self, tag: &str, code: &str) -> Vec<&str> {
+        let mut values = Vec::new();
+        for field in self.get_fields(tag) {
+            values.extend(field.get_subfields(code));
+        }
+        values
+    }
+
+    /// Add a data field, keeping fields sorted by tag.
+    pub fn insert_field(&mut self, field: Field) {
+        let pos = self
+            .fields
+            .iter()
+            .position(|f| f.tag() > field.tag())
+            .unwrap_or(self.fields.len());
+        self.fields.insert(pos, field);
+    }
+
+    /// Parse a MARCXML string.
+    pub fn from_xml(xml: &str) -> Result<Record, String> {
+        let doc = roxmltree::Document::parse(xml).map_err(|e| format!("Invalid MARCXML: {e}"))?;
+
+        let root = doc.root_element();
+
+        let record_node = if root.tag_name().name() == "record" {
+            root
+        } else {
+            root.descendants()
+                .find(|n| n.is_element() && n.tag_name().name() == "record")
+                .ok_or("MARCXML contains no record element")?
+        };
+
+        let mut record = Record::new();
+
+        for node in record_node.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "leader" => record.leader = node.text().unwrap_or("").to_string(),
+                "controlfield" => {
+                    if let Some(tag) = node.attribute("tag") {
+                        record
+                            .control_fields
+                            .push(Controlfield::new(tag, node.text().unwrap_or("")));
+                    }
+                }
+                "datafield" => {
+                    let tag = match node.attribute("tag") {
+                        Some(t) => t,
+                        None => continue,
+                    };
+
+                    let mut field = Field::new(tag);
+                    field.set_ind1(node.attribute("ind1").unwrap_or(" "));
+                    field.set_ind2(node.attribute("ind2").unwrap_or(" "));
+
+                    for sub in node
+                        .children()
+                        .filter(|n| n.is_element() && n.tag_name().name() == "subfield")
+                    {
+                        if let Some(code) = sub.attribute("code") {
+                            field.add_subfield(code, sub.text().unwrap_or(""));
+                        }
+                    }
+
+                    record.fields.push(field);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(record)
+    }
+
+    /// Serialize to a MARCXML string.
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!(r#"<record xmlns="{MARCXML_NAMESPACE}">"#);
+
+        if !self.leader.is_empty() {
+            xml += &format!("<leader>{}</leader>", escape_xml(&self.leader));
+        }
+
+        for cf in &self.control_fields {
+            xml += &format!(
+                r#"<controlfield tag="{}">{}</controlfield>"#,
+                escape_xml(cf.tag()),
+                escape_xml(cf.content())
+            );
+        }
+
+        for field in &self.fields {
+            xml += &format!(
+                r#"<datafield tag="{}" ind1="{}" ind2="{}">"#,
+                escape_xml(field.tag()),
+                escape_xml(field.ind1()),
+                escape_xml(field.ind2())
+            );
+
+            for sf in field.subfields() {
+                xml += &format!(
+                    r#"<subfield code="{}">{}</subfield>"#,
+                    escape_xml(sf.code()),
+                    escape_xml(sf.content())
+                );
+            }
+
+            xml += "</datafield>";
+        }
+
+        xml += "</record>";
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_XML: &str = r#"<record xmlns="http://www.loc.gov/MARC21/slim">
+        <leader>00000cam a2200000 a 4500</leader>
+        <controlfield tag="001">12345</controlfield>
+        <datafield tag="100" ind1="1" ind2=" ">
+            <subfield code="a">Melville, Herman,</subfield>
+            <subfield code="d">1819-1891.</subfield>
+        </datafield>
+        <datafield tag="245" ind1="1" ind2="0">
+            <subfield code="a">Moby Dick &amp; other tales</subfield>
+        </datafield>
+    </record>"#;
+
+    #[test]
+    fn test_xml_round_trip() {
+        let mut record = Record::from_xml(TEST_XML).expect("record should parse");
+
+        assert_eq!(record.control_field("001"), Some("12345"));
+        assert_eq!(
+            record.first_field("100").unwrap().first_subfield("a"),
+            Some("Melville, Herman,")
+        );
+        assert_eq!(record.get_values("245", "a"), vec!["Moby Dick & other tales"]);
+
+        for field in record.get_fields_mut("100") {
+            field.set_subfield("0", "999");
+        }
+
+        let xml = record.to_xml();
+        let record2 = Record::from_xml(&xml).expect("round trip should parse");
+        assert_eq!(
+            record2.first_field("100").unwrap().first_subfield("0"),
+            Some("999")
+        );
+        assert_eq!(
+            record2.get_values("245", "a"),
+            vec!["Moby Dick & other tales"]
+        );
+    }
+}
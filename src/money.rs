@@ -0,0 +1,229 @@
+//! Money handling and billing summaries.
+//!
+//! Amounts are fixed-point cents -- parsed from the decimal strings
+//! postgres numeric columns arrive as -- so repeated arithmetic
+//! cannot drift the way f64 does.  Also wraps the common
+//! money.billable_xact_summary lookups.
+
+use crate::editor::Editor;
+use crate::util;
+use json::JsonValue;
+use std::fmt;
+use std::ops::{Add, AddAssign, Neg, Sub};
+
+/// A money amount in cents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Money {
+    cents: i64,
+}
+
+impl Money {
+    pub fn from_cents(cents: i64) -> Money {
+        Money { cents }
+    }
+
+    pub fn zero() -> Money {
+        Money { cents: 0 }
+    }
+
+    pub fn cents(&self) -> i64 {
+        self.cents
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.cents == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.cents < 0
+    }
+
+    /// Parse a decimal amount string ("12.34", "-0.50", "10").
+    pub fn parse(value: &str) -> Result<Money, String> {
+        let value = value.trim();
+
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+
+        let (whole, frac) = match digits.split_once('.') {
+            Some((whole, frac)) => (whole, frac),
+            None => (digits, ""),
+        };
+
+        let whole: i64 = match whole {
+            "" => 0,
+            _ => whole
+                .parse()
+                .map_err(|_| format!("Invalid money amount: {value}"))?,
+        };
+
+        // Normalize the fraction to exactly two digits, rejecting
+        // sub-cent precision rather than silently rounding it.
+        let frac = format!("{frac:0<2}");
+        if frac.len() > 2 {
+            return Err(format!("Sub-cent precision in money amount: {value}"));
+        }
+        let frac: i64 = frac
+            .parse()
+            .map_err(|_| format!("Invalid money amount: {value}"))?;
+
+        let cents = whole * 100 + frac;
+
+        Ok(Money {
+            cents: if negative { -cents } else { cents },
+        })
+    }
+
+    /// Parse a JSON amount: a decimal string or a bare number.
+    pub fn from_json(value: &JsonValue) -> Result<Money, String> {
+        if let Some(text) = value.as_str() {
+            return Money::parse(text);
+        }
+        if value.is_number() {
+            return Money::parse(&value.dump());
+        }
+        Err(format!("Invalid money value: {}", value.dump()))
+    }
+
+    /// The decimal string APIs and billing rows expect ("12.34").
+    pub fn to_decimal(&self) -> String {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let cents = self.cents.abs();
+        format!("{sign}{}.{:02}", cents / 100, cents % 100)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_decimal())
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+    fn add(self, other: Money) -> Money {
+        Money::from_cents(self.cents + other.cents)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.cents += other.cents;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, other: Money) -> Money {
+        Money::from_cents(self.cents - other.cents)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money::from_cents(-self.cents)
+    }
+}
+
+/// Billing/payment totals for one billable transaction.
+#[derive(Debug, Clone, Default)]
+pub struct XactSummary {
+    pub xact_id: i64,
+    pub usr: i64,
+    pub xact_type: String,
+    pub total_owed: Money,
+    pub total_paid: Money,
+    pub balance_owed: Money,
+}
+
+impl XactSummary {
+    /// Build from an mbts (money.billable_xact_summary) row.
+    pub fn from_mbts(row: &JsonValue) -> Result<XactSummary, String> {
+        Ok(XactSummary {
+            xact_id: util::json_int(&row["id"])?,
+            usr: util::json_int(&row["usr"])?,
+            xact_type: row["xact_type"].as_str().unwrap_or("").to_string(),
+            total_owed: Money::from_json(&row["total_owed"])?,
+            total_paid: Money::from_json(&row["total_paid"])?,
+            balance_owed: Money::from_json(&row["balance_owed"])?,
+        })
+    }
+}
+
+/// Open billable transaction summaries for a user.
+pub fn open_user_xacts(editor: &mut Editor, usr_id: i64) -> Result<Vec<XactSummary>, String> {
+    let rows = editor.search(
+        "mbts",
+        json::object! {usr: usr_id, xact_finish: JsonValue::Null},
+    )?;
+
+    rows.iter().map(XactSummary::from_mbts).collect()
+}
+
+/// A user's total balance owed across open transactions.
+pub fn user_balance(editor: &mut Editor, usr_id: i64) -> Result<Money, String> {
+    let mut balance = Money::zero();
+    for xact in open_user_xacts(editor, usr_id)? {
+        balance += xact.balance_owed;
+    }
+    Ok(balance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(Money::parse("12.34").unwrap().cents(), 1234);
+        assert_eq!(Money::parse("10").unwrap().cents(), 1000);
+        assert_eq!(Money::parse("0.5").unwrap().cents(), 50);
+        assert_eq!(Money::parse("-0.50").unwrap().cents(), -50);
+        assert_eq!(Money::parse(".25").unwrap().cents(), 25);
+
+        assert!(Money::parse("12.345").is_err()); // sub-cent
+        assert!(Money::parse("twelve").is_err());
+
+        assert_eq!(Money::from_cents(1234).to_decimal(), "12.34");
+        assert_eq!(Money::from_cents(-50).to_decimal(), "-0.50");
+        assert_eq!(Money::from_cents(5).to_decimal(), "0.05");
+    }
+
+    #[test]
+    fn test_arithmetic_has_no_drift() {
+        // 0.1 + 0.2, the classic float trap.
+        let sum = Money::parse("0.10").unwrap() + Money::parse("0.20").unwrap();
+        assert_eq!(sum, Money::parse("0.30").unwrap());
+
+        let mut total = Money::zero();
+        for _ in 0..1000 {
+            total += Money::parse("0.10").unwrap();
+        }
+        assert_eq!(total.to_decimal(), "100.00");
+
+        let balance = Money::parse("5.00").unwrap() - Money::parse("7.25").unwrap();
+        assert!(balance.is_negative());
+        assert_eq!((-balance).to_decimal(), "2.25");
+    }
+
+    #[test]
+    fn test_from_json_and_mbts() {
+        assert_eq!(Money::from_json(&json::from("3.50")).unwrap().cents(), 350);
+        assert_eq!(Money::from_json(&json::from(3.5)).unwrap().cents(), 350);
+        assert!(Money::from_json(&JsonValue::Null).is_err());
+
+        let row = json::object! {
+            id: 42, usr: 7, xact_type: "circulation",
+            total_owed: "10.00", total_paid: "2.50", balance_owed: "7.50",
+        };
+        let summary = XactSummary::from_mbts(&row).unwrap();
+        assert_eq!(summary.balance_owed.to_decimal(), "7.50");
+        assert_eq!(
+            summary.total_owed - summary.total_paid,
+            summary.balance_owed
+        );
+    }
+}
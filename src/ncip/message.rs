@@ -0,0 +1,168 @@
+//! Parse inbound NCIP messages and build response documents.
+
+use crate::marc::escape_xml;
+use crate::ncip::{NCIP_NAMESPACE, NCIP_VERSION};
+
+/// The NCIP services we respond to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NcipService {
+    LookupUser,
+    CheckOutItem,
+    CheckInItem,
+    AcceptItem,
+    RequestItem,
+}
+
+impl NcipService {
+    pub fn from_element(name: &str) -> Option<Self> {
+        match name {
+            "LookupUser" => Some(Self::LookupUser),
+            "CheckOutItem" => Some(Self::CheckOutItem),
+            "CheckInItem" => Some(Self::CheckInItem),
+            "AcceptItem" => Some(Self::AcceptItem),
+            "RequestItem" => Some(Self::RequestItem),
+            _ => None,
+        }
+    }
+
+    /// Name of the response element for this service.
+    pub fn response_element(&self) -> &'static str {
+        match self {
+            Self::LookupUser => "LookupUserResponse",
+            Self::CheckOutItem => "CheckOutItemResponse",
+            Self::CheckInItem => "CheckInItemResponse",
+            Self::AcceptItem => "AcceptItemResponse",
+            Self::RequestItem => "RequestItemResponse",
+        }
+    }
+}
+
+/// A parsed inbound NCIP request.
+#[derive(Debug)]
+pub struct NcipRequest {
+    pub service: NcipService,
+    pub user_barcode: Option<String>,
+    pub item_barcode: Option<String>,
+    pub request_id: Option<String>,
+    pub request_type: Option<String>,
+    pub pickup_location: Option<String>,
+}
+
+impl NcipRequest {
+    /// Parse an NCIPMessage document into a request.
+    pub fn parse(xml: &str) -> Result<NcipRequest, String> {
+        let doc =
+            roxmltree::Document::parse(xml).map_err(|e| format!("Invalid NCIP XML: {e}"))?;
+
+        let root = doc.root_element();
+        if root.tag_name().name() != "NCIPMessage" {
+            return Err(format!(
+                "Expected NCIPMessage document, got {}",
+                root.tag_name().name()
+            ));
+        }
+
+        let service_node = root
+            .children()
+            .find(|n| n.is_element() && NcipService::from_element(n.tag_name().name()).is_some())
+            .ok_or("NCIPMessage contains no supported service element")?;
+
+        let service = NcipService::from_element(service_node.tag_name().name()).unwrap();
+
+        let text_of = |element: &str| -> Option<String> {
+            service_node
+                .descendants()
+                .find(|n| n.is_element() && n.tag_name().name() == element)
+                .and_then(|n| n.text())
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+        };
+
+        Ok(NcipRequest {
+            service,
+            user_barcode: text_of("UserIdentifierValue"),
+            item_barcode: text_of("ItemIdentifierValue"),
+            request_id: text_of("RequestIdentifierValue"),
+            request_type: text_of("RequestType"),
+            pickup_location: text_of("PickupLocation"),
+        })
+    }
+}
+
+/// Wrap response body XML in an NCIPMessage envelope.
+pub fn wrap_response(service: NcipService, body: &str) -> String {
+    let element = service.response_element();
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<NCIPMessage xmlns="{ns}" version="{version}">"#,
+            "<{element}>{body}</{element}>",
+            "</NCIPMessage>"
+        ),
+        ns = NCIP_NAMESPACE,
+        version = NCIP_VERSION,
+        element = element,
+        body = body,
+    )
+}
+
+/// Build a Problem response for a service.
+pub fn problem_response(service: NcipService, ptype: &str, detail: &str) -> String {
+    let body = format!(
+        concat!(
+            "<Problem>",
+            "<ProblemType>{}</ProblemType>",
+            "<ProblemDetail>{}</ProblemDetail>",
+            "</Problem>"
+        ),
+        escape_xml(ptype),
+        escape_xml(detail),
+    );
+
+    wrap_response(service, &body)
+}
+
+/// Build a UserId element.
+pub fn user_id_element(barcode: &str) -> String {
+    format!(
+        "<UserId><UserIdentifierValue>{}</UserIdentifierValue></UserId>",
+        escape_xml(barcode)
+    )
+}
+
+/// Build an ItemId element.
+pub fn item_id_element(barcode: &str) -> String {
+    format!(
+        "<ItemId><ItemIdentifierValue>{}</ItemIdentifierValue></ItemId>",
+        escape_xml(barcode)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOOKUP_USER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+    <NCIPMessage xmlns="http://www.niso.org/2008/ncip" version="2.0">
+      <LookupUser>
+        <UserId>
+          <UserIdentifierValue>23000012345678</UserIdentifierValue>
+        </UserId>
+      </LookupUser>
+    </NCIPMessage>"#;
+
+    #[test]
+    fn test_parse_lookup_user() {
+        let req = NcipRequest::parse(LOOKUP_USER).expect("message should parse");
+        assert_eq!(req.service, NcipService::LookupUser);
+        assert_eq!(req.user_barcode.as_deref(), Some("23000012345678"));
+        assert!(req.item_barcode.is_none());
+    }
+
+    #[test]
+    fn test_problem_response() {
+        let xml = problem_response(NcipService::CheckOutItem, "Unknown User", "No such patron");
+        assert!(xml.contains("<CheckOutItemResponse>"));
+        assert!(xml.contains("<ProblemType>Unknown User</ProblemType>"));
+    }
+}
@@ -0,0 +1,16 @@
+//! NCIP 2.0 responder support.
+//!
+//! Implements the subset of NCIP services resource-sharing networks
+//! typically require: LookupUser, CheckOutItem, CheckInItem,
+//! AcceptItem, and RequestItem.
+
+pub mod message;
+pub mod responder;
+
+pub use message::NcipRequest;
+pub use message::NcipService;
+pub use responder::Responder;
+
+pub const NCIP_VERSION: &str =
+    "http://www.niso.org/schemas/ncip/v2_0/imp1/xsd/ncip_v2_0.xsd";
+pub const NCIP_NAMESPACE: &str = "http://www.niso.org/2008/ncip";
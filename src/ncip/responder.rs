@@ -0,0 +1,364 @@
+//! Map NCIP requests to Evergreen API calls and build responses.
+
+use crate::editor::Editor;
+use crate::event::EgEvent;
+use crate::idl;
+use crate::marc::escape_xml;
+use crate::ncip::message;
+use crate::ncip::message::{NcipRequest, NcipService};
+use crate::osrf::Client;
+use crate::util;
+use json::JsonValue;
+use std::sync::Arc;
+
+const CIRC_TIMEOUT: u64 = 120;
+
+/// Handles NCIP requests on behalf of a logged-in staff account.
+pub struct Responder {
+    client: Client,
+    idl: Arc<idl::Parser>,
+    authtoken: String,
+    /// Org unit acting as the circulation and pickup location when
+    /// the message does not specify one.
+    work_ou: i64,
+}
+
+impl Responder {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str, work_ou: i64) -> Self {
+        Responder {
+            client: client.clone(),
+            idl: idl.clone(),
+            authtoken: authtoken.to_string(),
+            work_ou,
+        }
+    }
+
+    pub fn set_authtoken(&mut self, authtoken: &str) {
+        self.authtoken = authtoken.to_string();
+    }
+
+    fn editor(&self) -> Editor {
+        Editor::with_auth(&self.client, &self.idl, &self.authtoken)
+    }
+
+    /// Handle one inbound NCIP document, always producing a response
+    /// document.
+    pub fn handle(&mut self, xml: &str) -> String {
+        let request = match NcipRequest::parse(xml) {
+            Ok(r) => r,
+            Err(e) => {
+                log::warn!("Unparseable NCIP request: {e}");
+                return message::problem_response(
+                    NcipService::LookupUser,
+                    "Unsupported Service",
+                    &e,
+                );
+            }
+        };
+
+        log::info!("NCIP request: {:?}", request.service);
+
+        let result = match request.service {
+            NcipService::LookupUser => self.lookup_user(&request),
+            NcipService::CheckOutItem => self.checkout_item(&request),
+            NcipService::CheckInItem => self.checkin_item(&request),
+            NcipService::AcceptItem => self.accept_item(&request),
+            NcipService::RequestItem => self.request_item(&request),
+        };
+
+        match result {
+            Ok(body) => message::wrap_response(request.service, &body),
+            Err(e) => {
+                log::warn!("NCIP {:?} failed: {e}", request.service);
+                message::problem_response(request.service, "Processing Error", &e)
+            }
+        }
+    }
+
+    /// Call an open-ils.circ method and return its first response.
+    fn circ_request(&self, method: &str, params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        let session = self.client.session("open-ils.circ");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(CIRC_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Find a patron user object by barcode.
+    fn user_by_barcode(&self, barcode: &str) -> Result<Option<JsonValue>, String> {
+        let mut editor = self.editor();
+
+        let cards = editor.search("ac", json::object! {barcode: barcode})?;
+
+        let card = match cards.first() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        Ok(editor.retrieve("au", card["usr"].clone())?)
+    }
+
+    /// Find a copy object by barcode.
+    fn copy_by_barcode(&self, barcode: &str) -> Result<Option<JsonValue>, String> {
+        let mut editor = self.editor();
+
+        let copies = editor.search(
+            "acp",
+            json::object! {barcode: barcode, deleted: "f"},
+        )?;
+
+        Ok(copies.into_iter().next())
+    }
+
+    fn require_user_barcode<'a>(&self, request: &'a NcipRequest) -> Result<&'a str, String> {
+        request
+            .user_barcode
+            .as_deref()
+            .ok_or_else(|| "Message contains no UserIdentifierValue".to_string())
+    }
+
+    fn require_item_barcode<'a>(&self, request: &'a NcipRequest) -> Result<&'a str, String> {
+        request
+            .item_barcode
+            .as_deref()
+            .ok_or_else(|| "Message contains no ItemIdentifierValue".to_string())
+    }
+
+    fn lookup_user(&mut self, request: &NcipRequest) -> Result<String, String> {
+        let barcode = self.require_user_barcode(request)?;
+
+        let user = match self.user_by_barcode(barcode)? {
+            Some(u) => u,
+            None => return Ok(unknown_user_problem(barcode)),
+        };
+
+        let mut name_parts = Vec::new();
+        for field in ["first_given_name", "second_given_name", "family_name"] {
+            if let Some(part) = user[field].as_str() {
+                name_parts.push(escape_xml(part));
+            }
+        }
+
+        let mut body = message::user_id_element(barcode);
+
+        body += &format!(
+            concat!(
+                "<UserOptionalFields>",
+                "<NameInformation><PersonalNameInformation>",
+                "<UnstructuredPersonalUserName>{}</UnstructuredPersonalUserName>",
+                "</PersonalNameInformation></NameInformation>",
+                "</UserOptionalFields>"
+            ),
+            name_parts.join(" ")
+        );
+
+        if util::json_bool(&user["barred"]) || !util::json_bool(&user["active"]) {
+            body += concat!(
+                "<BlockOrTrap><BlockOrTrapType>",
+                "Block Check Out",
+                "</BlockOrTrapType></BlockOrTrap>"
+            );
+        }
+
+        Ok(body)
+    }
+
+    fn checkout_item(&mut self, request: &NcipRequest) -> Result<String, String> {
+        let user_barcode = self.require_user_barcode(request)?;
+        let item_barcode = self.require_item_barcode(request)?;
+
+        let resp = self.circ_request(
+            "open-ils.circ.checkout.full",
+            vec![
+                json::from(self.authtoken.as_str()),
+                json::object! {
+                    patron_barcode: user_barcode,
+                    copy_barcode: item_barcode,
+                },
+            ],
+        )?;
+
+        let evt = first_event(&resp)?;
+
+        if !evt.is_success() {
+            return Ok(event_problem("Item Not Checked Out", &evt));
+        }
+
+        let due_date = evt.payload()["circ"]["due_date"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+
+        let mut body = message::item_id_element(item_barcode);
+        body += &message::user_id_element(user_barcode);
+        body += &format!("<DateDue>{}</DateDue>", escape_xml(&due_date));
+
+        Ok(body)
+    }
+
+    fn checkin_item(&mut self, request: &NcipRequest) -> Result<String, String> {
+        let item_barcode = self.require_item_barcode(request)?;
+
+        let resp = self.circ_request(
+            "open-ils.circ.checkin",
+            vec![
+                json::from(self.authtoken.as_str()),
+                json::object! {copy_barcode: item_barcode},
+            ],
+        )?;
+
+        let evt = first_event(&resp)?;
+
+        // NO_CHANGE simply means the item was not circulating.
+        if !evt.is_success() && evt.textcode() != "NO_CHANGE" {
+            return Ok(event_problem("Item Not Checked In", &evt));
+        }
+
+        Ok(message::item_id_element(item_barcode))
+    }
+
+    /// Place a copy-level hold for a user.
+    fn place_copy_hold(&self, user: &JsonValue, copy: &JsonValue) -> Result<JsonValue, String> {
+        let requestor = user["id"].clone();
+
+        let hold = json::object! {
+            "_classname": "ahr",
+            usr: user["id"].clone(),
+            requestor: requestor,
+            hold_type: "C",
+            target: copy["id"].clone(),
+            pickup_lib: self.work_ou,
+        };
+
+        self.circ_request(
+            "open-ils.circ.holds.create",
+            vec![json::from(self.authtoken.as_str()), hold],
+        )
+    }
+
+    /// AcceptItem: an item from a lending institution has arrived for
+    /// one of our patrons.
+    ///
+    /// The item must already exist as a copy; precat copy creation is
+    /// not yet supported.
+    fn accept_item(&mut self, request: &NcipRequest) -> Result<String, String> {
+        let user_barcode = self.require_user_barcode(request)?;
+        let item_barcode = self.require_item_barcode(request)?;
+
+        let user = match self.user_by_barcode(user_barcode)? {
+            Some(u) => u,
+            None => return Ok(unknown_user_problem(user_barcode)),
+        };
+
+        let copy = match self.copy_by_barcode(item_barcode)? {
+            Some(c) => c,
+            None => return Ok(unknown_item_problem(item_barcode)),
+        };
+
+        let resp = self.place_copy_hold(&user, &copy)?;
+
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Ok(event_problem("Item Not Accepted", &evt));
+            }
+        }
+
+        let mut body = String::new();
+        if let Some(req_id) = &request.request_id {
+            body += &format!(
+                "<RequestId><RequestIdentifierValue>{}</RequestIdentifierValue></RequestId>",
+                escape_xml(req_id)
+            );
+        }
+        body += &message::item_id_element(item_barcode);
+
+        Ok(body)
+    }
+
+    fn request_item(&mut self, request: &NcipRequest) -> Result<String, String> {
+        let user_barcode = self.require_user_barcode(request)?;
+        let item_barcode = self.require_item_barcode(request)?;
+
+        let user = match self.user_by_barcode(user_barcode)? {
+            Some(u) => u,
+            None => return Ok(unknown_user_problem(user_barcode)),
+        };
+
+        let copy = match self.copy_by_barcode(item_barcode)? {
+            Some(c) => c,
+            None => return Ok(unknown_item_problem(item_barcode)),
+        };
+
+        let resp = self.place_copy_hold(&user, &copy)?;
+
+        let hold_id = match EgEvent::parse(&resp) {
+            Some(evt) => {
+                if !evt.is_success() {
+                    return Ok(event_problem("Item Not Requested", &evt));
+                }
+                util::json_int(evt.payload()).unwrap_or(0)
+            }
+            None => util::json_int(&resp)?,
+        };
+
+        let mut body = format!(
+            "<RequestId><RequestIdentifierValue>{hold_id}</RequestIdentifierValue></RequestId>"
+        );
+        body += &message::item_id_element(item_barcode);
+        body += &message::user_id_element(user_barcode);
+        body += &format!(
+            "<RequestType>{}</RequestType>",
+            escape_xml(request.request_type.as_deref().unwrap_or("Hold"))
+        );
+
+        Ok(body)
+    }
+}
+
+/// Extract the first event from a response that may be an event or an
+/// array of events.
+fn first_event(resp: &JsonValue) -> Result<EgEvent, String> {
+    let candidate = if resp.is_array() { &resp[0] } else { resp };
+
+    EgEvent::parse(candidate)
+        .ok_or_else(|| format!("Expected an event response, got: {}", candidate.dump()))
+}
+
+fn event_problem(ptype: &str, evt: &EgEvent) -> String {
+    format!(
+        concat!(
+            "<Problem>",
+            "<ProblemType>{}</ProblemType>",
+            "<ProblemDetail>{}</ProblemDetail>",
+            "</Problem>"
+        ),
+        escape_xml(ptype),
+        escape_xml(evt.textcode()),
+    )
+}
+
+fn unknown_user_problem(barcode: &str) -> String {
+    format!(
+        concat!(
+            "<Problem>",
+            "<ProblemType>Unknown User</ProblemType>",
+            "<ProblemDetail>No user with barcode {}</ProblemDetail>",
+            "</Problem>"
+        ),
+        escape_xml(barcode)
+    )
+}
+
+fn unknown_item_problem(barcode: &str) -> String {
+    format!(
+        concat!(
+            "<Problem>",
+            "<ProblemType>Unknown Item</ProblemType>",
+            "<ProblemDetail>No item with barcode {}</ProblemDetail>",
+            "</Problem>"
+        ),
+        escape_xml(barcode)
+    )
+}
@@ -0,0 +1,71 @@
+//! Text normalization routines for matching headings, titles, etc.
+
+/// Normalizes strings in the spirit of NACO normalization: uppercase,
+/// diacritics folded to ASCII, punctuation stripped, whitespace
+/// collapsed.
+pub struct Normalizer {}
+
+impl Normalizer {
+    pub fn new() -> Self {
+        Normalizer {}
+    }
+
+    /// Apply full normalization to a string.
+    pub fn naco_normalize(&self, value: &str) -> String {
+        let mut result = String::with_capacity(value.len());
+        let mut last_was_space = true; // trim leading whitespace
+
+        for ch in value.chars() {
+            let ch = Normalizer::fold_char(ch);
+
+            if ch.is_alphanumeric() {
+                for upper in ch.to_uppercase() {
+                    result.push(upper);
+                }
+                last_was_space = false;
+            } else if (ch.is_whitespace() || ch.is_ascii_punctuation()) && !last_was_space {
+                result.push(' ');
+                last_was_space = true;
+            }
+        }
+
+        result.trim_end().to_string()
+    }
+
+    /// Fold common Latin diacritics to their ASCII base characters.
+    fn fold_char(ch: char) -> char {
+        match ch {
+            'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' | 'Á' | 'À' | 'Â' | 'Ä' | 'Ã' | 'Å' => 'a',
+            'é' | 'è' | 'ê' | 'ë' | 'É' | 'È' | 'Ê' | 'Ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' | 'Í' | 'Ì' | 'Î' | 'Ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ø' | 'Ó' | 'Ò' | 'Ô' | 'Ö' | 'Õ' | 'Ø' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' | 'Ú' | 'Ù' | 'Û' | 'Ü' => 'u',
+            'ç' | 'Ç' => 'c',
+            'ñ' | 'Ñ' => 'n',
+            'ý' | 'ÿ' | 'Ý' => 'y',
+            'æ' | 'Æ' => 'a',
+            'œ' | 'Œ' => 'o',
+            'ß' => 's',
+            _ => ch,
+        }
+    }
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Normalizer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_naco_normalize() {
+        let norm = Normalizer::new();
+        assert_eq!(norm.naco_normalize("García Márquez, Gabriel"), "GARCIA MARQUEZ GABRIEL");
+        assert_eq!(norm.naco_normalize("  Dogs -- Fiction.  "), "DOGS FICTION");
+        assert_eq!(norm.naco_normalize("c'est la vie"), "C EST LA VIE");
+    }
+}
@@ -0,0 +1,316 @@
+//! Template-driven notice rendering and delivery.
+//!
+//! Renders email/SMS/print notices from Tera templates fed with
+//! Action/Trigger event data or ad-hoc datasets, and delivers email
+//! via SMTP with per-org sender configuration.
+
+use crate::editor::Editor;
+use crate::trigger;
+use crate::util;
+use json::JsonValue;
+use lettre::transport::smtp::Error as SmtpError;
+use lettre::{Message, SmtpTransport, Transport};
+
+const DEFAULT_SENDER_SETTING: &str = "org.noreply_email";
+
+
+/// Renders notice templates.
+pub struct Renderer {}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Renderer {}
+    }
+
+    /// Render a one-off template string with a JSON context.
+    pub fn render(&self, template: &str, context: &JsonValue) -> Result<String, String> {
+        let serde_value = util::json_to_serde(context);
+
+        let tera_context = tera::Context::from_serialize(serde_value)
+            .map_err(|e| format!("Invalid template context: {e}"))?;
+
+        tera::Tera::one_off(template, &tera_context, false)
+            .map_err(|e| format!("Template render failed: {e}"))
+    }
+
+    /// Build the standard context for an A/T event group: the event
+    /// definition, the events, and their core target objects.
+    pub fn context_from_events(
+        processor: &mut trigger::Processor,
+        def: &JsonValue,
+        events: &[JsonValue],
+    ) -> Result<JsonValue, String> {
+        let mut targets = json::array![];
+
+        for event in events {
+            let target = processor.target_object(def, event)?;
+            targets.push(target).expect("push to array succeeds");
+        }
+
+        Ok(json::object! {
+            event_def: def.clone(),
+            events: JsonValue::Array(events.to_vec()),
+            targets: targets,
+            target: events
+                .first()
+                .map(|e| e["target"].clone())
+                .unwrap_or(JsonValue::Null),
+        })
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer::new()
+    }
+}
+
+/// A rendered notice ready for delivery.
+#[derive(Debug, Clone)]
+pub struct Notice {
+    pub to: String,
+    pub from: Option<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Notice {
+    /// Parse a rendered email template of the classic Evergreen
+    /// form: header lines (To/From/Subject), a blank line, then the
+    /// body.
+    pub fn parse_email(rendered: &str) -> Result<Notice, String> {
+        let mut to = None;
+        let mut from = None;
+        let mut subject = String::new();
+
+        let mut lines = rendered.lines();
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+
+            match line.split_once(':') {
+                Some((name, value)) => match name.trim().to_lowercase().as_str() {
+                    "to" => to = Some(value.trim().to_string()),
+                    "from" => from = Some(value.trim().to_string()),
+                    "subject" => subject = value.trim().to_string(),
+                    _ => {} // ignore other headers
+                },
+                None => return Err(format!("Invalid notice header line: {line}")),
+            }
+        }
+
+        let body: Vec<&str> = lines.collect();
+
+        Ok(Notice {
+            to: to.ok_or("Notice has no To header")?,
+            from,
+            subject,
+            body: body.join("\n"),
+        })
+    }
+}
+
+/// The result of attempting delivery.
+#[derive(Debug)]
+pub enum SendOutcome {
+    Sent,
+    /// Permanent failure; do not retry this address.
+    Bounced(String),
+    /// Transient failure; retrying later may succeed.
+    Failed(String),
+}
+
+/// SMTP relay settings.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    /// Fallback sender when no org setting applies.
+    pub default_sender: String,
+}
+
+/// Delivers rendered email notices.
+pub struct EmailSender {
+    config: SmtpConfig,
+}
+
+impl EmailSender {
+    pub fn new(config: SmtpConfig) -> Self {
+        EmailSender { config }
+    }
+
+    /// Determine the sender address for notices from an org unit,
+    /// walking up the org tree for the noreply setting.
+    pub fn sender_for_org(&self, editor: &mut Editor, org_id: i64) -> Result<String, String> {
+        let mut org_id = org_id;
+
+        loop {
+            let settings = editor.search(
+                "aous",
+                json::object! {
+                    org_unit: org_id,
+                    name: DEFAULT_SENDER_SETTING,
+                },
+            )?;
+
+            if let Some(setting) = settings.first() {
+                if let Some(value) = setting["value"].as_str() {
+                    // Setting values are JSON-encoded strings.
+                    let parsed = json::parse(value).unwrap_or_else(|_| value.into());
+                    if let Some(addr) = parsed.as_str() {
+                        return Ok(addr.to_string());
+                    }
+                }
+            }
+
+            let org = match editor.retrieve("aou", json::from(org_id))? {
+                Some(o) => o,
+                None => break,
+            };
+
+            match util::json_int(&org["parent_ou"]) {
+                Ok(parent) => org_id = parent,
+                Err(_) => break,
+            }
+        }
+
+        Ok(self.config.default_sender.clone())
+    }
+
+    /// Deliver one notice, classifying failures as permanent
+    /// (bounce) or transient.
+    pub fn send(&self, notice: &Notice) -> SendOutcome {
+        let from = notice
+            .from
+            .as_deref()
+            .unwrap_or(&self.config.default_sender);
+
+        let mailbox_from = match from.parse() {
+            Ok(m) => m,
+            Err(e) => return SendOutcome::Bounced(format!("Invalid sender {from}: {e}")),
+        };
+
+        let mailbox_to = match notice.to.parse() {
+            Ok(m) => m,
+            Err(e) => return SendOutcome::Bounced(format!("Invalid recipient {}: {e}", notice.to)),
+        };
+
+        let message = match Message::builder()
+            .from(mailbox_from)
+            .to(mailbox_to)
+            .subject(&notice.subject)
+            .body(notice.body.clone())
+        {
+            Ok(m) => m,
+            Err(e) => return SendOutcome::Bounced(format!("Cannot build message: {e}")),
+        };
+
+        let transport = SmtpTransport::builder_dangerous(&self.config.host)
+            .port(self.config.port)
+            .build();
+
+        match transport.send(&message) {
+            Ok(_) => SendOutcome::Sent,
+            Err(e) => classify_smtp_error(&e),
+        }
+    }
+}
+
+fn classify_smtp_error(err: &SmtpError) -> SendOutcome {
+    if err.is_permanent() {
+        SendOutcome::Bounced(format!("{err}"))
+    } else {
+        SendOutcome::Failed(format!("{err}"))
+    }
+}
+
+/// An Action/Trigger reactor that renders the definition template
+/// with Tera and delivers the result as email.
+pub struct EmailReactor {
+    sender: EmailSender,
+}
+
+impl EmailReactor {
+    pub fn new(config: SmtpConfig) -> Self {
+        EmailReactor {
+            sender: EmailSender::new(config),
+        }
+    }
+}
+
+impl trigger::Reactor for EmailReactor {
+    fn react(&self, def: &JsonValue, events: &[JsonValue]) -> Result<Option<String>, String> {
+        let template = match def["template"].as_str() {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+
+        let renderer = Renderer::new();
+
+        let context = json::object! {
+            event_def: def.clone(),
+            events: JsonValue::Array(events.to_vec()),
+        };
+
+        let rendered = renderer.render(template, &context)?;
+        let notice = Notice::parse_email(&rendered)?;
+
+        match self.sender.send(&notice) {
+            SendOutcome::Sent => Ok(Some(rendered)),
+            SendOutcome::Bounced(why) => {
+                // A permanent failure still completes the event; the
+                // bounce is recorded as output for follow-up.
+                log::warn!("Notice to {} bounced: {why}", notice.to);
+                Ok(Some(format!("BOUNCED: {why}\n{rendered}")))
+            }
+            SendOutcome::Failed(why) => Err(format!("Notice delivery failed: {why}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let renderer = Renderer::new();
+
+        let context = json::object! {
+            user: {first_given_name: "Pat", family_name: "Example"},
+            circ_count: 3,
+        };
+
+        let out = renderer
+            .render(
+                "Dear {{ user.first_given_name }}, you have {{ circ_count }} items.",
+                &context,
+            )
+            .expect("template should render");
+
+        assert_eq!(out, "Dear Pat, you have 3 items.");
+    }
+
+    #[test]
+    fn test_parse_email() {
+        let rendered = concat!(
+            "To: patron@example.org\n",
+            "Subject: Overdue Notice\n",
+            "\n",
+            "You have overdue items.\n",
+        );
+
+        let notice = Notice::parse_email(rendered).expect("notice should parse");
+        assert_eq!(notice.to, "patron@example.org");
+        assert_eq!(notice.subject, "Overdue Notice");
+        assert_eq!(notice.body, "You have overdue items.");
+        assert!(notice.from.is_none());
+    }
+
+    #[test]
+    fn test_parse_email_requires_recipient() {
+        assert!(Notice::parse_email("Subject: hi\n\nbody").is_err());
+    }
+}
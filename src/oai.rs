@@ -0,0 +1,465 @@
+//! Harvest records from remote OAI-PMH endpoints.
+//!
+//! Harvested records are crosswalked to MARC and loaded into a
+//! Vandelay queue (or created directly as bib records) via the
+//! Editor.  Incremental harvesting state is stored in a local state
+//! file.
+
+use crate::editor::Editor;
+use crate::marc;
+use std::fs;
+
+const OAI_DC_PREFIX: &str = "oai_dc";
+
+/// Dublin Core element => MARC tag/subfield crosswalk.
+const DC_CROSSWALK: &[(&str, &str, &str)] = &[
+    ("title", "245", "a"),
+    ("creator", "100", "a"),
+    ("contributor", "700", "a"),
+    ("subject", "650", "a"),
+    ("description", "520", "a"),
+    ("publisher", "264", "b"),
+    ("date", "264", "c"),
+    ("language", "041", "a"),
+    ("identifier", "024", "a"),
+];
+
+/// Where and what to harvest.
+pub struct HarvestConfig {
+    endpoint: String,
+    metadata_prefix: String,
+    set: Option<String>,
+    state_file: Option<String>,
+}
+
+impl HarvestConfig {
+    pub fn new(endpoint: &str, metadata_prefix: &str) -> Self {
+        HarvestConfig {
+            endpoint: endpoint.to_string(),
+            metadata_prefix: metadata_prefix.to_string(),
+            set: None,
+            state_file: None,
+        }
+    }
+
+    pub fn set_set(&mut self, set: &str) {
+        self.set = Some(set.to_string());
+    }
+
+    /// Path for storing incremental harvest state.
+    pub fn set_state_file(&mut self, path: &str) {
+        self.state_file = Some(path.to_string());
+    }
+}
+
+/// One harvested record.
+pub struct HarvestedRecord {
+    pub identifier: String,
+    pub datestamp: Option<String>,
+    pub deleted: bool,
+    pub record: Option<marc::Record>,
+}
+
+/// One page of a ListRecords response.
+pub struct HarvestPage {
+    pub records: Vec<HarvestedRecord>,
+    pub resumption_token: Option<String>,
+    /// The repository's responseDate, used as the next "from" value.
+    pub response_date: Option<String>,
+}
+
+pub struct Harvester {
+    config: HarvestConfig,
+}
+
+impl Harvester {
+    pub fn new(config: HarvestConfig) -> Self {
+        Harvester { config }
+    }
+
+    /// The datestamp where the last completed harvest left off.
+    pub fn last_harvest(&self) -> Option<String> {
+        let path = self.config.state_file.as_ref()?;
+        let text = fs::read_to_string(path).ok()?;
+        let state = json::parse(&text).ok()?;
+        state["last_harvest"].as_str().map(|v| v.to_string())
+    }
+
+    /// Record the datestamp of a completed harvest.
+    pub fn save_state(&self, datestamp: &str) -> Result<(), String> {
+        let path = match &self.config.state_file {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let state = json::object! {
+            last_harvest: datestamp,
+            endpoint: self.config.endpoint.as_str(),
+        };
+
+        fs::write(path, state.dump()).map_err(|e| format!("Cannot write state file {path}: {e}"))
+    }
+
+    fn list_records_url(&self, from: Option<&str>, token: Option<&str>) -> String {
+        let mut url = format!("{}?verb=ListRecords", self.config.endpoint);
+
+        // A resumption token is an exclusive argument.
+        if let Some(token) = token {
+            url += &format!("&resumptionToken={token}");
+            return url;
+        }
+
+        url += &format!("&metadataPrefix={}", self.config.metadata_prefix);
+
+        if let Some(set) = &self.config.set {
+            url += &format!("&set={set}");
+        }
+
+        if let Some(from) = from {
+            url += &format!("&from={from}");
+        }
+
+        url
+    }
+
+    /// Fetch one page of records.
+    pub fn list_records(
+        &self,
+        from: Option<&str>,
+        token: Option<&str>,
+    ) -> Result<HarvestPage, String> {
+        let url = self.list_records_url(from, token);
+
+        log::info!("Harvesting {url}");
+
+        let xml = ureq::get(&url)
+            .call()
+            .map_err(|e| format!("OAI request failed: {e}"))?
+            .into_string()
+            .map_err(|e| format!("Error reading OAI response: {e}"))?;
+
+        self.parse_list_records(&xml)
+    }
+
+    /// Parse a ListRecords response document.
+    pub fn parse_list_records(&self, xml: &str) -> Result<HarvestPage, String> {
+        let doc =
+            roxmltree::Document::parse(xml).map_err(|e| format!("Invalid OAI response: {e}"))?;
+
+        let root = doc.root_element();
+
+        if let Some(err) = root
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == "error")
+        {
+            let code = err.attribute("code").unwrap_or("unknown");
+            // An empty incremental harvest is not an error.
+            if code == "noRecordsMatch" {
+                return Ok(HarvestPage {
+                    records: Vec::new(),
+                    resumption_token: None,
+                    response_date: None,
+                });
+            }
+            return Err(format!("OAI error {code}: {}", err.text().unwrap_or("")));
+        }
+
+        let response_date = root
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == "responseDate")
+            .and_then(|n| n.text())
+            .map(|t| t.to_string());
+
+        let list = root
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == "ListRecords")
+            .ok_or("OAI response contains no ListRecords element")?;
+
+        let mut page = HarvestPage {
+            records: Vec::new(),
+            resumption_token: None,
+            response_date,
+        };
+
+        for node in list.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "record" => page.records.push(self.parse_record(&node)?),
+                "resumptionToken" => {
+                    if let Some(token) = node.text() {
+                        if !token.trim().is_empty() {
+                            page.resumption_token = Some(token.trim().to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(page)
+    }
+
+    fn parse_record(&self, node: &roxmltree::Node) -> Result<HarvestedRecord, String> {
+        let header = node
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == "header")
+            .ok_or("OAI record has no header")?;
+
+        let identifier = header
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == "identifier")
+            .and_then(|n| n.text())
+            .unwrap_or("")
+            .to_string();
+
+        let datestamp = header
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == "datestamp")
+            .and_then(|n| n.text())
+            .map(|t| t.to_string());
+
+        let deleted = header.attribute("status") == Some("deleted");
+
+        if deleted {
+            return Ok(HarvestedRecord {
+                identifier,
+                datestamp,
+                deleted,
+                record: None,
+            });
+        }
+
+        let metadata = node
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == "metadata")
+            .ok_or_else(|| format!("OAI record {identifier} has no metadata"))?;
+
+        let record = if self.config.metadata_prefix == OAI_DC_PREFIX {
+            Some(crosswalk_dc(&metadata))
+        } else {
+            // Treat anything else as MARCXML.
+            let range = metadata.range();
+            let xml_slice = &metadata.document().input_text()[range];
+            Some(marc::Record::from_xml(xml_slice)?)
+        };
+
+        Ok(HarvestedRecord {
+            identifier,
+            datestamp,
+            deleted,
+            record,
+        })
+    }
+}
+
+/// Crosswalk a Dublin Core metadata node into a minimal MARC record.
+fn crosswalk_dc(metadata: &roxmltree::Node) -> marc::Record {
+    let mut record = marc::Record::new();
+    record.set_leader("00000nam a22000003u 4500");
+
+    for node in metadata.descendants().filter(|n| n.is_element()) {
+        let value = match node.text() {
+            Some(t) if !t.trim().is_empty() => t.trim(),
+            _ => continue,
+        };
+
+        let (_, tag, code) = match DC_CROSSWALK
+            .iter()
+            .find(|(dc, _, _)| *dc == node.tag_name().name())
+        {
+            Some(entry) => entry,
+            None => continue,
+        };
+
+        let mut field = marc::Field::new(tag);
+        field.add_subfield(code, value);
+        record.insert_field(field);
+    }
+
+    record
+}
+
+/// Loads harvested records into Evergreen.
+pub struct Loader {
+    editor: Editor,
+}
+
+impl Loader {
+    pub fn new(editor: Editor) -> Self {
+        Loader { editor }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// Add a batch of records to a Vandelay bib queue, returning the
+    /// number of queued records.
+    pub fn load_to_queue(
+        &mut self,
+        queue_id: i64,
+        records: &[HarvestedRecord],
+    ) -> Result<usize, String> {
+        let mut count = 0;
+
+        self.editor.xact_begin()?;
+
+        for hrec in records {
+            let marc_rec = match &hrec.record {
+                Some(r) => r,
+                None => continue, // deleted record
+            };
+
+            let vqbr = json::object! {
+                "_classname": "vqbr",
+                queue: queue_id,
+                marc: marc_rec.to_xml(),
+            };
+
+            let resp = self.editor.request(
+                "open-ils.cstore.direct.vandelay.queued_bib_record.create",
+                vec![vqbr],
+            )?;
+
+            if resp.is_null() {
+                self.editor.xact_rollback()?;
+                return Err(format!("Error queueing record {}", hrec.identifier));
+            }
+
+            count += 1;
+        }
+
+        self.editor.commit()?;
+
+        Ok(count)
+    }
+
+    /// Create bib records directly, bypassing Vandelay.  Returns the
+    /// new record IDs.
+    pub fn load_directly(
+        &mut self,
+        records: &[HarvestedRecord],
+        source: Option<&str>,
+    ) -> Result<Vec<i64>, String> {
+        let mut ids = Vec::new();
+
+        self.editor.xact_begin()?;
+
+        for hrec in records {
+            let marc_rec = match &hrec.record {
+                Some(r) => r,
+                None => continue,
+            };
+
+            let mut bre = json::object! {
+                "_classname": "bre",
+                marc: marc_rec.to_xml(),
+                last_xact_id: "OAI-HARVEST",
+            };
+
+            if let Some(source) = source {
+                bre["source"] = source.into();
+            }
+
+            let resp = self
+                .editor
+                .request("open-ils.cstore.direct.biblio.record_entry.create", vec![bre])?;
+
+            match crate::util::json_int(&resp["id"]) {
+                Ok(id) => ids.push(id),
+                Err(_) => {
+                    self.editor.xact_rollback()?;
+                    return Err(format!("Error creating record {}", hrec.identifier));
+                }
+            }
+        }
+
+        self.editor.commit()?;
+
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DC_RESPONSE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+    <OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
+      <responseDate>2024-05-01T00:00:00Z</responseDate>
+      <ListRecords>
+        <record>
+          <header>
+            <identifier>oai:example.org:1</identifier>
+            <datestamp>2024-04-30</datestamp>
+          </header>
+          <metadata>
+            <oai_dc:dc xmlns:oai_dc="http://www.openarchives.org/OAI/2.0/oai_dc/"
+                xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <dc:title>Test Title</dc:title>
+              <dc:creator>Author, Test</dc:creator>
+              <dc:subject>Testing</dc:subject>
+            </oai_dc:dc>
+          </metadata>
+        </record>
+        <record>
+          <header status="deleted">
+            <identifier>oai:example.org:2</identifier>
+            <datestamp>2024-04-29</datestamp>
+          </header>
+        </record>
+        <resumptionToken>token-123</resumptionToken>
+      </ListRecords>
+    </OAI-PMH>"#;
+
+    #[test]
+    fn test_parse_dc_list_records() {
+        let harvester = Harvester::new(HarvestConfig::new("http://example.org/oai", "oai_dc"));
+
+        let page = harvester
+            .parse_list_records(DC_RESPONSE)
+            .expect("response should parse");
+
+        assert_eq!(page.records.len(), 2);
+        assert_eq!(page.resumption_token.as_deref(), Some("token-123"));
+        assert_eq!(page.response_date.as_deref(), Some("2024-05-01T00:00:00Z"));
+
+        let rec = &page.records[0];
+        assert_eq!(rec.identifier, "oai:example.org:1");
+        assert!(!rec.deleted);
+
+        let marc_rec = rec.record.as_ref().unwrap();
+        assert_eq!(marc_rec.get_values("245", "a"), vec!["Test Title"]);
+        assert_eq!(marc_rec.get_values("100", "a"), vec!["Author, Test"]);
+
+        assert!(page.records[1].deleted);
+        assert!(page.records[1].record.is_none());
+    }
+
+    #[test]
+    fn test_no_records_match() {
+        let harvester = Harvester::new(HarvestConfig::new("http://example.org/oai", "oai_dc"));
+
+        let xml = r#"<OAI-PMH xmlns="http://www.openarchives.org/OAI/2.0/">
+            <error code="noRecordsMatch">No matches</error></OAI-PMH>"#;
+
+        let page = harvester.parse_list_records(xml).expect("should not error");
+        assert!(page.records.is_empty());
+        assert!(page.resumption_token.is_none());
+    }
+
+    #[test]
+    fn test_list_records_url() {
+        let mut config = HarvestConfig::new("http://example.org/oai", "marc21");
+        config.set_set("books");
+        let harvester = Harvester::new(config);
+
+        let url = harvester.list_records_url(Some("2024-01-01"), None);
+        assert_eq!(
+            url,
+            "http://example.org/oai?verb=ListRecords&metadataPrefix=marc21&set=books&from=2024-01-01"
+        );
+
+        let url = harvester.list_records_url(Some("2024-01-01"), Some("tok"));
+        assert_eq!(url, "http://example.org/oai?verb=ListRecords&resumptionToken=tok");
+    }
+}
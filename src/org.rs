@@ -0,0 +1,249 @@
+//! The org unit tree, loaded once and queried locally.
+//!
+//! One bulk fetch pulls every org unit and org unit type; from
+//! there ancestors, descendants, depths, and proximity are all
+//! in-memory walks instead of per-org service calls.
+
+use crate::editor::Editor;
+use crate::util;
+use json::JsonValue;
+use std::collections::HashMap;
+
+/// The org unit tree plus its type definitions.
+pub struct OrgTree {
+    /// org unit ID => aou row.
+    orgs: HashMap<i64, JsonValue>,
+    /// org unit type ID => aout row.
+    types: HashMap<i64, JsonValue>,
+}
+
+impl OrgTree {
+    /// Bulk-fetch all org units and types.
+    pub fn load(editor: &mut Editor) -> Result<OrgTree, String> {
+        let orgs = editor.search("aou", json::object! {id: {"!=": JsonValue::Null}})?;
+        let types = editor.search("aout", json::object! {id: {"!=": JsonValue::Null}})?;
+
+        OrgTree::from_rows(orgs, types)
+    }
+
+    /// Build a tree from already-fetched rows.
+    pub fn from_rows(orgs: Vec<JsonValue>, types: Vec<JsonValue>) -> Result<OrgTree, String> {
+        let mut tree = OrgTree {
+            orgs: HashMap::new(),
+            types: HashMap::new(),
+        };
+
+        for org in orgs {
+            tree.orgs.insert(util::json_int(&org["id"])?, org);
+        }
+        for org_type in types {
+            tree.types
+                .insert(util::json_int(&org_type["id"])?, org_type);
+        }
+
+        Ok(tree)
+    }
+
+    pub fn get(&self, org_id: i64) -> Option<&JsonValue> {
+        self.orgs.get(&org_id)
+    }
+
+    /// All org unit IDs, in no particular order.
+    pub fn all_ids(&self) -> Vec<i64> {
+        self.orgs.keys().copied().collect()
+    }
+
+    /// The root (parentless) org unit ID.
+    pub fn root(&self) -> Option<i64> {
+        self.orgs
+            .iter()
+            .find(|(_, org)| org["parent_ou"].is_null())
+            .map(|(id, _)| *id)
+    }
+
+    pub fn parent(&self, org_id: i64) -> Option<i64> {
+        let org = self.orgs.get(&org_id)?;
+        util::json_int(&org["parent_ou"]).ok()
+    }
+
+    /// The aout row for an org's type.
+    pub fn org_type(&self, org_id: i64) -> Option<&JsonValue> {
+        let org = self.orgs.get(&org_id)?;
+        let type_id = util::json_int(&org["ou_type"]).ok()?;
+        self.types.get(&type_id)
+    }
+
+    /// An org's depth in the tree, per its type.
+    pub fn depth(&self, org_id: i64) -> Result<i64, String> {
+        let org_type = self
+            .org_type(org_id)
+            .ok_or_else(|| format!("No such org unit: {org_id}"))?;
+        util::json_int(&org_type["depth"])
+    }
+
+    /// Whether an org's type allows users / copies to live there.
+    pub fn can_have_users(&self, org_id: i64) -> bool {
+        self.org_type(org_id)
+            .map(|t| t["can_have_users"].as_str() != Some("f"))
+            .unwrap_or(false)
+    }
+
+    /// The org and its ancestors, from the org up to the root.
+    pub fn ancestors(&self, org_id: i64) -> Vec<i64> {
+        let mut ancestors = Vec::new();
+        let mut current = Some(org_id);
+
+        while let Some(id) = current {
+            if !self.orgs.contains_key(&id) || ancestors.contains(&id) {
+                break; // unknown org or a cycle; stop walking
+            }
+            ancestors.push(id);
+            current = self.parent(id);
+        }
+
+        ancestors
+    }
+
+    /// The org and everything below it, breadth-first.
+    pub fn descendants(&self, org_id: i64) -> Vec<i64> {
+        let mut descendants = Vec::new();
+        let mut queue = vec![org_id];
+
+        while let Some(id) = queue.pop() {
+            if !self.orgs.contains_key(&id) || descendants.contains(&id) {
+                continue;
+            }
+            descendants.push(id);
+
+            for (child_id, org) in &self.orgs {
+                if util::json_int(&org["parent_ou"]) == Ok(id) {
+                    queue.push(*child_id);
+                }
+            }
+        }
+
+        descendants
+    }
+
+    /// Ancestors plus descendants: every org "in line" with this
+    /// one, root first.
+    pub fn full_path(&self, org_id: i64) -> Vec<i64> {
+        let mut path: Vec<i64> = self.ancestors(org_id).into_iter().rev().collect();
+
+        for id in self.descendants(org_id) {
+            if id != org_id {
+                path.push(id);
+            }
+        }
+
+        path
+    }
+
+    /// Tree distance between two orgs: steps from each up to their
+    /// nearest common ancestor.  None when they share no ancestor.
+    pub fn proximity(&self, org_a: i64, org_b: i64) -> Option<i64> {
+        let ancestors_a = self.ancestors(org_a);
+        let ancestors_b = self.ancestors(org_b);
+
+        for (steps_a, id) in ancestors_a.iter().enumerate() {
+            if let Some(steps_b) = ancestors_b.iter().position(|b| b == id) {
+                return Some((steps_a + steps_b) as i64);
+            }
+        }
+
+        None
+    }
+
+    /// Orgs in scope for a permission granted at `depth`: climb to
+    /// the ancestor at that depth, then take everything below it.
+    /// An org's own subtree when it already sits below the depth.
+    pub fn scope_for_depth(&self, org_id: i64, depth: i64) -> Result<Vec<i64>, String> {
+        let mut scope_top = org_id;
+
+        for id in self.ancestors(org_id) {
+            if self.depth(id)? < depth {
+                break;
+            }
+            scope_top = id;
+        }
+
+        Ok(self.descendants(scope_top))
+    }
+
+    /// Orgs consulted for an ancestor-default setting lookup: the
+    /// org itself, then up the tree.
+    pub fn setting_scope(&self, org_id: i64) -> Vec<i64> {
+        self.ancestors(org_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// CONS (depth 0) -> SYS1 (1) -> BR1, BR2 (2); BR1 -> SL1 (3).
+    fn test_tree() -> OrgTree {
+        let types = vec![
+            json::object! {id: 1, depth: 0, name: "Consortium", can_have_users: "f"},
+            json::object! {id: 2, depth: 1, name: "System", can_have_users: "f"},
+            json::object! {id: 3, depth: 2, name: "Branch", can_have_users: "t"},
+            json::object! {id: 4, depth: 3, name: "Sub-library", can_have_users: "t"},
+        ];
+
+        let orgs = vec![
+            json::object! {id: 1, parent_ou: null, ou_type: 1, shortname: "CONS"},
+            json::object! {id: 2, parent_ou: 1, ou_type: 2, shortname: "SYS1"},
+            json::object! {id: 3, parent_ou: 2, ou_type: 3, shortname: "BR1"},
+            json::object! {id: 4, parent_ou: 2, ou_type: 3, shortname: "BR2"},
+            json::object! {id: 5, parent_ou: 3, ou_type: 4, shortname: "SL1"},
+        ];
+
+        OrgTree::from_rows(orgs, types).expect("tree should build")
+    }
+
+    #[test]
+    fn test_walks() {
+        let tree = test_tree();
+
+        assert_eq!(tree.root(), Some(1));
+        assert_eq!(tree.ancestors(5), vec![5, 3, 2, 1]);
+        assert_eq!(tree.depth(5).unwrap(), 3);
+        assert!(tree.can_have_users(3));
+        assert!(!tree.can_have_users(1));
+
+        let mut descendants = tree.descendants(2);
+        descendants.sort();
+        assert_eq!(descendants, vec![2, 3, 4, 5]);
+
+        let path = tree.full_path(3);
+        assert_eq!(&path[..3], &[1, 2, 3]); // root-first ancestors
+        assert!(path.contains(&5));
+        assert!(!path.contains(&4)); // sibling, not in line
+    }
+
+    #[test]
+    fn test_proximity() {
+        let tree = test_tree();
+
+        assert_eq!(tree.proximity(3, 3), Some(0));
+        assert_eq!(tree.proximity(3, 4), Some(2)); // via SYS1
+        assert_eq!(tree.proximity(5, 4), Some(3)); // SL1 -> BR1 -> SYS1 -> BR2
+        assert_eq!(tree.proximity(5, 1), Some(3));
+        assert_eq!(tree.proximity(3, 99), None);
+    }
+
+    #[test]
+    fn test_scope_for_depth() {
+        let tree = test_tree();
+
+        // BR1 at system depth: the whole SYS1 subtree.
+        let mut scope = tree.scope_for_depth(3, 1).unwrap();
+        scope.sort();
+        assert_eq!(scope, vec![2, 3, 4, 5]);
+
+        // BR1 at its own depth: just its subtree.
+        let mut scope = tree.scope_for_depth(3, 2).unwrap();
+        scope.sort();
+        assert_eq!(scope, vec![3, 5]);
+    }
+}
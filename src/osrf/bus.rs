@@ -0,0 +1,96 @@
+//! Message bus connection for OpenSRF communication.
+//!
+//! Messages move between endpoints via Redis lists: each endpoint
+//! (client or service) owns a list keyed on its bus address.
+
+use crate::osrf::message::TransportMessage;
+use redis::Commands;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static ADDR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the bus address for a service's request queue.
+pub fn service_address(service: &str) -> String {
+    format!("opensrf:service:{service}")
+}
+
+/// A connection to the message bus with a unique client address.
+pub struct Bus {
+    connection: redis::Connection,
+    address: String,
+}
+
+impl Bus {
+    /// Connect to the bus at the provided redis:// URI.
+    pub fn new(uri: &str) -> Result<Self, String> {
+        let client =
+            redis::Client::open(uri).map_err(|e| format!("Invalid bus URI {uri}: {e}"))?;
+
+        let connection = client
+            .get_connection()
+            .map_err(|e| format!("Cannot connect to message bus at {uri}: {e}"))?;
+
+        Ok(Bus {
+            connection,
+            address: Bus::generate_address(),
+        })
+    }
+
+    /// Generate a bus address unique to this process + connection.
+    fn generate_address() -> String {
+        let counter = ADDR_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_micros();
+
+        format!("opensrf:client:{}:{}:{}", process::id(), epoch, counter)
+    }
+
+    /// The address messages for this connection should be sent to.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Send a transport message to the address in its "to" field.
+    pub fn send(&mut self, tmsg: &TransportMessage) -> Result<(), String> {
+        let text = tmsg.to_json_value().dump();
+
+        self.connection
+            .rpush::<_, _, i64>(tmsg.to(), text)
+            .map_err(|e| format!("Error sending to {}: {e}", tmsg.to()))?;
+
+        Ok(())
+    }
+
+    /// Wait up to timeout seconds for a message to arrive at our
+    /// address.  A timeout of 0 blocks indefinitely.
+    pub fn recv(&mut self, timeout: u64) -> Result<Option<TransportMessage>, String> {
+        let resp: Option<(String, String)> = self
+            .connection
+            .blpop(&self.address, timeout as f64)
+            .map_err(|e| format!("Error receiving from {}: {e}", self.address))?;
+
+        let text = match resp {
+            Some((_, text)) => text,
+            None => return Ok(None),
+        };
+
+        let jv = json::parse(&text).map_err(|e| format!("Cannot parse bus message: {e}"))?;
+
+        match TransportMessage::from_json_value(&jv) {
+            Some(tmsg) => Ok(Some(tmsg)),
+            None => Err(format!("Malformed transport message: {text}")),
+        }
+    }
+
+    /// Remove any pending messages from our address.
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.connection
+            .del::<_, i64>(&self.address)
+            .map_err(|e| format!("Error clearing bus address: {e}"))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+//! OpenSRF client handle.
+
+use crate::osrf::bus::Bus;
+use crate::osrf::gateway::Gateway;
+use crate::osrf::message::TransportMessage;
+use crate::osrf::websocket::WebSocket;
+use crate::osrf::session::SessionHandle;
+use crate::osrf::DataSerializer;
+use std::cell::RefCell;
+use std::env;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_BUS_URI: &str = "redis://127.0.0.1:6379/";
+
+/// Client connection settings.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    bus_uri: String,
+    /// When set, talk to this HTTP gateway endpoint instead of the
+    /// bus.
+    gateway_url: Option<String>,
+    /// When set, talk to this websocket translator endpoint instead
+    /// of the bus.
+    websocket_url: Option<String>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig::new()
+    }
+}
+
+impl ClientConfig {
+    pub fn new() -> Self {
+        ClientConfig {
+            bus_uri: DEFAULT_BUS_URI.to_string(),
+            gateway_url: None,
+            websocket_url: None,
+        }
+    }
+
+    /// Load settings from the environment (OSRF_BUS_URI,
+    /// OSRF_GATEWAY_URL, OSRF_WEBSOCKET_URL), falling back to
+    /// defaults.
+    pub fn from_env() -> Self {
+        let mut conf = ClientConfig::new();
+        if let Ok(uri) = env::var("OSRF_BUS_URI") {
+            conf.bus_uri = uri;
+        }
+        if let Ok(url) = env::var("OSRF_GATEWAY_URL") {
+            conf.gateway_url = Some(url);
+        }
+        if let Ok(url) = env::var("OSRF_WEBSOCKET_URL") {
+            conf.websocket_url = Some(url);
+        }
+        conf
+    }
+
+    pub fn bus_uri(&self) -> &str {
+        &self.bus_uri
+    }
+
+    pub fn set_bus_uri(&mut self, uri: &str) {
+        self.bus_uri = uri.to_string();
+    }
+
+    pub fn gateway_url(&self) -> Option<&str> {
+        self.gateway_url.as_deref()
+    }
+
+    /// Route requests through an HTTP gateway endpoint, e.g.
+    /// "https://example.org/osrf-gateway-v1", instead of the bus.
+    pub fn set_gateway_url(&mut self, url: &str) {
+        self.gateway_url = Some(url.to_string());
+    }
+
+    pub fn websocket_url(&self) -> Option<&str> {
+        self.websocket_url.as_deref()
+    }
+
+    /// Route requests through a websocket translator endpoint, e.g.
+    /// "wss://example.org:443/osrf-websocket-translate", instead of
+    /// the bus.
+    pub fn set_websocket_url(&mut self, url: &str) {
+        self.websocket_url = Some(url.to_string());
+    }
+}
+
+/// How a client moves messages: directly over the bus, or relayed
+/// through an HTTP gateway or websocket translator.
+enum Transport {
+    Bus(Bus),
+    Gateway(Gateway),
+    WebSocket(Box<WebSocket>),
+}
+
+pub struct ClientInner {
+    transport: Transport,
+    serializer: Option<Arc<dyn DataSerializer>>,
+    /// Messages received from the bus but not yet claimed by the
+    /// session they belong to.
+    backlog: Vec<TransportMessage>,
+}
+
+/// Cheaply cloneable handle to a single bus connection.
+///
+/// Clients (and their sessions) are single-threaded; each thread
+/// should create its own Client.
+#[derive(Clone)]
+pub struct Client {
+    inner: Rc<RefCell<ClientInner>>,
+}
+
+impl Client {
+    /// Connect to the message bus, or to a gateway or websocket
+    /// relay if the config names one.
+    pub fn connect(config: &ClientConfig) -> Result<Client, String> {
+        let transport = if let Some(url) = config.websocket_url() {
+            Transport::WebSocket(Box::new(WebSocket::connect(url)?))
+        } else if let Some(url) = config.gateway_url() {
+            Transport::Gateway(Gateway::new(url))
+        } else {
+            Transport::Bus(Bus::new(config.bus_uri())?)
+        };
+
+        Ok(Client {
+            inner: Rc::new(RefCell::new(ClientInner {
+                transport,
+                serializer: None,
+                backlog: Vec::new(),
+            })),
+        })
+    }
+
+    /// Apply a serializer for translating payloads as they enter and
+    /// exit the bus.
+    pub fn set_serializer(&self, serializer: Arc<dyn DataSerializer>) {
+        self.inner.borrow_mut().serializer = Some(serializer);
+    }
+
+    pub fn serializer(&self) -> Option<Arc<dyn DataSerializer>> {
+        self.inner.borrow().serializer.clone()
+    }
+
+    /// Create a client session for communicating with a service.
+    pub fn session(&self, service: &str) -> SessionHandle {
+        SessionHandle::new(self.clone(), service)
+    }
+
+    pub(crate) fn address(&self) -> String {
+        match &self.inner.borrow().transport {
+            Transport::Bus(bus) => bus.address().to_string(),
+            Transport::Gateway(gateway) => gateway.address().to_string(),
+            Transport::WebSocket(socket) => socket.address().to_string(),
+        }
+    }
+
+    pub(crate) fn send(&self, tmsg: &TransportMessage) -> Result<(), String> {
+        let mut inner = self.inner.borrow_mut();
+
+        match &mut inner.transport {
+            Transport::Bus(bus) => bus.send(tmsg),
+            Transport::WebSocket(socket) => socket.send(tmsg),
+            Transport::Gateway(gateway) => {
+                // The gateway answers synchronously; stash its
+                // responses for recv_for_thread to hand out.
+                let responses = gateway.send(tmsg)?;
+                inner.backlog.extend(responses);
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait up to timeout seconds for a transport message on the
+    /// specified session thread.
+    ///
+    /// Messages arriving for other threads are stashed for later
+    /// retrieval by their sessions.
+    pub(crate) fn recv_for_thread(
+        &self,
+        thread: &str,
+        timeout: u64,
+    ) -> Result<Option<TransportMessage>, String> {
+        let mut inner = self.inner.borrow_mut();
+
+        if let Some(pos) = inner.backlog.iter().position(|t| t.thread() == thread) {
+            return Ok(Some(inner.backlog.remove(pos)));
+        }
+
+        let duration = Duration::from_secs(timeout);
+        let start = Instant::now();
+
+        loop {
+            let elapsed = start.elapsed();
+            if elapsed >= duration {
+                return Ok(None);
+            }
+
+            let remaining = (duration - elapsed).as_secs().max(1);
+
+            let tmsg = match &mut inner.transport {
+                Transport::Bus(bus) => match bus.recv(remaining)? {
+                    Some(t) => t,
+                    None => continue,
+                },
+                Transport::WebSocket(socket) => match socket.recv(remaining)? {
+                    Some(t) => t,
+                    None => continue,
+                },
+                // All gateway responses arrive at send time; nothing
+                // more is coming.
+                Transport::Gateway(_) => return Ok(None),
+            };
+
+            if tmsg.thread() == thread {
+                return Ok(Some(tmsg));
+            }
+
+            inner.backlog.push(tmsg);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! HTTP gateway transport.
+//!
+//! Talks to an OpenSRF HTTP translator endpoint (osrf-gateway-v1)
+//! instead of the message bus, so tools can run from machines with
+//! only HTTPS access to the Evergreen server.
+//!
+//! The gateway is stateless: each request is one HTTP POST and all
+//! responses arrive in its reply.  Stateful (CONNECT-ed) sessions
+//! are not supported over this transport.
+
+use crate::osrf::message::{
+    Message, MessageStatus, MessageType, Payload, Result as OsrfResult, Status, TransportMessage,
+};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long we wait for the gateway to respond.
+const HTTP_TIMEOUT: u64 = 300;
+
+static ADDR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Extract the service name from a bus service address
+/// ("opensrf:service:open-ils.actor" => "open-ils.actor").
+fn service_from_address(address: &str) -> Result<&str, String> {
+    address
+        .strip_prefix("opensrf:service:")
+        .ok_or_else(|| format!("Cannot route non-service address via gateway: {address}"))
+}
+
+/// Translate one gateway response payload into the messages a bus
+/// service would have sent: one RESULT per payload entry, then a
+/// STATUS Complete.
+fn messages_from_payload(payload: &json::JsonValue, thread_trace: usize) -> Vec<Message> {
+    let mut messages = Vec::new();
+
+    for content in payload.members() {
+        messages.push(Message::new(
+            MessageType::Result,
+            thread_trace,
+            Payload::Result(OsrfResult::new(200, content.clone())),
+        ));
+    }
+
+    messages.push(Message::new(
+        MessageType::Status,
+        thread_trace,
+        Payload::Status(Status::new(MessageStatus::Complete, "Request Complete")),
+    ));
+
+    messages
+}
+
+/// A connection-less handle to an HTTP gateway endpoint.
+pub struct Gateway {
+    /// Full endpoint URL, e.g. "https://example.org/osrf-gateway-v1".
+    url: String,
+    /// Synthetic client address, used where the bus address would be.
+    address: String,
+}
+
+impl Gateway {
+    pub fn new(url: &str) -> Gateway {
+        let counter = ADDR_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_micros();
+
+        Gateway {
+            url: url.to_string(),
+            address: format!("opensrf:gateway:{}:{}:{}", process::id(), epoch, counter),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Relay a transport message to the gateway, returning the
+    /// response transport messages a bus peer would have delivered.
+    pub(crate) fn send(&self, tmsg: &TransportMessage) -> Result<Vec<TransportMessage>, String> {
+        let service = service_from_address(tmsg.to())?;
+
+        let mut responses = Vec::new();
+
+        for msg in tmsg.body() {
+            let body = match msg.mtype() {
+                MessageType::Request => match msg.payload() {
+                    Payload::Method(method) => {
+                        let payload = self.http_request(service, method.method(), method.params())?;
+                        messages_from_payload(&payload, msg.thread_trace())
+                    }
+                    _ => continue,
+                },
+                MessageType::Connect => {
+                    return Err(format!(
+                        "Stateful connections to {service} are not supported over the HTTP gateway"
+                    ));
+                }
+                // DISCONNECT et al. are no-ops for a stateless transport.
+                _ => continue,
+            };
+
+            let mut response =
+                TransportMessage::new(&self.address, &self.url, tmsg.thread());
+            response.body_mut().extend(body);
+            responses.push(response);
+        }
+
+        Ok(responses)
+    }
+
+    /// POST one method call and return the response payload array.
+    fn http_request(
+        &self,
+        service: &str,
+        method: &str,
+        params: &[json::JsonValue],
+    ) -> Result<json::JsonValue, String> {
+        let mut form: Vec<(&str, String)> = vec![
+            ("service", service.to_string()),
+            ("method", method.to_string()),
+        ];
+
+        for param in params {
+            form.push(("param", param.dump()));
+        }
+
+        let form: Vec<(&str, &str)> = form.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+        let response = ureq::post(&self.url)
+            .timeout(std::time::Duration::from_secs(HTTP_TIMEOUT))
+            .send_form(&form)
+            .map_err(|e| format!("Gateway request to {} failed: {e}", self.url))?;
+
+        let text = response
+            .into_string()
+            .map_err(|e| format!("Cannot read gateway response: {e}"))?;
+
+        let parsed =
+            json::parse(&text).map_err(|e| format!("Cannot parse gateway response: {e}"))?;
+
+        let status = parsed["status"].as_isize().unwrap_or(0);
+        if status != 200 {
+            return Err(format!(
+                "Gateway returned status {status} for {method}: {}",
+                parsed["debug"].as_str().unwrap_or("")
+            ));
+        }
+
+        Ok(parsed["payload"].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osrf::bus;
+
+    #[test]
+    fn test_service_from_address() {
+        let addr = bus::service_address("open-ils.actor");
+        assert_eq!(service_from_address(&addr).unwrap(), "open-ils.actor");
+
+        assert!(service_from_address("opensrf:client:123:456:0").is_err());
+    }
+
+    #[test]
+    fn test_messages_from_payload() {
+        let payload = json::array![json::object! {id: 1}, json::object! {id: 2}];
+        let messages = messages_from_payload(&payload, 7);
+
+        // Two results plus the completion status.
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].mtype(), MessageType::Result);
+        assert_eq!(messages[0].thread_trace(), 7);
+
+        match messages[2].payload() {
+            Payload::Status(stat) => assert_eq!(stat.status(), MessageStatus::Complete),
+            _ => panic!("expected a status payload"),
+        }
+    }
+}
@@ -0,0 +1,407 @@
+//! OpenSRF message types and their JSON wire formats.
+
+use json::JsonValue;
+
+const DEFAULT_LOCALE: &str = "en-US";
+
+/// The message types found within a transport message body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Connect,
+    Request,
+    Result,
+    Status,
+    Disconnect,
+    Unknown,
+}
+
+impl From<&str> for MessageType {
+    fn from(s: &str) -> Self {
+        match s {
+            "CONNECT" => Self::Connect,
+            "REQUEST" => Self::Request,
+            "RESULT" => Self::Result,
+            "STATUS" => Self::Status,
+            "DISCONNECT" => Self::Disconnect,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<MessageType> for &'static str {
+    fn from(t: MessageType) -> &'static str {
+        match t {
+            MessageType::Connect => "CONNECT",
+            MessageType::Request => "REQUEST",
+            MessageType::Result => "RESULT",
+            MessageType::Status => "STATUS",
+            MessageType::Disconnect => "DISCONNECT",
+            MessageType::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// OpenSRF status codes, a subset of HTTP status codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageStatus {
+    Continue = 100,
+    Ok = 200,
+    Accepted = 201,
+    Complete = 205,
+    BadRequest = 400,
+    Unauthorized = 401,
+    Forbidden = 403,
+    MethodNotFound = 404,
+    NotAllowed = 405,
+    Timeout = 408,
+    Expfailed = 417,
+    InternalServerError = 500,
+    Unknown = 0,
+}
+
+impl From<isize> for MessageStatus {
+    fn from(code: isize) -> Self {
+        match code {
+            100 => Self::Continue,
+            200 => Self::Ok,
+            201 => Self::Accepted,
+            205 => Self::Complete,
+            400 => Self::BadRequest,
+            401 => Self::Unauthorized,
+            403 => Self::Forbidden,
+            404 => Self::MethodNotFound,
+            405 => Self::NotAllowed,
+            408 => Self::Timeout,
+            417 => Self::Expfailed,
+            500 => Self::InternalServerError,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl MessageStatus {
+    pub fn is_error(&self) -> bool {
+        (*self as isize) >= 400
+    }
+}
+
+/// The payload of a single OpenSRF message.
+#[derive(Debug, Clone)]
+pub enum Payload {
+    Method(Method),
+    Result(Result),
+    Status(Status),
+    NoPayload,
+}
+
+impl Payload {
+    pub fn to_json_value(&self) -> JsonValue {
+        match self {
+            Payload::Method(m) => m.to_json_value(),
+            Payload::Result(r) => r.to_json_value(),
+            Payload::Status(s) => s.to_json_value(),
+            Payload::NoPayload => JsonValue::Null,
+        }
+    }
+}
+
+/// A method call with parameters.
+#[derive(Debug, Clone)]
+pub struct Method {
+    method: String,
+    params: Vec<JsonValue>,
+}
+
+impl Method {
+    pub fn new(method: &str, params: Vec<JsonValue>) -> Self {
+        Method {
+            method: method.to_string(),
+            params,
+        }
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn params(&self) -> &Vec<JsonValue> {
+        &self.params
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        json::object! {
+            "__c": "osrfMethod",
+            "__p": {
+                "method": self.method.as_str(),
+                "params": JsonValue::Array(self.params.clone()),
+            }
+        }
+    }
+}
+
+/// A single response value from a method call.
+#[derive(Debug, Clone)]
+pub struct Result {
+    status_code: isize,
+    content: JsonValue,
+}
+
+impl Result {
+    pub fn new(status_code: isize, content: JsonValue) -> Self {
+        Result {
+            status_code,
+            content,
+        }
+    }
+
+    pub fn content(&self) -> &JsonValue {
+        &self.content
+    }
+
+    pub fn take_content(&mut self) -> JsonValue {
+        self.content.take()
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        json::object! {
+            "__c": "osrfResult",
+            "__p": {
+                "status": "OK",
+                "statusCode": self.status_code,
+                "content": self.content.clone(),
+            }
+        }
+    }
+}
+
+/// A status (non-result) response.
+#[derive(Debug, Clone)]
+pub struct Status {
+    status: MessageStatus,
+    text: String,
+}
+
+impl Status {
+    pub fn new(status: MessageStatus, text: &str) -> Self {
+        Status {
+            status,
+            text: text.to_string(),
+        }
+    }
+
+    pub fn status(&self) -> MessageStatus {
+        self.status
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        json::object! {
+            "__c": "osrfStatus",
+            "__p": {
+                "status": self.text.as_str(),
+                "statusCode": self.status as isize,
+            }
+        }
+    }
+}
+
+/// One OpenSRF message within a transport message.
+#[derive(Debug, Clone)]
+pub struct Message {
+    mtype: MessageType,
+    thread_trace: usize,
+    payload: Payload,
+}
+
+impl Message {
+    pub fn new(mtype: MessageType, thread_trace: usize, payload: Payload) -> Self {
+        Message {
+            mtype,
+            thread_trace,
+            payload,
+        }
+    }
+
+    pub fn mtype(&self) -> MessageType {
+        self.mtype
+    }
+
+    pub fn thread_trace(&self) -> usize {
+        self.thread_trace
+    }
+
+    pub fn payload(&self) -> &Payload {
+        &self.payload
+    }
+
+    pub fn payload_mut(&mut self) -> &mut Payload {
+        &mut self.payload
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        let mtype: &str = self.mtype.into();
+        json::object! {
+            "__c": "osrfMessage",
+            "__p": {
+                "threadTrace": self.thread_trace,
+                "type": mtype,
+                "locale": DEFAULT_LOCALE,
+                "payload": self.payload.to_json_value(),
+            }
+        }
+    }
+
+    /// Parse a JSON value into a Message.
+    ///
+    /// Returns None if the value is not a well-formed osrfMessage.
+    pub fn from_json_value(jv: &JsonValue) -> Option<Message> {
+        if jv["__c"].as_str()? != "osrfMessage" {
+            return None;
+        }
+        let body = &jv["__p"];
+
+        let mtype: MessageType = body["type"].as_str()?.into();
+        let thread_trace = body["threadTrace"].as_usize().or_else(|| {
+            body["threadTrace"]
+                .as_str()
+                .and_then(|t| t.parse::<usize>().ok())
+        })?;
+
+        let pbody = &body["payload"]["__p"];
+
+        let payload = match body["payload"]["__c"].as_str() {
+            Some("osrfMethod") => Payload::Method(Method {
+                method: pbody["method"].as_str()?.to_string(),
+                params: match &pbody["params"] {
+                    JsonValue::Array(p) => p.clone(),
+                    _ => Vec::new(),
+                },
+            }),
+            Some("osrfResult") => Payload::Result(Result {
+                status_code: pbody["statusCode"].as_isize().unwrap_or(200),
+                content: pbody["content"].clone(),
+            }),
+            // All flavors of osrf*Status messages share a status code.
+            Some(_) => Payload::Status(Status {
+                status: pbody["statusCode"].as_isize().unwrap_or(0).into(),
+                text: pbody["status"].as_str().unwrap_or("").to_string(),
+            }),
+            None => Payload::NoPayload,
+        };
+
+        Some(Message {
+            mtype,
+            thread_trace,
+            payload,
+        })
+    }
+}
+
+/// The top-level envelope moved across the bus.
+#[derive(Debug, Clone)]
+pub struct TransportMessage {
+    to: String,
+    from: String,
+    thread: String,
+    body: Vec<Message>,
+}
+
+impl TransportMessage {
+    pub fn new(to: &str, from: &str, thread: &str) -> Self {
+        TransportMessage {
+            to: to.to_string(),
+            from: from.to_string(),
+            thread: thread.to_string(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_body(to: &str, from: &str, thread: &str, msg: Message) -> Self {
+        let mut tmsg = TransportMessage::new(to, from, thread);
+        tmsg.body.push(msg);
+        tmsg
+    }
+
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    pub fn thread(&self) -> &str {
+        &self.thread
+    }
+
+    pub fn body(&self) -> &Vec<Message> {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Vec<Message> {
+        &mut self.body
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        let body: Vec<JsonValue> = self.body.iter().map(|m| m.to_json_value()).collect();
+        json::object! {
+            "to": self.to.as_str(),
+            "from": self.from.as_str(),
+            "thread": self.thread.as_str(),
+            "body": JsonValue::Array(body),
+        }
+    }
+
+    pub fn from_json_value(jv: &JsonValue) -> Option<TransportMessage> {
+        let mut tmsg = TransportMessage::new(
+            jv["to"].as_str()?,
+            jv["from"].as_str()?,
+            jv["thread"].as_str()?,
+        );
+
+        for mjv in jv["body"].members() {
+            tmsg.body.push(Message::from_json_value(mjv)?);
+        }
+
+        Some(tmsg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_round_trip() {
+        let method = Method::new("opensrf.system.echo", vec![json::from("hello")]);
+        let msg = Message::new(MessageType::Request, 1, Payload::Method(method));
+        let tmsg = TransportMessage::with_body("service-addr", "client-addr", "thread-1", msg);
+
+        let jv = tmsg.to_json_value();
+        let tmsg2 = TransportMessage::from_json_value(&jv).expect("round trip should parse");
+
+        assert_eq!(tmsg2.to(), "service-addr");
+        assert_eq!(tmsg2.thread(), "thread-1");
+        assert_eq!(tmsg2.body().len(), 1);
+
+        let msg2 = &tmsg2.body()[0];
+        assert_eq!(msg2.mtype(), MessageType::Request);
+        match msg2.payload() {
+            Payload::Method(m) => {
+                assert_eq!(m.method(), "opensrf.system.echo");
+                assert_eq!(m.params()[0], "hello");
+            }
+            _ => panic!("expected a method payload"),
+        }
+    }
+
+    #[test]
+    fn test_status_codes() {
+        assert!(MessageStatus::MethodNotFound.is_error());
+        assert!(!MessageStatus::Complete.is_error());
+        assert_eq!(MessageStatus::from(205), MessageStatus::Complete);
+    }
+}
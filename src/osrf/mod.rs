@@ -0,0 +1,26 @@
+//! Minimal OpenSRF client implementation: just enough to converse
+//! with Evergreen services over the message bus.
+
+pub mod bus;
+pub mod client;
+pub mod gateway;
+pub mod message;
+pub mod session;
+pub mod websocket;
+
+pub use client::Client;
+pub use client::ClientConfig;
+
+use json::JsonValue;
+
+/// Implementors can translate JSON payloads between their wire format
+/// and a local format as they enter/exit the bus.
+///
+/// The IDL parser uses this to translate IDL-classed arrays to and
+/// from hashes.
+pub trait DataSerializer {
+    /// Translate a value arriving from the network.
+    fn unpack(&self, value: JsonValue) -> JsonValue;
+    /// Translate a value departing for the network.
+    fn pack(&self, value: JsonValue) -> JsonValue;
+}
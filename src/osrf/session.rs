@@ -0,0 +1,293 @@
+//! Client sessions and in-flight requests.
+
+use crate::osrf::bus;
+use crate::osrf::client::Client;
+use crate::osrf::message::{
+    Message, MessageStatus, MessageType, Method, Payload, TransportMessage,
+};
+use json::JsonValue;
+use std::cell::RefCell;
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long we wait for a CONNECT handshake to complete.
+const CONNECT_TIMEOUT: u64 = 10;
+
+static THREAD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn generate_thread() -> String {
+    let counter = THREAD_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_micros();
+    format!("{}-{}-{}", process::id(), epoch, counter)
+}
+
+pub struct Session {
+    client: Client,
+    service: String,
+    thread: String,
+    connected: bool,
+    /// Address of the specific worker backing this session once one
+    /// has responded to us.
+    remote_addr: Option<String>,
+    last_thread_trace: usize,
+    /// Messages pulled off the bus for this session but not yet
+    /// claimed by a request.
+    backlog: Vec<Message>,
+}
+
+impl Session {
+    fn destination(&self) -> String {
+        if self.connected {
+            if let Some(addr) = &self.remote_addr {
+                return addr.to_string();
+            }
+        }
+        bus::service_address(&self.service)
+    }
+}
+
+/// Cheaply cloneable handle to a client session.
+#[derive(Clone)]
+pub struct SessionHandle {
+    session: Rc<RefCell<Session>>,
+}
+
+impl SessionHandle {
+    pub(crate) fn new(client: Client, service: &str) -> SessionHandle {
+        SessionHandle {
+            session: Rc::new(RefCell::new(Session {
+                client,
+                service: service.to_string(),
+                thread: generate_thread(),
+                connected: false,
+                remote_addr: None,
+                last_thread_trace: 0,
+                backlog: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn service(&self) -> String {
+        self.session.borrow().service.to_string()
+    }
+
+    /// The session thread value, which the server echoes back and
+    /// logs, so client and server activity can be correlated.
+    pub fn thread(&self) -> String {
+        self.session.borrow().thread.to_string()
+    }
+
+    pub fn connected(&self) -> bool {
+        self.session.borrow().connected
+    }
+
+    /// Issue a method request, returning a Request for response
+    /// collection.
+    pub fn request(&self, method: &str, params: Vec<JsonValue>) -> Result<Request, String> {
+        let mut session = self.session.borrow_mut();
+        session.last_thread_trace += 1;
+        let thread_trace = session.last_thread_trace;
+
+        let params = match session.client.serializer() {
+            Some(s) => params.into_iter().map(|p| s.pack(p)).collect(),
+            None => params,
+        };
+
+        let msg = Message::new(
+            MessageType::Request,
+            thread_trace,
+            Payload::Method(Method::new(method, params)),
+        );
+
+        let tmsg = TransportMessage::with_body(
+            &session.destination(),
+            &session.client.address(),
+            &session.thread,
+            msg,
+        );
+
+        session.client.send(&tmsg)?;
+
+        drop(session);
+
+        Ok(Request {
+            session: self.clone(),
+            thread_trace,
+            complete: false,
+        })
+    }
+
+    /// Establish a stateful connection to a single service worker.
+    pub fn connect(&self) -> Result<(), String> {
+        if self.connected() {
+            return Ok(());
+        }
+
+        let thread_trace = {
+            let mut session = self.session.borrow_mut();
+            session.last_thread_trace += 1;
+
+            let msg = Message::new(
+                MessageType::Connect,
+                session.last_thread_trace,
+                Payload::NoPayload,
+            );
+
+            let tmsg = TransportMessage::with_body(
+                &session.destination(),
+                &session.client.address(),
+                &session.thread,
+                msg,
+            );
+
+            session.client.send(&tmsg)?;
+            session.last_thread_trace
+        };
+
+        // Wait for the OK status signaling the connection is open.
+        let mut req = Request {
+            session: self.clone(),
+            thread_trace,
+            complete: false,
+        };
+
+        req.recv(CONNECT_TIMEOUT)?;
+
+        if self.connected() {
+            Ok(())
+        } else {
+            Err(format!("CONNECT timed out for {}", self.service()))
+        }
+    }
+
+    /// Disconnect a stateful session.  This is fire-and-forget; no
+    /// response is expected.
+    pub fn disconnect(&self) -> Result<(), String> {
+        let mut session = self.session.borrow_mut();
+
+        if !session.connected {
+            return Ok(());
+        }
+
+        let msg = Message::new(MessageType::Disconnect, 0, Payload::NoPayload);
+
+        let tmsg = TransportMessage::with_body(
+            &session.destination(),
+            &session.client.address(),
+            &session.thread,
+            msg,
+        );
+
+        session.client.send(&tmsg)?;
+        session.connected = false;
+        session.remote_addr = None;
+
+        Ok(())
+    }
+}
+
+/// An in-flight API request.
+pub struct Request {
+    session: SessionHandle,
+    thread_trace: usize,
+    complete: bool,
+}
+
+impl Request {
+    pub fn complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Wait up to timeout seconds for the next response value.
+    ///
+    /// Returns None when the request is complete or the timeout is
+    /// exhausted.
+    pub fn recv(&mut self, timeout: u64) -> Result<Option<JsonValue>, String> {
+        if self.complete {
+            return Ok(None);
+        }
+
+        loop {
+            if let Some(value) = self.process_backlog()? {
+                return Ok(Some(value));
+            }
+            if self.complete {
+                return Ok(None);
+            }
+
+            let (client, thread) = {
+                let session = self.session.session.borrow();
+                (session.client.clone(), session.thread.to_string())
+            };
+
+            let tmsg = match client.recv_for_thread(&thread, timeout)? {
+                Some(t) => t,
+                None => return Ok(None), // timed out
+            };
+
+            let mut session = self.session.session.borrow_mut();
+            session.remote_addr = Some(tmsg.from().to_string());
+            for msg in tmsg.body() {
+                session.backlog.push(msg.clone());
+            }
+        }
+    }
+
+    /// Scan the session backlog for messages matching our thread
+    /// trace, consuming status messages until a result is found.
+    fn process_backlog(&mut self) -> Result<Option<JsonValue>, String> {
+        loop {
+            let mut session = self.session.session.borrow_mut();
+
+            let pos = match session
+                .backlog
+                .iter()
+                .position(|m| m.thread_trace() == self.thread_trace)
+            {
+                Some(p) => p,
+                None => return Ok(None),
+            };
+
+            let mut msg = session.backlog.remove(pos);
+
+            match msg.payload_mut() {
+                Payload::Result(res) => {
+                    let content = res.take_content();
+                    let content = match session.client.serializer() {
+                        Some(s) => s.unpack(content),
+                        None => content,
+                    };
+                    return Ok(Some(content));
+                }
+                Payload::Status(stat) => match stat.status() {
+                    MessageStatus::Ok => session.connected = true,
+                    MessageStatus::Continue | MessageStatus::Accepted => {}
+                    MessageStatus::Complete => {
+                        self.complete = true;
+                        return Ok(None);
+                    }
+                    MessageStatus::Timeout => {
+                        return Err(format!(
+                            "Request {} timed out server-side",
+                            self.thread_trace
+                        ));
+                    }
+                    s => {
+                        return Err(format!(
+                            "Request {} failed: {:?} {}",
+                            self.thread_trace,
+                            s,
+                            stat.text()
+                        ));
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! WebSocket translator transport.
+//!
+//! Speaks the same OpenSRF-over-websockets protocol as the browser
+//! staff client: each frame is a JSON envelope carrying the service,
+//! the session thread, and an array of osrfMessage values.  Unlike
+//! the HTTP gateway, the translator relays to the bus, so stateful
+//! (CONNECT-ed) sessions work over this transport.
+
+use crate::osrf::bus;
+use crate::osrf::message::{Message, MessageType, TransportMessage};
+use std::collections::HashMap;
+use std::net::TcpStream;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tungstenite::stream::MaybeTlsStream;
+
+static ADDR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Build the outbound frame for a transport message.
+fn frame_from_transport_message(service: &str, tmsg: &TransportMessage) -> String {
+    let body: Vec<json::JsonValue> = tmsg.body().iter().map(|m| m.to_json_value()).collect();
+
+    let frame = json::object! {
+        service: service,
+        thread: tmsg.thread(),
+        osrf_msg: json::JsonValue::Array(body),
+    };
+
+    frame.dump()
+}
+
+/// Parse an inbound frame into its thread and messages.
+///
+/// Tolerates osrf_msg arriving either as an array or as a
+/// JSON-encoded string, as different translator versions send.
+fn parse_frame(text: &str) -> Result<(String, Vec<Message>), String> {
+    let frame = json::parse(text).map_err(|e| format!("Cannot parse websocket frame: {e}"))?;
+
+    let thread = frame["thread"]
+        .as_str()
+        .ok_or_else(|| format!("Websocket frame has no thread: {text}"))?
+        .to_string();
+
+    let body = match frame["osrf_msg"].as_str() {
+        Some(encoded) => {
+            json::parse(encoded).map_err(|e| format!("Cannot parse osrf_msg: {e}"))?
+        }
+        None => frame["osrf_msg"].clone(),
+    };
+
+    let mut messages = Vec::new();
+    for mjv in body.members() {
+        match Message::from_json_value(mjv) {
+            Some(msg) => messages.push(msg),
+            None => return Err(format!("Malformed osrfMessage: {}", mjv.dump())),
+        }
+    }
+
+    Ok((thread, messages))
+}
+
+/// A connection to a websocket translator endpoint.
+pub struct WebSocket {
+    socket: tungstenite::WebSocket<MaybeTlsStream<TcpStream>>,
+    /// Synthetic client address, used where the bus address would be.
+    address: String,
+    /// Which service each active thread is talking to, so responses
+    /// can carry a service address in their "from" field.
+    thread_services: HashMap<String, String>,
+}
+
+impl WebSocket {
+    /// Connect to a translator endpoint, e.g.
+    /// "wss://example.org:443/osrf-websocket-translate".
+    pub fn connect(url: &str) -> Result<WebSocket, String> {
+        let (socket, _response) = tungstenite::connect(url)
+            .map_err(|e| format!("Cannot connect to websocket at {url}: {e}"))?;
+
+        let counter = ADDR_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_micros();
+
+        Ok(WebSocket {
+            socket,
+            address: format!("opensrf:websocket:{}:{}:{}", process::id(), epoch, counter),
+            thread_services: HashMap::new(),
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Send a transport message to the translator.
+    pub(crate) fn send(&mut self, tmsg: &TransportMessage) -> Result<(), String> {
+        // The translator routes by service name; sessions connected
+        // to a worker keep using the service recorded at request
+        // time.
+        let service = match tmsg.to().strip_prefix("opensrf:service:") {
+            Some(service) => service.to_string(),
+            None => self
+                .thread_services
+                .get(tmsg.thread())
+                .cloned()
+                .ok_or_else(|| {
+                    format!("Cannot route address via websocket: {}", tmsg.to())
+                })?,
+        };
+
+        let disconnecting = tmsg
+            .body()
+            .iter()
+            .any(|m| m.mtype() == MessageType::Disconnect);
+
+        let frame = frame_from_transport_message(&service, tmsg);
+
+        self.socket
+            .send(tungstenite::Message::Text(frame))
+            .map_err(|e| format!("Error sending to websocket: {e}"))?;
+
+        if disconnecting {
+            self.thread_services.remove(tmsg.thread());
+        } else {
+            self.thread_services
+                .insert(tmsg.thread().to_string(), service);
+        }
+
+        Ok(())
+    }
+
+    /// Wait up to timeout seconds for a transport message to arrive.
+    pub(crate) fn recv(&mut self, timeout: u64) -> Result<Option<TransportMessage>, String> {
+        self.set_read_timeout(timeout)?;
+
+        loop {
+            let frame = match self.socket.read() {
+                Ok(frame) => frame,
+                Err(tungstenite::Error::Io(e))
+                    if e.kind() == std::io::ErrorKind::WouldBlock
+                        || e.kind() == std::io::ErrorKind::TimedOut =>
+                {
+                    return Ok(None);
+                }
+                Err(e) => return Err(format!("Error receiving from websocket: {e}")),
+            };
+
+            let text = match frame {
+                tungstenite::Message::Text(text) => text,
+                // Control frames are handled by the library; wait on.
+                _ => continue,
+            };
+
+            let (thread, messages) = parse_frame(&text)?;
+
+            let from = match self.thread_services.get(&thread) {
+                Some(service) => bus::service_address(service),
+                None => self.address.clone(),
+            };
+
+            let mut tmsg = TransportMessage::new(&self.address, &from, &thread);
+            tmsg.body_mut().extend(messages);
+
+            return Ok(Some(tmsg));
+        }
+    }
+
+    /// Apply a read timeout to the underlying TCP stream.
+    fn set_read_timeout(&mut self, timeout: u64) -> Result<(), String> {
+        let duration = Some(Duration::from_secs(timeout.max(1)));
+
+        let result = match self.socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => stream.set_read_timeout(duration),
+            MaybeTlsStream::NativeTls(stream) => stream.get_ref().set_read_timeout(duration),
+            _ => Ok(()),
+        };
+
+        result.map_err(|e| format!("Cannot set websocket read timeout: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osrf::message::{Method, Payload};
+
+    #[test]
+    fn test_frame_round_trip() {
+        let method = Method::new("opensrf.system.echo", vec![json::from("hello")]);
+        let msg = Message::new(MessageType::Request, 1, Payload::Method(method));
+        let tmsg = TransportMessage::with_body(
+            &bus::service_address("open-ils.actor"),
+            "client-addr",
+            "thread-1",
+            msg,
+        );
+
+        let frame = frame_from_transport_message("open-ils.actor", &tmsg);
+
+        let (thread, messages) = parse_frame(&frame).expect("frame should parse");
+        assert_eq!(thread, "thread-1");
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].mtype(), MessageType::Request);
+    }
+
+    #[test]
+    fn test_parse_string_encoded_body() {
+        // Older translators JSON-encode the message array.
+        let frame = json::object! {
+            thread: "thread-2",
+            osrf_msg: json::array![
+                json::object! {
+                    "__c": "osrfMessage",
+                    "__p": {threadTrace: 1, type: "STATUS", locale: "en-US"},
+                }
+            ]
+            .dump(),
+        };
+
+        let (thread, messages) = parse_frame(&frame.dump()).expect("frame should parse");
+        assert_eq!(thread, "thread-2");
+        assert_eq!(messages.len(), 1);
+
+        assert!(parse_frame("{\"osrf_msg\": []}").is_err()); // no thread
+    }
+}
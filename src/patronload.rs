@@ -0,0 +1,494 @@
+//! Patron import/update from delimited files.
+//!
+//! Reads CSV or TSV patron data with a field-mapping config, matches
+//! incoming rows against existing patrons by barcode, username, or
+//! email, and creates or updates patrons in batched transactions.
+//! Embedded newlines inside quoted fields are not supported.
+
+use crate::editor::Editor;
+use crate::util;
+use json::JsonValue;
+use std::collections::HashMap;
+use std::fs;
+
+/// The pseudo-field naming the patron's active card barcode.  The
+/// barcode lives on the card object, not the patron, so it gets
+/// special handling throughout.
+pub const BARCODE_FIELD: &str = "barcode";
+
+/// How incoming rows are matched against existing patrons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Matchpoint {
+    Barcode,
+    Usrname,
+    Email,
+}
+
+impl std::str::FromStr for Matchpoint {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Matchpoint, String> {
+        match value {
+            "barcode" => Ok(Matchpoint::Barcode),
+            "usrname" => Ok(Matchpoint::Usrname),
+            "email" => Ok(Matchpoint::Email),
+            _ => Err(format!("Unknown matchpoint: {value}")),
+        }
+    }
+}
+
+/// Field-mapping configuration, loaded from a JSON file:
+///
+/// ```json
+/// {
+///     "matchpoint": "barcode",
+///     "fields": {
+///         "Patron Barcode": "barcode",
+///         "Last Name": "family_name",
+///         "Email Address": "email"
+///     },
+///     "defaults": {"profile": 2, "home_ou": 4}
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FieldMap {
+    /// Source column name => patron field name.
+    pub fields: HashMap<String, String>,
+    /// Patron field values applied to newly created patrons.
+    pub defaults: JsonValue,
+    pub matchpoint: Matchpoint,
+}
+
+impl FieldMap {
+    pub fn from_file(path: &str) -> Result<FieldMap, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Cannot read field map {path}: {e}"))?;
+        FieldMap::from_json_string(&text)
+    }
+
+    pub fn from_json_string(text: &str) -> Result<FieldMap, String> {
+        let conf = json::parse(text).map_err(|e| format!("Invalid field map JSON: {e}"))?;
+
+        let mut fields = HashMap::new();
+        for (column, field) in conf["fields"].entries() {
+            let field = field
+                .as_str()
+                .ok_or_else(|| format!("Invalid mapping for column {column}"))?;
+            fields.insert(column.to_string(), field.to_string());
+        }
+
+        if fields.is_empty() {
+            return Err("Field map has no field mappings".to_string());
+        }
+
+        let matchpoint = conf["matchpoint"].as_str().unwrap_or("barcode").parse()?;
+
+        Ok(FieldMap {
+            fields,
+            defaults: conf["defaults"].clone(),
+            matchpoint,
+        })
+    }
+}
+
+/// Split one delimited line, honoring double-quoted fields with
+/// doubled-quote escaping.
+pub fn split_row(line: &str, delimiter: char) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if quoted {
+            if ch == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    value.push('"');
+                } else {
+                    quoted = false;
+                }
+            } else {
+                value.push(ch);
+            }
+        } else if ch == '"' && value.is_empty() {
+            quoted = true;
+        } else if ch == delimiter {
+            values.push(std::mem::take(&mut value));
+        } else {
+            value.push(ch);
+        }
+    }
+
+    values.push(value);
+    values
+}
+
+/// Fields whose incoming values differ from an existing patron's,
+/// as (field, old, new) tuples.  Empty incoming values are treated
+/// as "no data", not as requests to clear the field.
+pub fn diff_fields(existing: &JsonValue, incoming: &JsonValue) -> Vec<(String, String, String)> {
+    let mut diffs = Vec::new();
+
+    for (field, new_value) in incoming.entries() {
+        if field == BARCODE_FIELD {
+            continue;
+        }
+
+        let new_str = match new_value.as_str() {
+            Some(s) if !s.is_empty() => s.to_string(),
+            Some(_) => continue,
+            None => new_value.dump(),
+        };
+
+        let old_value = &existing[field];
+        let old_str = match old_value.as_str() {
+            Some(s) => s.to_string(),
+            None if old_value.is_null() => String::new(),
+            None => old_value.dump(),
+        };
+
+        if old_str != new_str {
+            diffs.push((field.to_string(), old_str, new_str));
+        }
+    }
+
+    diffs
+}
+
+/// Counters for a load run.
+#[derive(Debug, Clone, Default)]
+pub struct LoadCounts {
+    pub rows_read: usize,
+    pub patrons_created: usize,
+    pub patrons_updated: usize,
+    pub unchanged: usize,
+    pub errors: usize,
+}
+
+pub struct PatronLoader {
+    editor: Editor,
+    map: FieldMap,
+    dry_run: bool,
+    counts: LoadCounts,
+    /// Creates/updates are committed in batches this large.
+    batch_size: usize,
+    pending_in_batch: usize,
+}
+
+impl PatronLoader {
+    pub fn new(editor: Editor, map: FieldMap, dry_run: bool) -> Self {
+        PatronLoader {
+            editor,
+            map,
+            dry_run,
+            counts: LoadCounts::default(),
+            batch_size: 100,
+            pending_in_batch: 0,
+        }
+    }
+
+    pub fn counts(&self) -> &LoadCounts {
+        &self.counts
+    }
+
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Load one delimited file.  The first row must be a header row
+    /// naming the columns used in the field map.
+    pub fn load_file(&mut self, path: &str, delimiter: char) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {path}: {e}"))?;
+
+        let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+
+        let headers: Vec<String> = match lines.next() {
+            Some(header) => split_row(header, delimiter),
+            None => return Err(format!("{path} has no header row")),
+        };
+
+        for line in lines {
+            self.counts.rows_read += 1;
+            let values = split_row(line, delimiter);
+
+            if let Err(e) = self.process_row(&headers, &values) {
+                self.counts.errors += 1;
+                log::error!("Error on row {}: {e}", self.counts.rows_read);
+            }
+        }
+
+        self.flush_batch()
+    }
+
+    /// Apply one data row: map it to patron fields, then create or
+    /// update as needed.
+    fn process_row(&mut self, headers: &[String], values: &[String]) -> Result<(), String> {
+        let mut incoming = json::object! {};
+        let mut barcode = None;
+
+        for (index, header) in headers.iter().enumerate() {
+            let field = match self.map.fields.get(header) {
+                Some(f) => f,
+                None => continue,
+            };
+
+            let value = values.get(index).map(|v| v.trim()).unwrap_or("");
+
+            if field == BARCODE_FIELD {
+                if !value.is_empty() {
+                    barcode = Some(value.to_string());
+                }
+            } else {
+                incoming[field.as_str()] = value.into();
+            }
+        }
+
+        let existing = self.find_existing(&incoming, barcode.as_deref())?;
+
+        match existing {
+            Some(patron) => self.update_patron(patron, &incoming),
+            None => self.create_patron(&incoming, barcode.as_deref()),
+        }
+    }
+
+    /// Look up an existing patron via the configured matchpoint.
+    fn find_existing(
+        &mut self,
+        incoming: &JsonValue,
+        barcode: Option<&str>,
+    ) -> Result<Option<JsonValue>, String> {
+        let (class, filter) = match self.map.matchpoint {
+            Matchpoint::Barcode => {
+                let barcode = match barcode {
+                    Some(b) => b,
+                    None => return Err("Row has no barcode to match on".to_string()),
+                };
+                ("ac", json::object! {barcode: barcode})
+            }
+            Matchpoint::Usrname => match incoming["usrname"].as_str() {
+                Some(u) if !u.is_empty() => ("au", json::object! {usrname: u}),
+                _ => return Err("Row has no usrname to match on".to_string()),
+            },
+            Matchpoint::Email => match incoming["email"].as_str() {
+                Some(e) if !e.is_empty() => ("au", json::object! {email: e}),
+                _ => return Err("Row has no email to match on".to_string()),
+            },
+        };
+
+        let mut results = self.editor.search(class, filter)?;
+
+        if results.len() > 1 {
+            return Err(format!("Matchpoint matches {} patrons", results.len()));
+        }
+
+        let hit = match results.pop() {
+            Some(h) => h,
+            None => return Ok(None),
+        };
+
+        if class == "au" {
+            return Ok(Some(hit));
+        }
+
+        // Barcode matches land on the card; fetch its patron.
+        Ok(self.editor.retrieve("au", hit["usr"].clone())?)
+    }
+
+    /// Update an existing patron with any changed incoming fields.
+    fn update_patron(&mut self, patron: JsonValue, incoming: &JsonValue) -> Result<(), String> {
+        let diffs = diff_fields(&patron, incoming);
+
+        if diffs.is_empty() {
+            self.counts.unchanged += 1;
+            return Ok(());
+        }
+
+        let patron_id = patron["id"].dump();
+
+        for (field, old, new) in &diffs {
+            if self.dry_run {
+                println!("patron {patron_id}: {field}: {old:?} -> {new:?}");
+            } else {
+                log::debug!("patron {patron_id}: {field}: {old:?} -> {new:?}");
+            }
+        }
+
+        self.counts.patrons_updated += 1;
+
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mut patron = patron;
+        for (field, _, new) in diffs {
+            patron[field.as_str()] = new.as_str().into();
+        }
+
+        self.batched_request("open-ils.cstore.direct.actor.user.update", vec![patron])
+    }
+
+    /// Create a patron from the incoming fields plus configured
+    /// defaults, along with its card when a barcode is present.
+    fn create_patron(&mut self, incoming: &JsonValue, barcode: Option<&str>) -> Result<(), String> {
+        self.counts.patrons_created += 1;
+
+        if self.dry_run {
+            println!(
+                "would create patron {} barcode={}",
+                incoming.dump(),
+                barcode.unwrap_or("<none>")
+            );
+            return Ok(());
+        }
+
+        let mut patron = json::object! {"_classname": "au"};
+
+        for (field, value) in self.map.defaults.entries() {
+            patron[field] = value.clone();
+        }
+
+        for (field, value) in incoming.entries() {
+            patron[field] = value.clone();
+        }
+
+        if !self.editor.in_transaction() {
+            self.editor.xact_begin()?;
+        }
+
+        let created = match self
+            .editor
+            .request("open-ils.cstore.direct.actor.user.create", vec![patron])
+        {
+            Ok(c) => c,
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                self.pending_in_batch = 0;
+                return Err(e.into());
+            }
+        };
+
+        if let Some(barcode) = barcode {
+            let patron_id = util::json_int(&created["id"])?;
+
+            let card = json::object! {
+                "_classname": "ac",
+                usr: patron_id,
+                barcode: barcode,
+                active: "t",
+            };
+
+            let card = match self
+                .editor
+                .request("open-ils.cstore.direct.actor.card.create", vec![card])
+            {
+                Ok(c) => c,
+                Err(e) => {
+                    self.editor.xact_rollback()?;
+                    self.pending_in_batch = 0;
+                    return Err(e.into());
+                }
+            };
+
+            let mut patron = created;
+            patron["card"] = card["id"].clone();
+
+            if let Err(e) = self
+                .editor
+                .request("open-ils.cstore.direct.actor.user.update", vec![patron])
+            {
+                self.editor.xact_rollback()?;
+                self.pending_in_batch = 0;
+                return Err(e.into());
+            }
+        }
+
+        self.pending_in_batch += 1;
+        if self.pending_in_batch >= self.batch_size {
+            self.flush_batch()?;
+        }
+
+        Ok(())
+    }
+
+    /// Issue a write request inside the running batch transaction.
+    fn batched_request(&mut self, method: &str, params: Vec<JsonValue>) -> Result<(), String> {
+        if !self.editor.in_transaction() {
+            self.editor.xact_begin()?;
+        }
+
+        match self.editor.request(method, params) {
+            Ok(_) => {
+                self.pending_in_batch += 1;
+                if self.pending_in_batch >= self.batch_size {
+                    self.flush_batch()?;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                self.pending_in_batch = 0;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Commit any open batch transaction.
+    pub fn flush_batch(&mut self) -> Result<(), String> {
+        if self.editor.in_transaction() {
+            self.editor.commit()?;
+        }
+        self.pending_in_batch = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_row() {
+        assert_eq!(split_row("a,b,c", ','), vec!["a", "b", "c"]);
+        assert_eq!(split_row("a\tb", '\t'), vec!["a", "b"]);
+        assert_eq!(split_row("a,,c", ','), vec!["a", "", "c"]);
+        assert_eq!(
+            split_row(r#""Smith, Pat",123"#, ','),
+            vec!["Smith, Pat", "123"]
+        );
+        assert_eq!(split_row(r#""say ""hi""",x"#, ','), vec![r#"say "hi""#, "x"]);
+    }
+
+    #[test]
+    fn test_diff_fields() {
+        let existing = json::object! {
+            family_name: "Smith",
+            email: "old@example.org",
+            day_phone: JsonValue::Null,
+        };
+
+        let incoming = json::object! {
+            family_name: "Smith",
+            email: "new@example.org",
+            day_phone: "",
+        };
+
+        let diffs = diff_fields(&existing, &incoming);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].0, "email");
+        assert_eq!(diffs[0].2, "new@example.org");
+    }
+
+    #[test]
+    fn test_field_map() {
+        let map = FieldMap::from_json_string(
+            r#"{"matchpoint": "email", "fields": {"Last": "family_name"}}"#,
+        )
+        .expect("field map should parse");
+
+        assert_eq!(map.matchpoint, Matchpoint::Email);
+        assert_eq!(map.fields.get("Last").unwrap(), "family_name");
+
+        assert!(FieldMap::from_json_string(r#"{"fields": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Standing penalty maintenance: recalculating system penalties
+//! and applying/removing manual ones.
+//!
+//! System penalties (PATRON_EXCEEDS_FINES and friends) are
+//! recalculated server-side, which fires the penalty triggers;
+//! manual penalties like STAFF_CHR are plain ausp rows with a note.
+
+use crate::editor::Editor;
+use crate::event::EgEvent;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::sync::Arc;
+
+const PENALTY_TIMEOUT: u64 = 60;
+
+/// Build the ausp object the penalty APIs expect.
+fn penalty_object(
+    usr_id: i64,
+    penalty_type: i64,
+    org_id: i64,
+    staff_id: i64,
+    note: Option<&str>,
+) -> JsonValue {
+    let mut penalty = json::object! {
+        "_classname": "ausp",
+        isnew: 1,
+        usr: usr_id,
+        standing_penalty: penalty_type,
+        org_unit: org_id,
+        staff: staff_id,
+        set_date: "now",
+    };
+
+    if let Some(note) = note {
+        penalty["note"] = note.into();
+    }
+
+    penalty
+}
+
+/// Drives penalty operations for one authenticated session.
+pub struct Penalties {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+}
+
+impl Penalties {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Penalties {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// Call an open-ils.actor method with the authtoken prepended
+    /// and return its first response.
+    fn request(&self, method: &str, mut params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        params.insert(0, json::from(self.authtoken.as_str()));
+
+        let session = self.client.session("open-ils.actor");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(PENALTY_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Fail on a non-success event response.
+    fn check_event(method: &str, resp: JsonValue) -> Result<JsonValue, String> {
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("{method} failed: {evt}"));
+            }
+        }
+        Ok(resp)
+    }
+
+    /// Recalculate a patron's system penalties, firing any penalty
+    /// triggers whose thresholds have been crossed or cleared.
+    pub fn recalculate(&self, usr_id: i64) -> Result<(), String> {
+        let method = "open-ils.actor.user.penalties.update";
+        let resp = self.request(method, vec![json::from(usr_id)])?;
+        Penalties::check_event(method, resp).map(|_| ())
+    }
+
+    /// A patron's active (un-stopped) penalties.
+    pub fn active_penalties(&mut self, usr_id: i64) -> Result<Vec<JsonValue>, String> {
+        Ok(self.editor.search(
+            "ausp",
+            json::object! {usr: usr_id, stop_date: JsonValue::Null},
+        )?)
+    }
+
+    /// Look up a penalty type (csp row) by name, e.g. "STAFF_CHR".
+    pub fn penalty_type(&mut self, name: &str) -> Result<Option<JsonValue>, String> {
+        let mut hits = self.editor.search("csp", json::object! {name: name})?;
+        Ok(hits.pop())
+    }
+
+    /// Apply a manual penalty to a patron, returning the new ausp
+    /// ID.  The staff ID comes from the authenticated requestor.
+    pub fn apply(
+        &mut self,
+        usr_id: i64,
+        penalty_type: i64,
+        org_id: i64,
+        note: Option<&str>,
+    ) -> Result<i64, String> {
+        if !self.editor.checkauth()? {
+            return Err("Authtoken is no longer valid".to_string());
+        }
+
+        let staff_id = match self.editor.requestor() {
+            Some(requestor) => util::json_int(&requestor["id"])?,
+            None => return Err("No requestor for penalty application".to_string()),
+        };
+
+        let penalty = penalty_object(usr_id, penalty_type, org_id, staff_id, note);
+
+        let method = "open-ils.actor.user.penalty.apply";
+        let resp = self.request(method, vec![penalty])?;
+        let resp = Penalties::check_event(method, resp)?;
+
+        util::json_int(&resp)
+    }
+
+    /// Remove a patron's active penalties of one type at an org.
+    /// Returns how many were removed.
+    pub fn remove(
+        &mut self,
+        usr_id: i64,
+        penalty_type: i64,
+        org_id: i64,
+    ) -> Result<usize, String> {
+        let rows = self.editor.search(
+            "ausp",
+            json::object! {
+                usr: usr_id,
+                standing_penalty: penalty_type,
+                org_unit: org_id,
+                stop_date: JsonValue::Null,
+            },
+        )?;
+
+        let method = "open-ils.actor.user.penalty.remove";
+        let mut removed = 0;
+
+        for row in rows {
+            let resp = self.request(method, vec![row])?;
+            Penalties::check_event(method, resp)?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_penalty_object() {
+        let penalty = penalty_object(7, 21, 4, 1, Some("Chronic offender"));
+
+        assert_eq!(penalty["_classname"], "ausp");
+        assert_eq!(penalty["usr"], 7);
+        assert_eq!(penalty["standing_penalty"], 21);
+        assert_eq!(penalty["org_unit"], 4);
+        assert_eq!(penalty["note"], "Chronic offender");
+
+        let penalty = penalty_object(7, 21, 4, 1, None);
+        assert!(penalty["note"].is_null());
+    }
+}
@@ -0,0 +1,149 @@
+//! Cached lookups for small, frequently referenced config tables:
+//! copy statuses (ccs), copy locations (acpl), circ modifiers
+//! (ccm), billing types (cbt), and the like.
+//!
+//! Each class is bulk-loaded on first use and answered from memory
+//! afterwards, by ID or by name, instead of issuing one retrieve
+//! per status code.  Long-running tools can refresh a class when
+//! config changes are expected.
+
+use crate::editor::Editor;
+use crate::util;
+use json::JsonValue;
+use std::collections::HashMap;
+
+/// One loaded class: rows by ID plus a name index.
+struct ClassCache {
+    by_id: HashMap<i64, JsonValue>,
+    /// Lowercased name/code/label => ID.
+    by_name: HashMap<String, i64>,
+}
+
+impl ClassCache {
+    fn from_rows(rows: Vec<JsonValue>) -> Result<ClassCache, String> {
+        let mut cache = ClassCache {
+            by_id: HashMap::new(),
+            by_name: HashMap::new(),
+        };
+
+        for row in rows {
+            let id = util::json_int(&row["id"])?;
+
+            // Tables vary in which field names the row; index
+            // whichever are present.
+            for field in ["name", "code", "label", "shortname"] {
+                if let Some(name) = row[field].as_str() {
+                    cache.by_name.insert(name.to_lowercase(), id);
+                }
+            }
+
+            cache.by_id.insert(id, row);
+        }
+
+        Ok(cache)
+    }
+}
+
+/// Lazily loaded reference data, keyed by IDL class.
+pub struct RefDataCache {
+    editor: Editor,
+    classes: HashMap<String, ClassCache>,
+}
+
+impl RefDataCache {
+    pub fn new(editor: Editor) -> Self {
+        RefDataCache {
+            editor,
+            classes: HashMap::new(),
+        }
+    }
+
+    /// Bulk-load a class if we have not already.
+    fn load(&mut self, idlclass: &str) -> Result<(), String> {
+        if self.classes.contains_key(idlclass) {
+            return Ok(());
+        }
+
+        let rows = self
+            .editor
+            .search(idlclass, json::object! {id: {"!=": JsonValue::Null}})?;
+
+        self.classes
+            .insert(idlclass.to_string(), ClassCache::from_rows(rows)?);
+
+        Ok(())
+    }
+
+    /// Drop and re-fetch one class on next use.
+    pub fn refresh(&mut self, idlclass: &str) {
+        self.classes.remove(idlclass);
+    }
+
+    /// Drop everything; classes reload on next use.
+    pub fn refresh_all(&mut self) {
+        self.classes.clear();
+    }
+
+    /// A row by ID, e.g. `get("ccs", 7)` for the Reshelving status.
+    pub fn get(&mut self, idlclass: &str, id: i64) -> Result<Option<&JsonValue>, String> {
+        self.load(idlclass)?;
+        Ok(self.classes[idlclass].by_id.get(&id))
+    }
+
+    /// A row by name/code/label, case-insensitive.
+    pub fn get_by_name(
+        &mut self,
+        idlclass: &str,
+        name: &str,
+    ) -> Result<Option<&JsonValue>, String> {
+        self.load(idlclass)?;
+
+        let cache = &self.classes[idlclass];
+        Ok(cache
+            .by_name
+            .get(&name.to_lowercase())
+            .and_then(|id| cache.by_id.get(id)))
+    }
+
+    /// The ID for a name, for callers that only need the key.
+    pub fn id_for_name(&mut self, idlclass: &str, name: &str) -> Result<Option<i64>, String> {
+        self.load(idlclass)?;
+        Ok(self.classes[idlclass].by_name.get(&name.to_lowercase()).copied())
+    }
+
+    /// All rows of a class, in no particular order.
+    pub fn all(&mut self, idlclass: &str) -> Result<Vec<&JsonValue>, String> {
+        self.load(idlclass)?;
+        Ok(self.classes[idlclass].by_id.values().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class_cache() {
+        let rows = vec![
+            json::object! {id: 0, name: "Available"},
+            json::object! {id: 1, name: "Checked out"},
+            json::object! {id: 7, name: "Reshelving"},
+        ];
+
+        let cache = ClassCache::from_rows(rows).expect("cache should build");
+
+        assert_eq!(cache.by_id[&7]["name"], "Reshelving");
+        assert_eq!(cache.by_name["checked out"], 1);
+        assert!(!cache.by_name.contains_key("lost"));
+    }
+
+    #[test]
+    fn test_code_indexing() {
+        // Circ modifiers key on code as well as name.
+        let rows = vec![json::object! {id: 3, code: "BOOK", name: "Book"}];
+        let cache = ClassCache::from_rows(rows).unwrap();
+
+        assert_eq!(cache.by_name["book"], 3);
+        assert_eq!(cache.by_name.len(), 1); // same id either way
+    }
+}
@@ -0,0 +1,593 @@
+//! Scheduled report execution: template compilation, query
+//! execution, and output generation.
+//!
+//! This is the engine behind the `eg-reporter` daemon, which stands
+//! in for the clark-kent runner.  Templates are simplified reporter
+//! templates: a core IDL class plus selected columns, filters whose
+//! values may be bound to user parameters, and ordering.
+
+use crate::db::DatabaseConnection;
+use crate::idl;
+use crate::idldb::Translator;
+use crate::util;
+use json::JsonValue;
+use std::fmt;
+use std::sync::Arc;
+
+/// Requested output flavor for a report run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Html,
+    /// SpreadsheetML; plain XML that Excel opens natively.
+    Excel,
+}
+
+impl OutputFormat {
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Html => "html",
+            OutputFormat::Excel => "xls",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<OutputFormat, String> {
+        match value {
+            "csv" => Ok(OutputFormat::Csv),
+            "html" => Ok(OutputFormat::Html),
+            "excel" | "xls" | "xlsx" => Ok(OutputFormat::Excel),
+            _ => Err(format!("Unknown output format: {value}")),
+        }
+    }
+}
+
+/// One selected output column.
+#[derive(Debug, Clone)]
+pub struct SelectField {
+    pub column: String,
+    pub alias: String,
+    /// Optional SQL aggregate/transform (count, sum, min, max, ...).
+    pub transform: Option<String>,
+}
+
+/// One filter condition.  A value of `{"param": "name"}` binds the
+/// condition to a user-supplied report parameter.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub column: String,
+    pub operator: String,
+    pub value: JsonValue,
+}
+
+const SUPPORTED_OPERATORS: &[&str] = &["=", "!=", "<", "<=", ">", ">=", "like", "ilike"];
+const SUPPORTED_TRANSFORMS: &[&str] = &["count", "sum", "min", "max", "avg"];
+
+/// A compiled-ready report template.
+#[derive(Debug, Clone)]
+pub struct ReportTemplate {
+    core_class: String,
+    tablename: String,
+    select: Vec<SelectField>,
+    filters: Vec<Filter>,
+    order_by: Vec<String>,
+}
+
+impl ReportTemplate {
+    /// Parse and validate template data JSON against the IDL.
+    pub fn from_json(idl: &Arc<idl::Parser>, data: &JsonValue) -> Result<ReportTemplate, String> {
+        let core_class = data["core_class"]
+            .as_str()
+            .ok_or("Template has no core_class")?
+            .to_string();
+
+        let class = idl
+            .get_class(&core_class)
+            .ok_or_else(|| format!("No such IDL class: {core_class}"))?;
+
+        let tablename = class
+            .tablename()
+            .ok_or_else(|| format!("IDL class {core_class} has no table"))?
+            .to_string();
+
+        let check_column = |column: &str| {
+            match class.fields().get(column) {
+                Some(f) if !f.is_virtual() => Ok(()),
+                _ => Err(format!("{core_class} has no column {column}")),
+            }
+        };
+
+        let mut select = Vec::new();
+        for field in data["select"].members() {
+            let column = field["column"]
+                .as_str()
+                .ok_or("Select entry has no column")?
+                .to_string();
+            check_column(&column)?;
+
+            let transform = match field["transform"].as_str() {
+                Some(t) => {
+                    let t = t.to_lowercase();
+                    if !SUPPORTED_TRANSFORMS.contains(&t.as_str()) {
+                        return Err(format!("Unsupported transform: {t}"));
+                    }
+                    Some(t)
+                }
+                None => None,
+            };
+
+            select.push(SelectField {
+                alias: field["alias"].as_str().unwrap_or(&column).to_string(),
+                column,
+                transform,
+            });
+        }
+
+        if select.is_empty() {
+            return Err("Template selects no columns".to_string());
+        }
+
+        let mut filters = Vec::new();
+        for filter in data["where"].members() {
+            let column = filter["column"]
+                .as_str()
+                .ok_or("Filter has no column")?
+                .to_string();
+            check_column(&column)?;
+
+            let operator = filter["operator"].as_str().unwrap_or("=").to_lowercase();
+            if !SUPPORTED_OPERATORS.contains(&operator.as_str()) {
+                return Err(format!("Unsupported operator: {operator}"));
+            }
+
+            filters.push(Filter {
+                column,
+                operator,
+                value: filter["value"].clone(),
+            });
+        }
+
+        let mut order_by = Vec::new();
+        for column in data["order_by"].members() {
+            let column = column.as_str().ok_or("Invalid order_by entry")?;
+            check_column(column)?;
+            order_by.push(column.to_string());
+        }
+
+        Ok(ReportTemplate {
+            core_class,
+            tablename,
+            select,
+            filters,
+            order_by,
+        })
+    }
+
+    pub fn core_class(&self) -> &str {
+        &self.core_class
+    }
+
+    /// The output column headers, in order.
+    pub fn column_headers(&self) -> Vec<String> {
+        self.select.iter().map(|f| f.alias.clone()).collect()
+    }
+
+    /// Compile to SQL, resolving `{"param": ...}` filter values from
+    /// the report's user parameters.
+    pub fn compile(&self, params: &JsonValue) -> Result<String, String> {
+        let columns: Vec<String> = self
+            .select
+            .iter()
+            .map(|field| match &field.transform {
+                Some(t) => format!("{}({}) AS \"{}\"", t.to_uppercase(), field.column, field.alias),
+                None => format!("{} AS \"{}\"", field.column, field.alias),
+            })
+            .collect();
+
+        let mut sql = format!("SELECT {} FROM {}", columns.join(", "), self.tablename);
+
+        let mut conditions = Vec::new();
+        for filter in &self.filters {
+            let value = match filter.value["param"].as_str() {
+                Some(name) => {
+                    let value = &params[name];
+                    if value.is_null() {
+                        return Err(format!("Report is missing parameter: {name}"));
+                    }
+                    value
+                }
+                None => &filter.value,
+            };
+
+            let value = if value.is_null() {
+                match filter.operator.as_str() {
+                    "=" => "IS NULL".to_string(),
+                    "!=" => "IS NOT NULL".to_string(),
+                    _ => return Err("NULL requires the = or != operator".to_string()),
+                }
+            } else {
+                format!("{} {}", filter.operator.to_uppercase(), sql_literal(value)?)
+            };
+
+            conditions.push(format!("{} {}", filter.column, value));
+        }
+
+        if !conditions.is_empty() {
+            sql += &format!(" WHERE {}", conditions.join(" AND "));
+        }
+
+        // Aggregates group by every non-aggregated column.
+        if self.select.iter().any(|f| f.transform.is_some()) {
+            let grouped: Vec<String> = self
+                .select
+                .iter()
+                .filter(|f| f.transform.is_none())
+                .map(|f| f.column.clone())
+                .collect();
+
+            if !grouped.is_empty() {
+                sql += &format!(" GROUP BY {}", grouped.join(", "));
+            }
+        }
+
+        if !self.order_by.is_empty() {
+            sql += &format!(" ORDER BY {}", self.order_by.join(", "));
+        }
+
+        Ok(sql)
+    }
+}
+
+/// Quote a JSON value as a SQL literal.
+fn sql_literal(value: &JsonValue) -> Result<String, String> {
+    if value.is_number() {
+        return Ok(value.dump());
+    }
+
+    match value.as_str() {
+        Some(s) => Ok(format!("'{}'", s.replace('\'', "''"))),
+        None => Err(format!("Cannot use value in a filter: {}", value.dump())),
+    }
+}
+
+/// Rows produced by a report run.
+pub struct ReportOutput {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<JsonValue>>,
+}
+
+impl ReportOutput {
+    /// Render the output in the requested format.
+    pub fn format(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Csv => self.to_csv(),
+            OutputFormat::Html => self.to_html(),
+            OutputFormat::Excel => self.to_excel(),
+        }
+    }
+
+    fn cell_text(value: &JsonValue) -> String {
+        match value.as_str() {
+            Some(s) => s.to_string(),
+            None if value.is_null() => String::new(),
+            None => value.dump(),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let csv_row = |cells: Vec<String>| {
+            let quoted: Vec<String> = cells
+                .iter()
+                .map(|c| format!("\"{}\"", c.replace('"', "\"\"")))
+                .collect();
+            quoted.join(",")
+        };
+
+        let mut lines = vec![csv_row(self.headers.clone())];
+        for row in &self.rows {
+            lines.push(csv_row(row.iter().map(ReportOutput::cell_text).collect()));
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    fn to_html(&self) -> String {
+        use crate::marc::escape_xml;
+
+        let mut html = String::from("<table>\n<thead><tr>");
+        for header in &self.headers {
+            html += &format!("<th>{}</th>", escape_xml(header));
+        }
+        html += "</tr></thead>\n<tbody>\n";
+
+        for row in &self.rows {
+            html += "<tr>";
+            for cell in row {
+                html += &format!("<td>{}</td>", escape_xml(&ReportOutput::cell_text(cell)));
+            }
+            html += "</tr>\n";
+        }
+
+        html += "</tbody>\n</table>\n";
+        html
+    }
+
+    fn to_excel(&self) -> String {
+        use crate::marc::escape_xml;
+
+        let mut xml = String::from(
+            r#"<?xml version="1.0"?>
+<Workbook xmlns="urn:schemas-microsoft-com:office:spreadsheet"
+ xmlns:ss="urn:schemas-microsoft-com:office:spreadsheet">
+<Worksheet ss:Name="Report"><Table>
+"#,
+        );
+
+        let excel_row = |cells: Vec<String>| {
+            let mut row = String::from("<Row>");
+            for cell in cells {
+                row += &format!(
+                    "<Cell><Data ss:Type=\"String\">{}</Data></Cell>",
+                    escape_xml(&cell)
+                );
+            }
+            row + "</Row>\n"
+        };
+
+        xml += &excel_row(self.headers.clone());
+        for row in &self.rows {
+            xml += &excel_row(row.iter().map(ReportOutput::cell_text).collect());
+        }
+
+        xml += "</Table></Worksheet></Workbook>\n";
+        xml
+    }
+}
+
+/// Executes compiled reports against the database.
+pub struct ReportRunner {
+    db: DatabaseConnection,
+    idl: Arc<idl::Parser>,
+    /// Per-report statement timeout, in seconds.  Zero disables.
+    timeout_secs: u64,
+}
+
+/// A claimed reporter.schedule row plus its report and template.
+#[derive(Debug)]
+pub struct ClaimedRun {
+    pub schedule_id: i64,
+    pub report_id: i64,
+    pub email: Option<String>,
+    pub format: OutputFormat,
+    pub report_params: JsonValue,
+    pub template_data: JsonValue,
+}
+
+impl fmt::Display for ClaimedRun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "run [schedule={} report={}]",
+            self.schedule_id, self.report_id
+        )
+    }
+}
+
+impl ReportRunner {
+    pub fn new(db: DatabaseConnection, idl: Arc<idl::Parser>) -> Self {
+        ReportRunner {
+            db,
+            idl,
+            timeout_secs: 3600,
+        }
+    }
+
+    pub fn set_timeout_secs(&mut self, secs: u64) {
+        self.timeout_secs = secs;
+    }
+
+    pub fn connect(&mut self) -> Result<(), String> {
+        self.db.connect()
+    }
+
+    /// Atomically claim the next pending schedule entry, returning
+    /// None when the queue is empty.
+    pub fn claim_next(&mut self) -> Result<Option<ClaimedRun>, String> {
+        let sql = r#"
+            UPDATE reporter.schedule sched
+                SET start_time = NOW()
+            WHERE sched.id = (
+                SELECT id FROM reporter.schedule
+                WHERE start_time IS NULL AND run_time <= NOW()
+                ORDER BY run_time
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING sched.id, sched.report, sched.email, sched.output_format
+        "#;
+
+        let row = match self
+            .db
+            .client()
+            .query_opt(sql, &[])
+            .map_err(|e| format!("Error claiming schedule entry: {e}"))?
+        {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+
+        let schedule_id: i64 = Translator::col_value_to_json_value(&row, 0)
+            .and_then(|v| util::json_int(&v))?;
+        let report_id: i64 =
+            Translator::col_value_to_json_value(&row, 1).and_then(|v| util::json_int(&v))?;
+        let email = Translator::col_value_to_json_value(&row, 2)?
+            .as_str()
+            .map(|e| e.to_string());
+        let format: OutputFormat = Translator::col_value_to_json_value(&row, 3)?
+            .as_str()
+            .unwrap_or("csv")
+            .parse()?;
+
+        let report_row = self
+            .db
+            .client()
+            .query_one(
+                "SELECT r.data, t.data AS template_data
+                    FROM reporter.report r
+                    JOIN reporter.template t ON (t.id = r.template)
+                    WHERE r.id = $1",
+                &[&report_id],
+            )
+            .map_err(|e| format!("Error fetching report {report_id}: {e}"))?;
+
+        let report_data: String = report_row.get(0);
+        let template_data: String = report_row.get(1);
+
+        Ok(Some(ClaimedRun {
+            schedule_id,
+            report_id,
+            email,
+            format,
+            report_params: json::parse(&report_data)
+                .map_err(|e| format!("Invalid report data: {e}"))?,
+            template_data: json::parse(&template_data)
+                .map_err(|e| format!("Invalid template data: {e}"))?,
+        }))
+    }
+
+    /// Compile and execute a claimed run, returning its rows.
+    pub fn execute(&mut self, run: &ClaimedRun) -> Result<ReportOutput, String> {
+        let template = ReportTemplate::from_json(&self.idl, &run.template_data)?;
+        let sql = template.compile(&run.report_params)?;
+
+        log::info!("{run} compiled to: {sql}");
+
+        let timeout = format!("SET statement_timeout = {}", self.timeout_secs * 1000);
+        self.db
+            .client()
+            .execute(timeout.as_str(), &[])
+            .map_err(|e| format!("Cannot set statement timeout: {e}"))?;
+
+        let rows = self
+            .db
+            .client()
+            .query(sql.as_str(), &[])
+            .map_err(|e| format!("Report query failed: {e}"))?;
+
+        let mut output_rows = Vec::new();
+        for row in &rows {
+            let mut cells = Vec::new();
+            for index in 0..row.columns().len() {
+                cells.push(Translator::col_value_to_json_value(row, index)?);
+            }
+            output_rows.push(cells);
+        }
+
+        Ok(ReportOutput {
+            headers: template.column_headers(),
+            rows: output_rows,
+        })
+    }
+
+    /// Record a completed run.
+    pub fn mark_complete(&mut self, run: &ClaimedRun) -> Result<(), String> {
+        self.db
+            .client()
+            .execute(
+                "UPDATE reporter.schedule SET complete_time = NOW() WHERE id = $1",
+                &[&run.schedule_id],
+            )
+            .map_err(|e| format!("Error completing {run}: {e}"))?;
+        Ok(())
+    }
+
+    /// Record a failed run.
+    pub fn mark_error(&mut self, run: &ClaimedRun, error: &str) -> Result<(), String> {
+        self.db
+            .client()
+            .execute(
+                "UPDATE reporter.schedule
+                    SET complete_time = NOW(), error_code = 1, error_text = $2
+                    WHERE id = $1",
+                &[&run.schedule_id, &error],
+            )
+            .map_err(|e| format!("Error recording failure for {run}: {e}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::tests::TEST_IDL;
+
+    fn test_template() -> JsonValue {
+        json::object! {
+            core_class: "aou",
+            select: [
+                {column: "id", alias: "ID"},
+                {column: "name", alias: "Name"},
+            ],
+            where: [
+                {column: "parent_ou", operator: "=", value: {param: "org"}},
+            ],
+            order_by: ["name"],
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        let idl = idl::Parser::parse_string(TEST_IDL).expect("IDL parses");
+        let template = ReportTemplate::from_json(&idl, &test_template())
+            .expect("template should validate");
+
+        let sql = template
+            .compile(&json::object! {org: 1})
+            .expect("template should compile");
+
+        assert_eq!(
+            sql,
+            "SELECT id AS \"ID\", name AS \"Name\" FROM actor.org_unit \
+                WHERE parent_ou = 1 ORDER BY name"
+        );
+
+        // Missing parameter
+        assert!(template.compile(&json::object! {}).is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        let idl = idl::Parser::parse_string(TEST_IDL).expect("IDL parses");
+
+        let mut data = test_template();
+        data["select"][0]["column"] = "no_such_column".into();
+        assert!(ReportTemplate::from_json(&idl, &data).is_err());
+
+        let mut data = test_template();
+        data["where"][0]["operator"] = "; DROP TABLE".into();
+        assert!(ReportTemplate::from_json(&idl, &data).is_err());
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let output = ReportOutput {
+            headers: vec!["ID".to_string(), "Name".to_string()],
+            rows: vec![vec![json::from(1), json::from("BR\"1\"")]],
+        };
+
+        let csv = output.format(OutputFormat::Csv);
+        assert_eq!(csv, "\"ID\",\"Name\"\n\"1\",\"BR\"\"1\"\"\"\n");
+
+        let html = output.format(OutputFormat::Html);
+        assert!(html.contains("<th>ID</th>"));
+        assert!(html.contains("<td>BR&quot;1&quot;</td>"));
+
+        let excel = output.format(OutputFormat::Excel);
+        assert!(excel.contains("urn:schemas-microsoft-com:office:spreadsheet"));
+        assert!(excel.contains("<Data ss:Type=\"String\">1</Data>"));
+    }
+}
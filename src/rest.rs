@@ -0,0 +1,470 @@
+//! REST+JSON surface over pcrud and selected business APIs.
+//!
+//! Maps HTTP verbs onto open-ils.pcrud calls -- retrieve, search,
+//! create, update, delete -- using the IDL for field validation and
+//! for generating an OpenAPI description of the surface.  Permission
+//! enforcement is pcrud's; this layer only requires that a request
+//! carry an authtoken.
+//!
+//! Routes:
+//!
+//!   POST   /auth/login        log in, returning an authtoken
+//!   GET    /openapi.json      the generated OpenAPI spec
+//!   GET    /idl/{class}/{id}  retrieve one object
+//!   GET    /idl/{class}?f=v   search by field values
+//!   POST   /idl/{class}       create from a JSON body
+//!   PUT    /idl/{class}/{id}  update from a JSON body
+//!   DELETE /idl/{class}/{id}  delete
+
+use crate::auth::{AuthLoginArgs, AuthSession};
+use crate::event::EgEvent;
+use crate::idl;
+use crate::osrf::client::Client;
+use json::JsonValue;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const PCRUD_TIMEOUT: u64 = 60;
+
+/// Largest search page a client may request.
+const MAX_LIMIT: usize = 1000;
+const DEFAULT_LIMIT: usize = 100;
+
+/// Decode a %-encoded query component.
+fn percent_decode(value: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut chars = value.bytes();
+
+    while let Some(byte) = chars.next() {
+        match byte {
+            b'%' => {
+                let hex: Vec<u8> = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&String::from_utf8_lossy(&hex), 16) {
+                    Ok(decoded) => bytes.push(decoded),
+                    Err(_) => bytes.extend_from_slice(&hex),
+                }
+            }
+            b'+' => bytes.push(b' '),
+            _ => bytes.push(byte),
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Parse a query string ("a=b&c=d") into a map.
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+
+    for pair in query.split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = match pair.split_once('=') {
+            Some((k, v)) => (k, v),
+            None => (pair, ""),
+        };
+        map.insert(percent_decode(key), percent_decode(value));
+    }
+
+    map
+}
+
+/// Build a pcrud search filter from query parameters, validating
+/// field names against the class.  limit/offset are reserved for
+/// paging.
+fn search_filter(
+    class: &idl::Class,
+    query: &HashMap<String, String>,
+) -> Result<JsonValue, String> {
+    let mut filter = json::object! {};
+
+    for (key, value) in query {
+        if key == "limit" || key == "offset" {
+            continue;
+        }
+
+        let field = class
+            .fields()
+            .get(key)
+            .ok_or_else(|| format!("No such field on {}: {key}", class.classname()))?;
+
+        if field.is_virtual() {
+            return Err(format!("Cannot search virtual field: {key}"));
+        }
+
+        // Numbers search as numbers so integer keys match.
+        filter[key] = match value.parse::<i64>() {
+            Ok(num) => num.into(),
+            Err(_) => value.as_str().into(),
+        };
+    }
+
+    if filter.is_empty() {
+        return Err("Search requires at least one field filter".to_string());
+    }
+
+    Ok(filter)
+}
+
+/// The OpenAPI type name for an IDL datatype.
+fn openapi_type(datatype: &idl::DataType) -> &'static str {
+    match datatype {
+        idl::DataType::Bool => "boolean",
+        idl::DataType::Id | idl::DataType::Int | idl::DataType::OrgUnit => "integer",
+        idl::DataType::Float | idl::DataType::Money | idl::DataType::Number => "number",
+        _ => "string",
+    }
+}
+
+/// Generate an OpenAPI 3.0 description of the CRUD surface.
+fn openapi_spec(idl: &idl::Parser) -> JsonValue {
+    let mut spec = json::object! {
+        openapi: "3.0.0",
+        info: {
+            title: "Evergreen REST",
+            version: "1.0",
+        },
+        paths: {},
+        components: {schemas: {}},
+    };
+
+    let mut classnames: Vec<&str> = idl.classes().keys().map(|c| c.as_str()).collect();
+    classnames.sort();
+
+    for classname in classnames {
+        let class = &idl.classes()[classname];
+
+        let mut properties = json::object! {};
+        for field in class.real_fields() {
+            properties[field.name()] = json::object! {
+                type: openapi_type(field.datatype()),
+            };
+        }
+
+        let schema = json::object! {
+            type: "object",
+            description: class.label().unwrap_or(classname),
+            properties: properties,
+        };
+        spec["components"]["schemas"][classname] = schema;
+
+        let reference = json::object! {
+            "$ref": format!("#/components/schemas/{classname}"),
+        };
+
+        spec["paths"][format!("/idl/{classname}")] = json::object! {
+            get: {summary: format!("Search {classname} by field values")},
+            post: {summary: format!("Create a {classname}")},
+        };
+        spec["paths"][format!("/idl/{classname}/{{id}}")] = json::object! {
+            get: {
+                summary: format!("Retrieve a {classname}"),
+                responses: {"200": {description: "OK", content: {
+                    "application/json": {schema: reference.clone()},
+                }}},
+            },
+            put: {summary: format!("Update a {classname}")},
+            delete: {summary: format!("Delete a {classname}")},
+        };
+    }
+
+    spec
+}
+
+/// One response, ready for HTTP framing by the caller.
+#[derive(Debug)]
+pub struct RestResponse {
+    pub status: u16,
+    pub body: JsonValue,
+}
+
+impl RestResponse {
+    fn ok(body: JsonValue) -> Self {
+        RestResponse { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        RestResponse {
+            status,
+            body: json::object! {error: message},
+        }
+    }
+}
+
+/// Routes REST requests onto pcrud.
+pub struct RestServer {
+    client: Client,
+    idl: Arc<idl::Parser>,
+}
+
+impl RestServer {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>) -> Self {
+        RestServer {
+            client: client.clone(),
+            idl: idl.clone(),
+        }
+    }
+
+    /// Dispatch one request.
+    pub fn handle(
+        &self,
+        method: &str,
+        path: &str,
+        query: &HashMap<String, String>,
+        authtoken: Option<&str>,
+        body: &str,
+    ) -> RestResponse {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        match (method, segments.as_slice()) {
+            ("POST", ["auth", "login"]) => self.login(body),
+            ("GET", ["openapi.json"]) => RestResponse::ok(openapi_spec(&self.idl)),
+            (_, ["idl", classname]) | (_, ["idl", classname, _]) => {
+                let token = match authtoken {
+                    Some(t) => t,
+                    None => return RestResponse::error(401, "Authentication required"),
+                };
+
+                if self.idl.get_class(classname).is_none() {
+                    return RestResponse::error(404, &format!("No such class: {classname}"));
+                }
+
+                let result = match (method, segments.as_slice()) {
+                    ("GET", ["idl", c, id]) => self.retrieve(token, c, id),
+                    ("GET", ["idl", c]) => self.search(token, c, query),
+                    ("POST", ["idl", c]) => self.create(token, c, body),
+                    ("PUT", ["idl", c, id]) => self.update(token, c, id, body),
+                    ("DELETE", ["idl", c, id]) => self.delete(token, c, id),
+                    _ => return RestResponse::error(405, "Method not allowed"),
+                };
+
+                match result {
+                    Ok(resp) => resp,
+                    Err(e) => RestResponse::error(400, &e),
+                }
+            }
+            _ => RestResponse::error(404, &format!("No such route: {path}")),
+        }
+    }
+
+    fn login(&self, body: &str) -> RestResponse {
+        let args = match json::parse(body) {
+            Ok(jv) => jv,
+            Err(e) => return RestResponse::error(400, &format!("Invalid JSON body: {e}")),
+        };
+
+        let (username, password) = match (args["username"].as_str(), args["password"].as_str()) {
+            (Some(u), Some(p)) => (u, p),
+            _ => return RestResponse::error(400, "username and password required"),
+        };
+
+        let login_args = AuthLoginArgs::new(
+            username,
+            password,
+            args["login_type"].as_str().unwrap_or("staff"),
+            args["workstation"].as_str(),
+        );
+
+        match AuthSession::login(&self.client, &login_args) {
+            Ok(Some(auth)) => RestResponse::ok(json::object! {
+                authtoken: auth.token(),
+                authtime: auth.authtime(),
+            }),
+            Ok(None) => RestResponse::error(401, "Login failed"),
+            Err(e) => RestResponse::error(500, &e),
+        }
+    }
+
+    /// Call a pcrud method on a throw-away session.
+    fn pcrud_request(&self, method: &str, params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        let session = self.client.session("open-ils.pcrud");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(PCRUD_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Run one write inside a pcrud transaction on a connected
+    /// session, committing on success.
+    fn pcrud_write(&self, method: &str, params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        let session = self.client.session("open-ils.pcrud");
+        session.connect()?;
+
+        let result = self.pcrud_write_internal(&session, method, params);
+
+        session.disconnect()?;
+        result
+    }
+
+    fn pcrud_write_internal(
+        &self,
+        session: &crate::osrf::session::SessionHandle,
+        method: &str,
+        params: Vec<JsonValue>,
+    ) -> Result<JsonValue, String> {
+        let token = params[0].clone();
+
+        let mut req = session.request("open-ils.pcrud.transaction.begin", vec![token.clone()])?;
+        req.recv(PCRUD_TIMEOUT)?
+            .ok_or("No response to transaction.begin")?;
+
+        let mut req = session.request(method, params)?;
+        let resp = match req.recv(PCRUD_TIMEOUT)? {
+            Some(resp) => resp,
+            None => return Err(format!("No response to {method}")),
+        };
+
+        let resp = RestServer::check_event(method, resp)?;
+
+        let mut req = session.request("open-ils.pcrud.transaction.commit", vec![token])?;
+        req.recv(PCRUD_TIMEOUT)?
+            .ok_or("No response to transaction.commit")?;
+
+        Ok(resp)
+    }
+
+    /// Fail on a non-success event response.
+    fn check_event(method: &str, resp: JsonValue) -> Result<JsonValue, String> {
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("{method} failed: {evt}"));
+            }
+        }
+        Ok(resp)
+    }
+
+    fn retrieve(&self, token: &str, classname: &str, id: &str) -> Result<RestResponse, String> {
+        let method = format!("open-ils.pcrud.retrieve.{classname}");
+
+        let resp = self.pcrud_request(&method, vec![token.into(), id.into()])?;
+
+        if resp.is_null() {
+            return Ok(RestResponse::error(
+                404,
+                &format!("No such {classname}: {id}"),
+            ));
+        }
+
+        RestServer::check_event(&method, resp).map(RestResponse::ok)
+    }
+
+    fn search(
+        &self,
+        token: &str,
+        classname: &str,
+        query: &HashMap<String, String>,
+    ) -> Result<RestResponse, String> {
+        let class = self.idl.get_class(classname).expect("class checked above");
+        let filter = search_filter(class, query)?;
+
+        let limit = query
+            .get("limit")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(DEFAULT_LIMIT)
+            .min(MAX_LIMIT);
+        let offset: usize = query.get("offset").and_then(|o| o.parse().ok()).unwrap_or(0);
+
+        let method = format!("open-ils.pcrud.search.{classname}.atomic");
+        let options = json::object! {limit: limit, offset: offset};
+
+        let resp = self.pcrud_request(&method, vec![token.into(), filter, options])?;
+        RestServer::check_event(&method, resp).map(RestResponse::ok)
+    }
+
+    fn create(&self, token: &str, classname: &str, body: &str) -> Result<RestResponse, String> {
+        let mut obj = json::parse(body).map_err(|e| format!("Invalid JSON body: {e}"))?;
+        obj[idl::CLASSNAME_KEY] = classname.into();
+
+        let method = format!("open-ils.pcrud.create.{classname}");
+        self.pcrud_write(&method, vec![token.into(), obj])
+            .map(RestResponse::ok)
+    }
+
+    fn update(
+        &self,
+        token: &str,
+        classname: &str,
+        id: &str,
+        body: &str,
+    ) -> Result<RestResponse, String> {
+        let mut obj = json::parse(body).map_err(|e| format!("Invalid JSON body: {e}"))?;
+        obj[idl::CLASSNAME_KEY] = classname.into();
+
+        // The path is authoritative for which object we're updating.
+        let body_id = obj["id"].to_string();
+        if obj["id"].is_null() {
+            obj["id"] = id.into();
+        } else if body_id != id {
+            return Err("Body id does not match path id".to_string());
+        }
+
+        let method = format!("open-ils.pcrud.update.{classname}");
+        self.pcrud_write(&method, vec![token.into(), obj])
+            .map(RestResponse::ok)
+    }
+
+    fn delete(&self, token: &str, classname: &str, id: &str) -> Result<RestResponse, String> {
+        // pcrud.delete wants the object; fetch it first.
+        let retrieve = format!("open-ils.pcrud.retrieve.{classname}");
+        let obj = self.pcrud_request(&retrieve, vec![token.into(), id.into()])?;
+
+        if obj.is_null() {
+            return Ok(RestResponse::error(
+                404,
+                &format!("No such {classname}: {id}"),
+            ));
+        }
+
+        let method = format!("open-ils.pcrud.delete.{classname}");
+        self.pcrud_write(&method, vec![token.into(), obj])
+            .map(RestResponse::ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("name=BR1%20Branch&parent_ou=1&flag");
+        assert_eq!(query["name"], "BR1 Branch");
+        assert_eq!(query["parent_ou"], "1");
+        assert_eq!(query["flag"], "");
+    }
+
+    #[test]
+    fn test_search_filter() {
+        let idl = idl::Parser::parse_string(idl::tests::TEST_IDL).expect("IDL parses");
+        let class = idl.get_class("aou").unwrap();
+
+        let query = parse_query("parent_ou=1&name=BR1");
+        let filter = search_filter(class, &query).expect("filter should build");
+        assert_eq!(filter["parent_ou"], 1);
+        assert_eq!(filter["name"], "BR1");
+
+        let query = parse_query("no_such_field=1");
+        assert!(search_filter(class, &query).is_err());
+
+        let query = parse_query("limit=10");
+        assert!(search_filter(class, &query).is_err()); // paging only
+    }
+
+    #[test]
+    fn test_openapi_spec() {
+        let idl = idl::Parser::parse_string(idl::tests::TEST_IDL).expect("IDL parses");
+        let spec = openapi_spec(&idl);
+
+        assert_eq!(spec["openapi"], "3.0.0");
+
+        let schema = &spec["components"]["schemas"]["aou"];
+        assert_eq!(schema["properties"]["opac_visible"]["type"], "boolean");
+        assert_eq!(schema["properties"]["name"]["type"], "string");
+        assert!(schema["properties"]["children"].is_null()); // virtual
+
+        assert!(spec["paths"]["/idl/aou/{id}"]["delete"].is_object());
+    }
+}
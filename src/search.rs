@@ -0,0 +1,329 @@
+//! Typed catalog search: a query builder and result structs around
+//! the open-ils.search multiclass query API.
+
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::fmt;
+
+const SEARCH_TIMEOUT: u64 = 120;
+
+/// Bib search classes understood by the query parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchClass {
+    Keyword,
+    Title,
+    Author,
+    Subject,
+    Series,
+}
+
+impl SearchClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchClass::Keyword => "keyword",
+            SearchClass::Title => "title",
+            SearchClass::Author => "author",
+            SearchClass::Subject => "subject",
+            SearchClass::Series => "series",
+        }
+    }
+}
+
+/// Builds a multiclass query string plus its API arguments.
+///
+/// ```
+/// use evergreen::search::{QueryBuilder, SearchClass};
+///
+/// let query = QueryBuilder::new()
+///     .term(SearchClass::Title, "a wrinkle in time")
+///     .term(SearchClass::Author, "l'engle")
+///     .site("BR1")
+///     .available()
+///     .sort("pubdate", true)
+///     .limit(10)
+///     .build();
+///
+/// assert_eq!(
+///     query.query_string(),
+///     "title:a wrinkle in time author:l'engle site(BR1) #available sort(pubdate) #descending"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    terms: Vec<(SearchClass, String)>,
+    /// Facet filters as (facet field, value), e.g.
+    /// ("author|personal", "Dickens, Charles").
+    facets: Vec<(String, String)>,
+    site: Option<String>,
+    depth: Option<i64>,
+    available: bool,
+    sort: Option<String>,
+    sort_descending: bool,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self {
+        QueryBuilder::default()
+    }
+
+    /// Add a search term for a class.
+    pub fn term(mut self, class: SearchClass, value: &str) -> Self {
+        self.terms.push((class, scrub(value)));
+        self
+    }
+
+    /// Shorthand for a keyword term.
+    pub fn keyword(self, value: &str) -> Self {
+        self.term(SearchClass::Keyword, value)
+    }
+
+    /// Filter on a facet field value.
+    pub fn facet(mut self, field: &str, value: &str) -> Self {
+        self.facets.push((field.to_string(), scrub(value)));
+        self
+    }
+
+    /// Scope to an org unit by shortname.
+    pub fn site(mut self, org_shortname: &str) -> Self {
+        self.site = Some(org_shortname.to_string());
+        self
+    }
+
+    /// Org tree depth for the site scope.
+    pub fn depth(mut self, depth: i64) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Limit to records with available copies.
+    pub fn available(mut self) -> Self {
+        self.available = true;
+        self
+    }
+
+    pub fn sort(mut self, field: &str, descending: bool) -> Self {
+        self.sort = Some(field.to_string());
+        self.sort_descending = descending;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn build(self) -> Query {
+        Query { builder: self }
+    }
+}
+
+/// Strip query-parser syntax characters from user-supplied values.
+fn scrub(value: &str) -> String {
+    value
+        .replace(['(', ')', ':', '#'], " ")
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// A finished query, ready to hand to the search API.
+#[derive(Debug, Clone)]
+pub struct Query {
+    builder: QueryBuilder,
+}
+
+impl Query {
+    /// The query-parser string.
+    pub fn query_string(&self) -> String {
+        let b = &self.builder;
+        let mut parts = Vec::new();
+
+        for (class, value) in &b.terms {
+            parts.push(format!("{}:{}", class.as_str(), value));
+        }
+
+        for (field, value) in &b.facets {
+            parts.push(format!("{field}[{value}]"));
+        }
+
+        if let Some(site) = &b.site {
+            parts.push(format!("site({site})"));
+        }
+
+        if let Some(depth) = b.depth {
+            parts.push(format!("depth({depth})"));
+        }
+
+        if b.available {
+            parts.push("#available".to_string());
+        }
+
+        if let Some(sort) = &b.sort {
+            parts.push(format!("sort({sort})"));
+            if b.sort_descending {
+                parts.push("#descending".to_string());
+            }
+        }
+
+        parts.join(" ")
+    }
+
+    /// The argument hash passed alongside the query string.
+    pub fn args(&self) -> JsonValue {
+        let mut args = json::object! {};
+
+        if let Some(limit) = self.builder.limit {
+            args["limit"] = limit.into();
+        }
+        if let Some(offset) = self.builder.offset {
+            args["offset"] = offset.into();
+        }
+
+        args
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.query_string())
+    }
+}
+
+/// A page of search results.
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    /// Total hits, ignoring paging.
+    pub count: i64,
+    /// Record IDs for this page, in rank order.
+    pub record_ids: Vec<i64>,
+    /// Key for fetching facet data, when the engine produced one.
+    pub facet_key: Option<String>,
+}
+
+impl SearchResults {
+    fn from_response(resp: &JsonValue) -> Result<SearchResults, String> {
+        let count = util::json_int(&resp["count"])
+            .map_err(|_| format!("Unexpected search response: {}", resp.dump()))?;
+
+        let mut record_ids = Vec::new();
+
+        // Each ids entry is an array whose first element is the
+        // record ID.
+        for entry in resp["ids"].members() {
+            let id = match entry {
+                JsonValue::Array(parts) => parts.first().unwrap_or(&JsonValue::Null),
+                other => other,
+            };
+            record_ids.push(util::json_int(id)?);
+        }
+
+        Ok(SearchResults {
+            count,
+            record_ids,
+            facet_key: resp["facet_key"].as_str().map(|k| k.to_string()),
+        })
+    }
+}
+
+/// Runs catalog searches.
+pub struct Searcher {
+    client: Client,
+    timeout: u64,
+}
+
+impl Searcher {
+    pub fn new(client: &Client) -> Self {
+        Searcher {
+            client: client.clone(),
+            timeout: SEARCH_TIMEOUT,
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: u64) {
+        self.timeout = timeout;
+    }
+
+    fn request(&self, method: &str, params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        let session = self.client.session("open-ils.search");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(self.timeout)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Run a bib record search.
+    pub fn bib_search(&self, query: &Query) -> Result<SearchResults, String> {
+        let resp = self.request(
+            "open-ils.search.biblio.multiclass.query",
+            vec![query.args(), json::from(query.query_string()), json::from(1)],
+        )?;
+
+        SearchResults::from_response(&resp)
+    }
+
+    /// Fetch the facet data for a result set's facet key.
+    pub fn facets(&self, facet_key: &str) -> Result<JsonValue, String> {
+        self.request(
+            "open-ils.search.facet_cache.retrieve",
+            vec![json::from(facet_key)],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_string() {
+        let query = QueryBuilder::new()
+            .term(SearchClass::Title, "wrinkle in time")
+            .term(SearchClass::Author, "l'engle")
+            .facet("author|personal", "L'Engle, Madeleine")
+            .site("BR1")
+            .depth(1)
+            .available()
+            .sort("pubdate", true)
+            .limit(10)
+            .offset(20)
+            .build();
+
+        assert_eq!(
+            query.query_string(),
+            "title:wrinkle in time author:l'engle author|personal[L'Engle, Madeleine] \
+                site(BR1) depth(1) #available sort(pubdate) #descending"
+        );
+
+        assert_eq!(query.args()["limit"], 10);
+        assert_eq!(query.args()["offset"], 20);
+    }
+
+    #[test]
+    fn test_scrub() {
+        assert_eq!(scrub("harry (potter): #1"), "harry potter 1");
+    }
+
+    #[test]
+    fn test_results() {
+        let resp = json::object! {
+            count: 2,
+            ids: [[31, null, "1.2"], [32, null, "1.1"]],
+            facet_key: "facet123",
+        };
+
+        let results = SearchResults::from_response(&resp).expect("results should parse");
+        assert_eq!(results.count, 2);
+        assert_eq!(results.record_ids, vec![31, 32]);
+        assert_eq!(results.facet_key.as_deref(), Some("facet123"));
+    }
+}
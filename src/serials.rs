@@ -0,0 +1,319 @@
+//! Serials caption/pattern handling, issue prediction, and
+//! receiving.
+//!
+//! Caption data follows the MFHD 853 layout, as stored in
+//! serial.caption_and_pattern rows: enumeration labels in $a-$f
+//! with $u (units per next higher level) and $v (restart vs
+//! continue) modifiers, chronology labels in $i-$l.
+
+use crate::editor::Editor;
+use crate::event::EgEvent;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::fmt;
+use std::sync::Arc;
+
+const SERIALS_TIMEOUT: u64 = 120;
+
+/// One enumeration level of a caption (e.g. "v." or "no.").
+#[derive(Debug, Clone)]
+pub struct EnumLevel {
+    pub label: String,
+    /// How many of this unit make up the next higher level; None
+    /// means the level never rolls over.
+    pub units_per_higher: Option<i64>,
+    /// Whether numbering restarts at 1 on rollover ("r") or runs
+    /// continuously ("c").
+    pub restarts: bool,
+}
+
+/// A parsed caption: enumeration levels plus chronology labels.
+#[derive(Debug, Clone, Default)]
+pub struct Caption {
+    pub enum_levels: Vec<EnumLevel>,
+    pub chron_labels: Vec<String>,
+}
+
+impl Caption {
+    /// Parse an scap.pattern_code value: a JSON-encoded flat array
+    /// of MFHD subfield code/value pairs.
+    pub fn from_pattern_code(pattern_code: &str) -> Result<Caption, String> {
+        let parsed = json::parse(pattern_code)
+            .map_err(|e| format!("Invalid pattern code JSON: {e}"))?;
+
+        let mut pairs = Vec::new();
+        let mut members = parsed.members();
+
+        // The leading members are the indicator values; skip any
+        // entry that isn't a subfield code/value pair.
+        while let (Some(code), Some(value)) = (members.next(), members.next()) {
+            if let (Some(code), Some(value)) = (code.as_str(), value.as_str()) {
+                pairs.push((code.to_string(), value.to_string()));
+            }
+        }
+
+        let mut caption = Caption::default();
+
+        for (code, value) in &pairs {
+            match code.as_str() {
+                "a" | "b" | "c" | "d" | "e" | "f" => caption.enum_levels.push(EnumLevel {
+                    label: value.clone(),
+                    units_per_higher: None,
+                    restarts: true,
+                }),
+                "i" | "j" | "k" | "l" => caption.chron_labels.push(value.clone()),
+                "u" => {
+                    if let Some(level) = caption.enum_levels.last_mut() {
+                        level.units_per_higher = value.parse().ok();
+                    }
+                }
+                "v" => {
+                    if let Some(level) = caption.enum_levels.last_mut() {
+                        level.restarts = value == "r";
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if caption.enum_levels.is_empty() {
+            return Err("Caption has no enumeration levels".to_string());
+        }
+
+        Ok(caption)
+    }
+}
+
+/// An issue's position: one value per enumeration level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holding {
+    pub values: Vec<i64>,
+}
+
+impl Holding {
+    pub fn new(values: Vec<i64>) -> Self {
+        Holding { values }
+    }
+
+    /// Render with a caption's labels, e.g. "v.10:no.3".
+    pub fn label(&self, caption: &Caption) -> String {
+        let parts: Vec<String> = self
+            .values
+            .iter()
+            .zip(caption.enum_levels.iter())
+            .map(|(value, level)| format!("{}{}", level.label, value))
+            .collect();
+        parts.join(":")
+    }
+}
+
+impl fmt::Display for Holding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let strings: Vec<String> = self.values.iter().map(|v| v.to_string()).collect();
+        write!(f, "{}", strings.join(":"))
+    }
+}
+
+/// Predict the issue following `current` under a caption:
+/// increment the lowest level and roll over any level that reaches
+/// its unit count.
+pub fn predict_next(caption: &Caption, current: &Holding) -> Result<Holding, String> {
+    if current.values.len() != caption.enum_levels.len() {
+        return Err(format!(
+            "Holding has {} levels; caption has {}",
+            current.values.len(),
+            caption.enum_levels.len()
+        ));
+    }
+
+    let mut values = current.values.clone();
+
+    // Walk from the lowest level up, carrying rollovers.
+    for index in (0..values.len()).rev() {
+        let level = &caption.enum_levels[index];
+
+        values[index] += 1;
+
+        let units = match level.units_per_higher {
+            Some(u) if index > 0 => u,
+            _ => return Ok(Holding::new(values)), // no rollover
+        };
+
+        if values[index] <= units {
+            return Ok(Holding::new(values));
+        }
+
+        if level.restarts {
+            values[index] = 1;
+        } else {
+            return Ok(Holding::new(values)); // continuous numbering
+        }
+        // carry into the next higher level
+    }
+
+    Ok(Holding::new(values))
+}
+
+/// Predict the next `count` issues after `current`.
+pub fn predict_issues(
+    caption: &Caption,
+    current: &Holding,
+    count: usize,
+) -> Result<Vec<Holding>, String> {
+    let mut issues = Vec::new();
+    let mut last = current.clone();
+
+    for _ in 0..count {
+        last = predict_next(caption, &last)?;
+        issues.push(last.clone());
+    }
+
+    Ok(issues)
+}
+
+/// Drives serials operations for one authenticated session.
+pub struct Serials {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+}
+
+impl Serials {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Serials {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+        }
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// Call an open-ils.serial method and return its first response.
+    fn request(&self, method: &str, mut params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        params.insert(0, json::from(self.authtoken.as_str()));
+
+        let session = self.client.session("open-ils.serial");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(SERIALS_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    fn check_event(method: &str, resp: JsonValue) -> Result<JsonValue, String> {
+        if let Some(evt) = EgEvent::parse(&resp) {
+            if !evt.is_success() {
+                return Err(format!("{method} failed: {evt}"));
+            }
+        }
+        Ok(resp)
+    }
+
+    /// The active caption/pattern for a subscription, parsed.
+    pub fn active_caption(&mut self, ssub_id: i64) -> Result<Caption, String> {
+        let mut rows = self.editor.search(
+            "scap",
+            json::object! {subscription: ssub_id, active: "t"},
+        )?;
+
+        let row = rows
+            .pop()
+            .ok_or_else(|| format!("Subscription {ssub_id} has no active caption"))?;
+
+        Caption::from_pattern_code(&util::json_string(&row["pattern_code"])?)
+    }
+
+    /// Have the serials service predict and create upcoming
+    /// issuances/items for a subscription.  Returns the new
+    /// issuance IDs.
+    pub fn make_predictions(&self, ssub_id: i64, num_to_predict: u32) -> Result<Vec<i64>, String> {
+        let method = "open-ils.serial.make_predictions";
+
+        let args = json::object! {
+            ssub_id: ssub_id,
+            num_to_predict: num_to_predict,
+        };
+
+        let resp = self.request(method, vec![args])?;
+        let resp = Serials::check_event(method, resp)?;
+
+        let mut ids = Vec::new();
+        for id in resp.members() {
+            ids.push(util::json_int(id)?);
+        }
+
+        Ok(ids)
+    }
+
+    /// Receive serial items by ID.
+    pub fn receive_items(&self, item_ids: &[i64]) -> Result<(), String> {
+        let method = "open-ils.serial.receive_items";
+
+        let ids: Vec<JsonValue> = item_ids.iter().map(|id| json::from(*id)).collect();
+
+        let resp = self.request(method, vec![JsonValue::Array(ids)])?;
+        Serials::check_event(method, resp).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 853 $a v. $b no. $u 12 $v r $i (year) $j (month): a monthly.
+    const MONTHLY: &str = r#"["8","1","a","v.","b","no.","u","12","v","r","i","(year)","j","(month)"]"#;
+
+    #[test]
+    fn test_caption_parse() {
+        let caption = Caption::from_pattern_code(MONTHLY).expect("caption should parse");
+
+        assert_eq!(caption.enum_levels.len(), 2);
+        assert_eq!(caption.enum_levels[0].label, "v.");
+        assert_eq!(caption.enum_levels[1].label, "no.");
+        assert_eq!(caption.enum_levels[1].units_per_higher, Some(12));
+        assert!(caption.enum_levels[1].restarts);
+        assert_eq!(caption.chron_labels, vec!["(year)", "(month)"]);
+    }
+
+    #[test]
+    fn test_predict() {
+        let caption = Caption::from_pattern_code(MONTHLY).unwrap();
+
+        // Mid-volume increment.
+        let next = predict_next(&caption, &Holding::new(vec![10, 3])).unwrap();
+        assert_eq!(next.values, vec![10, 4]);
+
+        // Rollover into the next volume.
+        let next = predict_next(&caption, &Holding::new(vec![10, 12])).unwrap();
+        assert_eq!(next.values, vec![11, 1]);
+
+        assert_eq!(next.label(&caption), "v.11:no.1");
+    }
+
+    #[test]
+    fn test_predict_run() {
+        let caption = Caption::from_pattern_code(MONTHLY).unwrap();
+
+        let issues = predict_issues(&caption, &Holding::new(vec![10, 11]), 3).unwrap();
+        assert_eq!(issues.len(), 3);
+        assert_eq!(issues[0].values, vec![10, 12]);
+        assert_eq!(issues[1].values, vec![11, 1]);
+        assert_eq!(issues[2].values, vec![11, 2]);
+    }
+
+    #[test]
+    fn test_continuous_numbering() {
+        // $v c: issue numbers run continuously, no restart.
+        let code = r#"["8","1","a","v.","b","no.","u","12","v","c"]"#;
+        let caption = Caption::from_pattern_code(code).unwrap();
+
+        let next = predict_next(&caption, &Holding::new(vec![10, 12])).unwrap();
+        assert_eq!(next.values, vec![10, 13]);
+    }
+}
@@ -0,0 +1,253 @@
+//! Org unit, user, and workstation setting lookups.
+//!
+//! Org settings use the ancestor-aware batch API so a value set
+//! anywhere up the org tree applies; user and workstation settings
+//! come straight from their setting tables.  Values are cached per
+//! instance, and batch fetches keep round trips down for callers
+//! that need several settings at once.
+
+use crate::editor::Editor;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const SETTINGS_TIMEOUT: u64 = 60;
+
+/// Setting rows store their values JSON-encoded.
+pub(crate) fn parse_setting_value(row_value: &JsonValue) -> Result<JsonValue, String> {
+    let text = match row_value.as_str() {
+        Some(text) => text,
+        // Already-decoded values pass through.
+        None => return Ok(row_value.clone()),
+    };
+
+    json::parse(text).map_err(|e| format!("Cannot parse setting value {text}: {e}"))
+}
+
+/// Caching access to org/user/workstation settings.
+pub struct Settings {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+    org_cache: HashMap<(i64, String), JsonValue>,
+    user_cache: HashMap<(i64, String), JsonValue>,
+    workstation_cache: HashMap<(i64, String), JsonValue>,
+}
+
+impl Settings {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Settings {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+            org_cache: HashMap::new(),
+            user_cache: HashMap::new(),
+            workstation_cache: HashMap::new(),
+        }
+    }
+
+    /// Discard all cached values.
+    pub fn clear(&mut self) {
+        self.org_cache.clear();
+        self.user_cache.clear();
+        self.workstation_cache.clear();
+    }
+
+    // -- Org unit settings -------------------------------------------
+
+    /// An org setting value, honoring values set on ancestor orgs.
+    /// Returns JSON null when the setting is unset.
+    pub fn ou_setting(&mut self, org_id: i64, name: &str) -> Result<JsonValue, String> {
+        let values = self.ou_setting_batch(org_id, &[name])?;
+        Ok(values.get(name).cloned().unwrap_or(JsonValue::Null))
+    }
+
+    /// Fetch several org settings in one call, ancestor-aware.
+    pub fn ou_setting_batch(
+        &mut self,
+        org_id: i64,
+        names: &[&str],
+    ) -> Result<HashMap<String, JsonValue>, String> {
+        let mut values = HashMap::new();
+        let mut needed = Vec::new();
+
+        for name in names {
+            match self.org_cache.get(&(org_id, name.to_string())) {
+                Some(value) => {
+                    values.insert(name.to_string(), value.clone());
+                }
+                None => needed.push(*name),
+            }
+        }
+
+        if needed.is_empty() {
+            return Ok(values);
+        }
+
+        let method = "open-ils.actor.ou_setting.ancestor_default.batch";
+
+        let name_list: Vec<JsonValue> = needed.iter().map(|n| json::from(*n)).collect();
+
+        let session = self.client.session("open-ils.actor");
+        let mut req = session.request(
+            method,
+            vec![
+                json::from(org_id),
+                JsonValue::Array(name_list),
+                json::from(self.authtoken.as_str()),
+            ],
+        )?;
+
+        let resp = match req.recv(SETTINGS_TIMEOUT)? {
+            Some(resp) => resp,
+            None => return Err(format!("No response to {method}")),
+        };
+
+        for name in needed {
+            // Each entry is {org: x, value: v} or null.
+            let value = resp[name]["value"].clone();
+            self.org_cache
+                .insert((org_id, name.to_string()), value.clone());
+            values.insert(name.to_string(), value);
+        }
+
+        Ok(values)
+    }
+
+    // -- User settings -----------------------------------------------
+
+    /// A user setting value, or JSON null when unset.
+    pub fn user_setting(&mut self, usr_id: i64, name: &str) -> Result<JsonValue, String> {
+        let values = self.user_setting_batch(usr_id, &[name])?;
+        Ok(values.get(name).cloned().unwrap_or(JsonValue::Null))
+    }
+
+    /// Fetch several user settings in one search.
+    pub fn user_setting_batch(
+        &mut self,
+        usr_id: i64,
+        names: &[&str],
+    ) -> Result<HashMap<String, JsonValue>, String> {
+        self.setting_table_batch(usr_id, names, "aus", "usr", SettingScope::User)
+    }
+
+    // -- Workstation settings ----------------------------------------
+
+    /// A workstation setting value, or JSON null when unset.
+    pub fn workstation_setting(
+        &mut self,
+        workstation_id: i64,
+        name: &str,
+    ) -> Result<JsonValue, String> {
+        let values = self.workstation_setting_batch(workstation_id, &[name])?;
+        Ok(values.get(name).cloned().unwrap_or(JsonValue::Null))
+    }
+
+    /// Fetch several workstation settings in one search.
+    pub fn workstation_setting_batch(
+        &mut self,
+        workstation_id: i64,
+        names: &[&str],
+    ) -> Result<HashMap<String, JsonValue>, String> {
+        self.setting_table_batch(
+            workstation_id,
+            names,
+            "awss",
+            "workstation",
+            SettingScope::Workstation,
+        )
+    }
+
+    /// Shared fetch path for the plain setting tables: search for
+    /// rows owned by the context object, decode values, and cache
+    /// misses as nulls so absent settings are not re-fetched.
+    fn setting_table_batch(
+        &mut self,
+        owner_id: i64,
+        names: &[&str],
+        idlclass: &str,
+        owner_field: &str,
+        scope: SettingScope,
+    ) -> Result<HashMap<String, JsonValue>, String> {
+        let mut values = HashMap::new();
+        let mut needed = Vec::new();
+
+        for name in names {
+            let cached = match scope {
+                SettingScope::User => self.user_cache.get(&(owner_id, name.to_string())),
+                SettingScope::Workstation => {
+                    self.workstation_cache.get(&(owner_id, name.to_string()))
+                }
+            };
+
+            match cached {
+                Some(value) => {
+                    values.insert(name.to_string(), value.clone());
+                }
+                None => needed.push(*name),
+            }
+        }
+
+        if needed.is_empty() {
+            return Ok(values);
+        }
+
+        let name_list: Vec<JsonValue> = needed.iter().map(|n| json::from(*n)).collect();
+
+        let mut filter = json::object! {name: JsonValue::Array(name_list)};
+        filter[owner_field] = owner_id.into();
+
+        let rows = self.editor.search(idlclass, filter)?;
+
+        let mut fetched: HashMap<String, JsonValue> = HashMap::new();
+        for row in &rows {
+            let name = util::json_string(&row["name"])?;
+            fetched.insert(name, parse_setting_value(&row["value"])?);
+        }
+
+        for name in needed {
+            let value = fetched.remove(name).unwrap_or(JsonValue::Null);
+
+            let cache = match scope {
+                SettingScope::User => &mut self.user_cache,
+                SettingScope::Workstation => &mut self.workstation_cache,
+            };
+
+            cache.insert((owner_id, name.to_string()), value.clone());
+            values.insert(name.to_string(), value);
+        }
+
+        Ok(values)
+    }
+}
+
+/// Which per-object cache a table fetch belongs to.
+#[derive(Clone, Copy)]
+enum SettingScope {
+    User,
+    Workstation,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_setting_value() {
+        // Setting tables JSON-encode their values.
+        let value = parse_setting_value(&json::from("\"circ\"")).unwrap();
+        assert_eq!(value, "circ");
+
+        let value = parse_setting_value(&json::from("42")).unwrap();
+        assert_eq!(value, 42);
+
+        // Pre-decoded values pass through untouched.
+        let value = parse_setting_value(&json::from(7)).unwrap();
+        assert_eq!(value, 7);
+
+        assert!(parse_setting_value(&json::from("{broken")).is_err());
+    }
+}
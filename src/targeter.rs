@@ -0,0 +1,324 @@
+//! Hold (re)targeting: eligible copy discovery, proximity-aware
+//! best-copy selection, and retarget interval handling.
+
+use crate::date::parse_timestamp;
+use crate::editor::Editor;
+use crate::idldb::{IdlClassSearch, Translator};
+use crate::util;
+use chrono::Local;
+use json::JsonValue;
+use std::collections::HashMap;
+
+/// Copy statuses eligible for hold targeting.
+const TARGETABLE_COPY_STATUSES: &[i64] = &[0, 7]; // Available, Reshelving
+
+/// Counters for a targeting run.
+#[derive(Debug, Clone, Default)]
+pub struct TargeterCounts {
+    pub processed: usize,
+    pub retargeted: usize,
+    pub untargetable: usize,
+    pub skipped: usize,
+    pub errors: usize,
+}
+
+impl TargeterCounts {
+    pub fn absorb(&mut self, other: &TargeterCounts) {
+        self.processed += other.processed;
+        self.retargeted += other.retargeted;
+        self.untargetable += other.untargetable;
+        self.skipped += other.skipped;
+        self.errors += other.errors;
+    }
+}
+
+/// Pick the copy with the best (lowest) proximity, preferring the
+/// earlier entry on ties.
+pub fn choose_best_copy(copies: &[(i64, i64)]) -> Option<i64> {
+    copies
+        .iter()
+        .min_by_key(|(_, prox)| *prox)
+        .map(|(copy, _)| *copy)
+}
+
+pub struct HoldTargeter {
+    editor: Editor,
+    /// Optional direct-database path for candidate hold discovery.
+    translator: Option<Translator>,
+    /// (from_org, to_org) => proximity cache.
+    prox_cache: HashMap<(i64, i64), i64>,
+    counts: TargeterCounts,
+}
+
+impl HoldTargeter {
+    pub fn new(editor: Editor) -> Self {
+        HoldTargeter {
+            editor,
+            translator: None,
+            prox_cache: HashMap::new(),
+            counts: TargeterCounts::default(),
+        }
+    }
+
+    /// Use a direct database connection for candidate queries.
+    pub fn set_translator(&mut self, translator: Translator) {
+        self.translator = Some(translator);
+    }
+
+    pub fn counts(&self) -> &TargeterCounts {
+        &self.counts
+    }
+
+    /// Find open, unfrozen, uncaptured holds whose previous target
+    /// check is older than the retarget interval.
+    pub fn holds_to_target(&mut self, retarget_interval_secs: i64) -> Result<Vec<i64>, String> {
+        let filter = json::object! {
+            capture_time: JsonValue::Null,
+            cancel_time: JsonValue::Null,
+            fulfillment_time: JsonValue::Null,
+            frozen: "f",
+        };
+
+        let holds = match &self.translator {
+            Some(translator) => {
+                let mut search = IdlClassSearch::new("ahr");
+                search.set_filter(filter);
+                translator.idl_class_search(&search)?
+            }
+            None => self.editor.search("ahr", filter)?,
+        };
+
+        let cutoff = Local::now().fixed_offset()
+            - chrono::Duration::seconds(retarget_interval_secs);
+
+        let mut ids = Vec::new();
+
+        for hold in holds {
+            let needs_target = match hold["prev_check_time"].as_str() {
+                Some(prev) => match parse_timestamp(prev) {
+                    Ok(dt) => dt < cutoff,
+                    Err(_) => true,
+                },
+                None => true,
+            };
+
+            if needs_target {
+                ids.push(util::json_int(&hold["id"])?);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Retarget a single hold.  Returns true if a new copy was
+    /// targeted.
+    pub fn target_hold(&mut self, hold_id: i64) -> Result<bool, String> {
+        self.counts.processed += 1;
+
+        let mut hold = match self.editor.retrieve("ahr", json::from(hold_id))? {
+            Some(h) => h,
+            None => return Err(format!("No such hold: {hold_id}")),
+        };
+
+        if !hold["capture_time"].is_null()
+            || !hold["cancel_time"].is_null()
+            || !hold["fulfillment_time"].is_null()
+            || util::json_bool(&hold["frozen"])
+        {
+            log::debug!("Hold {hold_id} is not targetable; skipping");
+            self.counts.skipped += 1;
+            return Ok(false);
+        }
+
+        let pickup_lib = util::json_int(&hold["pickup_lib"])?;
+        let copies = self.eligible_copies(&hold)?;
+
+        let mut weighted = Vec::new();
+        for copy in &copies {
+            let copy_id = util::json_int(&copy["id"])?;
+            let circ_lib = util::json_int(&copy["circ_lib"])?;
+            let prox = self.proximity(circ_lib, pickup_lib)?;
+            weighted.push((copy_id, prox));
+        }
+
+        let best = choose_best_copy(&weighted);
+
+        let now = Local::now().format("%Y-%m-%dT%H:%M:%S%z").to_string();
+        hold["prev_check_time"] = now.as_str().into();
+
+        match best {
+            Some(copy_id) => hold["current_copy"] = copy_id.into(),
+            None => {
+                hold["current_copy"] = JsonValue::Null;
+                self.counts.untargetable += 1;
+            }
+        }
+
+        let copy_ids: Vec<i64> = weighted.iter().map(|(id, _)| *id).collect();
+        self.commit_hold(hold, &copy_ids)?;
+
+        if best.is_some() {
+            self.counts.retargeted += 1;
+        }
+
+        Ok(best.is_some())
+    }
+
+    /// Update the hold and rebuild its copy maps in one transaction.
+    fn commit_hold(&mut self, hold: JsonValue, copy_ids: &[i64]) -> Result<(), String> {
+        let hold_id = util::json_int(&hold["id"])?;
+
+        self.editor.xact_begin()?;
+
+        let result = self.commit_hold_internal(hold, hold_id, copy_ids);
+
+        match result {
+            Ok(()) => Ok(self.editor.commit()?),
+            Err(e) => {
+                self.counts.errors += 1;
+                self.editor.xact_rollback()?;
+                Err(e)
+            }
+        }
+    }
+
+    fn commit_hold_internal(
+        &mut self,
+        hold: JsonValue,
+        hold_id: i64,
+        copy_ids: &[i64],
+    ) -> Result<(), String> {
+        self.editor.request(
+            "open-ils.cstore.direct.action.hold_request.update",
+            vec![hold],
+        )?;
+
+        // Replace existing copy maps with the new eligible set.
+        let maps = self
+            .editor
+            .search("ahcm", json::object! {hold: hold_id})?;
+
+        for map in maps {
+            self.editor.request(
+                "open-ils.cstore.direct.action.hold_copy_map.delete",
+                vec![map],
+            )?;
+        }
+
+        for copy_id in copy_ids {
+            let map = json::object! {
+                "_classname": "ahcm",
+                hold: hold_id,
+                target_copy: *copy_id,
+            };
+
+            self.editor.request(
+                "open-ils.cstore.direct.action.hold_copy_map.create",
+                vec![map],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Discover copies eligible to fill a hold, honoring the hold
+    /// type's target level.
+    fn eligible_copies(&mut self, hold: &JsonValue) -> Result<Vec<JsonValue>, String> {
+        let target = util::json_int(&hold["target"])?;
+        let hold_type = hold["hold_type"].as_str().unwrap_or("T");
+
+        let copies = match hold_type {
+            "C" | "R" | "F" => {
+                // Copy-level hold; the target is the copy.
+                match self.editor.retrieve("acp", json::from(target))? {
+                    Some(c) => vec![c],
+                    None => Vec::new(),
+                }
+            }
+            "V" => self.editor.search(
+                "acp",
+                json::object! {call_number: target, deleted: "f"},
+            )?,
+            _ => {
+                // Title-level (and metarecord, approximated): all
+                // copies under the record's call numbers.
+                let call_numbers = self.editor.search(
+                    "acn",
+                    json::object! {record: target, deleted: "f"},
+                )?;
+
+                let mut cn_ids = Vec::new();
+                for cn in &call_numbers {
+                    cn_ids.push(util::json_int(&cn["id"])?);
+                }
+
+                if cn_ids.is_empty() {
+                    Vec::new()
+                } else {
+                    self.editor.search(
+                        "acp",
+                        json::object! {call_number: cn_ids, deleted: "f"},
+                    )?
+                }
+            }
+        };
+
+        // Apply copy-level eligibility tests.
+        let mut eligible = Vec::new();
+        for copy in copies {
+            let status = util::json_int(&copy["status"]).unwrap_or(-1);
+
+            if !TARGETABLE_COPY_STATUSES.contains(&status) {
+                continue;
+            }
+
+            if !util::json_bool(&copy["holdable"]) || !util::json_bool(&copy["circulate"]) {
+                continue;
+            }
+
+            eligible.push(copy);
+        }
+
+        Ok(eligible)
+    }
+
+    /// Org-to-org proximity, cached per targeter.
+    fn proximity(&mut self, from_org: i64, to_org: i64) -> Result<i64, String> {
+        if from_org == to_org {
+            return Ok(0);
+        }
+
+        if let Some(prox) = self.prox_cache.get(&(from_org, to_org)) {
+            return Ok(*prox);
+        }
+
+        let rows = self.editor.search(
+            "aoup",
+            json::object! {from_org: from_org, to_org: to_org},
+        )?;
+
+        let prox = match rows.first() {
+            Some(row) => util::json_int(&row["prox"])?,
+            // With no proximity data, treat unrelated orgs as distant.
+            None => i64::MAX,
+        };
+
+        self.prox_cache.insert((from_org, to_org), prox);
+
+        Ok(prox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_best_copy() {
+        assert_eq!(choose_best_copy(&[]), None);
+        assert_eq!(choose_best_copy(&[(10, 2), (11, 0), (12, 5)]), Some(11));
+        // Ties prefer the first entry.
+        assert_eq!(choose_best_copy(&[(10, 1), (11, 1)]), Some(10));
+    }
+
+}
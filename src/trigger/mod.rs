@@ -0,0 +1,368 @@
+//! Action/Trigger event creation and processing.
+
+pub mod reactor;
+pub mod validator;
+
+use crate::editor::Editor;
+use crate::date::interval_to_seconds;
+use crate::util;
+use chrono::Local;
+use json::JsonValue;
+use std::collections::HashMap;
+
+pub use reactor::Reactor;
+pub use validator::Validator;
+
+// Event states, as stored in action_trigger.event.state.
+pub const STATE_PENDING: &str = "pending";
+pub const STATE_VALIDATING: &str = "validating";
+pub const STATE_REACTING: &str = "reacting";
+pub const STATE_COMPLETE: &str = "complete";
+pub const STATE_INVALID: &str = "invalid";
+pub const STATE_ERROR: &str = "error";
+
+fn now_str() -> String {
+    Local::now().format("%Y-%m-%dT%H:%M:%S%z").to_string()
+}
+
+/// Creates and processes action_trigger events.
+pub struct Processor {
+    editor: Editor,
+    validators: HashMap<String, Box<dyn Validator>>,
+    reactors: HashMap<String, Box<dyn Reactor>>,
+    /// hook key => hook object cache
+    hooks: HashMap<String, JsonValue>,
+}
+
+impl Processor {
+    /// Create a processor with the stock validators and reactors
+    /// registered.
+    pub fn new(editor: Editor) -> Self {
+        let mut processor = Processor {
+            editor,
+            validators: HashMap::new(),
+            reactors: HashMap::new(),
+            hooks: HashMap::new(),
+        };
+
+        validator::register_builtins(&mut processor);
+        reactor::register_builtins(&mut processor);
+
+        processor
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    /// Register a validator implementation by name.
+    pub fn add_validator(&mut self, name: &str, validator: Box<dyn Validator>) {
+        self.validators.insert(name.to_string(), validator);
+    }
+
+    /// Register a reactor implementation by name.
+    pub fn add_reactor(&mut self, name: &str, reactor: Box<dyn Reactor>) {
+        self.reactors.insert(name.to_string(), reactor);
+    }
+
+    /// Fetch (and cache) a hook object by key.
+    fn hook(&mut self, key: &str) -> Result<JsonValue, String> {
+        if let Some(hook) = self.hooks.get(key) {
+            return Ok(hook.clone());
+        }
+
+        let hook = self
+            .editor
+            .retrieve("ath", json::from(key))?
+            .ok_or_else(|| format!("No such hook: {key}"))?;
+
+        self.hooks.insert(key.to_string(), hook.clone());
+        Ok(hook)
+    }
+
+    /// Create pending events for an event definition and a set of
+    /// target IDs, honoring the definition's delay.
+    pub fn create_events(&mut self, def: &JsonValue, targets: &[i64]) -> Result<usize, String> {
+        let def_id = util::json_int(&def["id"])?;
+
+        if !util::json_bool(&def["active"]) {
+            log::debug!("Skipping inactive event definition {def_id}");
+            return Ok(0);
+        }
+
+        let delay = match def["delay"].as_str() {
+            Some(d) => interval_to_seconds(d)?,
+            None => 0,
+        };
+
+        let run_time = (Local::now() + chrono::Duration::seconds(delay))
+            .format("%Y-%m-%dT%H:%M:%S%z")
+            .to_string();
+
+        self.editor.xact_begin()?;
+
+        let mut created = 0;
+
+        for target in targets {
+            let event = json::object! {
+                "_classname": "atev",
+                event_def: def_id,
+                target: *target,
+                state: STATE_PENDING,
+                run_time: run_time.as_str(),
+                add_time: now_str(),
+            };
+
+            let resp = self
+                .editor
+                .request("open-ils.cstore.direct.action_trigger.event.create", vec![event]);
+
+            if let Err(e) = resp {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+
+            created += 1;
+        }
+
+        self.editor.commit()?;
+
+        Ok(created)
+    }
+
+    /// Create pending events for every active definition on a hook.
+    pub fn create_events_for_hook(
+        &mut self,
+        hook: &str,
+        targets: &[i64],
+    ) -> Result<usize, String> {
+        let defs = self
+            .editor
+            .search("atevdef", json::object! {hook: hook, active: "t"})?;
+
+        let mut created = 0;
+        for def in defs {
+            created += self.create_events(&def, targets)?;
+        }
+
+        Ok(created)
+    }
+
+    /// Process all pending events whose run time has arrived,
+    /// grouped per the event definition's group field.  Returns the
+    /// number of events processed.
+    pub fn process_pending(&mut self, def_filter: Option<i64>) -> Result<usize, String> {
+        let mut filter = json::object! {
+            state: STATE_PENDING,
+            run_time: {"<": now_str()},
+        };
+
+        if let Some(def_id) = def_filter {
+            filter["event_def"] = def_id.into();
+        }
+
+        let events = self.editor.search("atev", filter)?;
+
+        log::info!("Trigger processor found {} runnable events", events.len());
+
+        let groups = self.group_events(events)?;
+        let mut processed = 0;
+
+        for (_, group) in groups {
+            processed += group.len();
+            if let Err(e) = self.process_group(&group) {
+                log::error!("Event group processing failed: {e}");
+                self.mark_events(&group, STATE_ERROR, None).ok();
+            }
+        }
+
+        Ok(processed)
+    }
+
+    /// Group runnable events by definition plus the definition's
+    /// group field value on the target object.
+    fn group_events(
+        &mut self,
+        events: Vec<JsonValue>,
+    ) -> Result<HashMap<String, Vec<JsonValue>>, String> {
+        let mut groups: HashMap<String, Vec<JsonValue>> = HashMap::new();
+
+        for event in events {
+            let def_id = util::json_int(&event["event_def"])?;
+
+            let def = self
+                .editor
+                .retrieve("atevdef", json::from(def_id))?
+                .ok_or_else(|| format!("No such event definition: {def_id}"))?;
+
+            let key = match def["group_field"].as_str() {
+                Some(group_field) => {
+                    let target = self.target_object(&def, &event)?;
+                    format!("{def_id}:{}", target[group_field].dump())
+                }
+                None => {
+                    // Ungrouped events process individually.
+                    format!("{def_id}:event-{}", event["id"].dump())
+                }
+            };
+
+            groups.entry(key).or_default().push(event);
+        }
+
+        Ok(groups)
+    }
+
+    /// Fetch the core target object for an event.
+    pub fn target_object(
+        &mut self,
+        def: &JsonValue,
+        event: &JsonValue,
+    ) -> Result<JsonValue, String> {
+        let hook_key = def["hook"]
+            .as_str()
+            .ok_or_else(|| format!("Event definition has no hook: {}", def.dump()))?
+            .to_string();
+
+        let hook = self.hook(&hook_key)?;
+
+        let core_type = hook["core_type"]
+            .as_str()
+            .ok_or_else(|| format!("Hook {hook_key} has no core_type"))?
+            .to_string();
+
+        self.editor
+            .retrieve(&core_type, event["target"].clone())?
+            .ok_or_else(|| {
+                format!("No {core_type} target for event {}", event["id"].dump())
+            })
+    }
+
+    /// Run validation + reaction for one event group.
+    fn process_group(&mut self, events: &[JsonValue]) -> Result<(), String> {
+        let def_id = util::json_int(&events[0]["event_def"])?;
+
+        let def = self
+            .editor
+            .retrieve("atevdef", json::from(def_id))?
+            .ok_or_else(|| format!("No such event definition: {def_id}"))?;
+
+        // Validate each event; invalid events drop out of the group.
+        self.mark_events(events, STATE_VALIDATING, None)?;
+
+        let mut valid_events = Vec::new();
+        let mut invalid_events = Vec::new();
+
+        for event in events {
+            if self.validate_event(&def, event)? {
+                valid_events.push(event.clone());
+            } else {
+                invalid_events.push(event.clone());
+            }
+        }
+
+        if !invalid_events.is_empty() {
+            self.mark_events(&invalid_events, STATE_INVALID, None)?;
+        }
+
+        if valid_events.is_empty() {
+            return Ok(());
+        }
+
+        self.mark_events(&valid_events, STATE_REACTING, None)?;
+
+        let reactor_name = def["reactor"]
+            .as_str()
+            .ok_or_else(|| format!("Event definition {def_id} has no reactor"))?
+            .to_string();
+
+        let reactor = self
+            .reactors
+            .get(&reactor_name)
+            .ok_or_else(|| format!("No such reactor: {reactor_name}"))?;
+
+        let output = reactor.react(&def, &valid_events)?;
+
+        let output_id = match output {
+            Some(text) => Some(self.record_output(&text)?),
+            None => None,
+        };
+
+        self.mark_events(&valid_events, STATE_COMPLETE, output_id)
+    }
+
+    fn validate_event(&mut self, def: &JsonValue, event: &JsonValue) -> Result<bool, String> {
+        let validator_name = match def["validator"].as_str() {
+            Some(v) => v.to_string(),
+            None => return Ok(true),
+        };
+
+        let target = self.target_object(def, event)?;
+
+        let validator = self
+            .validators
+            .get(&validator_name)
+            .ok_or_else(|| format!("No such validator: {validator_name}"))?;
+
+        validator.validate(def, event, &target)
+    }
+
+    /// Store reactor output, returning the new output row ID.
+    fn record_output(&mut self, text: &str) -> Result<i64, String> {
+        let output = json::object! {
+            "_classname": "ateo",
+            data: text,
+            is_error: "f",
+        };
+
+        self.editor.xact_begin()?;
+
+        let resp = self.editor.request(
+            "open-ils.cstore.direct.action_trigger.event_output.create",
+            vec![output],
+        );
+
+        match resp {
+            Ok(created) => {
+                self.editor.commit()?;
+                util::json_int(&created["id"])
+            }
+            Err(e) => {
+                self.editor.xact_rollback()?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Update the state (and optionally the output link) on a set of
+    /// events.
+    fn mark_events(
+        &mut self,
+        events: &[JsonValue],
+        state: &str,
+        output_id: Option<i64>,
+    ) -> Result<(), String> {
+        self.editor.xact_begin()?;
+
+        for event in events {
+            let mut event = event.clone();
+            event["state"] = state.into();
+            event["update_time"] = now_str().as_str().into();
+
+            if let Some(output_id) = output_id {
+                event["template_output"] = output_id.into();
+            }
+
+            let resp = self.editor.request(
+                "open-ils.cstore.direct.action_trigger.event.update",
+                vec![event],
+            );
+
+            if let Err(e) = resp {
+                self.editor.xact_rollback()?;
+                return Err(e.into());
+            }
+        }
+
+        Ok(self.editor.commit()?)
+    }
+}
@@ -0,0 +1,53 @@
+//! Stock Action/Trigger reactors.
+
+use crate::trigger::Processor;
+use json::JsonValue;
+
+/// Acts on a group of validated events.
+pub trait Reactor {
+    /// React to a group of events, returning any output text to be
+    /// recorded on the events.
+    fn react(&self, def: &JsonValue, events: &[JsonValue]) -> Result<Option<String>, String>;
+}
+
+/// Register the stock reactors on a processor.
+pub fn register_builtins(processor: &mut Processor) {
+    processor.add_reactor("NOOP", Box::new(Noop));
+    processor.add_reactor("ProcessTemplate", Box::new(ProcessTemplate));
+    // Email/SMS/print reactors render their templates here; actual
+    // delivery is handled by the notice tooling.
+    processor.add_reactor("SendEmail", Box::new(ProcessTemplate));
+    processor.add_reactor("SendSMS", Box::new(ProcessTemplate));
+    processor.add_reactor("ProcessPrint", Box::new(ProcessTemplate));
+}
+
+/// Does nothing, successfully.
+pub struct Noop;
+
+impl Reactor for Noop {
+    fn react(&self, _: &JsonValue, _: &[JsonValue]) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+}
+
+/// Renders the definition's template and records it as event output.
+///
+/// Until a real template engine is wired in, only the simple
+/// [%- event_count -%] and [%- event_def -%] placeholders are
+/// expanded; everything else passes through verbatim.
+pub struct ProcessTemplate;
+
+impl Reactor for ProcessTemplate {
+    fn react(&self, def: &JsonValue, events: &[JsonValue]) -> Result<Option<String>, String> {
+        let template = match def["template"].as_str() {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+
+        let output = template
+            .replace("[%- event_count -%]", &events.len().to_string())
+            .replace("[%- event_def -%]", &def["id"].dump());
+
+        Ok(Some(output))
+    }
+}
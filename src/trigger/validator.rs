@@ -0,0 +1,101 @@
+//! Stock Action/Trigger validators.
+
+use crate::trigger::Processor;
+use crate::util;
+use json::JsonValue;
+
+/// Decides whether an event should proceed to its reactor.
+pub trait Validator {
+    /// Returns true if the event is valid for reacting.
+    fn validate(
+        &self,
+        def: &JsonValue,
+        event: &JsonValue,
+        target: &JsonValue,
+    ) -> Result<bool, String>;
+}
+
+/// Register the stock validators on a processor.
+pub fn register_builtins(processor: &mut Processor) {
+    processor.add_validator("NOOP_True", Box::new(NoopTrue));
+    processor.add_validator("NOOP_False", Box::new(NoopFalse));
+    processor.add_validator("CircIsOpen", Box::new(CircIsOpen));
+    processor.add_validator("CircIsOverdue", Box::new(CircIsOverdue));
+    processor.add_validator("HoldIsAvailable", Box::new(HoldIsAvailable));
+}
+
+/// Always valid.
+pub struct NoopTrue;
+
+impl Validator for NoopTrue {
+    fn validate(&self, _: &JsonValue, _: &JsonValue, _: &JsonValue) -> Result<bool, String> {
+        Ok(true)
+    }
+}
+
+/// Never valid.
+pub struct NoopFalse;
+
+impl Validator for NoopFalse {
+    fn validate(&self, _: &JsonValue, _: &JsonValue, _: &JsonValue) -> Result<bool, String> {
+        Ok(false)
+    }
+}
+
+/// The target circulation is still open.
+pub struct CircIsOpen;
+
+impl Validator for CircIsOpen {
+    fn validate(
+        &self,
+        _def: &JsonValue,
+        _event: &JsonValue,
+        target: &JsonValue,
+    ) -> Result<bool, String> {
+        Ok(target["checkin_time"].is_null() && target["xact_finish"].is_null())
+    }
+}
+
+/// The target circulation is open and past due.
+pub struct CircIsOverdue;
+
+impl Validator for CircIsOverdue {
+    fn validate(
+        &self,
+        _def: &JsonValue,
+        _event: &JsonValue,
+        target: &JsonValue,
+    ) -> Result<bool, String> {
+        if !target["checkin_time"].is_null() || !target["stop_fines"].is_null() {
+            return Ok(false);
+        }
+
+        let due = match target["due_date"].as_str() {
+            Some(d) => d.to_string(),
+            None => return Ok(false),
+        };
+
+        let now = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%z").to_string();
+
+        // ISO timestamps compare lexically.
+        Ok(due < now)
+    }
+}
+
+/// The target hold is captured and on the hold shelf.
+pub struct HoldIsAvailable;
+
+impl Validator for HoldIsAvailable {
+    fn validate(
+        &self,
+        _def: &JsonValue,
+        _event: &JsonValue,
+        target: &JsonValue,
+    ) -> Result<bool, String> {
+        Ok(!target["capture_time"].is_null()
+            && !target["current_copy"].is_null()
+            && target["cancel_time"].is_null()
+            && target["fulfillment_time"].is_null()
+            && !util::json_bool(&target["frozen"]))
+    }
+}
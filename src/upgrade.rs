@@ -0,0 +1,182 @@
+//! Database schema upgrade tracking and application.
+//!
+//! Upgrade scripts are SQL files named `<version>.<description>.sql`
+//! (e.g. `1405.schema.copy-alerts.sql`).  Applied versions are
+//! recorded in config.upgrade_log; pending scripts run in version
+//! order, each inside its own transaction.
+
+use crate::db::DatabaseConnection;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const APPLIED_SQL: &str = "SELECT version FROM config.upgrade_log";
+const LOG_SQL: &str = "INSERT INTO config.upgrade_log (version) VALUES ($1)";
+
+/// Extract the version token from an upgrade script file name.
+/// Returns None for files that are not versioned .sql scripts.
+pub fn parse_version(filename: &str) -> Option<String> {
+    let stem = filename.strip_suffix(".sql")?;
+    let version = stem.split('.').next()?;
+
+    if version.is_empty() || !version.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    Some(version.to_string())
+}
+
+/// One on-disk upgrade script.
+#[derive(Debug, Clone)]
+pub struct UpgradeScript {
+    pub version: String,
+    pub path: PathBuf,
+}
+
+/// Collect the upgrade scripts in a directory, sorted by version.
+pub fn scan_dir(dir: &Path) -> Result<Vec<UpgradeScript>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Cannot read {dir:?}: {e}"))?;
+
+    let mut scripts = Vec::new();
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Cannot read {dir:?}: {e}"))?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if let Some(version) = parse_version(&name) {
+            scripts.push(UpgradeScript {
+                version,
+                path: entry.path(),
+            });
+        }
+    }
+
+    scripts.sort_by(|a, b| a.version.cmp(&b.version));
+
+    Ok(scripts)
+}
+
+/// Summary of one upgrade run.
+#[derive(Debug, Default)]
+pub struct UpgradeCounts {
+    pub applied: usize,
+    pub skipped: usize,
+    pub errors: usize,
+}
+
+/// Applies pending upgrade scripts and records them in
+/// config.upgrade_log.
+pub struct Upgrader {
+    db: DatabaseConnection,
+}
+
+impl Upgrader {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Upgrader { db }
+    }
+
+    pub fn connect(&mut self) -> Result<(), String> {
+        self.db.connect()
+    }
+
+    /// The versions already recorded in config.upgrade_log.
+    pub fn applied_versions(&mut self) -> Result<HashSet<String>, String> {
+        let rows = self
+            .db
+            .client()
+            .query(APPLIED_SQL, &[])
+            .map_err(|e| format!("Cannot read config.upgrade_log: {e}"))?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Filter a script list down to the not-yet-applied entries.
+    pub fn pending(&mut self, scripts: &[UpgradeScript]) -> Result<Vec<UpgradeScript>, String> {
+        let applied = self.applied_versions()?;
+
+        Ok(scripts
+            .iter()
+            .filter(|s| !applied.contains(&s.version))
+            .cloned()
+            .collect())
+    }
+
+    /// Apply one script and log its version, all in one transaction.
+    pub fn apply(&mut self, script: &UpgradeScript) -> Result<(), String> {
+        let sql = fs::read_to_string(&script.path)
+            .map_err(|e| format!("Cannot read {:?}: {e}", script.path))?;
+
+        let mut xact = self
+            .db
+            .client()
+            .transaction()
+            .map_err(|e| format!("Cannot start transaction: {e}"))?;
+
+        xact.batch_execute(&sql)
+            .map_err(|e| format!("Script {} failed: {e}", script.version))?;
+
+        xact.execute(LOG_SQL, &[&script.version])
+            .map_err(|e| format!("Cannot log version {}: {e}", script.version))?;
+
+        xact.commit()
+            .map_err(|e| format!("Cannot commit {}: {e}", script.version))
+    }
+
+    /// Apply every pending script in order.  With dry_run the
+    /// pending scripts are only reported.  With stop_on_error the
+    /// run aborts at the first failure; otherwise failing scripts
+    /// are counted and the rest proceed.
+    pub fn run(
+        &mut self,
+        scripts: &[UpgradeScript],
+        dry_run: bool,
+        stop_on_error: bool,
+    ) -> Result<UpgradeCounts, String> {
+        let pending = self.pending(scripts)?;
+
+        let mut counts = UpgradeCounts {
+            skipped: scripts.len() - pending.len(),
+            ..Default::default()
+        };
+
+        for script in &pending {
+            if dry_run {
+                println!("Would apply {} ({:?})", script.version, script.path);
+                continue;
+            }
+
+            match self.apply(script) {
+                Ok(()) => {
+                    println!("Applied {}", script.version);
+                    counts.applied += 1;
+                }
+                Err(e) => {
+                    counts.errors += 1;
+                    if stop_on_error {
+                        return Err(e);
+                    }
+                    log::error!("{e}");
+                }
+            }
+        }
+
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("1405.schema.copy-alerts.sql"),
+            Some("1405".to_string())
+        );
+        assert_eq!(parse_version("0099.data.seed.sql"), Some("0099".to_string()));
+        assert_eq!(parse_version("README"), None);
+        assert_eq!(parse_version(".sql"), None);
+        assert_eq!(parse_version("14 05.schema.sql"), None);
+    }
+}
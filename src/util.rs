@@ -0,0 +1,156 @@
+//! Utility functions, mostly for extracting typed values from JSON.
+
+use json::JsonValue;
+
+/// Translate a number or numeric string into an i64.
+///
+/// Values arriving from the network may be numbers or strings depending
+/// on the originating language, so we accept both.
+pub fn json_int(value: &JsonValue) -> Result<i64, String> {
+    if let Some(n) = value.as_i64() {
+        return Ok(n);
+    }
+    if let Some(s) = value.as_str() {
+        if let Ok(n) = s.parse::<i64>() {
+            return Ok(n);
+        }
+    }
+    Err(format!("Invalid integer value: {}", value))
+}
+
+/// Translate a number or numeric string into an f64.
+pub fn json_float(value: &JsonValue) -> Result<f64, String> {
+    if let Some(n) = value.as_f64() {
+        return Ok(n);
+    }
+    if let Some(s) = value.as_str() {
+        if let Ok(n) = s.parse::<f64>() {
+            return Ok(n);
+        }
+    }
+    Err(format!("Invalid float value: {}", value))
+}
+
+/// Translate a JSON value into a String.
+///
+/// Numbers are stringified; all other non-string types are errors.
+pub fn json_string(value: &JsonValue) -> Result<String, String> {
+    if let Some(s) = value.as_str() {
+        return Ok(s.to_string());
+    }
+    if value.is_number() {
+        return Ok(format!("{}", value));
+    }
+    Err(format!("Invalid string value: {}", value))
+}
+
+/// Translate a JSON value into a boolean, honoring the Evergreen
+/// convention of "t" / "f" strings.
+pub fn json_bool(value: &JsonValue) -> bool {
+    if let Some(b) = value.as_bool() {
+        return b;
+    }
+    if let Some(s) = value.as_str() {
+        return s.eq_ignore_ascii_case("t") || s.eq_ignore_ascii_case("true") || s == "1";
+    }
+    if let Some(n) = value.as_i64() {
+        return n != 0;
+    }
+    false
+}
+
+/// Translate a json-crate value into a serde_json value without a
+/// stringify/reparse round trip.
+pub fn json_to_serde(value: &JsonValue) -> serde_json::Value {
+    match value {
+        JsonValue::Null => serde_json::Value::Null,
+        JsonValue::Boolean(b) => (*b).into(),
+        JsonValue::Short(s) => s.as_str().into(),
+        JsonValue::String(s) => s.as_str().into(),
+        JsonValue::Number(_) => {
+            // Keep integers integral; serde distinguishes them.
+            if let Some(n) = value.as_i64() {
+                n.into()
+            } else {
+                value.as_f64().unwrap_or(0.0).into()
+            }
+        }
+        JsonValue::Array(arr) => arr.iter().map(json_to_serde).collect(),
+        JsonValue::Object(_) => {
+            let mut map = serde_json::Map::new();
+            for (key, val) in value.entries() {
+                map.insert(key.to_string(), json_to_serde(val));
+            }
+            serde_json::Value::Object(map)
+        }
+    }
+}
+
+/// Translate a serde_json value into a json-crate value.
+pub fn serde_to_json(value: &serde_json::Value) -> JsonValue {
+    match value {
+        serde_json::Value::Null => JsonValue::Null,
+        serde_json::Value::Bool(b) => (*b).into(),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                i.into()
+            } else {
+                n.as_f64().unwrap_or(0.0).into()
+            }
+        }
+        serde_json::Value::String(s) => s.as_str().into(),
+        serde_json::Value::Array(arr) => {
+            let mut out = json::array![];
+            for item in arr {
+                out.push(serde_to_json(item))
+                    .expect("push to array succeeds");
+            }
+            out
+        }
+        serde_json::Value::Object(map) => {
+            let mut obj = json::object! {};
+            for (key, val) in map {
+                obj[key.as_str()] = serde_to_json(val);
+            }
+            obj
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_int() {
+        assert_eq!(json_int(&json::from(12)).unwrap(), 12);
+        assert_eq!(json_int(&json::from("12")).unwrap(), 12);
+        assert!(json_int(&json::from("twelve")).is_err());
+    }
+
+    #[test]
+    fn test_json_bool() {
+        assert!(json_bool(&json::from("t")));
+        assert!(json_bool(&json::from(true)));
+        assert!(!json_bool(&json::from("f")));
+        assert!(!json_bool(&JsonValue::Null));
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let value = json::object! {
+            id: 12,
+            rate: 1.5,
+            name: "BR1",
+            deleted: false,
+            parent: JsonValue::Null,
+            tags: ["a", "b"],
+        };
+
+        let serde_value = json_to_serde(&value);
+        assert_eq!(serde_value["id"], serde_json::json!(12));
+        assert_eq!(serde_value["tags"][1], serde_json::json!("b"));
+
+        assert_eq!(serde_to_json(&serde_value), value);
+    }
+}
@@ -0,0 +1,200 @@
+//! A blessed-object wrapper pairing a JSON value with its IDL class.
+//!
+//! Raw JsonValue indexing silently returns Null for typo'd field
+//! names; EgValue validates every access against the IDL and keeps
+//! the isnew/ischanged/isdeleted flags consistent on mutation.
+
+use crate::error::EgResult;
+use crate::idl;
+use crate::util;
+use json::JsonValue;
+use std::fmt;
+use std::sync::Arc;
+
+/// Mutation-state flags carried alongside the real fields.
+const FLAG_FIELDS: [&str; 3] = ["isnew", "ischanged", "isdeleted"];
+
+/// A hash-formatted IDL object with field access checked against its
+/// class definition.
+#[derive(Clone)]
+pub struct EgValue {
+    classname: String,
+    idl: Arc<idl::Parser>,
+    value: JsonValue,
+}
+
+impl EgValue {
+    /// Create a blank object of an IDL class with all fields present
+    /// and flagged as new.
+    pub fn create(idl: &Arc<idl::Parser>, classname: &str) -> EgResult<EgValue> {
+        let value = idl.new_object(classname)?;
+
+        Ok(EgValue {
+            classname: classname.to_string(),
+            idl: idl.clone(),
+            value,
+        })
+    }
+
+    /// Wrap a hash-formatted object, e.g. one returned by an Editor.
+    pub fn from_json(idl: &Arc<idl::Parser>, value: JsonValue) -> EgResult<EgValue> {
+        let classname = match value[idl::CLASSNAME_KEY].as_str() {
+            Some(cn) => cn.to_string(),
+            None => return Err("Value carries no IDL class".into()),
+        };
+
+        if idl.get_class(&classname).is_none() {
+            return Err(format!("No such IDL class: {classname}").into());
+        }
+
+        Ok(EgValue {
+            classname,
+            idl: idl.clone(),
+            value,
+        })
+    }
+
+    pub fn classname(&self) -> &str {
+        &self.classname
+    }
+
+    fn class(&self) -> &idl::Class {
+        self.idl
+            .get_class(&self.classname)
+            .expect("class checked at construction")
+    }
+
+    fn check_field(&self, field: &str) -> EgResult<()> {
+        if FLAG_FIELDS.contains(&field) || self.class().fields().contains_key(field) {
+            Ok(())
+        } else {
+            Err(format!("Class {} has no field {field}", self.classname).into())
+        }
+    }
+
+    /// A field's value; unknown field names are an error rather than
+    /// a silent Null.
+    pub fn get(&self, field: &str) -> EgResult<&JsonValue> {
+        self.check_field(field)?;
+        Ok(&self.value[field])
+    }
+
+    /// The primary key value as an integer.
+    pub fn id(&self) -> EgResult<i64> {
+        let pkey = match self.class().pkey() {
+            Some(p) => p.to_string(),
+            None => return Err(format!("Class {} has no primary key", self.classname).into()),
+        };
+        self.as_i64_field(&pkey)
+    }
+
+    pub fn as_i64_field(&self, field: &str) -> EgResult<i64> {
+        Ok(util::json_int(self.get(field)?)?)
+    }
+
+    pub fn as_f64_field(&self, field: &str) -> EgResult<f64> {
+        Ok(util::json_float(self.get(field)?)?)
+    }
+
+    pub fn as_str_field(&self, field: &str) -> EgResult<String> {
+        Ok(util::json_string(self.get(field)?)?)
+    }
+
+    pub fn as_bool_field(&self, field: &str) -> EgResult<bool> {
+        Ok(util::json_bool(self.get(field)?))
+    }
+
+    /// Set a field, marking the object changed unless it is new.
+    pub fn set(&mut self, field: &str, value: impl Into<JsonValue>) -> EgResult<()> {
+        self.check_field(field)?;
+        self.value[field] = value.into();
+
+        if !self.isnew() {
+            self.value["ischanged"] = true.into();
+        }
+
+        Ok(())
+    }
+
+    pub fn isnew(&self) -> bool {
+        util::json_bool(&self.value["isnew"])
+    }
+
+    pub fn ischanged(&self) -> bool {
+        util::json_bool(&self.value["ischanged"])
+    }
+
+    pub fn isdeleted(&self) -> bool {
+        util::json_bool(&self.value["isdeleted"])
+    }
+
+    /// Flag the object for deletion by a later update call.
+    pub fn mark_deleted(&mut self) {
+        self.value["isdeleted"] = true.into();
+    }
+
+    pub fn json(&self) -> &JsonValue {
+        &self.value
+    }
+
+    pub fn into_json(self) -> JsonValue {
+        self.value
+    }
+}
+
+impl fmt::Display for EgValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.classname, self.value.dump())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser() -> Arc<idl::Parser> {
+        idl::Parser::parse_string(idl::tests::TEST_IDL).expect("IDL parses")
+    }
+
+    #[test]
+    fn test_field_access() {
+        let idl = parser();
+
+        let mut obj = json::object! {id: 1, name: "BR1", opac_visible: "t"};
+        obj[idl::CLASSNAME_KEY] = "aou".into();
+
+        let value = EgValue::from_json(&idl, obj).expect("wraps");
+        assert_eq!(value.classname(), "aou");
+        assert_eq!(value.id().unwrap(), 1);
+        assert_eq!(value.as_str_field("name").unwrap(), "BR1");
+        assert!(value.as_bool_field("opac_visible").unwrap());
+
+        // The typo'd field name is an error, not a silent Null.
+        assert!(value.get("nmae").is_err());
+
+        assert!(EgValue::from_json(&idl, json::object! {id: 1}).is_err());
+    }
+
+    #[test]
+    fn test_mutation_flags() {
+        let idl = parser();
+
+        let mut created = EgValue::create(&idl, "aou").expect("creates");
+        assert!(created.isnew());
+        created.set("name", "BR2").expect("sets");
+        assert!(!created.ischanged());
+
+        let mut obj = json::object! {id: 1, name: "BR1"};
+        obj[idl::CLASSNAME_KEY] = "aou".into();
+        let mut fetched = EgValue::from_json(&idl, obj).expect("wraps");
+
+        assert!(!fetched.ischanged());
+        fetched.set("name", "BR1 Annex").expect("sets");
+        assert!(fetched.ischanged());
+        assert!(fetched.set("nmae", "oops").is_err());
+
+        fetched.mark_deleted();
+        assert!(fetched.isdeleted());
+        assert_eq!(fetched.json()["name"], "BR1 Annex");
+    }
+}
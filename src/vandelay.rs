@@ -0,0 +1,284 @@
+//! Client wrappers for the open-ils.vandelay record import APIs.
+
+use crate::editor::Editor;
+use crate::idl;
+use crate::osrf::client::Client;
+use crate::util;
+use json::JsonValue;
+use std::sync::Arc;
+
+/// Imports can run long; give the service room to stream.
+const VANDELAY_TIMEOUT: u64 = 300;
+
+/// Which flavor of queue/record we're working with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueType {
+    Bib,
+    Authority,
+}
+
+impl QueueType {
+    /// The name fragment used in vandelay API method names.
+    pub fn api_name(&self) -> &'static str {
+        match self {
+            QueueType::Bib => "bib",
+            QueueType::Authority => "authority",
+        }
+    }
+
+    /// The IDL class for queued records of this type.
+    pub fn queued_record_class(&self) -> &'static str {
+        match self {
+            QueueType::Bib => "vqbr",
+            QueueType::Authority => "vqar",
+        }
+    }
+
+    /// The cstore fieldmapper path for queued records of this type.
+    fn queued_record_fm(&self) -> &'static str {
+        match self {
+            QueueType::Bib => "vandelay.queued_bib_record",
+            QueueType::Authority => "vandelay.queued_authority_record",
+        }
+    }
+}
+
+/// Import-time behavior flags, passed through to the import API.
+#[derive(Debug, Clone, Default)]
+pub struct ImportArgs {
+    pub merge_profile: Option<i64>,
+    /// Import records that matched nothing as new records.
+    pub import_no_match: bool,
+    /// Overlay automatically when exactly one exact match exists.
+    pub auto_overlay_exact: bool,
+    /// Overlay automatically when exactly one match of any quality
+    /// exists.
+    pub auto_overlay_1match: bool,
+}
+
+impl ImportArgs {
+    fn to_json_value(&self) -> JsonValue {
+        let mut args = json::object! {
+            import_no_match: if self.import_no_match { "t" } else { "f" },
+            auto_overlay_exact: if self.auto_overlay_exact { "t" } else { "f" },
+            auto_overlay_1match: if self.auto_overlay_1match { "t" } else { "f" },
+        };
+
+        if let Some(profile) = self.merge_profile {
+            args["merge_profile"] = profile.into();
+        }
+
+        args
+    }
+}
+
+/// Split a MARCXML collection document into individual record
+/// documents.
+pub fn split_collection(xml: &str) -> Result<Vec<String>, String> {
+    let doc = roxmltree::Document::parse(xml).map_err(|e| format!("Invalid MARCXML: {e}"))?;
+
+    let records = doc
+        .descendants()
+        .filter(|n| n.is_element() && n.tag_name().name() == "record")
+        .map(|n| doc.input_text()[n.range()].to_string())
+        .collect();
+
+    Ok(records)
+}
+
+/// Drives vandelay queue creation, record upload, matching, and
+/// import.
+pub struct Vandelay {
+    client: Client,
+    editor: Editor,
+    authtoken: String,
+}
+
+impl Vandelay {
+    pub fn new(client: &Client, idl: &Arc<idl::Parser>, authtoken: &str) -> Self {
+        Vandelay {
+            client: client.clone(),
+            editor: Editor::with_auth(client, idl, authtoken),
+            authtoken: authtoken.to_string(),
+        }
+    }
+
+    /// Call an open-ils.vandelay method and return its first
+    /// response.
+    fn request(&self, method: &str, params: Vec<JsonValue>) -> Result<JsonValue, String> {
+        let session = self.client.session("open-ils.vandelay");
+        let mut req = session.request(method, params)?;
+
+        match req.recv(VANDELAY_TIMEOUT)? {
+            Some(resp) => Ok(resp),
+            None => Err(format!("No response to {method}")),
+        }
+    }
+
+    /// Create a record queue, returning its ID.
+    pub fn create_queue(
+        &self,
+        queue_type: QueueType,
+        name: &str,
+        match_set: Option<i64>,
+        import_def: Option<i64>,
+    ) -> Result<i64, String> {
+        let method = format!("open-ils.vandelay.{}_queue.create", queue_type.api_name());
+
+        let queue = self.request(
+            &method,
+            vec![
+                json::from(self.authtoken.as_str()),
+                json::from(name),
+                JsonValue::Null, // owner defaults to the requestor
+                json::from(queue_type.api_name()),
+                match_set.map(json::from).unwrap_or(JsonValue::Null),
+                import_def.map(json::from).unwrap_or(JsonValue::Null),
+            ],
+        )?;
+
+        if let Some(evt) = crate::event::EgEvent::parse(&queue) {
+            return Err(format!("Queue creation failed: {evt}"));
+        }
+
+        util::json_int(&queue["id"])
+    }
+
+    /// Add records from a MARCXML collection to a queue, returning
+    /// the queued record IDs.  Records are created directly rather
+    /// than through the spool-upload dance the web client uses.
+    pub fn queue_records(
+        &mut self,
+        queue_type: QueueType,
+        queue_id: i64,
+        xml: &str,
+    ) -> Result<Vec<i64>, String> {
+        let records = split_collection(xml)?;
+        let method = format!(
+            "open-ils.cstore.direct.{}.create",
+            queue_type.queued_record_fm()
+        );
+
+        self.editor.xact_begin()?;
+
+        let mut ids = Vec::new();
+
+        for record_xml in records {
+            let queued = json::object! {
+                "_classname": queue_type.queued_record_class(),
+                queue: queue_id,
+                marc: record_xml,
+            };
+
+            match self.editor.request(&method, vec![queued]) {
+                Ok(created) => ids.push(util::json_int(&created["id"])?),
+                Err(e) => {
+                    self.editor.xact_rollback()?;
+                    return Err(e.into());
+                }
+            }
+        }
+
+        self.editor.commit()?;
+
+        Ok(ids)
+    }
+
+    /// Run the queue's match set against its queued records,
+    /// returning the number of matches found.
+    pub fn run_matches(&self, queue_type: QueueType, queue_id: i64) -> Result<i64, String> {
+        let method = format!(
+            "open-ils.vandelay.{}_queue.match_set.run",
+            queue_type.api_name()
+        );
+
+        let resp = self.request(
+            &method,
+            vec![json::from(self.authtoken.as_str()), json::from(queue_id)],
+        )?;
+
+        if let Some(evt) = crate::event::EgEvent::parse(&resp) {
+            return Err(format!("Match run failed: {evt}"));
+        }
+
+        util::json_int(&resp["matches"])
+    }
+
+    /// Import a whole queue, draining the progress stream and
+    /// returning the final summary response.
+    pub fn import_queue(
+        &self,
+        queue_type: QueueType,
+        queue_id: i64,
+        args: &ImportArgs,
+    ) -> Result<JsonValue, String> {
+        let method = format!("open-ils.vandelay.{}_queue.import", queue_type.api_name());
+
+        let session = self.client.session("open-ils.vandelay");
+        let mut req = session.request(
+            &method,
+            vec![
+                json::from(self.authtoken.as_str()),
+                json::from(queue_id),
+                args.to_json_value(),
+            ],
+        )?;
+
+        let mut last = JsonValue::Null;
+
+        while let Some(resp) = req.recv(VANDELAY_TIMEOUT)? {
+            if let Some(evt) = crate::event::EgEvent::parse(&resp) {
+                if !evt.is_success() {
+                    return Err(format!("Import failed: {evt}"));
+                }
+            }
+            last = resp;
+        }
+
+        Ok(last)
+    }
+
+    /// Fetch the queue summary (counts of queued/imported/etc.).
+    pub fn queue_summary(&self, queue_type: QueueType, queue_id: i64) -> Result<JsonValue, String> {
+        let method = format!(
+            "open-ils.vandelay.{}_queue.summary.retrieve",
+            queue_type.api_name()
+        );
+
+        self.request(
+            &method,
+            vec![json::from(self.authtoken.as_str()), json::from(queue_id)],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_collection() {
+        let xml = r#"<collection xmlns="http://www.loc.gov/MARC21/slim">
+            <record><leader>00000nam a2200000 a 4500</leader></record>
+            <record><leader>00000nam a2200000 a 4500</leader></record>
+        </collection>"#;
+
+        let records = split_collection(xml).expect("collection should parse");
+        assert_eq!(records.len(), 2);
+        assert!(records[0].starts_with("<record>"));
+    }
+
+    #[test]
+    fn test_import_args() {
+        let args = ImportArgs {
+            merge_profile: Some(2),
+            auto_overlay_exact: true,
+            ..Default::default()
+        };
+
+        let value = args.to_json_value();
+        assert_eq!(value["merge_profile"], 2);
+        assert_eq!(value["auto_overlay_exact"], "t");
+        assert_eq!(value["import_no_match"], "f");
+    }
+}